@@ -52,7 +52,7 @@ typedef struct VAEFatPtr_i32 {
   union VAEMeta_i32 meta;
 } VAEFatPtr_i32;
 
-typedef void (*VAEVaeFun)(uintptr_t, struct VAEFatPtr_f64*, struct VAEFatPtr_f64*, struct VAEFatPtr_f64*, struct VAEFatPtr_i32*, const char**, struct VAEFatPtr_f64*, struct VAEFatPtr_i32*, struct VAEFatPtr_f64*, void*);
+typedef void (*VAEVaeFun)(uintptr_t, struct VAEFatPtr_f64*, struct VAEFatPtr_f64*, struct VAEFatPtr_f64*, struct VAEFatPtr_i32*, const char**, struct VAEFatPtr_f64*, struct VAEFatPtr_i32*, struct VAEFatPtr_f64*, void*, double**);
 
 typedef struct VAESlice_u8 {
   uint8_t *ptr;
@@ -94,6 +94,7 @@ typedef struct VAEOpts {
   struct VAESlice_u8 target;
   struct VAESlice_Slice_u8 cg_flags;
   VAEVfs vfs;
+  struct VAESlice_Slice_u8 derivatives;
 } VAEOpts;
 
 /**
@@ -415,6 +416,17 @@ const double *verilogae_fun_voltage_defaults(const void *lib, const char *fun);
  */
 const double *verilogae_fun_current_defaults(const void *lib, const char *fun);
 
+/**
+ *This function returns a pointer to the `derivatives` global
+ * of a VerilogAE model loaded with `load`.
+ *
+ * # Safety
+ *
+ * `lib` must be a valid pointer returned by the `load` functions or `dlopen`
+ *`sym_name` must batch the schema fun.{NUM}derivatives
+ */
+const char *const *verilogae_fun_derivatives(const void *lib, const char *fun);
+
 /**
  *This function returns a pointer to the `params.real.cnt` global
  * of a VerilogAE model loaded with `load`.
@@ -505,6 +517,16 @@ uintptr_t verilogae_fun_voltage_default_cnt(const void *lib, const char *fun);
  */
 uintptr_t verilogae_fun_current_default_cnt(const void *lib, const char *fun);
 
+/**
+ *This function returns a pointer to the `derivatives.cnt` global
+ * of a VerilogAE model loaded with `load`.
+ *
+ * # Safety
+ *
+ * `lib` must be a valid pointer returned by the `load` functions or `dlopen`
+ */
+uintptr_t verilogae_fun_derivative_cnt(const void *lib, const char *fun);
+
 /**
  * Obtains a pointer to the modelcard initialization function of a VerilogAE model loaded with `load`.
  *
@@ -533,6 +555,8 @@ const char *verilogae_module_name(const void *lib);
  * # Safety
  *
  * All required parameters must be initialized appropriately
+ * `derivatives` must point to one output array for each entry of `verilogae_fun_derivatives`
+ * (or may be null if the function has no derivatives)
  */
 int32_t verilogae_call_fun_parallel(VAEVaeFun fun,
                                     uintptr_t cnt,
@@ -544,7 +568,8 @@ int32_t verilogae_call_fun_parallel(VAEVaeFun fun,
                                     struct VAEFatPtr_f64 *real_dep_break,
                                     struct VAEFatPtr_i32 *int_dep_break,
                                     struct VAEFatPtr_f64 *temp,
-                                    void *out);
+                                    void *out,
+                                    double **derivatives);
 
 struct VAEOpts *verilogae_new_opts(void);
 
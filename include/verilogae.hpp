@@ -43,7 +43,7 @@ struct FatPtr {
   Meta<T> meta;
 };
 
-using VaeFun = void(*)(uintptr_t, FatPtr<double>*, FatPtr<double>*, FatPtr<double>*, FatPtr<int32_t>*, const char**, FatPtr<double>*, FatPtr<int32_t>*, FatPtr<double>*, void*);
+using VaeFun = void(*)(uintptr_t, FatPtr<double>*, FatPtr<double>*, FatPtr<double>*, FatPtr<int32_t>*, const char**, FatPtr<double>*, FatPtr<int32_t>*, FatPtr<double>*, void*, double**);
 
 template<typename T>
 struct Slice {
@@ -71,6 +71,7 @@ struct Opts {
   Slice<uint8_t> target;
   Slice<Slice<uint8_t>> cg_flags;
   Vfs vfs;
+  Slice<Slice<uint8_t>> derivatives;
 };
 
 extern "C" {
@@ -332,6 +333,15 @@ const double *verilogae_fun_voltage_defaults(const void *lib, const char *fun);
 ///`sym_name` must batch the schema fun.{NUM}currents.default
 const double *verilogae_fun_current_defaults(const void *lib, const char *fun);
 
+///This function returns a pointer to the `derivatives` global
+/// of a VerilogAE model loaded with `load`.
+///
+/// # Safety
+///
+/// `lib` must be a valid pointer returned by the `load` functions or `dlopen`
+///`sym_name` must batch the schema fun.{NUM}derivatives
+const char *const *verilogae_fun_derivatives(const void *lib, const char *fun);
+
 ///This function returns a pointer to the `params.real.cnt` global
 /// of a VerilogAE model loaded with `load`.
 ///
@@ -404,6 +414,14 @@ uintptr_t verilogae_fun_voltage_default_cnt(const void *lib, const char *fun);
 /// `lib` must be a valid pointer returned by the `load` functions or `dlopen`
 uintptr_t verilogae_fun_current_default_cnt(const void *lib, const char *fun);
 
+///This function returns a pointer to the `derivatives.cnt` global
+/// of a VerilogAE model loaded with `load`.
+///
+/// # Safety
+///
+/// `lib` must be a valid pointer returned by the `load` functions or `dlopen`
+uintptr_t verilogae_fun_derivative_cnt(const void *lib, const char *fun);
+
 /// Obtains a pointer to the modelcard initialization function of a VerilogAE model loaded with `load`.
 ///
 /// # Safety
@@ -425,6 +443,8 @@ const char *verilogae_module_name(const void *lib);
 /// # Safety
 ///
 /// All required parameters must be initialized appropriately
+/// `derivatives` must point to one output array for each entry of `verilogae_fun_derivatives`
+/// (or may be null if the function has no derivatives)
 int32_t verilogae_call_fun_parallel(VaeFun fun,
                                     uintptr_t cnt,
                                     FatPtr<double> *voltages,
@@ -435,7 +455,8 @@ int32_t verilogae_call_fun_parallel(VaeFun fun,
                                     FatPtr<double> *real_dep_break,
                                     FatPtr<int32_t> *int_dep_break,
                                     FatPtr<double> *temp,
-                                    void *out);
+                                    void *out,
+                                    double **derivatives);
 
 Opts *verilogae_new_opts();
 
@@ -461,6 +482,16 @@ void verilogae_free_vfs(Vfs vfs);
 /// * opts must only contain valid data
 const void *verilogae_load(NativePath path, bool full_compile, const Opts *opts);
 
+/// Compiles a model like `verilogae_load` but links it into the current process with the LLVM
+/// JIT instead of the system linker. The object cache is not used. The returned handle can be
+/// passed to all other functions in place of a library handle, but not to `dlsym`.
+///
+/// # Safety
+/// * path must be valid for reads
+/// * opts must be valid for reads or null
+/// * opts must only contain valid data
+const void *verilogae_load_jit(NativePath path, bool full_compile, const Opts *opts);
+
 } // extern "C"
 
 } // namespace vae
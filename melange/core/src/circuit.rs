@@ -223,6 +223,7 @@ impl Circuit {
             model,
             parameters: ParamList::default(),
             connections: terminal_connections,
+            dtemp: None,
         };
         let id = self.instances.push_and_get_key(instance);
         self.insert_into_namespace(name, id)?;
@@ -251,6 +252,20 @@ impl Circuit {
         self.nodes.index(name)
     }
 
+    /// Lookup an instance by name
+    ///
+    /// # Returns
+    ///
+    /// The instance in this circuit that has the name `name`
+    ///
+    /// If no such instance exists returns `None`
+    pub fn lookup_instance(&self, name: &str) -> Option<InstanceId> {
+        match self.namespace.get(name) {
+            Some(&NameSpaceEntry::Instance(instance)) => Some(instance),
+            _ => None,
+        }
+    }
+
     /// returns the name of a node
     pub fn node_name(&self, node: Node) -> &str {
         &self.nodes[node]
@@ -404,7 +419,8 @@ impl Circuit {
     /// # Returns
     ///
     /// An error if `param_name` is not an instance parameter of the device implementation
-    /// associated with `instance`
+    /// associated with `instance`. `dtemp` sets [`CircuitInstance::dtemp`] unless the device
+    /// has a parameter with that name.
     pub fn set_instance_param(
         &mut self,
         instance: InstanceId,
@@ -415,6 +431,10 @@ impl Circuit {
         let dev = &self.devices[self.models[inst.model].device];
         let (id, info) = match dev.parameters.lookup_param(param_name) {
             Some((id, info)) => (id, info),
+            None if param_name == "dtemp" => {
+                inst.dtemp = Some(val);
+                return Ok(());
+            }
             None => bail!("unknown parameter '{param_name}' for {}", dev.name),
         };
 
@@ -450,6 +470,33 @@ impl Circuit {
         Ok(())
     }
 
+    /// Sets the value of an instance or model parameter of an instance.
+    /// Instance parameters are set on the instance itself while model parameters are set on the
+    /// model associated with `instance`.
+    ///
+    /// # Parameters
+    ///
+    /// * **`instance`** - an instance within this circuit
+    /// * **`param_name`** - the name of the parameter to be changed
+    /// * **`val`** - an expression that represents the value of this parameter
+    ///
+    /// # Returns
+    ///
+    /// An error if `param_name` is not a parameter of the device implementation associated
+    /// with `instance`
+    pub fn set_param(&mut self, instance: InstanceId, param_name: &str, val: Expr) -> Result<()> {
+        let model = self.instances[instance].model;
+        let dev = &self.devices[self.models[model].device];
+        match dev.parameters.lookup_param(param_name) {
+            Some((_, info)) if info.is_instance_param => {
+                self.set_instance_param(instance, param_name, val)
+            }
+            Some(_) => self.set_model_param(model, param_name, val),
+            None if param_name == "dtemp" => self.set_instance_param(instance, param_name, val),
+            None => bail!("unknown parameter '{param_name}' for {}", dev.name),
+        }
+    }
+
     /// Create a new parameter with name `name` and optionally a default value
     ///
     /// # Returns
@@ -475,7 +522,7 @@ impl Circuit {
     ///
     /// The parameters index and an expression that can be used to read the parameter.
     /// `None` if no parameter `name` was found
-    pub fn lookup_param(&mut self, name: &str, earena: &Arena) -> Option<(CircuitParam, Expr)> {
+    pub fn lookup_param(&self, name: &str, earena: &Arena) -> Option<(CircuitParam, Expr)> {
         earena.lookup_param_by_name(self.ctx, name)
     }
 }
@@ -637,4 +684,8 @@ pub struct CircuitInstance {
     /// While linear devices (vsource, resistor, isource) will emit an error, Verilog-A devices
     /// usually handle this case using the `$terminal_connected` function.
     pub connections: Vec<Node>,
+
+    /// Offset (in K) of the temperature of this instance from the circuit temperature.
+    /// Set with the `dtemp` parameter if the device has no parameter with that name.
+    pub dtemp: Option<Expr>,
 }
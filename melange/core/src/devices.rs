@@ -9,10 +9,10 @@ use stdx::{impl_debug_display, impl_idx_from};
 use typed_index_collections::TiSlice;
 
 use crate::circuit::Node;
-pub use crate::devices::params::{DeviceParams, ParamId, Type};
+pub use crate::devices::params::{DeviceParams, ParamId, ParamInfo, Type};
 use crate::devices::resistor::Resistor;
 use crate::devices::vsource::VoltageSrc;
-use crate::simulation::{MatrixEntryIter, SimBuilder, SimInfo};
+use crate::simulation::{MatrixEntryIter, SimBuilder, SimInfo, SimParams};
 
 mod params;
 mod resistor;
@@ -26,7 +26,7 @@ pub trait DeviceImpl {
 }
 
 pub trait ModelImpl {
-    fn process_params(&self) -> Result<()>;
+    fn process_params(&self, sim_params: &SimParams) -> Result<()>;
     fn set_real_param(&self, param: ParamId, val: f64);
     fn set_int_param(&self, param: ParamId, _val: i32) {
         unreachable!("unknown int param {param:?}")
@@ -79,6 +79,13 @@ pub trait InstanceImpl {
     ) -> Result<()>;
     fn set_real_param(&mut self, param: ParamId, val: f64);
 
+    /// Recalculates the temperature dependent values of the instance for the temperature
+    /// `temp` (in K) after `process_params` was called. The unknowns and matrix entries of the
+    /// instance must not change.
+    fn update_temperature(&mut self, _temp: f64, _sim_params: &SimParams) -> Result<()> {
+        Ok(())
+    }
+
     fn set_int_param(&mut self, param: ParamId, _val: i32) {
         unreachable!("unknown int param {param:?}")
     }
@@ -94,8 +101,26 @@ pub trait InstanceImpl {
     unsafe fn load_matrix_resist(&self);
     unsafe fn load_matrix_react(&self, alpha: f64);
 
+    /// Like `load_matrix_resist` but every matrix entry pointer is shifted by `offset` (in
+    /// units of `f64`). Used by harmonic balance to store the jacobian of each time sample.
+    unsafe fn load_matrix_resist_with_offset(&self, offset: usize);
+    /// Like `load_matrix_react` with `alpha = 1` but every matrix entry pointer is shifted by
+    /// `offset` (in units of `f64`).
+    unsafe fn load_matrix_react_with_offset(&self, offset: usize);
+
     fn load_residual_react(&self, prev_solve: &TiSlice<Node, f64>, rhs: &mut TiSlice<Node, f64>);
     fn load_residual_resist(&self, prev_solve: &TiSlice<Node, f64>, rhs: &mut TiSlice<Node, f64>);
+
+    /// Adds `J(x_lim) (x_lim - x)` to `lim_rhs` for the unknowns that were changed by `$limit`
+    /// during the last call to `eval`.
+    fn load_limit_rhs_resist(&self, _lim_rhs: &mut TiSlice<Node, f64>) {}
+    fn load_limit_rhs_react(&self, _lim_rhs: &mut TiSlice<Node, f64>) {}
+
+    /// Whether `$limit` changed any value during the last call to `eval`. The Newton iteration
+    /// can not be considered converged if that is the case.
+    fn limited(&self) -> bool {
+        false
+    }
     fn load_ac_residual(
         &self,
         _dc_solve: &TiSlice<Node, f64>,
@@ -106,6 +131,33 @@ pub trait InstanceImpl {
     fn load_lead_current_resist(&self, _dc_solve: &TiSlice<Node, f64>, dst: &mut [f64]);
     fn load_lead_current_react(&self, _dc_solve: &TiSlice<Node, f64>, dst: &mut [f64]);
     fn load_ac_lead_current(&self, _ac_solve: &TiSlice<Node, Complex64>, _dst: &mut [Complex64]) {}
+
+    /// Returns the operating point variables calculated by the last call to `eval` with the
+    /// `CALC_OP` flag set.
+    fn op_vars(&self) -> Vec<OpVar> {
+        Vec::new()
+    }
+
+    /// Whether the device calculates the derivatives of the residual by its (real) parameters
+    /// when `eval` is called with the `CALC_PARAM_SENSITIVITY` flag. The DC sensitivity
+    /// analysis falls back to finite differences otherwise.
+    fn has_param_sensitivities(&self) -> bool {
+        false
+    }
+
+    /// Adds the derivative of the resistive residual by `param` (calculated by the last call
+    /// to `eval`) to `dst`. `param` may be an instance or a model parameter.
+    fn load_param_sensitivity(&self, _param: ParamId, _dst: &mut TiSlice<Node, f64>) {}
+}
+
+/// An operating point variable (for example the small signal parameters of a transistor)
+/// exposed by a device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpVar {
+    pub name: &'static str,
+    pub value: f64,
+    pub units: &'static str,
+    pub description: &'static str,
 }
 
 pub struct DeviceInfo {
@@ -34,11 +34,11 @@ impl DeviceParams {
         self.names.insert(name, param);
     }
 
-    pub(crate) fn lookup_param_id(&self, name: &str) -> Option<ParamId> {
+    pub fn lookup_param_id(&self, name: &str) -> Option<ParamId> {
         self.names.get(name).copied()
     }
 
-    pub(crate) fn lookup_param(&self, name: &str) -> Option<(ParamId, ParamInfo)> {
+    pub fn lookup_param(&self, name: &str) -> Option<(ParamId, ParamInfo)> {
         let id = self.lookup_param_id(name)?;
         Some((id, self.params[id]))
     }
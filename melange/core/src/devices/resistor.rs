@@ -9,7 +9,7 @@ use typed_index_collections::TiSlice;
 use super::{ModelImpl, ParamId, SimInfo};
 use crate::circuit::Node;
 use crate::devices::{update_matrix_entry, DeviceImpl, DeviceParams, InstanceImpl, Type};
-use crate::simulation::{MatrixEntryIter, SimBuilder};
+use crate::simulation::{MatrixEntryIter, SimBuilder, SimParams};
 
 pub struct Resistor;
 
@@ -52,7 +52,7 @@ struct ResistorModel {
 }
 
 impl ModelImpl for ResistorModel {
-    fn process_params(&self) -> Result<()> {
+    fn process_params(&self, _sim_params: &SimParams) -> Result<()> {
         Ok(())
     }
 
@@ -102,6 +102,16 @@ impl InstanceImpl for ResistorInstance {
 
     unsafe fn load_matrix_react(&self, _alpha: f64) {}
 
+    unsafe fn load_matrix_resist_with_offset(&self, offset: usize) {
+        let entry = |i: usize| &*self.matrix_entries[i].as_ptr().add(offset);
+        update_matrix_entry(entry(MATRIX_ANODE_ANODE), self.conductance);
+        update_matrix_entry(entry(MATRIX_ANODE_CATHODE), -self.conductance);
+        update_matrix_entry(entry(MATRIX_CATHODE_ANODE), -self.conductance);
+        update_matrix_entry(entry(MATRIX_CATHODE_CATHODE), self.conductance);
+    }
+
+    unsafe fn load_matrix_react_with_offset(&self, _offset: usize) {}
+
     fn load_residual_react(&self, _prev_solve: &TiSlice<Node, f64>, _rhs: &mut TiSlice<Node, f64>) {
     }
 
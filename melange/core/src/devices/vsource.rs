@@ -10,7 +10,7 @@ use typed_index_collections::TiSlice;
 use super::{ModelImpl, ParamId, SimInfo};
 use crate::circuit::Node;
 use crate::devices::{update_matrix_entry, DeviceImpl, DeviceParams, InstanceImpl, Type};
-use crate::simulation::{MatrixEntryIter, SimBuilder};
+use crate::simulation::{MatrixEntryIter, SimBuilder, SimParams};
 
 pub struct VoltageSrc;
 
@@ -59,7 +59,7 @@ struct VoltageSrcModel {
 }
 
 impl ModelImpl for VoltageSrcModel {
-    fn process_params(&self) -> Result<()> {
+    fn process_params(&self, _sim_params: &SimParams) -> Result<()> {
         Ok(())
     }
 
@@ -79,6 +79,7 @@ impl ModelImpl for VoltageSrcModel {
             cathode: Node::GROUND,
             branch: Node::GROUND,
             dc: self.dc.get(),
+            source_factor: 1.0,
             ac: Complex64::from_polar(self.mag.get(), self.phase.get()),
             matrix_entries: [NonNull::dangling(); 4],
         })
@@ -90,6 +91,8 @@ struct VoltageSrcInstance {
     cathode: Node,
     branch: Node,
     dc: f64,
+    /// scales `dc` during source stepping
+    source_factor: f64,
     ac: Complex64,
     matrix_entries: [NonNull<Cell<f64>>; 4],
 }
@@ -136,7 +139,8 @@ impl InstanceImpl for VoltageSrcInstance {
         }
     }
 
-    fn eval(&mut self, _sim_info: SimInfo<'_>) -> Result<()> {
+    fn eval(&mut self, sim_info: SimInfo<'_>) -> Result<()> {
+        self.source_factor = sim_info.source_factor;
         Ok(())
     }
 
@@ -149,13 +153,23 @@ impl InstanceImpl for VoltageSrcInstance {
 
     unsafe fn load_matrix_react(&self, _alpha: f64) {}
 
+    unsafe fn load_matrix_resist_with_offset(&self, offset: usize) {
+        let entry = |i: usize| &*self.matrix_entries[i].as_ptr().add(offset);
+        update_matrix_entry(entry(MATRIX_ANODE_BR), 1.0);
+        update_matrix_entry(entry(MATRIX_BR_ANODE), 1.0);
+        update_matrix_entry(entry(MATRIX_CATHODE_BR), -1.0);
+        update_matrix_entry(entry(MATRIX_BR_CATHODE), -1.0);
+    }
+
+    unsafe fn load_matrix_react_with_offset(&self, _offset: usize) {}
+
     fn load_residual_react(&self, _prev_solve: &TiSlice<Node, f64>, _rhs: &mut TiSlice<Node, f64>) {
     }
 
     fn load_residual_resist(&self, prev_solve: &TiSlice<Node, f64>, rhs: &mut TiSlice<Node, f64>) {
         rhs[self.anode] += prev_solve[self.branch];
         rhs[self.cathode] -= prev_solve[self.branch];
-        rhs[self.branch] -= self.dc;
+        rhs[self.branch] -= self.dc * self.source_factor;
         rhs[self.branch] += prev_solve[self.anode] - prev_solve[self.cathode];
     }
 
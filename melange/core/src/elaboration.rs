@@ -181,7 +181,14 @@ impl Circuit {
                 let terminals = self.elaborate_dev_terminals(dev, instance.terminal_connections)?;
                 let (inst, model) = self.new_device_instance(instance.name, dev, terminals)?;
                 for (param_name, val) in instance.parameters {
-                    self.set_model_param(model, &param_name, val)?;
+                    // the temperature offset belongs to the instance unless the device defines it
+                    if param_name == "dtemp"
+                        && self[dev].parameters.lookup_param_id("dtemp").is_none()
+                    {
+                        self[inst].dtemp = Some(val);
+                    } else {
+                        self.set_model_param(model, &param_name, val)?;
+                    }
                 }
                 inst
             }
@@ -632,6 +632,11 @@ impl Arena {
         Ok((param, read_expr))
     }
 
+    /// Interns a string so that it can be used as the value of a parameter
+    pub fn str_value(&mut self, val: &str) -> Value {
+        Value::Str(self.intern.get_or_intern(val))
+    }
+
     /// Add a new context for parameter
     pub fn add_ctx(&mut self) -> CircuitParamCtx {
         self.params.push_and_get_key(TiMap::default())
@@ -1,16 +1,17 @@
 pub use crate::circuit::Circuit;
 pub use crate::elaboration::CircuitDescription;
-pub use crate::expr::{Arena, Expr, ExprEvalCtx, Value};
+pub use crate::expr::{Arena, CircuitParam, Expr, ExprEvalCtx, Value};
 
 // #[macro_use]
 // mod utils;
 pub mod circuit;
-mod devices;
+pub mod devices;
 pub mod elaboration;
 mod expr;
+pub mod output;
 pub mod simulation;
 mod utils;
-mod veriloga;
+pub mod veriloga;
 
 // #[cfg(all(test, not(windows)))]
 // mod tests;
@@ -0,0 +1,301 @@
+//! Writers that export simulation results to files that can be read by waveform viewers.
+//!
+//! Results can be written in the ASCII and binary SPICE rawfile format (as produced by ngspice)
+//! or as CSV. Each unknown of the simulation becomes a variable/column that is named after the
+//! corresponding [`NodeInfo`]. The ground node is always zero and therefore omitted.
+//! Network parameters are written as Touchstone files.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use num_complex::Complex64;
+use typed_index_collections::{TiSlice, TiVec};
+
+use crate::circuit::Node;
+use crate::simulation::{NetworkParams, NodeInfo};
+
+#[cfg(test)]
+mod tests;
+
+/// A value that can be written to a result file.
+pub trait OutputValue: Copy {
+    const IS_COMPLEX: bool;
+    fn write_ascii(self, dst: &mut dyn Write) -> io::Result<()>;
+    fn write_binary(self, dst: &mut dyn Write) -> io::Result<()>;
+    fn write_csv(self, dst: &mut dyn Write) -> io::Result<()>;
+}
+
+impl OutputValue for f64 {
+    const IS_COMPLEX: bool = false;
+
+    fn write_ascii(self, dst: &mut dyn Write) -> io::Result<()> {
+        write!(dst, "{self:.15e}")
+    }
+
+    fn write_binary(self, dst: &mut dyn Write) -> io::Result<()> {
+        dst.write_all(&self.to_le_bytes())
+    }
+
+    fn write_csv(self, dst: &mut dyn Write) -> io::Result<()> {
+        write!(dst, "{self:e}")
+    }
+}
+
+impl OutputValue for Complex64 {
+    const IS_COMPLEX: bool = true;
+
+    fn write_ascii(self, dst: &mut dyn Write) -> io::Result<()> {
+        write!(dst, "{:.15e},{:.15e}", self.re, self.im)
+    }
+
+    fn write_binary(self, dst: &mut dyn Write) -> io::Result<()> {
+        dst.write_all(&self.re.to_le_bytes())?;
+        dst.write_all(&self.im.to_le_bytes())
+    }
+
+    fn write_csv(self, dst: &mut dyn Write) -> io::Result<()> {
+        write!(dst, "{:e},{:e}", self.re, self.im)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RawFormat {
+    Ascii,
+    Binary,
+}
+
+/// The independent variable of a sweep.
+#[derive(Clone, Copy, Debug)]
+pub struct SweepVar<'a> {
+    pub name: &'a str,
+    pub units: &'a str,
+    pub values: &'a [f64],
+}
+
+/// The results of a single analysis.
+pub struct Results<'a, T> {
+    plot_name: &'static str,
+    nodes: &'a TiSlice<Node, NodeInfo>,
+    sweep: Option<SweepVar<'a>>,
+    points: Vec<&'a TiSlice<Node, T>>,
+}
+
+impl<'a> Results<'a, f64> {
+    /// The results of [`dc_op`](crate::simulation::Simulation::dc_op).
+    pub fn op(nodes: &'a TiSlice<Node, NodeInfo>, solution: &'a TiSlice<Node, f64>) -> Self {
+        Results { plot_name: "Operating Point", nodes, sweep: None, points: vec![solution] }
+    }
+
+    /// The results of [`dc_sweep`](crate::simulation::Simulation::dc_sweep).
+    /// `sweep` contains the values assigned to the swept source.
+    pub fn dc_sweep(
+        nodes: &'a TiSlice<Node, NodeInfo>,
+        sweep: SweepVar<'a>,
+        solutions: &'a [TiVec<Node, f64>],
+    ) -> Self {
+        Self::sweep("DC transfer characteristic", nodes, sweep, solutions)
+    }
+}
+
+impl<'a> Results<'a, Complex64> {
+    /// The results of [`ac`](crate::simulation::Simulation::ac) performed at each frequency in
+    /// `freqs`.
+    pub fn ac_sweep(
+        nodes: &'a TiSlice<Node, NodeInfo>,
+        freqs: &'a [f64],
+        solutions: &'a [TiVec<Node, Complex64>],
+    ) -> Self {
+        let sweep = SweepVar { name: "frequency", units: "Hz", values: freqs };
+        Self::sweep("AC Analysis", nodes, sweep, solutions)
+    }
+}
+
+impl<'a, T: OutputValue> Results<'a, T> {
+    fn sweep(
+        plot_name: &'static str,
+        nodes: &'a TiSlice<Node, NodeInfo>,
+        sweep: SweepVar<'a>,
+        solutions: &'a [TiVec<Node, T>],
+    ) -> Self {
+        assert_eq!(sweep.values.len(), solutions.len(), "each sweep point requires a solution");
+        let points = solutions.iter().map(|solution| &**solution).collect();
+        Results { plot_name, nodes, sweep: Some(sweep), points }
+    }
+
+    fn unknowns(&self) -> impl Iterator<Item = (Node, &'a NodeInfo)> + 'a {
+        self.nodes.iter_enumerated().filter(|(node, _)| *node != Node::GROUND)
+    }
+
+    fn rawfile_type(units: &str) -> &'static str {
+        match units {
+            "V" => "voltage",
+            "A" => "current",
+            "Hz" => "frequency",
+            "s" => "time",
+            _ => "notype",
+        }
+    }
+
+    /// Writes the results in the SPICE rawfile format.
+    pub fn write_raw(&self, dst: &mut dyn Write, title: &str, format: RawFormat) -> io::Result<()> {
+        let num_vars = self.unknowns().count() + self.sweep.is_some() as usize;
+        let flags = if T::IS_COMPLEX { "complex" } else { "real" };
+        writeln!(dst, "Title: {title}")?;
+        writeln!(dst, "Plotname: {}", self.plot_name)?;
+        writeln!(dst, "Flags: {flags}")?;
+        writeln!(dst, "No. Variables: {num_vars}")?;
+        writeln!(dst, "No. Points: {}", self.points.len())?;
+        writeln!(dst, "Variables:")?;
+
+        let mut i = 0;
+        if let Some(sweep) = self.sweep {
+            writeln!(dst, "\t0\t{}\t{}", sweep.name, Self::rawfile_type(sweep.units))?;
+            i += 1;
+        }
+        for (_, info) in self.unknowns() {
+            writeln!(dst, "\t{i}\t{}\t{}", info.name, Self::rawfile_type(info.units))?;
+            i += 1;
+        }
+
+        match format {
+            RawFormat::Ascii => {
+                writeln!(dst, "Values:")?;
+                for (point, solution) in self.points.iter().enumerate() {
+                    write!(dst, " {point}")?;
+                    // in complex plots the sweep variable is complex too
+                    if let Some(sweep) = self.sweep {
+                        write!(dst, "\t")?;
+                        if T::IS_COMPLEX {
+                            Complex64::from(sweep.values[point]).write_ascii(dst)?;
+                        } else {
+                            sweep.values[point].write_ascii(dst)?;
+                        }
+                        writeln!(dst)?;
+                    }
+                    for (node, _) in self.unknowns() {
+                        write!(dst, "\t")?;
+                        solution[node].write_ascii(dst)?;
+                        writeln!(dst)?;
+                    }
+                    writeln!(dst)?;
+                }
+            }
+            RawFormat::Binary => {
+                writeln!(dst, "Binary:")?;
+                for (point, solution) in self.points.iter().enumerate() {
+                    if let Some(sweep) = self.sweep {
+                        if T::IS_COMPLEX {
+                            Complex64::from(sweep.values[point]).write_binary(dst)?;
+                        } else {
+                            sweep.values[point].write_binary(dst)?;
+                        }
+                    }
+                    for (node, _) in self.unknowns() {
+                        solution[node].write_binary(dst)?;
+                    }
+                }
+            }
+        }
+
+        dst.flush()
+    }
+
+    /// Writes the results as CSV with one row per sweep point. Complex values are split into
+    /// two columns containing the real and imaginary part.
+    pub fn write_csv(&self, dst: &mut dyn Write) -> io::Result<()> {
+        let mut columns = Vec::new();
+        if let Some(sweep) = self.sweep {
+            columns.push(format!("{} [{}]", sweep.name, sweep.units));
+        }
+        for (_, info) in self.unknowns() {
+            if T::IS_COMPLEX {
+                columns.push(format!("re({}) [{}]", info.name, info.units));
+                columns.push(format!("im({}) [{}]", info.name, info.units));
+            } else {
+                columns.push(format!("{} [{}]", info.name, info.units));
+            }
+        }
+        writeln!(dst, "{}", columns.join(","))?;
+
+        for (point, solution) in self.points.iter().enumerate() {
+            let mut first = true;
+            if let Some(sweep) = self.sweep {
+                sweep.values[point].write_csv(dst)?;
+                first = false;
+            }
+            for (node, _) in self.unknowns() {
+                if !first {
+                    write!(dst, ",")?;
+                }
+                solution[node].write_csv(dst)?;
+                first = false;
+            }
+            writeln!(dst)?;
+        }
+
+        dst.flush()
+    }
+}
+
+/// The kind of network parameters written to a Touchstone file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NetworkParam {
+    S,
+    Y,
+    Z,
+}
+
+impl fmt::Display for NetworkParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NetworkParam::S => "S",
+            NetworkParam::Y => "Y",
+            NetworkParam::Z => "Z",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Writes network parameters in the Touchstone (version 1) format using real/imaginary pairs.
+/// Y and Z-parameters are normalized to the reference impedance as required by the format.
+/// The file should be saved with the extension `.sNp` where `N` is the number of ports.
+pub fn write_touchstone(
+    dst: &mut dyn Write,
+    params: &NetworkParams,
+    kind: NetworkParam,
+) -> io::Result<()> {
+    let num_ports = params.num_ports();
+    writeln!(dst, "! {num_ports}-port network parameters")?;
+    writeln!(dst, "# Hz {kind} RI R {}", params.z0)?;
+
+    for (i, freq) in params.freqs.iter().enumerate() {
+        let (matrix, scale) = match kind {
+            NetworkParam::S => (&params.s[i], 1.0),
+            NetworkParam::Y => (&params.y[i], params.z0),
+            NetworkParam::Z => (&params.z[i], 1.0 / params.z0),
+        };
+        write!(dst, "{freq:.15e}")?;
+        if num_ports == 2 {
+            // two-port data is the only case that is written in column major order
+            for (row, col) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let val = matrix[(row, col)] * scale;
+                write!(dst, " {:.15e} {:.15e}", val.re, val.im)?;
+            }
+            writeln!(dst)?;
+        } else {
+            for row in 0..num_ports {
+                for col in 0..num_ports {
+                    // lines may contain at most four entries
+                    if col != 0 && col % 4 == 0 {
+                        writeln!(dst)?;
+                    }
+                    let val = matrix[(row, col)] * scale;
+                    write!(dst, " {:.15e} {:.15e}", val.re, val.im)?;
+                }
+                writeln!(dst)?;
+            }
+        }
+    }
+
+    dst.flush()
+}
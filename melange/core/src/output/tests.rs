@@ -0,0 +1,328 @@
+use anyhow::Result;
+use num_complex::Complex64;
+use typed_index_collections::TiVec;
+
+use super::{write_touchstone, NetworkParam, RawFormat, Results, SweepVar};
+use crate::circuit::Node;
+use crate::expr::CircuitParam;
+use crate::simulation::{NodeInfo, Port, SimConfig};
+use crate::{Arena, Circuit, ExprEvalCtx};
+
+fn node_info(name: &str, units: &'static str, is_flow: bool) -> NodeInfo {
+    NodeInfo { name: name.to_owned(), atol: 1e-6, units, residual_units: "A", is_flow }
+}
+
+fn nodes() -> TiVec<Node, NodeInfo> {
+    vec![
+        node_info("ground", "V", false),
+        node_info("x", "V", false),
+        node_info("vsrc.branch", "A", true),
+    ]
+    .into()
+}
+
+/// A parsed rawfile, complex values are returned for real plots too.
+struct Rawfile {
+    header: Vec<String>,
+    values: Vec<Vec<Complex64>>,
+}
+
+fn header_val<'a>(header: &'a [String], key: &str) -> &'a str {
+    header.iter().find_map(|line| line.strip_prefix(key)).expect("missing header").trim()
+}
+
+fn parse_raw(data: &[u8]) -> Rawfile {
+    let mut header = Vec::new();
+    let mut pos = 0;
+    let format = loop {
+        let end = pos + data[pos..].iter().position(|&c| c == b'\n').expect("unterminated header");
+        let line = std::str::from_utf8(&data[pos..end]).unwrap().to_owned();
+        pos = end + 1;
+        match &*line {
+            "Values:" => break RawFormat::Ascii,
+            "Binary:" => break RawFormat::Binary,
+            _ => header.push(line),
+        }
+    };
+
+    let num_vars: usize = header_val(&header, "No. Variables:").parse().unwrap();
+    let num_points: usize = header_val(&header, "No. Points:").parse().unwrap();
+    let complex = header_val(&header, "Flags:") == "complex";
+
+    let values = match format {
+        RawFormat::Ascii => {
+            let mut tokens = std::str::from_utf8(&data[pos..]).unwrap().split_whitespace();
+            let values = (0..num_points)
+                .map(|point| {
+                    assert_eq!(tokens.next(), Some(&*point.to_string()));
+                    (0..num_vars)
+                        .map(|_| {
+                            let token = tokens.next().unwrap();
+                            if complex {
+                                let (re, im) = token.split_once(',').unwrap();
+                                Complex64::new(re.parse().unwrap(), im.parse().unwrap())
+                            } else {
+                                token.parse::<f64>().unwrap().into()
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            assert_eq!(tokens.next(), None);
+            values
+        }
+        RawFormat::Binary => {
+            let mut vals = data[pos..]
+                .chunks_exact(8)
+                .map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap()));
+            let values = (0..num_points)
+                .map(|_| {
+                    (0..num_vars)
+                        .map(|_| {
+                            let re = vals.next().unwrap();
+                            let im = if complex { vals.next().unwrap() } else { 0.0 };
+                            Complex64::new(re, im)
+                        })
+                        .collect()
+                })
+                .collect();
+            assert_eq!(vals.next(), None);
+            assert_eq!((data.len() - pos) % 8, 0);
+            values
+        }
+    };
+
+    Rawfile { header, values }
+}
+
+fn assert_values_eq(val: &[Vec<Complex64>], ref_val: &[Vec<Complex64>], rtol: f64) {
+    assert_eq!(val.len(), ref_val.len());
+    for (point, ref_point) in val.iter().zip(ref_val) {
+        assert_eq!(point.len(), ref_point.len());
+        for (val, ref_val) in point.iter().zip(ref_point) {
+            assert!((val - ref_val).norm() <= rtol * ref_val.norm(), "{val} != {ref_val}");
+        }
+    }
+}
+
+const SWEEP: [f64; 3] = [0.0, 0.5, 1.0 / 3.0];
+
+fn dc_solutions() -> Vec<TiVec<Node, f64>> {
+    SWEEP.iter().map(|&v| vec![0.0, v, -v / 3e3 - 2.5e-13].into()).collect()
+}
+
+fn ac_solutions() -> Vec<TiVec<Node, Complex64>> {
+    SWEEP
+        .iter()
+        .map(|&v| {
+            vec![0.0.into(), Complex64::new(v, -1e-300), Complex64::new(1e300, v / 7.0)].into()
+        })
+        .collect()
+}
+
+/// The values of the sweep variable followed by the unknowns at each point.
+fn expected<T: Copy + Into<Complex64>>(
+    solutions: &[TiVec<Node, T>],
+    freqs: &[f64],
+) -> Vec<Vec<Complex64>> {
+    solutions
+        .iter()
+        .zip(freqs)
+        .map(|(solution, &freq)| {
+            let mut point = vec![freq.into()];
+            point.extend(solution.raw[1..].iter().map(|&val| val.into()));
+            point
+        })
+        .collect()
+}
+
+#[test]
+fn rawfile_dc_sweep() {
+    let nodes = nodes();
+    let solutions = dc_solutions();
+    let sweep = SweepVar { name: "v-sweep", units: "V", values: &SWEEP };
+    let results = Results::dc_sweep(&nodes, sweep, &solutions);
+    let expected = expected(&solutions, &SWEEP);
+
+    for (format, rtol) in [(RawFormat::Ascii, 1e-15), (RawFormat::Binary, 0.0)] {
+        let mut buf = Vec::new();
+        results.write_raw(&mut buf, "dc sweep", format).unwrap();
+        let raw = parse_raw(&buf);
+        assert_eq!(
+            raw.header,
+            [
+                "Title: dc sweep",
+                "Plotname: DC transfer characteristic",
+                "Flags: real",
+                "No. Variables: 3",
+                "No. Points: 3",
+                "Variables:",
+                "\t0\tv-sweep\tvoltage",
+                "\t1\tx\tvoltage",
+                "\t2\tvsrc.branch\tcurrent",
+            ]
+        );
+        assert_values_eq(&raw.values, &expected, rtol);
+    }
+}
+
+#[test]
+fn rawfile_ac_sweep() {
+    let nodes = nodes();
+    let solutions = ac_solutions();
+    let freqs = [1.0, 1e3, 1e9];
+    let results = Results::ac_sweep(&nodes, &freqs, &solutions);
+    let expected = expected(&solutions, &freqs);
+
+    for (format, rtol) in [(RawFormat::Ascii, 1e-15), (RawFormat::Binary, 0.0)] {
+        let mut buf = Vec::new();
+        results.write_raw(&mut buf, "ac", format).unwrap();
+        let raw = parse_raw(&buf);
+        assert_eq!(header_val(&raw.header, "Plotname:"), "AC Analysis");
+        assert_eq!(header_val(&raw.header, "Flags:"), "complex");
+        assert!(raw.header.iter().any(|line| line == "\t0\tfrequency\tfrequency"));
+        assert_values_eq(&raw.values, &expected, rtol);
+    }
+}
+
+#[test]
+fn rawfile_op() {
+    let nodes = nodes();
+    let solution: TiVec<Node, f64> = vec![0.0, 1.0, -1e-3].into();
+    let mut buf = Vec::new();
+    Results::op(&nodes, &solution).write_raw(&mut buf, "op", RawFormat::Ascii).unwrap();
+    let raw = parse_raw(&buf);
+    assert_eq!(header_val(&raw.header, "No. Variables:"), "2");
+    assert_values_eq(&raw.values, &[vec![Complex64::from(1.0), Complex64::from(-1e-3)]], 1e-15);
+}
+
+fn parse_csv(data: &[u8]) -> (Vec<String>, Vec<Vec<f64>>) {
+    let data = std::str::from_utf8(data).unwrap();
+    let mut lines = data.lines();
+    let columns = lines.next().unwrap().split(',').map(str::to_owned).collect();
+    let rows =
+        lines.map(|line| line.split(',').map(|val| val.parse().unwrap()).collect()).collect();
+    (columns, rows)
+}
+
+#[test]
+fn csv_dc_sweep() {
+    let nodes = nodes();
+    let solutions = dc_solutions();
+    let sweep = SweepVar { name: "v-sweep", units: "V", values: &SWEEP };
+    let mut buf = Vec::new();
+    Results::dc_sweep(&nodes, sweep, &solutions).write_csv(&mut buf).unwrap();
+
+    let (columns, rows) = parse_csv(&buf);
+    assert_eq!(columns, ["v-sweep [V]", "x [V]", "vsrc.branch [A]"]);
+    let expected: Vec<Vec<f64>> = SWEEP
+        .iter()
+        .zip(&solutions)
+        .map(|(&v, solution)| vec![v, solution.raw[1], solution.raw[2]])
+        .collect();
+    // CSV values use the shortest representation that round-trips exactly
+    assert_eq!(rows, expected);
+}
+
+#[test]
+fn csv_ac_sweep() {
+    let nodes = nodes();
+    let solutions = ac_solutions();
+    let freqs = [1.0, 1e3, 1e9];
+    let mut buf = Vec::new();
+    Results::ac_sweep(&nodes, &freqs, &solutions).write_csv(&mut buf).unwrap();
+
+    let (columns, rows) = parse_csv(&buf);
+    assert_eq!(
+        columns,
+        ["frequency [Hz]", "re(x) [V]", "im(x) [V]", "re(vsrc.branch) [A]", "im(vsrc.branch) [A]"]
+    );
+    let expected: Vec<Vec<f64>> = freqs
+        .iter()
+        .zip(&solutions)
+        .map(|(&freq, solution)| {
+            let [_, x, br] = [solution.raw[0], solution.raw[1], solution.raw[2]];
+            vec![freq, x.re, x.im, br.re, br.im]
+        })
+        .collect();
+    assert_eq!(rows, expected);
+}
+
+/// Parses the frequencies and the (row major) port matrices of a Touchstone file.
+fn parse_touchstone(data: &[u8], num_ports: usize) -> (Vec<String>, Vec<(f64, Vec<Complex64>)>) {
+    let data = std::str::from_utf8(data).unwrap();
+    let (header, body): (Vec<_>, Vec<_>) =
+        data.lines().partition(|line| line.starts_with('!') || line.starts_with('#'));
+    let header = header.into_iter().map(str::to_owned).collect();
+    let tokens: Vec<f64> = body
+        .iter()
+        .flat_map(|line| line.split_whitespace())
+        .map(|val| val.parse().unwrap())
+        .collect();
+
+    let points = tokens
+        .chunks_exact(1 + 2 * num_ports * num_ports)
+        .map(|point| {
+            let vals: Vec<_> =
+                point[1..].chunks_exact(2).map(|val| Complex64::new(val[0], val[1])).collect();
+            // two-port data is written in column major order
+            let vals = if num_ports == 2 { vec![vals[0], vals[2], vals[1], vals[3]] } else { vals };
+            (point[0], vals)
+        })
+        .collect();
+    (header, points)
+}
+
+#[test]
+fn touchstone_resistor_network() -> Result<()> {
+    // T-network: r1 from p1 to mid, r2 from p2 to mid and r3 from mid to ground
+    // Z = [[r1 + r3, r3], [r3, r2 + r3]]
+    let mut arena = Arena::new();
+    let mut circ = Circuit::new("t_network".to_owned(), &mut arena);
+    let gnd = circ.lookup_node("ground").expect("ground node");
+    let p1 = circ.node("p1".to_owned());
+    let p2 = circ.node("p2".to_owned());
+    let mid = circ.node("mid".to_owned());
+    for (name, nodes, r) in
+        [("r1", [p1, mid], 10.0), ("r2", [p2, mid], 20.0), ("r3", [mid, gnd], 50.0)]
+    {
+        let (res, _) =
+            circ.new_device_instance_by_name(name.to_owned(), "resistor", nodes.to_vec())?;
+        circ.set_instance_param(res, "r", r.into())?;
+    }
+
+    let mut ctx = ExprEvalCtx::new(&arena);
+    ctx.set_param(CircuitParam::TEMPERATURE, 300.0.into());
+    let mut sim = circ.prepare_simulation(ctx.borrow(), &arena, SimConfig::default())?;
+    let ports = [Port { pos: p1, neg: gnd }, Port { pos: p2, neg: gnd }];
+    let freqs = [1e6, 1e9];
+    let params = sim.sparams(&ports, 50.0, &freqs)?;
+
+    // Y and Z are normalized to z0 = 50
+    let expected = [
+        (NetworkParam::S, [-13.0 / 107.0, 50.0 / 107.0, 50.0 / 107.0, -3.0 / 107.0]),
+        (NetworkParam::Y, [70.0 / 34.0, -50.0 / 34.0, -50.0 / 34.0, 60.0 / 34.0]),
+        (NetworkParam::Z, [1.2, 1.0, 1.0, 1.4]),
+    ];
+    for (kind, expected) in expected {
+        let mut buf = Vec::new();
+        write_touchstone(&mut buf, &params, kind)?;
+        let (header, points) = parse_touchstone(&buf, 2);
+        assert_eq!(
+            header,
+            ["! 2-port network parameters".to_owned(), format!("# Hz {kind} RI R 50")]
+        );
+        assert_eq!(points.len(), freqs.len());
+        for ((freq, vals), ref_freq) in points.iter().zip(freqs) {
+            assert_eq!(*freq, ref_freq);
+            for (val, ref_val) in vals.iter().zip(expected) {
+                assert!(
+                    (*val - ref_val).norm() <= 1e-9 * ref_val.abs(),
+                    "{kind}: {val} != {ref_val}"
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
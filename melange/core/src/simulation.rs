@@ -1,24 +1,38 @@
+use std::f64::consts::TAU;
 use std::mem::replace;
 use std::rc::Rc;
 
 use anyhow::{bail, Context, Result};
 use cli_table::{print_stdout, Cell, Style, Table, TableStruct};
 use klu_rs::{FixedKluMatrix, KluData};
+use log::{debug, info};
 use num_complex::Complex64;
 use stdx::iter::zip;
 use typed_index_collections::{TiSlice, TiVec};
 
 use crate::circuit::{CircuitModelSrc, InstanceId, ModelId, Node};
-use crate::devices::{InstanceImpl, ModelImpl, Type};
+use crate::devices::{update_matrix_entry, InstanceImpl, ModelImpl, OpVar, Type};
 use crate::expr::{CircuitParam, ExprEvalCtxRef};
 use crate::simulation::flags::{EvalFlags, OperatingPointAnalysis, SimulationState};
+pub use crate::simulation::hb::HbSolution;
+pub use crate::simulation::homotopy::Homotopy;
 pub use crate::simulation::matrix::MatrixEntryIter;
 use crate::simulation::matrix::{MatrixBuilder, SimulationMatrix};
+pub use crate::simulation::network::{NetworkParams, Port, PortMatrix};
+pub use crate::simulation::pz::{damping, natural_freq, PzResult};
+pub use crate::simulation::sensitivity::SensParam;
+pub use crate::simulation::sim_params::SimParams;
 use crate::utils::PrettyPrint;
 use crate::{Arena, Circuit, Value};
 
 mod flags;
+mod hb;
+mod homotopy;
 mod matrix;
+mod network;
+mod pz;
+mod sensitivity;
+mod sim_params;
 
 pub struct Simulation<'a> {
     circ: &'a Circuit,
@@ -34,7 +48,24 @@ pub struct Simulation<'a> {
     ac_solution: TiVec<Node, Complex64>,
     residual_resist: TiVec<Node, f64>,
     residual_react: TiVec<Node, f64>,
+    lim_rhs_resist: TiVec<Node, f64>,
+    lim_rhs_react: TiVec<Node, f64>,
+    /// conductance from every node to `shunt_ref`, used by gmin stepping and pseudo-transient
+    /// continuation
+    shunt_conductance: f64,
+    shunt_ref: TiVec<Node, f64>,
+    /// factor that all independent sources are scaled by, used by source stepping
+    source_factor: f64,
+    /// whether `$limit` has values from a previous iteration
+    lim_initialized: bool,
+    op_homotopy: Option<Homotopy>,
     pub config: SimConfig,
+    /// values of `$simparam`, derived from `config` by `prepare_solver`
+    sim_params: SimParams,
+    /// temperature offset of each instance from the circuit temperature
+    dtemp: TiVec<InstanceId, f64>,
+    /// temperature each instance was last set up for
+    instance_temps: TiVec<InstanceId, f64>,
     state: SimulationState,
 
     omega: f64,
@@ -46,6 +77,8 @@ pub struct NodeInfo {
     pub atol: f64,
     pub units: &'static str,
     pub residual_units: &'static str,
+    /// whether this unknown is a flow (branch current) instead of a potential
+    pub is_flow: bool,
 }
 
 pub struct SimBuilder<'a> {
@@ -54,6 +87,7 @@ pub struct SimBuilder<'a> {
     node_info: &'a mut TiVec<Node, NodeInfo>,
     circ: &'a Circuit,
     pub config: &'a SimConfig,
+    pub sim_params: &'a SimParams,
 }
 
 impl<'a> SimBuilder<'a> {
@@ -63,11 +97,11 @@ impl<'a> SimBuilder<'a> {
     }
 
     pub fn new_internal_branch(&mut self, name: &'static str) -> Node {
-        self.new_internal_unknown(name, self.config.voltage_atol, "V", "A")
+        self.new_internal_unknown(name, self.config.voltage_atol, "V", "A", true)
     }
 
     pub fn new_internal_node(&mut self, name: &'static str) -> Node {
-        self.new_internal_unknown(name, self.config.current_atol, "A", "V")
+        self.new_internal_unknown(name, self.config.current_atol, "A", "V", false)
     }
     pub fn new_internal_unknown(
         &mut self,
@@ -75,9 +109,10 @@ impl<'a> SimBuilder<'a> {
         atol: f64,
         units: &'static str,
         residual_units: &'static str,
+        is_flow: bool,
     ) -> Node {
         let name = format!("{}::{name}", self.circ[self.instance].name);
-        self.node_info.push_and_get_key(NodeInfo { atol, name, units, residual_units })
+        self.node_info.push_and_get_key(NodeInfo { atol, name, units, residual_units, is_flow })
     }
 
     pub fn ensure_matrix_entry(&mut self, column: Node, row: Node) {
@@ -121,6 +156,7 @@ impl Circuit {
                 atol: config.current_atol,
                 units: "V",
                 residual_units: "A",
+                is_flow: false,
             })
             .collect();
 
@@ -137,6 +173,16 @@ impl Circuit {
             ac_solution: vec![Complex64::default(); self.num_nodes() as usize].into(),
             residual_resist: vec![0f64; self.num_nodes() as usize].into(),
             residual_react: vec![0f64; self.num_nodes() as usize].into(),
+            lim_rhs_resist: vec![0f64; self.num_nodes() as usize].into(),
+            lim_rhs_react: vec![0f64; self.num_nodes() as usize].into(),
+            shunt_conductance: 0.0,
+            shunt_ref: vec![0f64; self.num_nodes() as usize].into(),
+            source_factor: 1.0,
+            lim_initialized: false,
+            op_homotopy: None,
+            sim_params: SimParams::new(),
+            dtemp: vec![0f64; self.num_instances() as usize].into(),
+            instance_temps: vec![0f64; self.num_instances() as usize].into(),
             omega: 1.0,
         };
 
@@ -154,6 +200,11 @@ impl Simulation<'_> {
         res.table()
     }
 
+    /// Information about every unknown of the simulation, indexed like the solution.
+    pub fn nodes(&self) -> &TiSlice<Node, NodeInfo> {
+        &self.nodes
+    }
+
     pub fn print_solution(&self) {
         print_stdout(Self::vec_table(&self.solution.raw, &self.nodes.raw)).unwrap()
     }
@@ -201,8 +252,21 @@ impl Simulation<'_> {
         Ok(Self::matrix_table(&self.nodes, matrix))
     }
 
-    pub fn prepare_solver(&mut self, mut eval_ctx: ExprEvalCtxRef, arena: &Arena) -> Result<()> {
+    pub fn prepare_solver(&mut self, eval_ctx: ExprEvalCtxRef, arena: &Arena) -> Result<()> {
+        self.prepare_solver_perturbed(eval_ctx, arena, None)
+    }
+
+    /// Like [`prepare_solver`](Self::prepare_solver) but `delta` is added to the value of
+    /// `param` if `perturbation` is `Some((param, delta))`. Used to calculate sensitivities with
+    /// finite differences.
+    fn prepare_solver_perturbed(
+        &mut self,
+        mut eval_ctx: ExprEvalCtxRef,
+        arena: &Arena,
+        perturbation: Option<(SensParam, f64)>,
+    ) -> Result<()> {
         self.wipe_solution();
+        self.sim_params = SimParams::from_config(&self.config);
         for param in arena.ctx_params(self.circ.ctx) {
             if self.circ.param_assignments.contains_key(&param) {
                 continue;
@@ -215,9 +279,21 @@ impl Simulation<'_> {
             }
         }
 
+        if let Some((SensParam::Circuit(param), delta)) = perturbation {
+            if !self.circ.param_assignments.contains_key(&param) {
+                let val = eval_ctx[param].to_num()?;
+                eval_ctx.set_param(param, (val + delta).into());
+            }
+        }
+
         for (&param, &val) in self.circ.param_assignments.iter() {
-            let val = val.eval(eval_ctx.borrow());
-            eval_ctx.set_param(param, val?);
+            let mut val = val.eval(eval_ctx.borrow())?;
+            if let Some((SensParam::Circuit(dst), delta)) = perturbation {
+                if dst == param {
+                    val = (val.to_num()? + delta).into();
+                }
+            }
+            eval_ctx.set_param(param, val);
         }
 
         for model in self.circ.models() {
@@ -239,7 +315,12 @@ impl Simulation<'_> {
                 };
                 match dev.parameters[param].ty {
                     Type::Real => {
-                        let val = val.eval_num(eval_ctx.borrow()).with_context(context)?;
+                        let mut val = val.eval_num(eval_ctx.borrow()).with_context(context)?;
+                        if let Some((SensParam::Model(dst, dst_param), delta)) = perturbation {
+                            if dst == model && dst_param == param {
+                                val += delta;
+                            }
+                        }
                         model_data.set_real_param(param, val);
                     }
 
@@ -256,7 +337,7 @@ impl Simulation<'_> {
                     }
                 }
             }
-            model_data.process_params()?;
+            model_data.process_params(&self.sim_params)?;
         }
 
         self.matrix_builder.reset(self.circ);
@@ -268,6 +349,7 @@ impl Simulation<'_> {
             matrix_builder: &mut self.matrix_builder,
             node_info: &mut self.nodes,
             config: &self.config,
+            sim_params: &self.sim_params,
         };
 
         let temp = eval_ctx[CircuitParam::TEMPERATURE].to_num().context("invalid tempetaure")?;
@@ -285,7 +367,12 @@ impl Simulation<'_> {
                 };
                 match dev.parameters[param].ty {
                     Type::Real => {
-                        let val = val.eval_num(eval_ctx.borrow()).with_context(context)?;
+                        let mut val = val.eval_num(eval_ctx.borrow()).with_context(context)?;
+                        if let Some((SensParam::Instance(dst, dst_param), delta)) = perturbation {
+                            if dst == inst && dst_param == param {
+                                val += delta;
+                            }
+                        }
                         instance_data.set_real_param(param, val);
                     }
                     Type::Int => {
@@ -302,16 +389,35 @@ impl Simulation<'_> {
                 }
             }
 
+            let dtemp = match instance_info.dtemp {
+                Some(val) => val.eval_num(eval_ctx.borrow()).with_context(|| {
+                    format!("while evaluating dtemp of instance '{}'", instance_info.name)
+                })?,
+                None => 0.0,
+            };
+            self.dtemp[inst] = dtemp;
+            self.instance_temps[inst] = temp + dtemp;
+
             builder.process_instance(inst);
-            instance_data.process_params(temp, &mut builder, &self.circ[inst].connections)?;
+            instance_data.process_params(temp + dtemp, &mut builder, &instance_info.connections)?;
         }
 
         let num_nodes = self.nodes.len();
         self.solution.resize(num_nodes, 0f64);
         self.residual_resist.resize(num_nodes, 0f64);
         self.residual_react.resize(num_nodes, 0f64);
+        self.lim_rhs_resist.resize(num_nodes, 0f64);
+        self.lim_rhs_react.resize(num_nodes, 0f64);
+        self.shunt_ref.resize(num_nodes, 0f64);
         self.ac_solution.resize(num_nodes, Complex64::default());
 
+        // the homotopies add a conductance to ground to every node
+        for (node, node_info) in self.nodes.iter_enumerated().skip(1) {
+            if !node_info.is_flow {
+                self.matrix_builder.ensure_diagonal(node);
+            }
+        }
+
         let matrix = SimulationMatrix::new_or_reset(self.matrix.take(), &self.matrix_builder);
         for (instance, data) in self.instance_data.iter_mut_enumerated() {
             let instance_entries = MatrixEntryIter::new(&matrix, &self.matrix_builder, instance);
@@ -324,6 +430,7 @@ impl Simulation<'_> {
 
     pub fn wipe_solution(&mut self) {
         self.solution.raw.fill(0.0);
+        self.lim_initialized = false;
         self.state.clear()
     }
 
@@ -359,6 +466,7 @@ impl Simulation<'_> {
 
     pub fn set_initial_guess(&mut self, guess: &TiSlice<Node, f64>) {
         self.solution.copy_from_slice(guess);
+        self.lim_initialized = false;
         self.state.clear();
     }
 
@@ -369,6 +477,18 @@ impl Simulation<'_> {
             return Ok(());
         }
 
+        let homotopy = self.solve_op_homotopy(analysis)?;
+        if homotopy != Homotopy::Newton {
+            info!("operating point found with {homotopy}");
+        }
+        self.op_homotopy = Some(homotopy);
+        self.state = op_flag;
+        Ok(())
+    }
+
+    /// Runs Newton iterations starting at the current solution. Returns whether the iteration
+    /// converged within `maxiters` iterations.
+    fn newton(&mut self, analysis: OperatingPointAnalysis) -> Result<bool> {
         let flags = analysis.eval_flags();
 
         let debug = self.config.debug;
@@ -377,18 +497,60 @@ impl Simulation<'_> {
 
         let mut i = 0;
         loop {
-            let sim_info = SimInfo { abstime: 0f64, prev_solve: &self.solution, flags };
+            // $limit has no previous values to start from if the solution was reset
+            let flags = if self.lim_initialized { flags } else { flags | EvalFlags::INIT_LIM };
+            self.lim_initialized = true;
+            let sim_info = SimInfo {
+                abstime: 0f64,
+                prev_solve: &self.solution,
+                flags,
+                source_factor: self.source_factor,
+                sim_params: &self.sim_params,
+            };
+            let mut limited = false;
             for inst in &mut *self.instance_data {
                 inst.eval(sim_info)?;
+                limited |= inst.limited();
 
                 // this is save because we call populate_matrix_ptrs during Simulation construction
                 unsafe { inst.load_matrix_resist() }
                 inst.load_residual_resist(&self.solution, &mut self.residual_resist);
+                inst.load_limit_rhs_resist(&mut self.lim_rhs_resist);
 
                 if analysis.time_integration() {
                     let alpha = 0.0;
                     unsafe { inst.load_matrix_react(alpha) }
                     inst.load_residual_react(&self.solution, &mut self.residual_react);
+                    inst.load_limit_rhs_react(&mut self.lim_rhs_react);
+                }
+            }
+
+            // the jacobian was calculated at the limited solution x_lim:
+            // J (x_new - x) = -(f(x_lim) - J (x_lim - x))
+            for (residual, lim_rhs) in
+                zip(&mut self.residual_resist.raw, &mut self.lim_rhs_resist.raw)
+            {
+                *residual -= replace(lim_rhs, 0f64);
+            }
+            if analysis.time_integration() {
+                for (residual, lim_rhs) in
+                    zip(&mut self.residual_react.raw, &mut self.lim_rhs_react.raw)
+                {
+                    *residual -= replace(lim_rhs, 0f64);
+                }
+            }
+
+            // conductance from every node to ground (or to the previous pseudo-transient
+            // solution) used by the homotopies
+            if self.shunt_conductance != 0.0 {
+                let g = self.shunt_conductance;
+                for (node, node_info) in self.nodes.iter_enumerated().skip(1) {
+                    if node_info.is_flow {
+                        continue;
+                    }
+                    let idx = node.matrix_idx();
+                    update_matrix_entry(&matrix.nonlinear_matrix[(idx, idx)], g);
+                    self.residual_resist[node] += g * (self.solution[node] - self.shunt_ref[node]);
                 }
             }
 
@@ -400,7 +562,10 @@ impl Simulation<'_> {
 
             let singular = matrix.nonlinear_matrix.lu_factorize(None);
             if singular {
-                bail!("matrix is singular")
+                debug!("matrix is singular in iteration {i}");
+                matrix.nonlinear_matrix.write_zero();
+                self.residual_resist.raw.fill(0f64);
+                return Ok(false);
             }
 
             matrix.nonlinear_matrix.solve_linear_system(&mut self.residual_resist.raw[1..]);
@@ -409,18 +574,24 @@ impl Simulation<'_> {
                 print_stdout(Self::vec_table(&self.residual_resist.raw, &self.nodes.raw)).unwrap();
             }
 
+            // damp the update so that no node voltage changes by more than max_voltage_step
+            let max_delta = zip(&self.residual_resist.raw[1..], &self.nodes.raw[1..])
+                .filter(|(_, node_info)| !node_info.is_flow)
+                .fold(0f64, |max, (delta, _)| max.max(delta.abs()));
+            let damping = (self.config.max_voltage_step / max_delta).min(1.0);
+
             // reset matrix
             matrix.nonlinear_matrix.write_zero();
-            let mut found_solution = true;
+            let mut found_solution = damping == 1.0;
             for ((dst, delta), node_info) in
                 zip(&mut self.solution.raw[1..], &mut self.residual_resist.raw[1..])
                     .zip(&self.nodes.raw[1..])
             {
-                let delta = replace(delta, 0f64);
+                let delta = replace(delta, 0f64) * damping;
                 let new_val = *dst - delta;
                 let atol = node_info.atol;
-                let tol = atol.max(new_val * self.config.rtol);
-                if delta > tol {
+                let tol = atol.max(new_val.abs() * self.config.rtol);
+                if !(delta.abs() <= tol) {
                     found_solution = false;
                 }
                 *dst = new_val;
@@ -430,20 +601,90 @@ impl Simulation<'_> {
                 print_stdout(Self::vec_table(&self.solution.raw, &self.nodes.raw)).unwrap();
             }
 
-            if found_solution && i > 0 {
-                break;
+            if found_solution && !limited && i > 0 {
+                return Ok(true);
             }
             i += 1;
 
             if i == self.config.maxiters {
-                bail!("Simulation failed to converge after {i} iterations")
+                debug!("Newton iteration failed to converge after {i} iterations");
+                return Ok(false);
             }
         }
+    }
 
-        self.state = op_flag;
+    /// Calculates the DC operating point for each value of the `dc` parameter of `source`.
+    /// Each operating point is used as the initial guess for the next value. The source keeps
+    /// the last value until [`prepare_solver`](Self::prepare_solver) is called again.
+    pub fn dc_sweep(
+        &mut self,
+        source: InstanceId,
+        values: &[f64],
+    ) -> Result<Vec<TiVec<Node, f64>>> {
+        let circ = self.circ;
+        let instance_info = &circ[source];
+        let dev = &circ[circ[instance_info.model].device];
+        let param = match dev.parameters.lookup_param("dc") {
+            Some((param, info)) if info.is_instance_param && info.ty == Type::Real => param,
+            _ => {
+                bail!("'{}' can not be swept: {} has no dc parameter", instance_info.name, dev.name)
+            }
+        };
+
+        let mut res = Vec::with_capacity(values.len());
+        for &val in values {
+            self.instance_data[source].set_real_param(param, val);
+            self.state.clear();
+            self.solve_op(OperatingPointAnalysis::DC)
+                .with_context(|| format!("dc sweep failed at {} = {val}", instance_info.name))?;
+            res.push(self.solution.clone());
+        }
+        Ok(res)
+    }
+
+    /// Changes the temperature of the circuit to `temp` (in K) without re-evaluating any
+    /// parameters. Only instances whose temperature (including their `dtemp`) changed are set up
+    /// again. The previous solution is kept as the initial guess of the next analysis.
+    pub fn set_temperature(&mut self, temp: f64) -> Result<()> {
+        let circ = self.circ;
+        for (inst, data) in self.instance_data.iter_mut_enumerated() {
+            let inst_temp = temp + self.dtemp[inst];
+            if inst_temp == self.instance_temps[inst] {
+                continue;
+            }
+            data.update_temperature(inst_temp, &self.sim_params).with_context(|| {
+                format!("failed to change the temperature of '{}'", circ[inst].name)
+            })?;
+            self.instance_temps[inst] = inst_temp;
+            self.state.clear();
+        }
         Ok(())
     }
 
+    /// The temperature (in K) of `inst`.
+    pub fn instance_temperature(&self, inst: InstanceId) -> f64 {
+        self.instance_temps[inst]
+    }
+
+    /// Calculates the DC operating point at each temperature (in K) in `temps`. Each operating
+    /// point is used as the initial guess for the next temperature. The circuit keeps the last
+    /// temperature until [`prepare_solver`](Self::prepare_solver) is called again.
+    pub fn temp_sweep(&mut self, temps: &[f64]) -> Result<Vec<TiVec<Node, f64>>> {
+        let mut res = Vec::with_capacity(temps.len());
+        for &temp in temps {
+            self.set_temperature(temp)?;
+            self.solve_op(OperatingPointAnalysis::DC)
+                .with_context(|| format!("temperature sweep failed at T = {temp} K"))?;
+            res.push(self.solution.clone());
+        }
+        Ok(res)
+    }
+
+    /// The homotopy that found the last operating point.
+    pub fn op_homotopy(&self) -> Option<Homotopy> {
+        self.op_homotopy
+    }
+
     pub fn set_omega(&mut self, omega: f64) {
         if (self.omega - omega).abs() > f64::EPSILON {
             self.state.remove(SimulationState::AT_AC)
@@ -463,31 +704,32 @@ impl Simulation<'_> {
         matrix.ac_matrix.write_zero();
         self.ac_solution.raw.fill(Complex64::default());
 
-        if self.state.contains(SimulationState::HAS_AC_EVAL) {
-            for inst in &mut *self.instance_data {
-                unsafe {
-                    inst.load_matrix_react(self.omega);
-                }
-                inst.load_ac_residual(&self.solution, &mut self.ac_solution);
-            }
-        } else {
-            let sim_info =
-                SimInfo { abstime: 0f64, prev_solve: &self.solution, flags: EvalFlags::AC };
-            for inst in &mut *self.instance_data {
+        // the resistive jacobian does not depend on the frequency so devices are only
+        // evaluated once, any further frequencies simply reload the stored matrix entries
+        let eval = !self.state.contains(SimulationState::HAS_AC_EVAL);
+        let sim_info = SimInfo {
+            abstime: 0f64,
+            prev_solve: &self.solution,
+            flags: EvalFlags::AC,
+            source_factor: 1.0,
+            sim_params: &self.sim_params,
+        };
+        for inst in &mut *self.instance_data {
+            if eval {
                 inst.eval(sim_info)?;
-
-                unsafe {
-                    inst.load_matrix_resist();
-                    inst.load_matrix_react(self.omega);
-                }
-                inst.load_ac_residual(&self.solution, &mut self.ac_solution);
             }
-            self.state.insert(SimulationState::HAS_AC_EVAL);
 
-            for (dst, src) in zip(matrix.ac_matrix.data(), matrix.nonlinear_matrix.data()) {
-                let val = Complex64::new(src.get(), dst.get().im);
-                dst.set(val);
+            unsafe {
+                inst.load_matrix_resist();
+                inst.load_matrix_react(self.omega);
             }
+            inst.load_ac_residual(&self.solution, &mut self.ac_solution);
+        }
+        self.state.insert(SimulationState::HAS_AC_EVAL);
+
+        for (dst, src) in zip(matrix.ac_matrix.data(), matrix.nonlinear_matrix.data()) {
+            let val = Complex64::new(src.get(), dst.get().im);
+            dst.set(val);
         }
 
         if self.config.debug {
@@ -506,6 +748,19 @@ impl Simulation<'_> {
         Ok(&self.ac_solution)
     }
 
+    /// Performs a small signal simulation at each frequency in `freqs`. All frequencies share
+    /// the symbolic factorization of the ac matrix and the device evaluation at the operating
+    /// point, only the reactive matrix entries are reloaded.
+    pub fn ac_sweep(&mut self, freqs: &[f64]) -> Result<Vec<TiVec<Node, Complex64>>> {
+        let mut res = Vec::with_capacity(freqs.len());
+        for &freq in freqs {
+            self.set_omega(TAU * freq);
+            self.ac().with_context(|| format!("ac sweep failed at f = {freq} Hz"))?;
+            res.push(self.ac_solution.clone());
+        }
+        Ok(res)
+    }
+
     pub fn ac_lead_current(&mut self, inst: InstanceId) -> Result<Vec<Complex64>> {
         self.ac()?;
         let mut dst = vec![Complex64::default(); self.circ[inst].connections.len()];
@@ -519,6 +774,65 @@ impl Simulation<'_> {
         self.instance_data[inst].load_lead_current_resist(&self.solution, &mut dst);
         Ok(dst)
     }
+
+    /// Returns the operating point variables of `inst` at the DC operating point.
+    pub fn op_vars(&mut self, inst: InstanceId) -> Result<Vec<OpVar>> {
+        self.dc_op()?;
+        // opvars are only stored when they are explicitly requested, the residual is
+        // recalculated so that lead currents stay consistent with the solution
+        let sim_info = SimInfo {
+            abstime: 0f64,
+            prev_solve: &self.solution,
+            flags: EvalFlags::DC_OP_VARS,
+            source_factor: 1.0,
+            sim_params: &self.sim_params,
+        };
+        self.instance_data[inst].eval(sim_info)?;
+        Ok(self.instance_data[inst].op_vars())
+    }
+
+    /// Prints the operating point variables, lead currents and dissipated power of every
+    /// instance at the DC operating point.
+    pub fn print_op_report(&mut self) -> Result<()> {
+        let circ = self.circ;
+        for inst in circ.instances() {
+            let op_vars = self.op_vars(inst)?;
+            let currents = self.dc_lead_current(inst)?;
+
+            let info = &circ[inst];
+            let device = &circ[circ[info.model].device];
+            let mut table = vec![vec![
+                info.name.clone().cell().bold(true).intense(true),
+                device.name.cell().bold(true),
+                "".cell(),
+            ]];
+
+            for op_var in op_vars {
+                let val = format!("{} {}", op_var.value.pretty_str(), op_var.units);
+                table.push(vec![
+                    op_var.name.cell().bold(true),
+                    val.cell(),
+                    op_var.description.cell(),
+                ]);
+            }
+
+            let mut power = 0f64;
+            for ((&current, &node), terminal) in
+                zip(zip(&currents, &info.connections), &*device.terminals)
+            {
+                power += current * self.solution[node];
+                let name = format!("i({terminal})");
+                let val = format!("{} A", current.pretty_str());
+                table.push(vec![name.cell().bold(true), val.cell(), "terminal current".cell()]);
+            }
+            let val = format!("{} W", power.pretty_str());
+            table.push(vec!["p".cell().bold(true), val.cell(), "dissipated power".cell()]);
+
+            print_stdout(table.table()).unwrap();
+        }
+
+        Ok(())
+    }
 }
 
 pub struct SimConfig {
@@ -527,6 +841,33 @@ pub struct SimConfig {
     pub voltage_atol: f64,
     pub current_atol: f64,
     pub rtol: f64,
+    /// largest change of a node voltage in a single Newton iteration
+    pub max_voltage_step: f64,
+    /// homotopies that are tried (in order) if Newton's method does not converge
+    pub homotopies: Vec<Homotopy>,
+    /// initial conductance to ground of gmin stepping
+    pub gmin_start: f64,
+    /// gmin stepping and pseudo-transient continuation end once the conductance to ground
+    /// is smaller than `gmin_min`
+    pub gmin_min: f64,
+    /// initial number of steps of source stepping
+    pub source_steps: u32,
+    /// initial pseudo capacitance (divided by the time step) of pseudo-transient continuation
+    pub ptran_start: f64,
+    /// maximum number of time steps of pseudo-transient continuation
+    pub ptran_max_steps: u32,
+    /// number of GMRES iterations after which the Krylov subspace is discarded (harmonic
+    /// balance)
+    pub gmres_restart: usize,
+    /// maximum number of GMRES iterations for a single Newton step (harmonic balance)
+    pub gmres_maxiters: usize,
+    /// relative residual at which GMRES is considered converged (harmonic balance)
+    pub gmres_rtol: f64,
+    /// nominal temperature (in °C) returned by `$simparam("tnom")`
+    pub tnom: f64,
+    /// additional values of `$simparam`, take precedence over the values derived from this
+    /// config
+    pub sim_params: Vec<(String, f64)>,
 }
 
 impl Default for SimConfig {
@@ -537,6 +878,22 @@ impl Default for SimConfig {
             voltage_atol: 1e-6,
             current_atol: 1e-12,
             rtol: 1e-3,
+            max_voltage_step: 5.0,
+            homotopies: vec![
+                Homotopy::GminStepping,
+                Homotopy::SourceStepping,
+                Homotopy::PseudoTransient,
+            ],
+            gmin_start: 1e-2,
+            gmin_min: 1e-12,
+            source_steps: 10,
+            ptran_start: 1e-2,
+            ptran_max_steps: 500,
+            gmres_restart: 30,
+            gmres_maxiters: 300,
+            gmres_rtol: 1e-6,
+            tnom: 27.0,
+            sim_params: Vec::new(),
         }
     }
 }
@@ -546,4 +903,7 @@ pub struct SimInfo<'a> {
     pub abstime: f64,
     pub prev_solve: &'a TiSlice<Node, f64>,
     pub flags: EvalFlags,
+    /// factor that independent sources are scaled by during source stepping
+    pub source_factor: f64,
+    pub sim_params: &'a SimParams,
 }
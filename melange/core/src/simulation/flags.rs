@@ -10,6 +10,12 @@ bitflags! {
         const CALC_RESIST_RESIDUAL = CALC_RESIST_RESIDUAL;
         const CALC_REACT_RESIDUAL = CALC_REACT_RESIDUAL;
         const CALC_NOISE = CALC_NOISE;
+        const CALC_OP = CALC_OP;
+        const CALC_PARAM_SENSITIVITY = CALC_PARAM_SENSITIVITY;
+        const CALC_RESIST_LIM_RHS = CALC_RESIST_LIM_RHS;
+        const CALC_REACT_LIM_RHS = CALC_REACT_LIM_RHS;
+        const ENABLE_LIM = ENABLE_LIM;
+        const INIT_LIM = INIT_LIM;
         const ANALYSIS_DC = ANALYSIS_DC;
         const ANALYSIS_AC = ANALYSIS_AC;
         const ANALYSIS_STATIC = ANALYSIS_STATIC;
@@ -34,28 +40,35 @@ macro_rules! private_flags {
 }
 
 private_flags! {
-    pub(super) const OP =
-        CALC_RESIST_JACOBIAN | CALC_RESIST_RESIDUAL | ANALYSIS_STATIC;
+    pub(super) const OP = CALC_RESIST_JACOBIAN
+        | CALC_RESIST_RESIDUAL
+        | CALC_RESIST_LIM_RHS
+        | ENABLE_LIM
+        | ANALYSIS_STATIC;
     pub(super) const DC_OP = OP | ANALYSIS_DC;
     pub(super) const AC_OP = OP | ANALYSIS_AC;
+    pub(super) const DC_OP_VARS = CALC_OP | CALC_RESIST_RESIDUAL | ANALYSIS_DC | ANALYSIS_STATIC;
+    // limiting is disabled so that the residual is evaluated exactly at the solution
+    pub(super) const DC_RESIDUAL = CALC_RESIST_RESIDUAL | ANALYSIS_DC | ANALYSIS_STATIC;
+    pub(super) const DC_SENSITIVITY = DC_RESIDUAL | CALC_RESIST_JACOBIAN | CALC_PARAM_SENSITIVITY;
     // pub(super) const NOISE_OP = Self::OP.0.bits | ANALYSIS_NOISE;
     // pub(super) const LARGE_SIGNAL_IC_OP = Self::OP.0.bits | ANALYSIS_TRAN | ANALYSIS_IC;
 
     pub(super) const AC = CALC_RESIST_JACOBIAN | CALC_REACT_JACOBIAN | ANALYSIS_AC;
     // pub(super) const NOISE = CALC_RESIST_JACOBIAN | CALC_REACT_JACOBIAN | CALC_NOISE | ANALYSIS_NOISE;
-    // pub(super) const LARGE_SIGNAL = ANALYSIS_TRAN
-    //     | CALC_RESIST_JACOBIAN
-    //     | CALC_RESIST_RESIDUAL
-    //     | CALC_REACT_JACOBIAN
-    //     | CALC_REACT_RESIDUAL;
+    pub(super) const LARGE_SIGNAL = ANALYSIS_TRAN
+        | CALC_RESIST_JACOBIAN
+        | CALC_RESIST_RESIDUAL
+        | CALC_REACT_JACOBIAN
+        | CALC_REACT_RESIDUAL;
 }
 
-// impl EvalFlags {
-//     pub(super) const TRAN_IC_OP: Self = Self::LARGE_SIGNAL_IC_OP;
-//     pub(super) const HB_IC_OP: Self = Self::LARGE_SIGNAL_IC_OP;
-//     pub(super) const HB: Self = Self::LARGE_SIGNAL;
-//     pub(super) const TRAN: Self = Self::LARGE_SIGNAL;
-// }
+impl EvalFlags {
+    // pub(super) const TRAN_IC_OP: Self = Self::LARGE_SIGNAL_IC_OP;
+    // pub(super) const HB_IC_OP: Self = Self::LARGE_SIGNAL_IC_OP;
+    pub(super) const HB: Self = Self::LARGE_SIGNAL;
+    // pub(super) const TRAN: Self = Self::LARGE_SIGNAL;
+}
 
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub(super) enum OperatingPointAnalysis {
@@ -0,0 +1,422 @@
+use std::cell::Cell;
+use std::f64::consts::{PI, TAU};
+
+use anyhow::{bail, Context, Result};
+use cli_table::{print_stdout, Cell as _, Style, Table};
+use log::debug;
+use num_complex::Complex64;
+use typed_index_collections::{TiSlice, TiVec};
+
+use crate::circuit::Node;
+use crate::simulation::flags::EvalFlags;
+use crate::simulation::hb::gmres::gmres;
+use crate::simulation::matrix::{ComplexMatrix, MatrixEntryIter};
+use crate::simulation::{SimInfo, Simulation};
+use crate::utils::PrettyPrint;
+
+mod gmres;
+#[cfg(test)]
+mod tests;
+
+/// The periodic steady state found by harmonic balance.
+#[derive(Debug, Clone)]
+pub struct HbSolution {
+    /// fundamental frequency
+    pub freq: f64,
+    /// spectrum of every unknown, `spectra[node][k]` is the phasor of the `k`th harmonic
+    /// (`k = 0` is the DC value) such that `x(t) = Re(sum_k X_k exp(j k omega t))`
+    pub spectra: TiVec<Node, Box<[Complex64]>>,
+}
+
+/// In place radix-2 FFT computing `X_k = sum_n x_n exp(-j 2 pi k n / N)`, or with a positive
+/// exponent if `inverse` is set. The result is not normalized.
+fn fft(data: &mut [Complex64], inverse: bool) {
+    let n = data.len();
+    debug_assert!(n.is_power_of_two());
+
+    // bit reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let w_len = Complex64::from_polar(1.0, sign * TAU / len as f64);
+        for chunk in data.chunks_mut(len) {
+            let mut w = Complex64::new(1.0, 0.0);
+            let (lo, hi) = chunk.split_at_mut(len / 2);
+            for (a, b) in lo.iter_mut().zip(hi) {
+                let t = *b * w;
+                *b = *a - t;
+                *a += t;
+                w *= w_len;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// The harmonic balance system of a single tone analysis.
+///
+/// The unknowns are stored as a real vector containing blocks of all circuit unknowns (without
+/// ground): the DC values followed by the real and imaginary parts of each harmonic.
+/// Time domain quantities are stored sample by sample with one value per node (including
+/// ground).
+struct HbSystem<'a> {
+    num_harmonics: usize,
+    num_samples: usize,
+    num_nodes: usize,
+    omega: f64,
+    /// `(equation, unknown)` of each matrix entry
+    entry_nodes: &'a [(Node, Node)],
+    /// distance between the jacobians of consecutive time samples
+    stride: usize,
+    resist_jacobian: &'a [Cell<f64>],
+    react_jacobian: &'a [Cell<f64>],
+    fft_buf: Vec<Complex64>,
+}
+
+impl HbSystem<'_> {
+    fn len(&self) -> usize {
+        (self.num_nodes - 1) * (2 * self.num_harmonics + 1)
+    }
+
+    fn harmonic(&self, vec: &[f64], unknown: usize, k: usize) -> Complex64 {
+        let n = self.num_nodes - 1;
+        if k == 0 {
+            Complex64::new(vec[unknown], 0.0)
+        } else {
+            Complex64::new(vec[(2 * k - 1) * n + unknown], vec[2 * k * n + unknown])
+        }
+    }
+
+    fn set_harmonic(&self, vec: &mut [f64], unknown: usize, k: usize, val: Complex64) {
+        let n = self.num_nodes - 1;
+        if k == 0 {
+            vec[unknown] = val.re
+        } else {
+            vec[(2 * k - 1) * n + unknown] = val.re;
+            vec[2 * k * n + unknown] = val.im;
+        }
+    }
+
+    /// Converts the harmonics in `vec` to time samples.
+    fn to_time(&mut self, vec: &[f64], samples: &mut [f64]) {
+        for unknown in 0..self.num_nodes - 1 {
+            self.fft_buf.fill(Complex64::default());
+            self.fft_buf[0] = self.harmonic(vec, unknown, 0);
+            for k in 1..=self.num_harmonics {
+                let val = self.harmonic(vec, unknown, k) / 2.0;
+                self.fft_buf[k] = val;
+                self.fft_buf[self.num_samples - k] = val.conj();
+            }
+            fft(&mut self.fft_buf, true);
+            for (s, val) in self.fft_buf.iter().enumerate() {
+                samples[s * self.num_nodes + unknown + 1] = val.re;
+            }
+        }
+    }
+
+    /// Converts the time samples of `resist` and `react` to the harmonics of
+    /// `resist + d/dt react` and writes them to `vec`.
+    fn to_freq(&mut self, resist: &[f64], react: &[f64], vec: &mut [f64]) {
+        let scale = 1.0 / self.num_samples as f64;
+        for unknown in 0..self.num_nodes - 1 {
+            for (s, dst) in self.fft_buf.iter_mut().enumerate() {
+                *dst = Complex64::new(resist[s * self.num_nodes + unknown + 1], 0.0);
+            }
+            fft(&mut self.fft_buf, false);
+            let dc = self.fft_buf[0] * scale;
+            self.set_harmonic(vec, unknown, 0, dc);
+            for k in 1..=self.num_harmonics {
+                let val = self.fft_buf[k] * 2.0 * scale;
+                self.set_harmonic(vec, unknown, k, val);
+            }
+
+            // the time derivative does not contribute to the DC value
+            for (s, dst) in self.fft_buf.iter_mut().enumerate() {
+                *dst = Complex64::new(react[s * self.num_nodes + unknown + 1], 0.0);
+            }
+            fft(&mut self.fft_buf, false);
+            for k in 1..=self.num_harmonics {
+                let ddt = Complex64::new(0.0, k as f64 * self.omega * 2.0 * scale);
+                let val = self.harmonic(vec, unknown, k) + ddt * self.fft_buf[k];
+                self.set_harmonic(vec, unknown, k, val);
+            }
+        }
+    }
+
+    /// Computes `dst = J src` where `J` is the jacobian of the harmonic balance residual.
+    fn apply_jacobian(
+        &mut self,
+        src: &[f64],
+        dst: &mut [f64],
+        samples: &mut [f64],
+        resist: &mut [f64],
+        react: &mut [f64],
+    ) {
+        self.to_time(src, samples);
+        resist.fill(0.0);
+        react.fill(0.0);
+        for s in 0..self.num_samples {
+            let off = s * self.num_nodes;
+            for (i, &(eq, unknown)) in self.entry_nodes.iter().enumerate() {
+                if unknown == Node::GROUND {
+                    continue;
+                }
+                let val = samples[off + usize::from(unknown)];
+                let jac_idx = s * self.stride + i;
+                resist[off + usize::from(eq)] += self.resist_jacobian[jac_idx].get() * val;
+                react[off + usize::from(eq)] += self.react_jacobian[jac_idx].get() * val;
+            }
+        }
+        self.to_freq(resist, react, dst);
+    }
+}
+
+impl Simulation<'_> {
+    /// Finds the periodic steady state of the circuit with a single tone harmonic balance
+    /// analysis at the fundamental frequency `freq`, considering `num_harmonics` harmonics.
+    ///
+    /// The tone is produced by the small signal excitation of the sources (for example the
+    /// `mag` and `phase` parameters of voltage sources). The DC operating point is used as the
+    /// initial guess.
+    pub fn harmonic_balance(&mut self, freq: f64, num_harmonics: usize) -> Result<HbSolution> {
+        if num_harmonics == 0 {
+            bail!("harmonic balance requires at least one harmonic")
+        }
+        self.dc_op()?;
+        let dc_solution = self.solution.clone();
+
+        let matrix = self
+            .matrix
+            .as_ref()
+            .context("simulation must be setup before harmonic_balance() is called")?;
+        let entry_nodes = matrix.entry_nodes(&self.matrix_builder);
+        // an additional dump entry is used for the ground row/column
+        let stride = matrix.num_entries() + 1;
+        let num_samples = (2 * num_harmonics + 1).next_power_of_two();
+        let new_jacobian =
+            || -> Box<[Cell<f64>]> { (0..stride * num_samples).map(|_| Cell::new(0.0)).collect() };
+        let resist_jacobian = new_jacobian();
+        let react_jacobian = new_jacobian();
+
+        for (inst, data) in self.instance_data.iter_mut_enumerated() {
+            let entries = MatrixEntryIter::with_hb_jacobian(
+                matrix,
+                &self.matrix_builder,
+                inst,
+                &resist_jacobian,
+                &react_jacobian,
+            );
+            data.populate_matrix_ptrs(entries);
+        }
+
+        let mut system = HbSystem {
+            num_harmonics,
+            num_samples,
+            num_nodes: self.nodes.len(),
+            omega: TAU * freq,
+            entry_nodes: &entry_nodes,
+            stride,
+            resist_jacobian: &resist_jacobian,
+            react_jacobian: &react_jacobian,
+            fft_buf: vec![Complex64::default(); num_samples],
+        };
+        let res = self.hb_newton(&mut system, &dc_solution);
+
+        // the instances must not keep pointers to the harmonic balance jacobian
+        let matrix = self.matrix.as_ref().unwrap();
+        for (inst, data) in self.instance_data.iter_mut_enumerated() {
+            data.populate_matrix_ptrs(MatrixEntryIter::new(matrix, &self.matrix_builder, inst));
+        }
+        // devices were evaluated away from the operating point
+        self.state.clear();
+
+        let solution = res?;
+        let spectra = (0..system.num_nodes)
+            .map(|node| {
+                (0..=num_harmonics)
+                    .map(|k| {
+                        if node == 0 {
+                            Complex64::default()
+                        } else {
+                            system.harmonic(&solution, node - 1, k)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(HbSolution { freq, spectra })
+    }
+
+    fn hb_newton(
+        &mut self,
+        system: &mut HbSystem,
+        dc_solution: &TiSlice<Node, f64>,
+    ) -> Result<Vec<f64>> {
+        let len = system.len();
+        let num_nodes = system.num_nodes;
+        let num_samples = system.num_samples;
+        let num_unknowns = num_nodes - 1;
+
+        let mut solution = vec![0f64; len];
+        solution[..num_unknowns].copy_from_slice(&dc_solution.raw[1..]);
+
+        // the phasors of the tone are the small signal excitation of the sources
+        let mut sources: TiVec<Node, Complex64> = vec![Complex64::default(); num_nodes].into();
+        for inst in &*self.instance_data {
+            inst.load_ac_residual(dc_solution, &mut sources);
+        }
+
+        let mut samples = vec![0f64; num_samples * num_nodes];
+        let mut resist = vec![0f64; num_samples * num_nodes];
+        let mut react = vec![0f64; num_samples * num_nodes];
+        let mut residual = vec![0f64; len];
+        let mut delta = vec![0f64; len];
+        let mut precond: Vec<ComplexMatrix> = Vec::with_capacity(system.num_harmonics + 1);
+        let mut precond_buf = vec![Complex64::default(); num_unknowns];
+
+        for iter in 0..self.config.maxiters {
+            system.to_time(&solution, &mut samples);
+            resist.fill(0.0);
+            react.fill(0.0);
+            system.resist_jacobian.iter().for_each(|entry| entry.set(0.0));
+            system.react_jacobian.iter().for_each(|entry| entry.set(0.0));
+
+            for s in 0..num_samples {
+                let range = s * num_nodes..(s + 1) * num_nodes;
+                let sim_info = SimInfo {
+                    abstime: s as f64 / (num_samples as f64 * system.omega / TAU),
+                    prev_solve: TiSlice::from_ref(&samples[range.clone()]),
+                    flags: EvalFlags::HB,
+                    source_factor: 1.0,
+                    sim_params: &self.sim_params,
+                };
+                let sample_resist = TiSlice::from_mut(&mut resist[range.clone()]);
+                let sample_react = TiSlice::from_mut(&mut react[range]);
+                for inst in &mut *self.instance_data {
+                    inst.eval(sim_info)?;
+                    inst.load_residual_resist(sim_info.prev_solve, sample_resist);
+                    inst.load_residual_react(sim_info.prev_solve, sample_react);
+                    // SAFETY: the matrix pointers were populated with the harmonic balance jacobian
+                    unsafe {
+                        inst.load_matrix_resist_with_offset(s * system.stride);
+                        inst.load_matrix_react_with_offset(s * system.stride);
+                    }
+                }
+            }
+
+            system.to_freq(&resist, &react, &mut residual);
+            for (node, &source) in sources.iter_enumerated().skip(1) {
+                let unknown = usize::from(node) - 1;
+                let val = system.harmonic(&residual, unknown, 1) - source;
+                system.set_harmonic(&mut residual, unknown, 1, val);
+            }
+
+            // block diagonal preconditioner: the jacobian of each harmonic calculated with
+            // the average of the time sample jacobians
+            precond.clear();
+            for k in 0..=system.num_harmonics {
+                let matrix = ComplexMatrix::new(self.matrix.as_ref().unwrap().spec.clone())
+                    .expect("non empty matrix");
+                for (i, dst) in matrix.data().iter().enumerate() {
+                    let (mut g, mut c) = (0.0, 0.0);
+                    for s in 0..num_samples {
+                        g += system.resist_jacobian[s * system.stride + i].get();
+                        c += system.react_jacobian[s * system.stride + i].get();
+                    }
+                    let scale = 1.0 / num_samples as f64;
+                    dst.set(Complex64::new(g * scale, k as f64 * system.omega * c * scale));
+                }
+                if matrix.lu_factorize(None) {
+                    bail!("harmonic balance preconditioner is singular for harmonic {k}")
+                }
+                precond.push(matrix);
+            }
+
+            let precondition = |src: &[f64], dst: &mut [f64]| {
+                for (k, matrix) in precond.iter().enumerate() {
+                    for (unknown, val) in precond_buf.iter_mut().enumerate() {
+                        *val = system.harmonic(src, unknown, k);
+                    }
+                    matrix.solve_linear_system(&mut precond_buf);
+                    for (unknown, &val) in precond_buf.iter().enumerate() {
+                        system.set_harmonic(dst, unknown, k, val);
+                    }
+                }
+            };
+
+            // `precondition` borrows the system so the jacobian product uses a copy with its
+            // own FFT buffer
+            let mut jac_system = HbSystem { fft_buf: system.fft_buf.clone(), ..*system };
+            let apply_a = |src: &[f64], dst: &mut [f64]| {
+                jac_system.apply_jacobian(src, dst, &mut samples, &mut resist, &mut react)
+            };
+
+            delta.fill(0.0);
+            let converged = gmres(
+                apply_a,
+                precondition,
+                &residual,
+                &mut delta,
+                self.config.gmres_restart,
+                self.config.gmres_maxiters,
+                self.config.gmres_rtol,
+            );
+            if !converged {
+                debug!("gmres did not converge in harmonic balance iteration {iter}");
+            }
+
+            let mut found_solution = true;
+            for (i, (dst, delta)) in solution.iter_mut().zip(&delta).enumerate() {
+                let new_val = *dst - delta;
+                let atol = self.nodes[Node::from(i % num_unknowns + 1)].atol;
+                let tol = atol.max(new_val.abs() * self.config.rtol);
+                if !(delta.abs() <= tol) {
+                    found_solution = false;
+                }
+                *dst = new_val;
+            }
+
+            if found_solution && iter > 0 {
+                debug!("harmonic balance converged after {iter} iterations");
+                return Ok(solution);
+            }
+        }
+
+        bail!("harmonic balance failed to converge after {} iterations", self.config.maxiters)
+    }
+
+    /// Prints the magnitude of each harmonic of every unknown.
+    pub fn print_hb_spectra(&self, solution: &HbSolution) {
+        let num_harmonics = solution.spectra.raw.first().map_or(0, |spectrum| spectrum.len());
+        let mut header = vec!["".cell()];
+        for k in 0..num_harmonics {
+            let freq = k as f64 * solution.freq;
+            header.push(format!("{} Hz", freq.pretty_str()).cell().bold(true).intense(true));
+        }
+        let mut table = vec![header];
+        for (node, spectrum) in solution.spectra.iter_enumerated().skip(1) {
+            let info = &self.nodes[node];
+            let mut row = vec![format!("{} [{}]", info.name, info.units).cell().bold(true)];
+            for val in spectrum.iter() {
+                let phase = val.arg() * 180.0 / PI;
+                row.push(format!("{} ∠ {}°", val.norm().pretty_str(), phase.pretty_str()).cell());
+            }
+            table.push(row);
+        }
+        print_stdout(table.table()).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use stdx::iter::zip;
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    zip(a, b).map(|(a, b)| a * b).sum()
+}
+
+fn norm(a: &[f64]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+/// Solves `A x = b` with restarted GMRES using right preconditioning, i.e. `A M^-1 y = b` is
+/// solved and `x = M^-1 y`. `apply_a` computes `dst = A src` and `precondition` computes
+/// `dst = M^-1 src`. `x` contains the initial guess and is overwritten with the result.
+///
+/// Returns whether the residual was reduced below `rtol * |b|` within `maxiters` iterations.
+pub(super) fn gmres(
+    mut apply_a: impl FnMut(&[f64], &mut [f64]),
+    mut precondition: impl FnMut(&[f64], &mut [f64]),
+    b: &[f64],
+    x: &mut [f64],
+    restart: usize,
+    maxiters: usize,
+    rtol: f64,
+) -> bool {
+    let n = b.len();
+    let tol = rtol * norm(b);
+    if tol == 0.0 {
+        x.fill(0.0);
+        return true;
+    }
+
+    let mut basis = vec![vec![0f64; n]; restart + 1];
+    // hessenberg matrix stored column wise
+    let mut hessenberg = vec![vec![0f64; restart + 1]; restart];
+    let (mut cs, mut sn) = (vec![0f64; restart], vec![0f64; restart]);
+    let mut g = vec![0f64; restart + 1];
+    let mut w = vec![0f64; n];
+    let mut z = vec![0f64; n];
+
+    let mut iter = 0;
+    loop {
+        // r = b - A x
+        apply_a(x, &mut w);
+        for (r, (&b, &w)) in zip(&mut basis[0], zip(b, &w)) {
+            *r = b - w;
+        }
+        let beta = norm(&basis[0]);
+        if beta <= tol {
+            return true;
+        }
+        if iter >= maxiters {
+            return false;
+        }
+        basis[0].iter_mut().for_each(|val| *val /= beta);
+        g.fill(0.0);
+        g[0] = beta;
+
+        let mut k = 0;
+        while k < restart && iter < maxiters {
+            precondition(&basis[k], &mut z);
+            apply_a(&z, &mut w);
+
+            // modified Gram-Schmidt
+            let h = &mut hessenberg[k];
+            for (i, v) in basis.iter().enumerate().take(k + 1) {
+                h[i] = dot(&w, v);
+                for (w, v) in zip(&mut w, v) {
+                    *w -= h[i] * v;
+                }
+            }
+            h[k + 1] = norm(&w);
+            let breakdown = h[k + 1] == 0.0;
+            if !breakdown {
+                for (dst, w) in zip(&mut basis[k + 1], &w) {
+                    *dst = w / h[k + 1];
+                }
+            }
+
+            // eliminate the subdiagonal with Givens rotations
+            for i in 0..k {
+                let tmp = cs[i] * h[i] + sn[i] * h[i + 1];
+                h[i + 1] = -sn[i] * h[i] + cs[i] * h[i + 1];
+                h[i] = tmp;
+            }
+            let denom = h[k].hypot(h[k + 1]);
+            cs[k] = h[k] / denom;
+            sn[k] = h[k + 1] / denom;
+            h[k] = denom;
+            h[k + 1] = 0.0;
+            g[k + 1] = -sn[k] * g[k];
+            g[k] *= cs[k];
+
+            k += 1;
+            iter += 1;
+            if g[k].abs() <= tol || breakdown {
+                break;
+            }
+        }
+
+        // solve the upper triangular system H y = g
+        let mut y = g[..k].to_vec();
+        for i in (0..k).rev() {
+            y[i] /= hessenberg[i][i];
+            for j in 0..i {
+                y[j] -= hessenberg[i][j] * y[i];
+            }
+        }
+
+        // x += M^-1 V y
+        w.fill(0.0);
+        for (v, &y) in zip(&basis, &y) {
+            for (w, v) in zip(&mut w, v) {
+                *w += y * v;
+            }
+        }
+        precondition(&w, &mut z);
+        for (x, z) in zip(&mut *x, &z) {
+            *x += z;
+        }
+    }
+}
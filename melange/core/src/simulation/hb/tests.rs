@@ -0,0 +1,175 @@
+use std::f64::consts::TAU;
+
+use anyhow::Result;
+use camino::Utf8PathBuf;
+use num_complex::Complex64;
+use stdx::project_root;
+
+use super::fft;
+use super::gmres::gmres;
+use crate::expr::CircuitParam;
+use crate::simulation::SimConfig;
+use crate::{veriloga, Arena, Circuit, ExprEvalCtx};
+
+fn assert_close(val: Complex64, ref_val: Complex64, tol: f64) {
+    assert!((val - ref_val).norm() <= tol, "{val} != {ref_val}");
+}
+
+#[test]
+fn fft_matches_dft() {
+    let n = 16;
+    let data: Vec<_> = (0..n)
+        .map(|i| Complex64::new((i as f64 * 0.7).sin() + 0.1 * i as f64, (i * i % 5) as f64))
+        .collect();
+
+    for inverse in [false, true] {
+        let sign = if inverse { 1.0 } else { -1.0 };
+        let mut res = data.clone();
+        fft(&mut res, inverse);
+        for (k, &val) in res.iter().enumerate() {
+            let dft: Complex64 = data
+                .iter()
+                .enumerate()
+                .map(|(i, &x)| {
+                    x * Complex64::from_polar(1.0, sign * TAU * (k * i) as f64 / n as f64)
+                })
+                .sum();
+            assert_close(val, dft, 1e-12 * n as f64);
+        }
+    }
+
+    // the inverse transform is not normalized
+    let mut res = data.clone();
+    fft(&mut res, false);
+    fft(&mut res, true);
+    for (val, &x) in res.iter().zip(&data) {
+        assert_close(*val / n as f64, x, 1e-13);
+    }
+}
+
+#[test]
+fn fft_single_tone() {
+    let n = 8;
+    let mut data: Vec<_> =
+        (0..n).map(|i| Complex64::from_polar(2.0, TAU * 3.0 * i as f64 / n as f64)).collect();
+    fft(&mut data, false);
+    for (k, &val) in data.iter().enumerate() {
+        let expected = if k == 3 { 2.0 * n as f64 } else { 0.0 };
+        assert_close(val, expected.into(), 1e-12);
+    }
+}
+
+/// Non-symmetric, diagonally dominant test matrix
+fn test_matrix(n: usize) -> Vec<Vec<f64>> {
+    (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| match j as isize - i as isize {
+                    0 => 4.0 + i as f64,
+                    1 => 1.0,
+                    -1 => -2.0,
+                    2 => 0.5,
+                    _ => 0.0,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn mat_vec(a: &[Vec<f64>], src: &[f64], dst: &mut [f64]) {
+    for (dst, row) in dst.iter_mut().zip(a) {
+        *dst = row.iter().zip(src).map(|(a, x)| a * x).sum();
+    }
+}
+
+#[test]
+fn gmres_known_solution() {
+    let n = 8;
+    let a = test_matrix(n);
+    let expected: Vec<_> = (0..n).map(|i| 1.0 - 0.25 * i as f64).collect();
+    let mut b = vec![0.0; n];
+    mat_vec(&a, &expected, &mut b);
+
+    // a restart smaller than the system forces restarts, the jacobi preconditioner must not
+    // change the solution
+    let identity = |src: &[f64], dst: &mut [f64]| dst.copy_from_slice(src);
+    let jacobi = |src: &[f64], dst: &mut [f64]| {
+        for (i, (dst, src)) in dst.iter_mut().zip(src).enumerate() {
+            *dst = src / a[i][i];
+        }
+    };
+    for restart in [n, 3] {
+        let mut x = vec![0.0; n];
+        assert!(gmres(|src, dst| mat_vec(&a, src, dst), identity, &b, &mut x, restart, 200, 1e-12));
+        for (x, expected) in x.iter().zip(&expected) {
+            assert!((x - expected).abs() <= 1e-9, "{x} != {expected}");
+        }
+
+        let mut x = vec![0.0; n];
+        assert!(gmres(|src, dst| mat_vec(&a, src, dst), jacobi, &b, &mut x, restart, 200, 1e-12));
+        for (x, expected) in x.iter().zip(&expected) {
+            assert!((x - expected).abs() <= 1e-9, "{x} != {expected}");
+        }
+    }
+
+    // too few iterations
+    let mut x = vec![0.0; n];
+    assert!(!gmres(|src, dst| mat_vec(&a, src, dst), identity, &b, &mut x, n, 1, 1e-12));
+
+    // a zero right hand side has the trivial solution
+    let zero = vec![0.0; n];
+    let mut x = vec![1.0; n];
+    assert!(gmres(|src, dst| mat_vec(&a, src, dst), identity, &zero, &mut x, n, 1, 1e-12));
+    assert!(x.iter().all(|&x| x == 0.0));
+}
+
+#[test]
+fn rc_first_harmonic_matches_ac() -> Result<()> {
+    let r = 1e3;
+    let c = 1e-9;
+    let mut arena = Arena::new();
+    let mut circ = Circuit::new("rc".to_owned(), &mut arena);
+    let path = Utf8PathBuf::from_path_buf(project_root())
+        .expect("only utf8 paths are supported")
+        .join("melange")
+        .join("core")
+        .join("test_data")
+        .join("capacitor.va");
+    circ.load_veriloga_file(path, &veriloga::Opts::default())?;
+
+    let gnd = circ.lookup_node("ground").expect("ground node");
+    let node_in = circ.node("in".to_owned());
+    let node_out = circ.node("out".to_owned());
+    let (vsrc, _) =
+        circ.new_device_instance_by_name("vsrc".to_owned(), "vsource", vec![node_in, gnd])?;
+    circ.set_instance_param(vsrc, "dc", 0.5.into())?;
+    circ.set_instance_param(vsrc, "mag", 1.0.into())?;
+    let (res, _) =
+        circ.new_device_instance_by_name("res".to_owned(), "resistor", vec![node_in, node_out])?;
+    circ.set_instance_param(res, "r", r.into())?;
+    let (_, cap) =
+        circ.new_device_instance_by_name("cap".to_owned(), "capacitor_va", vec![node_out, gnd])?;
+    circ.set_model_param(cap, "c", c.into())?;
+
+    let mut ctx = ExprEvalCtx::new(&arena);
+    ctx.set_param(CircuitParam::TEMPERATURE, 300.0.into());
+    let mut sim = circ.prepare_simulation(ctx.borrow(), &arena, SimConfig::default())?;
+
+    // corner frequency of the RC lowpass: V(out) = 1 / (1 + j)
+    let freq = 1.0 / (TAU * r * c);
+    sim.set_omega(TAU * freq);
+    let ac = sim.ac()?[node_out];
+    assert_close(ac, Complex64::new(0.5, -0.5), 1e-9);
+
+    let hb = sim.harmonic_balance(freq, 4)?;
+    let spectrum = &hb.spectra[node_out];
+    assert_eq!(spectrum.len(), 5);
+    assert_close(spectrum[0], 0.5.into(), 1e-4);
+    assert_close(spectrum[1], ac, 1e-4);
+    for &harmonic in &spectrum[2..] {
+        assert_close(harmonic, Complex64::default(), 1e-4);
+    }
+    assert_close(hb.spectra[node_in][1], 1.0.into(), 1e-4);
+
+    Ok(())
+}
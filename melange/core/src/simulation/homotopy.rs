@@ -0,0 +1,159 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+use log::debug;
+
+use crate::simulation::flags::OperatingPointAnalysis;
+use crate::simulation::Simulation;
+
+#[cfg(test)]
+mod tests;
+
+/// Strategies used to find an operating point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Homotopy {
+    /// Newton's method starting at the initial guess (always tried first)
+    Newton,
+    /// a conductance from every node to ground is reduced from `gmin_start` to zero
+    GminStepping,
+    /// all independent sources are ramped up from zero to their full value
+    SourceStepping,
+    /// a capacitance from every node to ground is added and the circuit is integrated
+    /// with increasing time steps until it reaches a steady state
+    PseudoTransient,
+}
+
+impl fmt::Display for Homotopy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Homotopy::Newton => "newton",
+            Homotopy::GminStepping => "gmin stepping",
+            Homotopy::SourceStepping => "source stepping",
+            Homotopy::PseudoTransient => "pseudo-transient continuation",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Simulation<'_> {
+    /// Finds the operating point with Newton's method. If that fails the homotopies from the
+    /// config are tried in order, each starting from the initial guess.
+    pub(super) fn solve_op_homotopy(
+        &mut self,
+        analysis: OperatingPointAnalysis,
+    ) -> Result<Homotopy> {
+        let initial_guess = self.solution.clone();
+        if self.newton(analysis)? {
+            return Ok(Homotopy::Newton);
+        }
+
+        for i in 0..self.config.homotopies.len() {
+            let homotopy = self.config.homotopies[i];
+            debug!("newton failed to converge, trying {homotopy}");
+            self.solution.copy_from_slice(&initial_guess);
+            self.lim_initialized = false;
+            let converged = match homotopy {
+                Homotopy::Newton => self.newton(analysis),
+                Homotopy::GminStepping => self.gmin_stepping(analysis),
+                Homotopy::SourceStepping => self.source_stepping(analysis),
+                Homotopy::PseudoTransient => self.pseudo_transient(analysis),
+            };
+            self.shunt_conductance = 0.0;
+            self.shunt_ref.raw.fill(0.0);
+            self.source_factor = 1.0;
+            if converged? {
+                return Ok(homotopy);
+            }
+        }
+
+        if self.config.homotopies.is_empty() {
+            bail!("Simulation failed to converge after {} iterations", self.config.maxiters)
+        }
+        let tried: Vec<_> = self.config.homotopies.iter().map(ToString::to_string).collect();
+        bail!("Simulation failed to converge (tried newton, {})", tried.join(", "))
+    }
+
+    fn gmin_stepping(&mut self, analysis: OperatingPointAnalysis) -> Result<bool> {
+        let mut last_solution = self.solution.clone();
+        let mut gmin = self.config.gmin_start;
+        let mut factor = 10f64;
+        // the last gmin that converged
+        let mut accepted = None;
+        loop {
+            self.shunt_conductance = gmin;
+            if self.newton(analysis)? {
+                // the final step solves the circuit without gmin
+                if gmin == 0.0 {
+                    return Ok(true);
+                }
+                last_solution.copy_from_slice(&self.solution);
+                accepted = Some(gmin);
+                gmin = if gmin / factor < self.config.gmin_min { 0.0 } else { gmin / factor };
+            } else {
+                let accepted = if let Some(accepted) = accepted {
+                    accepted
+                } else {
+                    return Ok(false);
+                };
+                self.solution.copy_from_slice(&last_solution);
+                factor = factor.sqrt();
+                if factor < 1.01 {
+                    return Ok(false);
+                }
+                gmin = accepted / factor;
+            }
+            debug!("gmin stepping: gmin = {gmin}");
+        }
+    }
+
+    fn source_stepping(&mut self, analysis: OperatingPointAnalysis) -> Result<bool> {
+        self.source_factor = 0.0;
+        if !self.newton(analysis)? {
+            return Ok(false);
+        }
+
+        let mut last_solution = self.solution.clone();
+        let mut factor = 0.0;
+        let mut step = 1.0 / self.config.source_steps.max(1) as f64;
+        while factor < 1.0 {
+            let next = (factor + step).min(1.0);
+            debug!("source stepping: sources scaled by {next}");
+            self.source_factor = next;
+            if self.newton(analysis)? {
+                last_solution.copy_from_slice(&self.solution);
+                factor = next;
+                step *= 2.0;
+            } else {
+                self.solution.copy_from_slice(&last_solution);
+                step /= 4.0;
+                if step < 1e-6 {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn pseudo_transient(&mut self, analysis: OperatingPointAnalysis) -> Result<bool> {
+        // the pseudo capacitance is implemented as a conductance C/h to the solution of the
+        // previous time step
+        let mut conductance = self.config.ptran_start;
+        self.shunt_ref.copy_from_slice(&self.solution);
+        for _ in 0..self.config.ptran_max_steps {
+            self.shunt_conductance = conductance;
+            if self.newton(analysis)? {
+                self.shunt_ref.copy_from_slice(&self.solution);
+                if conductance < self.config.gmin_min {
+                    self.shunt_conductance = 0.0;
+                    return self.newton(analysis);
+                }
+                conductance /= 4.0;
+            } else {
+                self.solution.copy_from_slice(&self.shunt_ref);
+                conductance *= 8.0;
+            }
+            debug!("pseudo-transient continuation: C/h = {conductance}");
+        }
+        Ok(false)
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use camino::Utf8PathBuf;
+use stdx::project_root;
+
+use crate::expr::CircuitParam;
+use crate::simulation::{Homotopy, SimConfig};
+use crate::{veriloga, Arena, Circuit, ExprEvalCtx};
+
+const VDC: f64 = 10.0;
+const R: f64 = 1e3;
+const IS: f64 = 1e-14;
+const VT: f64 = 0.025;
+
+/// Voltage across the diode found by bisection of the KCL at the anode.
+fn reference_vd() -> f64 {
+    let (mut lo, mut hi) = (0.0, 1.0);
+    for _ in 0..100 {
+        let mid = 0.5 * (lo + hi);
+        if (VDC - mid) / R > IS * ((mid / VT).exp() - 1.0) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+/// A 10 V source driving a diode without limiting through a 1k resistor. The first Newton step
+/// puts almost the full 10 V across the diode, plain Newton then only recovers by one thermal
+/// voltage per iteration and runs out of iterations.
+fn solve_diode_circuit(homotopies: Vec<Homotopy>) -> Result<(f64, Option<Homotopy>)> {
+    let mut arena = Arena::new();
+    let mut circ = Circuit::new("homotopy".to_owned(), &mut arena);
+    let path = Utf8PathBuf::from_path_buf(project_root())
+        .expect("only utf8 paths are supported")
+        .join("melange")
+        .join("core")
+        .join("test_data")
+        .join("diode_nolim.va");
+    circ.load_veriloga_file(path, &veriloga::Opts::default())?;
+
+    let gnd = circ.lookup_node("ground").expect("ground node");
+    let node_a = circ.node("A".to_owned());
+    let node_b = circ.node("B".to_owned());
+
+    let (vsrc, _) =
+        circ.new_device_instance_by_name("vsrc".to_owned(), "vsource", vec![node_a, gnd])?;
+    circ.set_instance_param(vsrc, "dc", VDC.into())?;
+    let (res, _) =
+        circ.new_device_instance_by_name("res".to_owned(), "resistor", vec![node_a, node_b])?;
+    circ.set_instance_param(res, "r", R.into())?;
+    let (_, diode) =
+        circ.new_device_instance_by_name("diode".to_owned(), "diode_nolim", vec![node_b, gnd])?;
+    circ.set_model_param(diode, "is", IS.into())?;
+    circ.set_model_param(diode, "vt", VT.into())?;
+
+    let mut ctx = ExprEvalCtx::new(&arena);
+    ctx.set_param(CircuitParam::TEMPERATURE, 300.0.into());
+    let config = SimConfig { homotopies, ..SimConfig::default() };
+    let mut sim = circ.prepare_simulation(ctx.borrow(), &arena, config)?;
+    let vd = sim.dc_op()?[node_b];
+    Ok((vd, sim.op_homotopy()))
+}
+
+fn assert_converged(homotopy: Homotopy) {
+    let (vd, found_with) = solve_diode_circuit(vec![homotopy]).unwrap();
+    assert_eq!(found_with, Some(homotopy));
+    let vd_ref = reference_vd();
+    assert!((vd - vd_ref).abs() <= 1e-3 * vd_ref, "{vd} != {vd_ref}");
+}
+
+#[test]
+fn newton_fails() {
+    assert!(solve_diode_circuit(Vec::new()).is_err());
+}
+
+#[test]
+fn gmin_stepping() {
+    assert_converged(Homotopy::GminStepping)
+}
+
+#[test]
+fn source_stepping() {
+    assert_converged(Homotopy::SourceStepping)
+}
@@ -16,7 +16,7 @@ pub type ComplexMatrix = FixedKluMatrix<i32, Complex64>;
 pub type MatrixSpec = Rc<KluMatrixSpec<i32>>;
 
 pub(super) struct SimulationMatrix {
-    spec: MatrixSpec,
+    pub spec: MatrixSpec,
     pub nonlinear_matrix: RealMatrix,
     pub ac_matrix: ComplexMatrix,
 }
@@ -51,6 +51,33 @@ impl SimulationMatrix {
             .expect("matrix is not empty");
         SimulationMatrix { spec: self.spec, nonlinear_matrix, ac_matrix }
     }
+
+    pub fn num_entries(&self) -> usize {
+        self.nonlinear_matrix.data().len()
+    }
+
+    /// Position of the matrix entry `(column, row)` in the data of the matrices.
+    pub fn entry_idx(&self, column: Node, row: Node) -> usize {
+        let entry = &self.nonlinear_matrix[(row.matrix_idx(), column.matrix_idx())];
+        // SAFETY: the entry is part of the data allocation of the matrix
+        unsafe {
+            (entry as *const Cell<f64>).offset_from(self.nonlinear_matrix.data().as_ptr()) as usize
+        }
+    }
+
+    /// Returns the `(equation, unknown)` node pair for every entry in the data of the matrices.
+    /// These are the `column` and `row` passed to [`MatrixBuilder::insert`].
+    pub fn entry_nodes(&self, builder: &MatrixBuilder) -> Box<[(Node, Node)]> {
+        let mut res = vec![(Node::GROUND, Node::GROUND); self.num_entries()].into_boxed_slice();
+        for entries in &*builder.instance_entries {
+            for &(eq, unknown) in entries {
+                if unknown != Node::GROUND {
+                    res[self.entry_idx(eq, unknown)] = (eq, unknown);
+                }
+            }
+        }
+        res
+    }
 }
 
 pub(crate) struct MatrixBuilder {
@@ -96,6 +123,13 @@ impl MatrixBuilder {
         }
     }
 
+    /// Ensures that the diagonal entry of `node` exists even if no instance writes to it.
+    pub fn ensure_diagonal(&mut self, node: Node) {
+        if node != Node::GROUND {
+            self.inner.add_entry(node.matrix_idx(), node.matrix_idx());
+        }
+    }
+
     pub fn clear_instance(&mut self, instance: InstanceId) {
         self.instance_entries[instance].clear()
     }
@@ -103,9 +137,10 @@ impl MatrixBuilder {
 
 pub struct MatrixEntryIter<'a> {
     iter: slice::Iter<'a, (Node, Node)>,
-    nonlinear_matrix: &'a RealMatrix,
-    ac_matrix: &'a ComplexMatrix,
+    matrix: &'a SimulationMatrix,
     dump: &'a Cell<f64>,
+    /// harmonic balance stores the (resistive, reactive) jacobian outside of the matrices
+    hb_jacobian: Option<(&'a [Cell<f64>], &'a [Cell<f64>])>,
 }
 
 impl<'a> MatrixEntryIter<'a> {
@@ -116,11 +151,26 @@ impl<'a> MatrixEntryIter<'a> {
     ) -> MatrixEntryIter<'a> {
         MatrixEntryIter {
             iter: builder.instance_entries[inst].iter(),
-            nonlinear_matrix: &matrix.nonlinear_matrix,
-            ac_matrix: &matrix.ac_matrix,
+            matrix,
             dump: unsafe { builder.dump.as_ref() },
+            hb_jacobian: None,
         }
     }
+
+    /// Like [`new`](Self::new) but the matrix entries point into `resist` and `react` at the
+    /// same position as in the matrix data. The last element of both slices is used for
+    /// entries in the ground row/column.
+    pub(super) fn with_hb_jacobian(
+        matrix: &'a SimulationMatrix,
+        builder: &'a MatrixBuilder,
+        inst: InstanceId,
+        resist: &'a [Cell<f64>],
+        react: &'a [Cell<f64>],
+    ) -> MatrixEntryIter<'a> {
+        let mut res = Self::new(matrix, builder, inst);
+        res.hb_jacobian = Some((resist, react));
+        res
+    }
 }
 
 impl<'a> Iterator for MatrixEntryIter<'a> {
@@ -128,11 +178,19 @@ impl<'a> Iterator for MatrixEntryIter<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next().map(|&(col, row)| {
+            if let Some((resist, react)) = self.hb_jacobian {
+                let idx = if row == Node::GROUND {
+                    self.matrix.num_entries()
+                } else {
+                    self.matrix.entry_idx(col, row)
+                };
+                return MatrixEntry { resist: &resist[idx], react: &react[idx] };
+            }
             if row == Node::GROUND {
                 return MatrixEntry { resist: self.dump, react: self.dump };
             }
-            let resist = &self.nonlinear_matrix[(row.matrix_idx(), col.matrix_idx())];
-            let complex = &self.ac_matrix[(row.matrix_idx(), col.matrix_idx())]
+            let resist = &self.matrix.nonlinear_matrix[(row.matrix_idx(), col.matrix_idx())];
+            let complex = &self.matrix.ac_matrix[(row.matrix_idx(), col.matrix_idx())]
                 as *const Cell<Complex64> as *const Cell<[f64; 2]>;
 
             // this is save because the original cell is still valid and because Complex64 is
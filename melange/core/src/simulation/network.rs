@@ -0,0 +1,192 @@
+use std::f64::consts::TAU;
+use std::ops::{Index, IndexMut};
+
+use anyhow::{bail, Context, Result};
+use num_complex::Complex64;
+use typed_index_collections::TiVec;
+
+use crate::circuit::Node;
+use crate::simulation::Simulation;
+
+/// A port of a network analysis. The port voltage is measured from `pos` to `neg` and the port
+/// current flows into `pos` and out of `neg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Port {
+    pub pos: Node,
+    pub neg: Node,
+}
+
+/// A square matrix with one row and column for every port.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortMatrix {
+    num_ports: usize,
+    data: Box<[Complex64]>,
+}
+
+impl PortMatrix {
+    fn zeros(num_ports: usize) -> PortMatrix {
+        PortMatrix { num_ports, data: vec![Complex64::default(); num_ports * num_ports].into() }
+    }
+
+    fn identity(num_ports: usize) -> PortMatrix {
+        let mut res = Self::zeros(num_ports);
+        for i in 0..num_ports {
+            res[(i, i)] = Complex64::new(1.0, 0.0);
+        }
+        res
+    }
+
+    pub fn num_ports(&self) -> usize {
+        self.num_ports
+    }
+
+    /// Inverts the matrix with Gauss-Jordan elimination and partial pivoting.
+    /// Returns `None` if the matrix is singular.
+    fn inverse(&self) -> Option<PortMatrix> {
+        let n = self.num_ports;
+        let mut src = self.clone();
+        let mut res = Self::identity(n);
+        for col in 0..n {
+            let pivot = (col..n).max_by(|&i, &j| {
+                src[(i, col)].norm().partial_cmp(&src[(j, col)].norm()).unwrap()
+            })?;
+            if src[(pivot, col)].norm() == 0.0 {
+                return None;
+            }
+            for k in 0..n {
+                src.data.swap(col * n + k, pivot * n + k);
+                res.data.swap(col * n + k, pivot * n + k);
+            }
+
+            let scale = src[(col, col)].inv();
+            for k in 0..n {
+                src[(col, k)] *= scale;
+                res[(col, k)] *= scale;
+            }
+
+            for row in 0..n {
+                if row == col {
+                    continue;
+                }
+                let factor = src[(row, col)];
+                for k in 0..n {
+                    let (src_val, res_val) = (src[(col, k)], res[(col, k)]);
+                    src[(row, k)] -= factor * src_val;
+                    res[(row, k)] -= factor * res_val;
+                }
+            }
+        }
+        Some(res)
+    }
+
+    fn mul(&self, other: &PortMatrix) -> PortMatrix {
+        let n = self.num_ports;
+        let mut res = Self::zeros(n);
+        for i in 0..n {
+            for j in 0..n {
+                res[(i, j)] = (0..n).map(|k| self[(i, k)] * other[(k, j)]).sum();
+            }
+        }
+        res
+    }
+
+    /// Returns `self + val * I`
+    fn add_diagonal(&self, val: f64) -> PortMatrix {
+        let mut res = self.clone();
+        for i in 0..self.num_ports {
+            res[(i, i)] += val;
+        }
+        res
+    }
+}
+
+impl Index<(usize, usize)> for PortMatrix {
+    type Output = Complex64;
+
+    fn index(&self, (row, col): (usize, usize)) -> &Complex64 {
+        &self.data[row * self.num_ports + col]
+    }
+}
+
+impl IndexMut<(usize, usize)> for PortMatrix {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut Complex64 {
+        &mut self.data[row * self.num_ports + col]
+    }
+}
+
+/// The Z, Y and S-parameters of a network at each frequency of a sweep.
+#[derive(Debug, Clone)]
+pub struct NetworkParams {
+    pub freqs: Vec<f64>,
+    /// reference impedance of all ports used for the S-parameters
+    pub z0: f64,
+    pub z: Vec<PortMatrix>,
+    pub y: Vec<PortMatrix>,
+    pub s: Vec<PortMatrix>,
+}
+
+impl NetworkParams {
+    pub fn num_ports(&self) -> usize {
+        self.z.first().map_or(0, PortMatrix::num_ports)
+    }
+}
+
+impl Simulation<'_> {
+    /// Calculates the network parameters of the circuit as seen from `ports` at each frequency
+    /// in `freqs`. The Z-parameters are obtained by injecting a unit current into one port at a
+    /// time while all other ports are left open. Independent sources inside the circuit are
+    /// turned off (voltage sources are shorts).
+    pub fn sparams(&mut self, ports: &[Port], z0: f64, freqs: &[f64]) -> Result<NetworkParams> {
+        if ports.is_empty() {
+            bail!("network analysis requires at least one port")
+        }
+        if z0 <= 0.0 {
+            bail!("reference impedance must be positive (found {z0})")
+        }
+
+        let num_ports = ports.len();
+        let mut res = NetworkParams {
+            freqs: freqs.to_vec(),
+            z0,
+            z: Vec::with_capacity(freqs.len()),
+            y: Vec::with_capacity(freqs.len()),
+            s: Vec::with_capacity(freqs.len()),
+        };
+
+        let mut rhs: TiVec<Node, Complex64> = vec![Complex64::default(); self.nodes.len()].into();
+        for &freq in freqs {
+            self.set_omega(TAU * freq);
+            // factorizes the ac matrix at this frequency
+            self.ac().with_context(|| format!("network analysis failed at f = {freq} Hz"))?;
+            let matrix = self.matrix.as_mut().unwrap();
+
+            let mut z = PortMatrix::zeros(num_ports);
+            for (col, port) in ports.iter().enumerate() {
+                rhs.raw.fill(Complex64::default());
+                rhs[port.pos] += 1.0;
+                rhs[port.neg] -= 1.0;
+                rhs[Node::GROUND] = Complex64::default();
+                matrix.ac_matrix.solve_linear_system(&mut rhs.raw[1..]);
+                for (row, port) in ports.iter().enumerate() {
+                    z[(row, col)] = rhs[port.pos] - rhs[port.neg];
+                }
+            }
+
+            let y = z.inverse().with_context(|| {
+                format!("network has no Y-parameters at f = {freq} Hz (Z matrix is singular)")
+            })?;
+            // S = (Z - z0 I) (Z + z0 I)^-1
+            let s = z
+                .add_diagonal(z0)
+                .inverse()
+                .with_context(|| format!("network has no S-parameters at f = {freq} Hz"))?;
+            let s = z.add_diagonal(-z0).mul(&s);
+
+            res.z.push(z);
+            res.y.push(y);
+            res.s.push(s);
+        }
+
+        Ok(res)
+    }
+}
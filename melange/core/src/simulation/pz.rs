@@ -0,0 +1,359 @@
+use std::f64::consts::TAU;
+
+use anyhow::{bail, Context, Result};
+use cli_table::{print_stdout, Cell, Style, Table};
+use num_complex::Complex64;
+
+use crate::circuit::Node;
+use crate::simulation::{Port, Simulation};
+use crate::utils::PrettyPrint;
+
+#[cfg(test)]
+mod tests;
+
+/// Eigenvalues smaller than this (relative to the largest eigenvalue) correspond to roots at
+/// infinity and are discarded.
+const INFINITE_ROOT_TOL: f64 = 1e-9;
+
+/// The poles and zeros of a transfer function.
+#[derive(Debug, Clone, Default)]
+pub struct PzResult {
+    pub poles: Vec<Complex64>,
+    pub zeros: Vec<Complex64>,
+}
+
+impl PzResult {
+    /// Whether any pole lies in the right half plane.
+    pub fn is_unstable(&self) -> bool {
+        self.poles.iter().any(|pole| pole.re > 0.0)
+    }
+
+    /// Prints every pole and zero with its natural frequency and damping ratio.
+    pub fn print(&self) {
+        let header = ["", "real", "imag", "frequency [Hz]", "damping"];
+        let header = header.iter().map(|name| name.cell().bold(true)).collect();
+        let mut table = vec![header];
+        for (kind, roots) in [("pole", &self.poles), ("zero", &self.zeros)] {
+            for &root in roots {
+                table.push(vec![
+                    kind.cell().bold(true),
+                    root.re.pretty_str().cell(),
+                    root.im.pretty_str().cell(),
+                    natural_freq(root).pretty_str().cell(),
+                    damping(root).pretty_str().cell(),
+                ]);
+            }
+        }
+        print_stdout(table.table()).unwrap();
+    }
+}
+
+/// The natural frequency `|s| / 2 pi` of a root.
+pub fn natural_freq(root: Complex64) -> f64 {
+    root.norm() / TAU
+}
+
+/// The damping ratio `-Re(s) / |s|` of a root. Roots in the right half plane have a negative
+/// damping ratio.
+pub fn damping(root: Complex64) -> f64 {
+    if root.norm() == 0.0 {
+        1.0
+    } else {
+        -root.re / root.norm()
+    }
+}
+
+/// A dense row major square matrix.
+#[derive(Clone)]
+struct DenseMatrix {
+    n: usize,
+    data: Vec<Complex64>,
+}
+
+impl DenseMatrix {
+    fn zeros(n: usize) -> DenseMatrix {
+        DenseMatrix { n, data: vec![Complex64::default(); n * n] }
+    }
+
+    fn at(&self, row: usize, col: usize) -> Complex64 {
+        self.data[row * self.n + col]
+    }
+
+    fn at_mut(&mut self, row: usize, col: usize) -> &mut Complex64 {
+        &mut self.data[row * self.n + col]
+    }
+
+    fn max_norm(&self) -> f64 {
+        self.data.iter().fold(0f64, |max, val| max.max(val.norm()))
+    }
+
+    /// LU factorization with partial pivoting, returns the row permutation or `None` if the
+    /// matrix is singular.
+    fn lu_factorize(&mut self) -> Option<Vec<usize>> {
+        let n = self.n;
+        let mut perm: Vec<usize> = (0..n).collect();
+        let tol = f64::EPSILON * self.max_norm() * n as f64;
+        for col in 0..n {
+            let pivot = (col..n)
+                .max_by(|&i, &j| self.at(i, col).norm().total_cmp(&self.at(j, col).norm()))?;
+            if self.at(pivot, col).norm() <= tol {
+                return None;
+            }
+            if pivot != col {
+                perm.swap(col, pivot);
+                for k in 0..n {
+                    self.data.swap(col * n + k, pivot * n + k);
+                }
+            }
+            let diag = self.at(col, col);
+            for row in col + 1..n {
+                let factor = self.at(row, col) / diag;
+                *self.at_mut(row, col) = factor;
+                for k in col + 1..n {
+                    let val = self.at(col, k);
+                    *self.at_mut(row, k) -= factor * val;
+                }
+            }
+        }
+        Some(perm)
+    }
+
+    /// Solves `A x = rhs` in place with the factors from [`lu_factorize`](Self::lu_factorize).
+    fn lu_solve(&self, perm: &[usize], rhs: &mut [Complex64]) {
+        let n = self.n;
+        let mut res: Vec<_> = perm.iter().map(|&i| rhs[i]).collect();
+        for row in 0..n {
+            for k in 0..row {
+                let val = self.at(row, k) * res[k];
+                res[row] -= val;
+            }
+        }
+        for row in (0..n).rev() {
+            for k in row + 1..n {
+                let val = self.at(row, k) * res[k];
+                res[row] -= val;
+            }
+            res[row] /= self.at(row, row);
+        }
+        rhs.copy_from_slice(&res);
+    }
+
+    /// Reduces the matrix to upper Hessenberg form with stabilized elementary similarity
+    /// transformations.
+    fn to_hessenberg(&mut self) {
+        let n = self.n;
+        for m in 1..n.saturating_sub(1) {
+            let pivot = (m..n)
+                .max_by(|&i, &j| self.at(i, m - 1).norm().total_cmp(&self.at(j, m - 1).norm()))
+                .unwrap();
+            if pivot != m {
+                for k in 0..n {
+                    self.data.swap(pivot * n + k, m * n + k);
+                }
+                for k in 0..n {
+                    self.data.swap(k * n + pivot, k * n + m);
+                }
+            }
+            let x = self.at(m, m - 1);
+            if x.norm() == 0.0 {
+                continue;
+            }
+            for i in m + 1..n {
+                let y = self.at(i, m - 1) / x;
+                if y.norm() == 0.0 {
+                    continue;
+                }
+                for k in m - 1..n {
+                    let val = self.at(m, k);
+                    *self.at_mut(i, k) -= y * val;
+                }
+                for k in 0..n {
+                    let val = self.at(k, i);
+                    *self.at_mut(k, m) += y * val;
+                }
+            }
+        }
+    }
+
+    /// Computes all eigenvalues with the shifted QR algorithm. The matrix is destroyed.
+    fn eigenvalues(mut self) -> Result<Vec<Complex64>> {
+        let n = self.n;
+        self.to_hessenberg();
+
+        let mut res = Vec::with_capacity(n);
+        let mut hi = n;
+        let mut iter = 0;
+        while hi > 0 {
+            // find the start of the unreduced block that ends at hi
+            let mut lo = hi - 1;
+            while lo > 0 {
+                let scale = self.at(lo, lo).norm() + self.at(lo - 1, lo - 1).norm();
+                if self.at(lo, lo - 1).norm() <= f64::EPSILON * scale {
+                    *self.at_mut(lo, lo - 1) = Complex64::default();
+                    break;
+                }
+                lo -= 1;
+            }
+
+            if lo == hi - 1 {
+                res.push(self.at(lo, lo));
+                hi -= 1;
+                iter = 0;
+                continue;
+            }
+
+            iter += 1;
+            if iter > 30 * n {
+                bail!("eigenvalue calculation did not converge")
+            }
+
+            // wilkinson shift (with an exceptional shift to break cycles)
+            let (a, b) = (self.at(hi - 2, hi - 2), self.at(hi - 2, hi - 1));
+            let (c, d) = (self.at(hi - 1, hi - 2), self.at(hi - 1, hi - 1));
+            let half_trace = (a + d) / 2.0;
+            let disc = (half_trace * half_trace - (a * d - b * c)).sqrt();
+            let (mu1, mu2) = (half_trace + disc, half_trace - disc);
+            let mut shift = if (mu1 - d).norm() < (mu2 - d).norm() { mu1 } else { mu2 };
+            if iter % 10 == 0 {
+                shift += c.norm();
+            }
+
+            // QR step on the active block: H - shift I = QR, H = RQ + shift I
+            for k in lo..hi {
+                *self.at_mut(k, k) -= shift;
+            }
+            let mut rotations = Vec::with_capacity(hi - lo - 1);
+            for k in lo..hi - 1 {
+                let (x, y) = (self.at(k, k), self.at(k + 1, k));
+                let r = x.norm().hypot(y.norm());
+                let (c, s) = if r == 0.0 {
+                    (Complex64::new(1.0, 0.0), Complex64::default())
+                } else {
+                    (x / r, y / r)
+                };
+                for j in k..hi {
+                    let (u, v) = (self.at(k, j), self.at(k + 1, j));
+                    *self.at_mut(k, j) = c.conj() * u + s.conj() * v;
+                    *self.at_mut(k + 1, j) = -s * u + c * v;
+                }
+                rotations.push((c, s));
+            }
+            for (k, (c, s)) in (lo..hi - 1).zip(rotations) {
+                for i in lo..(k + 2).min(hi) {
+                    let (u, v) = (self.at(i, k), self.at(i, k + 1));
+                    *self.at_mut(i, k) = u * c + v * s;
+                    *self.at_mut(i, k + 1) = -u * s.conj() + v * c.conj();
+                }
+            }
+            for k in lo..hi {
+                *self.at_mut(k, k) += shift;
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Finds the finite roots `s` of `det(g + s c) = 0`. The problem is transformed to the standard
+/// eigenproblem of `(g + shift c)^-1 c` whose eigenvalues are `-1 / (s - shift)`.
+fn generalized_roots(g: &DenseMatrix, c: &DenseMatrix) -> Result<Vec<Complex64>> {
+    let n = g.n;
+    let c_norm = c.max_norm();
+    if n == 0 || c_norm == 0.0 {
+        return Ok(Vec::new());
+    }
+
+    // the shift must not be a root itself, try a couple of unusual values
+    let scale = g.max_norm().max(f64::MIN_POSITIVE) / c_norm;
+    let mut factors = None;
+    for shift in [0.0, 0.318, -1.37, 2.71, -7.3].map(|factor| factor * scale) {
+        let mut lu = g.clone();
+        for (dst, &val) in lu.data.iter_mut().zip(&c.data) {
+            *dst += val * shift;
+        }
+        if let Some(perm) = lu.lu_factorize() {
+            factors = Some((lu, perm, shift));
+            break;
+        }
+    }
+    let (lu, perm, shift) =
+        factors.context("the jacobian is singular for every tried frequency")?;
+
+    let mut m = DenseMatrix::zeros(n);
+    let mut col = vec![Complex64::default(); n];
+    for j in 0..n {
+        for (i, dst) in col.iter_mut().enumerate() {
+            *dst = c.at(i, j);
+        }
+        lu.lu_solve(&perm, &mut col);
+        for (i, &val) in col.iter().enumerate() {
+            *m.at_mut(i, j) = val;
+        }
+    }
+
+    let eigenvalues = m.eigenvalues()?;
+    let max = eigenvalues.iter().fold(0f64, |max, val| max.max(val.norm()));
+    let mut roots: Vec<_> = eigenvalues
+        .into_iter()
+        .filter(|lambda| lambda.norm() > INFINITE_ROOT_TOL * max)
+        .map(|lambda| shift - lambda.inv())
+        .collect();
+    roots.sort_by(|a, b| a.norm().total_cmp(&b.norm()));
+    Ok(roots)
+}
+
+impl Simulation<'_> {
+    /// Calculates the poles of the circuit and the zeros of the transfer function from a
+    /// current injected into `input` to the voltage across `output` at the DC operating point.
+    /// All unknowns (including the internal unknowns of Verilog-A models) are considered.
+    pub fn pz(&mut self, input: Port, output: Port) -> Result<PzResult> {
+        if input.pos == input.neg || output.pos == output.neg {
+            bail!("the input and output ports of the pole-zero analysis must not be shorted")
+        }
+
+        // at omega = 1 the ac matrix contains G + jC
+        self.set_omega(1.0);
+        self.ac()?;
+        let matrix = self.matrix.as_ref().unwrap();
+        let n = self.nodes.len() - 1;
+
+        // the zeros are the roots of the system bordered with the input and output, the
+        // poles only use the upper left block
+        let mut g = DenseMatrix::zeros(n + 1);
+        let mut c = DenseMatrix::zeros(n + 1);
+        let entry_nodes = matrix.entry_nodes(&self.matrix_builder);
+        for (&(eq, unknown), val) in entry_nodes.iter().zip(matrix.ac_matrix.data()) {
+            if unknown == Node::GROUND {
+                continue;
+            }
+            let (row, col) = (usize::from(eq) - 1, usize::from(unknown) - 1);
+            let val = val.get();
+            *g.at_mut(row, col) += val.re;
+            *c.at_mut(row, col) += val.im;
+        }
+
+        let mut poles_g = DenseMatrix::zeros(n);
+        let mut poles_c = DenseMatrix::zeros(n);
+        for row in 0..n {
+            for col in 0..n {
+                *poles_g.at_mut(row, col) = g.at(row, col);
+                *poles_c.at_mut(row, col) = c.at(row, col);
+            }
+        }
+        let poles = generalized_roots(&poles_g, &poles_c).context("pole calculation failed")?;
+
+        for (node, sign) in [(input.pos, 1.0), (input.neg, -1.0)] {
+            if node != Node::GROUND {
+                *g.at_mut(usize::from(node) - 1, n) += sign;
+            }
+        }
+        for (node, sign) in [(output.pos, 1.0), (output.neg, -1.0)] {
+            if node != Node::GROUND {
+                *g.at_mut(n, usize::from(node) - 1) += sign;
+            }
+        }
+        let zeros = generalized_roots(&g, &c).context("zero calculation failed")?;
+
+        Ok(PzResult { poles, zeros })
+    }
+}
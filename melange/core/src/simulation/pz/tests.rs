@@ -0,0 +1,53 @@
+use std::f64::consts::TAU;
+
+use anyhow::Result;
+use camino::Utf8PathBuf;
+use stdx::project_root;
+
+use super::{damping, natural_freq};
+use crate::expr::CircuitParam;
+use crate::simulation::{Port, SimConfig};
+use crate::{veriloga, Arena, Circuit, ExprEvalCtx};
+
+#[test]
+fn rc_pole() -> Result<()> {
+    // R and C in parallel: Z(s) = R / (1 + s R C)
+    let r = 1e3;
+    let c = 1e-9;
+    let mut arena = Arena::new();
+    let mut circ = Circuit::new("rc".to_owned(), &mut arena);
+    let path = Utf8PathBuf::from_path_buf(project_root())
+        .expect("only utf8 paths are supported")
+        .join("melange")
+        .join("core")
+        .join("test_data")
+        .join("capacitor.va");
+    circ.load_veriloga_file(path, &veriloga::Opts::default())?;
+
+    let gnd = circ.lookup_node("ground").expect("ground node");
+    let node_out = circ.node("out".to_owned());
+    let (res, _) =
+        circ.new_device_instance_by_name("res".to_owned(), "resistor", vec![node_out, gnd])?;
+    circ.set_instance_param(res, "r", r.into())?;
+    let (_, cap) =
+        circ.new_device_instance_by_name("cap".to_owned(), "capacitor_va", vec![node_out, gnd])?;
+    circ.set_model_param(cap, "c", c.into())?;
+
+    let mut ctx = ExprEvalCtx::new(&arena);
+    ctx.set_param(CircuitParam::TEMPERATURE, 300.0.into());
+    let mut sim = circ.prepare_simulation(ctx.borrow(), &arena, SimConfig::default())?;
+    let port = Port { pos: node_out, neg: gnd };
+    let res = sim.pz(port, port)?;
+
+    assert_eq!(res.poles.len(), 1);
+    assert!(res.zeros.is_empty());
+    let pole = res.poles[0];
+    let expected = -1.0 / (r * c);
+    assert!((pole.re - expected).abs() <= 1e-9 * expected.abs(), "{pole} != {expected}");
+    assert!(pole.im.abs() <= 1e-9 * expected.abs(), "{pole} is not real");
+    assert!(!res.is_unstable());
+    assert!((damping(pole) - 1.0).abs() <= 1e-9);
+    assert!((natural_freq(pole) - 1.0 / (TAU * r * c)).abs() <= 1e-9 / (TAU * r * c));
+
+    Ok(())
+}
@@ -0,0 +1,258 @@
+use anyhow::{bail, Context, Result};
+use stdx::iter::zip;
+use typed_index_collections::{TiSlice, TiVec};
+
+use crate::circuit::{CircuitModelSrc, InstanceId, ModelId, Node};
+use crate::devices::{ParamId, Type};
+use crate::expr::{CircuitParam, ExprEvalCtxRef};
+use crate::simulation::flags::EvalFlags;
+use crate::simulation::{SimInfo, Simulation};
+use crate::Arena;
+
+#[cfg(test)]
+mod tests;
+
+/// relative step used to calculate the derivatives of the residual with finite differences
+const FD_REL_STEP: f64 = 1e-6;
+
+/// A parameter that the DC operating point can be differentiated by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensParam {
+    /// A circuit parameter. All parameter expressions that depend on it are re-evaluated.
+    Circuit(CircuitParam),
+    /// A real instance parameter of a device.
+    Instance(InstanceId, ParamId),
+    /// A real model parameter of a device, affects all instances of the model.
+    Model(ModelId, ParamId),
+}
+
+impl Simulation<'_> {
+    /// Calculates the derivative of the DC operating point value of `output` by each of
+    /// `params` with the adjoint method: `d output / dp = -λᵀ df/dp` where `Jᵀλ = e_output`
+    /// is solved once with the factorized jacobian at the operating point.
+    ///
+    /// The derivatives of the residual `df/dp` are provided by the device if it supports
+    /// parameter sensitivities (Verilog-A models compiled with `param_sensitivities`).
+    /// Otherwise they are calculated with central finite differences, which re-evaluates the
+    /// parameters like [`prepare_solver`](Self::prepare_solver). `eval_ctx` and `arena` must
+    /// therefore be the same that were passed to `prepare_solver`. Device parameters are only
+    /// supported by finite differences if they are set explicitly in the circuit.
+    pub fn dc_sensitivity(
+        &mut self,
+        mut eval_ctx: ExprEvalCtxRef,
+        arena: &Arena,
+        output: Node,
+        params: &[SensParam],
+    ) -> Result<Vec<f64>> {
+        if output == Node::GROUND {
+            bail!("the ground node can not be the output of a sensitivity analysis")
+        }
+
+        let op: TiVec<Node, f64> = self.dc_op()?.to_owned();
+        let num_nodes = op.len();
+
+        let mut dfdp: Vec<TiVec<Node, f64>> = vec![vec![0f64; num_nodes].into(); params.len()];
+        // the instances that provide analytic derivatives for each parameter
+        let mut analytic = Vec::with_capacity(params.len());
+        let mut perturbed = false;
+        for (&param, dst) in zip(params, &mut dfdp) {
+            let instances = self.sens_param_instances(arena, param)?;
+            let has_sensitivities =
+                instances.iter().all(|&inst| self.instance_data[inst].has_param_sensitivities());
+            if matches!(param, SensParam::Circuit(_)) || !has_sensitivities {
+                self.fd_residual_derivative(eval_ctx.borrow(), arena, &op, param, &instances, dst)
+                    .with_context(|| {
+                        format!(
+                            "failed to calculate the sensitivity of {}",
+                            self.sens_param_name(arena, param)
+                        )
+                    })?;
+                perturbed = true;
+                analytic.push(None);
+            } else {
+                analytic.push(Some(instances));
+            }
+        }
+
+        if perturbed {
+            // restore the unperturbed parameters
+            self.prepare_solver(eval_ctx.borrow(), arena)?;
+        }
+
+        let matrix =
+            self.matrix.as_mut().context("Simulation must be populated before it can run")?;
+        let sim_info = SimInfo {
+            abstime: 0f64,
+            prev_solve: &op,
+            flags: EvalFlags::DC_SENSITIVITY,
+            source_factor: 1.0,
+            sim_params: &self.sim_params,
+        };
+        for inst in &mut *self.instance_data {
+            inst.eval(sim_info)?;
+            // this is save because we call populate_matrix_ptrs during Simulation construction
+            unsafe { inst.load_matrix_resist() }
+        }
+        for ((&param, instances), dst) in zip(zip(params, &analytic), &mut dfdp) {
+            let param = match param {
+                SensParam::Instance(_, param) | SensParam::Model(_, param) => param,
+                SensParam::Circuit(_) => continue,
+            };
+            if let Some(instances) = instances {
+                for &inst in instances {
+                    self.instance_data[inst].load_param_sensitivity(param, dst);
+                }
+            }
+        }
+
+        let singular = matrix.nonlinear_matrix.lu_factorize(None);
+        if singular {
+            matrix.nonlinear_matrix.write_zero();
+            bail!("jacobian is singular at the DC operating point")
+        }
+
+        // adjoint system: Jᵀλ = e_output
+        let mut adjoint: TiVec<Node, f64> = vec![0f64; num_nodes].into();
+        adjoint[output] = 1.0;
+        matrix.nonlinear_matrix.solve_linear_tranpose_system(&mut adjoint.raw[1..]);
+        matrix.nonlinear_matrix.write_zero();
+
+        let res = dfdp
+            .iter()
+            .map(|dfdp| -zip(&adjoint.raw[1..], &dfdp.raw[1..]).map(|(l, d)| l * d).sum::<f64>())
+            .collect();
+
+        self.restore_dc_op(&op);
+        // the $limit states were overwritten by the evaluations above
+        self.lim_initialized = false;
+        Ok(res)
+    }
+
+    /// Calculates the derivative of the residual of `instances` by `param` with central
+    /// finite differences and adds it to `dst`.
+    fn fd_residual_derivative(
+        &mut self,
+        mut eval_ctx: ExprEvalCtxRef,
+        arena: &Arena,
+        op: &TiSlice<Node, f64>,
+        param: SensParam,
+        instances: &[InstanceId],
+        dst: &mut TiSlice<Node, f64>,
+    ) -> Result<()> {
+        let val = self.sens_param_value(eval_ctx.borrow(), arena, param)?;
+        let step = if val == 0.0 { FD_REL_STEP } else { FD_REL_STEP * val.abs() };
+        let mut residual: TiVec<Node, f64> = vec![0f64; op.len()].into();
+
+        for (delta, scale) in [(step, 1.0), (-step, -1.0)] {
+            // circuit parameters are changed in place by prepare_solver_perturbed
+            let old = match param {
+                SensParam::Circuit(param) => Some((param, eval_ctx[param])),
+                _ => None,
+            };
+            let res = self.prepare_solver_perturbed(eval_ctx.borrow(), arena, Some((param, delta)));
+            if let Some((param, old)) = old {
+                eval_ctx.set_param(param, old);
+            }
+            res?;
+
+            if self.nodes.len() != op.len() {
+                bail!("changing the parameter altered the unknowns of the circuit")
+            }
+
+            let sim_info = SimInfo {
+                abstime: 0f64,
+                prev_solve: op,
+                flags: EvalFlags::DC_RESIDUAL,
+                source_factor: 1.0,
+                sim_params: &self.sim_params,
+            };
+            residual.raw.fill(0f64);
+            for &inst in instances {
+                let inst = &mut self.instance_data[inst];
+                inst.eval(sim_info)?;
+                inst.load_residual_resist(op, &mut residual);
+            }
+            for (dst, residual) in zip(&mut dst.raw, &residual.raw) {
+                *dst += scale * residual / (2.0 * step);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the instances whose residual depends on `param`.
+    fn sens_param_instances(&self, arena: &Arena, param: SensParam) -> Result<Vec<InstanceId>> {
+        let circ = self.circ;
+        let (model, param_id) = match param {
+            SensParam::Circuit(_) => return Ok(circ.instances().collect()),
+            SensParam::Instance(inst, param) => (circ[inst].model, param),
+            SensParam::Model(model, param) => (model, param),
+        };
+        if circ[circ[model].device].parameters[param_id].ty != Type::Real {
+            bail!(
+                "sensitivities can only be calculated for real parameters but {} is not real",
+                self.sens_param_name(arena, param)
+            )
+        }
+        let res = match param {
+            SensParam::Instance(inst, _) => vec![inst],
+            _ => circ.instances().filter(|&inst| circ[inst].model == model).collect(),
+        };
+        Ok(res)
+    }
+
+    fn sens_param_value(
+        &self,
+        eval_ctx: ExprEvalCtxRef,
+        arena: &Arena,
+        param: SensParam,
+    ) -> Result<f64> {
+        let circ = self.circ;
+        let val = match param {
+            SensParam::Circuit(param) => return eval_ctx[param].to_num(),
+            SensParam::Instance(inst, param) => {
+                circ[inst].parameters.iter().find(|(dst, _)| *dst == param)
+            }
+            SensParam::Model(model, param) => {
+                circ[model].parameters.iter().find(|(dst, _)| *dst == param)
+            }
+        };
+        if let Some((_, val)) = val {
+            val.eval_num(eval_ctx)
+        } else {
+            bail!(
+                "{} must be set explicitly to calculate its sensitivity with finite differences",
+                self.sens_param_name(arena, param)
+            )
+        }
+    }
+
+    fn sens_param_name(&self, arena: &Arena, param: SensParam) -> String {
+        let circ = self.circ;
+        match param {
+            SensParam::Circuit(param) => {
+                let (name, _) =
+                    arena.lookup_param_info(param).expect("parameter belongs to the same arena");
+                format!("parameter '{name}'")
+            }
+            SensParam::Instance(inst, param) => {
+                let info = &circ[inst];
+                let dev = &circ[circ[info.model].device];
+                format!("parameter '{}' of instance '{}'", dev.parameters[param].name, info.name)
+            }
+            SensParam::Model(model, param) => {
+                let info = &circ[model];
+                let dev = &circ[info.device];
+                match info.src {
+                    CircuitModelSrc::Explicit(ref name) => {
+                        format!("parameter '{}' of model '{name}'", dev.parameters[param].name)
+                    }
+                    CircuitModelSrc::Implicit(inst) => format!(
+                        "parameter '{}' of instance '{}'",
+                        dev.parameters[param].name, circ[inst].name
+                    ),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::Result;
+use camino::Utf8PathBuf;
+use stdx::project_root;
+
+use crate::circuit::ModelId;
+use crate::expr::CircuitParam;
+use crate::simulation::{SensParam, SimConfig};
+use crate::{veriloga, Arena, Circuit, ExprEvalCtx};
+
+/// relative step of the finite differences of the re-solved operating points
+const REL_STEP: f64 = 1e-6;
+
+#[test]
+fn divider() -> Result<()> {
+    // vdc drives r1 (builtin, finite differences) in series with r2 (Verilog-A compiled with
+    // analytic parameter sensitivities): V(out) = vdc * r2 / (r1 + r2)
+    let mut arena = Arena::new();
+    let mut circ = Circuit::new("divider".to_owned(), &mut arena);
+    let path = Utf8PathBuf::from_path_buf(project_root())
+        .expect("only utf8 paths are supported")
+        .join("melange")
+        .join("core")
+        .join("test_data")
+        .join("resistor.va");
+    let mut opts = veriloga::Opts::default();
+    opts.param_sensitivities = true;
+    circ.load_veriloga_file(path, &opts)?;
+
+    let gnd = circ.lookup_node("ground").expect("ground node");
+    let node_in = circ.node("in".to_owned());
+    let node_out = circ.node("out".to_owned());
+    let (vdc_param, vdc) = arena.def_param(circ.ctx, "vdc".to_owned())?;
+    let (r1_param, r1) = arena.def_param(circ.ctx, "r1".to_owned())?;
+    let (r2_param, r2) = arena.def_param(circ.ctx, "r2".to_owned())?;
+
+    let (vsrc, _) =
+        circ.new_device_instance_by_name("vsrc".to_owned(), "vsource", vec![node_in, gnd])?;
+    circ.set_instance_param(vsrc, "dc", vdc)?;
+    let (res1, model1) =
+        circ.new_device_instance_by_name("r1".to_owned(), "resistor", vec![node_in, node_out])?;
+    circ.set_instance_param(res1, "r", r1)?;
+    let (res2, model2) =
+        circ.new_device_instance_by_name("r2".to_owned(), "resistor_va", vec![node_out, gnd])?;
+    circ.set_model_param(model2, "r", r2)?;
+    let r_id = |model: ModelId| circ[circ[model].device].parameters.lookup_param_id("r").unwrap();
+    let (r1_id, r2_id) = (r_id(model1), r_id(model2));
+
+    let vals = [(vdc_param, 2.0), (r1_param, 1e3), (r2_param, 3e3)];
+    let mut ctx = ExprEvalCtx::new(&arena);
+    ctx.set_param(CircuitParam::TEMPERATURE, 300.0.into());
+    for (param, val) in vals {
+        ctx.set_param(param, val.into());
+    }
+    let mut sim = circ.prepare_simulation(ctx.borrow(), &arena, SimConfig::default())?;
+
+    // central differences of the operating point re-solved for each perturbed parameter
+    let mut reference = Vec::new();
+    for (param, val) in vals {
+        let step = REL_STEP * val;
+        let mut vout = [0.0; 2];
+        for (dst, delta) in vout.iter_mut().zip([step, -step]) {
+            ctx.set_param(param, (val + delta).into());
+            sim.prepare_solver(ctx.borrow(), &arena)?;
+            *dst = sim.dc_op()?[node_out];
+        }
+        ctx.set_param(param, val.into());
+        reference.push((vout[0] - vout[1]) / (2.0 * step));
+    }
+    sim.prepare_solver(ctx.borrow(), &arena)?;
+
+    // the closed form derivatives of the divider
+    let (vdc, r1, r2) = (2.0, 1e3, 3e3);
+    let exact = [r2 / (r1 + r2), -vdc * r2 / (r1 + r2).powi(2), vdc * r1 / (r1 + r2).powi(2)];
+    for (reference, exact) in reference.iter().zip(exact) {
+        assert!((reference - exact).abs() <= 1e-6 * exact.abs(), "{reference} != {exact}");
+    }
+
+    let params = [
+        SensParam::Circuit(vdc_param),
+        SensParam::Circuit(r1_param),
+        SensParam::Circuit(r2_param),
+        SensParam::Instance(res1, r1_id),
+        SensParam::Model(model2, r2_id),
+    ];
+    // r1 uses finite differences and r2 the analytic derivatives
+    assert!(!sim.instance_data[res1].has_param_sensitivities());
+    assert!(sim.instance_data[res2].has_param_sensitivities());
+    let sens = sim.dc_sensitivity(ctx.borrow(), &arena, node_out, &params)?;
+    // the device parameters are the same as the circuit parameters r1 and r2
+    let expected = [reference[0], reference[1], reference[2], reference[1], reference[2]];
+    for ((param, sens), expected) in params.iter().zip(&sens).zip(expected) {
+        assert!(
+            (sens - expected).abs() <= 1e-5 * expected.abs(),
+            "sensitivity by {param:?}: {sens} != {expected}"
+        );
+    }
+
+    // the operating point is restored
+    assert!((sim.dc_op()?[node_out] - 1.5).abs() <= 1e-9);
+
+    Ok(())
+}
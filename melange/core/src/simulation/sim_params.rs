@@ -0,0 +1,79 @@
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::simulation::SimConfig;
+
+/// Values of simulator parameters that Verilog-A models can read with `$simparam`.
+#[derive(Debug)]
+pub struct SimParams {
+    names: Vec<CString>,
+    /// null terminated list of pointers to `names` as expected by OSDI
+    name_ptrs: Vec<*mut c_char>,
+    values: Vec<f64>,
+    /// string valued parameters (`$simparam_str`) are not supported yet so this only contains
+    /// the null terminator
+    str_name_ptrs: [*mut c_char; 1],
+}
+
+impl SimParams {
+    pub fn new() -> SimParams {
+        SimParams {
+            names: Vec::new(),
+            name_ptrs: vec![ptr::null_mut()],
+            values: Vec::new(),
+            str_name_ptrs: [ptr::null_mut()],
+        }
+    }
+
+    /// The parameters derived from `config` (`tnom`, `gmin`, `reltol`, `vntol` and `abstol`)
+    /// followed by the values in `config.sim_params`.
+    pub fn from_config(config: &SimConfig) -> SimParams {
+        let mut res = SimParams::new();
+        res.set("tnom", config.tnom);
+        res.set("gmin", config.gmin_min);
+        res.set("reltol", config.rtol);
+        res.set("vntol", config.voltage_atol);
+        res.set("abstol", config.current_atol);
+        for (name, val) in &config.sim_params {
+            res.set(name, *val);
+        }
+        res
+    }
+
+    /// Sets the value of the `$simparam` `name`, an existing value is overwritten.
+    pub fn set(&mut self, name: &str, val: f64) {
+        if let Some(i) = self.names.iter().position(|dst| dst.as_bytes() == name.as_bytes()) {
+            self.values[i] = val;
+            return;
+        }
+        let name = CString::new(name).expect("string may not contain null terminators");
+        // the heap allocation of the string does not move when it is moved into `names`
+        self.name_ptrs.insert(self.names.len(), name.as_ptr() as *mut c_char);
+        self.names.push(name);
+        self.values.push(val);
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        let i = self.names.iter().position(|dst| dst.as_bytes() == name.as_bytes())?;
+        Some(self.values[i])
+    }
+
+    pub(crate) fn names_ffi_ptr(&self) -> *mut *mut c_char {
+        self.name_ptrs.as_ptr() as *mut *mut c_char
+    }
+
+    pub(crate) fn values_ffi_ptr(&self) -> *mut f64 {
+        self.values.as_ptr() as *mut f64
+    }
+
+    pub(crate) fn str_names_ffi_ptr(&self) -> *mut *mut c_char {
+        self.str_name_ptrs.as_ptr() as *mut *mut c_char
+    }
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        SimParams::new()
+    }
+}
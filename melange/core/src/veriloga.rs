@@ -3,28 +3,31 @@ use std::os::raw::c_char;
 use std::panic::catch_unwind;
 use std::slice;
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
 use libc::c_void;
 use libloading::Library;
 use log::{debug, error, info, warn};
 use openvaf::{
-    AbsPathBuf, CompilationDestination, CompilationTermination, LLVMCodeGenOptLevel, LintLevel,
-    Target,
+    AbsPathBuf, CompilationDestination, CompilationTermination, JitTermination,
+    LLVMCodeGenOptLevel, LintLevel, OsdiJit, Target,
 };
 pub(crate) use osdi_0_4::{
     ANALYSIS_AC, ANALYSIS_DC, ANALYSIS_IC, ANALYSIS_NOISE, ANALYSIS_STATIC, ANALYSIS_TRAN,
-    CALC_NOISE, CALC_REACT_JACOBIAN, CALC_REACT_RESIDUAL, CALC_RESIST_JACOBIAN,
-    CALC_RESIST_RESIDUAL,
+    CALC_NOISE, CALC_OP, CALC_PARAM_SENSITIVITY, CALC_REACT_JACOBIAN, CALC_REACT_LIM_RHS,
+    CALC_REACT_RESIDUAL, CALC_RESIST_JACOBIAN, CALC_RESIST_LIM_RHS, CALC_RESIST_RESIDUAL,
+    ENABLE_LIM, INIT_LIM,
 };
 
 use crate::devices::DeviceImpl;
+use crate::veriloga::limit::populate_lim_table;
 use crate::veriloga::osdi_0_4::{
-    OsdiDescriptor, LOG_FMT_ERR, LOG_LVL_DEBUG, LOG_LVL_DISPLAY, LOG_LVL_ERR, LOG_LVL_FATAL,
-    LOG_LVL_INFO, LOG_LVL_MASK, LOG_LVL_WARN,
+    OsdiDescriptor, OsdiLimFunction, LOG_FMT_ERR, LOG_LVL_DEBUG, LOG_LVL_DISPLAY, LOG_LVL_ERR,
+    LOG_LVL_FATAL, LOG_LVL_INFO, LOG_LVL_MASK, LOG_LVL_WARN,
 };
 use crate::veriloga::osdi_device::OsdiDevice;
 
+mod limit;
 // autogenerated
 #[allow(warnings)]
 mod osdi_0_4;
@@ -38,6 +41,13 @@ pub struct Opts {
     pub lints: Vec<(String, LintLevel)>,
     include: Vec<AbsPathBuf>,
     pub opt_lvl: Option<LLVMCodeGenOptLevel>,
+    /// Link the compiled model into the current process with the LLVM JIT
+    /// instead of building (and caching) a shared library with the system linker.
+    pub jit: bool,
+    /// Generate analytic derivatives of the residual by the model parameters that are used
+    /// by [`dc_sensitivity`](crate::simulation::Simulation::dc_sensitivity) instead of finite
+    /// differences.
+    pub param_sensitivities: bool,
 }
 
 impl Opts {
@@ -78,44 +88,81 @@ pub fn compile_va(path: &Utf8Path, opts: &Opts) -> Result<Vec<Box<dyn DeviceImpl
         dump_unopt_mir: false,
         dump_ir: false,
         dump_unopt_ir: false,
+        dump_opt_stats: false,
+        param_sensitivities: opts.param_sensitivities,
+        hessian: false,
+        debug_info: false,
+        modelcard: None,
     };
 
-    let res = openvaf::compile(&openvaf_opts);
-    let res = res.with_context(|| format!("openvaf: compilation of {path} failed"))?;
-    let lib_file = match res {
-        CompilationTermination::Compiled { lib_file } => lib_file,
-        CompilationTermination::FatalDiagnostic => {
-            bail!("openvaf: compilation of {path} failed");
-        }
+    let descriptors = if opts.jit {
+        let res = openvaf::compile_jit(&openvaf_opts);
+        let res = res.with_context(|| format!("openvaf: compilation of {path} failed"))?;
+        let lib = match res {
+            JitTermination::Compiled { lib } => lib,
+            JitTermination::FatalDiagnostic => {
+                bail!("openvaf: compilation of {path} failed");
+            }
+        };
+        unsafe { load_osdi_jit(lib)? }
+    } else {
+        let res = openvaf::compile(&openvaf_opts);
+        let res = res.with_context(|| format!("openvaf: compilation of {path} failed"))?;
+        let lib_file = match res {
+            CompilationTermination::Compiled { lib_file } => lib_file,
+            CompilationTermination::FatalDiagnostic => {
+                bail!("openvaf: compilation of {path} failed");
+            }
+        };
+        unsafe { load_osdi_lib(&lib_file)? }
     };
-    let libs = unsafe { load_osdi_lib(&lib_file)? };
-    let libs = libs.iter().map(|descriptor| Box::new(OsdiDevice { descriptor }) as _).collect();
+    let libs = descriptors
+        .iter()
+        .map(|descriptor| {
+            Box::new(OsdiDevice { descriptor, param_sensitivities: opts.param_sensitivities }) as _
+        })
+        .collect();
     Ok(libs)
 }
 
 unsafe fn load_osdi_lib(path: &Utf8Path) -> Result<&'static [OsdiDescriptor]> {
     let lib = Library::new(path)?;
     let lib = Box::leak(Box::new(lib));
+    load_osdi_symbols(path.as_str(), |sym| Ok(*lib.get::<*mut c_void>(sym.as_bytes())?))
+}
 
-    let major_version: &u32 = *lib.get(b"OSDI_VERSION_MAJOR\0")?;
-    let minor_version: &u32 = *lib.get(b"OSDI_VERSION_MINOR\0")?;
+unsafe fn load_osdi_jit(lib: OsdiJit) -> Result<&'static [OsdiDescriptor]> {
+    let lib = Box::leak(Box::new(lib));
+    load_osdi_symbols("<jit>", |sym| lib.symbol(sym).map_err(|err| anyhow!("{err}")))
+}
+
+unsafe fn load_osdi_symbols(
+    name: &str,
+    sym: impl Fn(&str) -> Result<*mut c_void>,
+) -> Result<&'static [OsdiDescriptor]> {
+    let major_version = *(sym("OSDI_VERSION_MAJOR")? as *const u32);
+    let minor_version = *(sym("OSDI_VERSION_MINOR")? as *const u32);
 
-    if *major_version != 0 || *minor_version != 3 {
+    if major_version != 0 || minor_version != 4 {
         bail!(
-            "melange only supports OSDI v0.3 but {path} targets v{major_version}.{minor_version}",
+            "melange only supports OSDI v0.4 but {name} targets v{major_version}.{minor_version}",
         );
     }
 
-    let num_descriptors: &u32 = *lib.get(b"OSDI_NUM_DESCRIPTORS\0")?;
-    let descriptors: *const OsdiDescriptor = *lib.get(b"OSDI_DESCRIPTORS\0")?;
+    let num_descriptors = *(sym("OSDI_NUM_DESCRIPTORS")? as *const u32);
+    let descriptors = sym("OSDI_DESCRIPTORS")? as *const OsdiDescriptor;
 
     let descriptors: &[OsdiDescriptor] =
-        slice::from_raw_parts(descriptors, *num_descriptors as usize);
+        slice::from_raw_parts(descriptors, num_descriptors as usize);
 
-    if let Ok(osdi_log_ptr) =
-        lib.get::<*mut unsafe fn(*mut c_void, *const c_char, u32)>(b"osdi_log\0")
-    {
-        osdi_log_ptr.write(osdi_log)
+    if let Ok(osdi_log_ptr) = sym("osdi_log") {
+        (osdi_log_ptr as *mut unsafe fn(*mut c_void, *const c_char, u32)).write(osdi_log)
+    }
+    if let Ok(lim_table) = sym("OSDI_LIM_TABLE") {
+        let lim_table_len = *(sym("OSDI_LIM_TABLE_LEN")? as *const u32);
+        let lim_table =
+            slice::from_raw_parts_mut(lim_table as *mut OsdiLimFunction, lim_table_len as usize);
+        populate_lim_table(lim_table).with_context(|| format!("failed to load {name}"))?;
     }
     Ok(descriptors)
 }
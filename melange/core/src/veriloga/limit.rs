@@ -0,0 +1,191 @@
+//! Implementations of the `$limit` functions that a model can request with the
+//! `OSDI_LIM_TABLE`. These are the classic SPICE limiting functions.
+
+use std::ffi::c_void;
+use std::panic::catch_unwind;
+use std::process::abort;
+
+use anyhow::{bail, Result};
+
+use crate::veriloga::osdi_0_4::OsdiLimFunction;
+use crate::veriloga::osdi_device::osdi_str;
+
+#[cfg(test)]
+mod tests;
+
+/// Fills the function pointers of the `OSDI_LIM_TABLE` exported by a model.
+///
+/// # Safety
+/// `table` must be the `OSDI_LIM_TABLE` of a valid OSDI library
+pub(super) unsafe fn populate_lim_table(table: &mut [OsdiLimFunction]) -> Result<()> {
+    for lim_func in table {
+        let name = osdi_str(lim_func.name);
+        let (num_args, func_ptr) = match name {
+            "pnjlim" => {
+                let ptr: unsafe extern "C" fn(bool, *mut bool, f64, f64, f64, f64) -> f64 =
+                    osdi_pnjlim;
+                (2, ptr as *mut c_void)
+            }
+            "fetlim" => {
+                let ptr: unsafe extern "C" fn(bool, *mut bool, f64, f64, f64) -> f64 = osdi_fetlim;
+                (1, ptr as *mut c_void)
+            }
+            "limvds" => {
+                let ptr: unsafe extern "C" fn(bool, *mut bool, f64, f64) -> f64 = osdi_limvds;
+                (0, ptr as *mut c_void)
+            }
+            _ => bail!("$limit function '{name}' is not supported by melange"),
+        };
+        if lim_func.num_args != num_args {
+            bail!(
+                "$limit function '{name}' expects {num_args} arguments but was called with {}",
+                lim_func.num_args
+            )
+        }
+        lim_func.func_ptr = func_ptr;
+    }
+    Ok(())
+}
+
+/// Calls a limiting function from FFI. `check` is set if the value was changed so that the
+/// simulator does not consider the current iteration converged.
+unsafe fn call_lim_function(check: *mut bool, f: impl FnOnce() -> (f64, bool)) -> f64 {
+    match catch_unwind(f) {
+        Ok((res, limited)) => {
+            if limited {
+                *check = true;
+            }
+            res
+        }
+        // unwinding into the model is UB
+        Err(_) => abort(),
+    }
+}
+
+unsafe extern "C" fn osdi_pnjlim(
+    init: bool,
+    check: *mut bool,
+    vnew: f64,
+    vold: f64,
+    vt: f64,
+    vcrit: f64,
+) -> f64 {
+    call_lim_function(check, || if init { (vcrit, true) } else { pnjlim(vnew, vold, vt, vcrit) })
+}
+
+unsafe extern "C" fn osdi_fetlim(
+    init: bool,
+    check: *mut bool,
+    vnew: f64,
+    vold: f64,
+    vto: f64,
+) -> f64 {
+    call_lim_function(check, || {
+        if init {
+            (vto + 0.1, true)
+        } else {
+            let res = fetlim(vnew, vold, vto);
+            (res, res != vnew)
+        }
+    })
+}
+
+unsafe extern "C" fn osdi_limvds(init: bool, check: *mut bool, vnew: f64, vold: f64) -> f64 {
+    call_lim_function(check, || {
+        if init {
+            (0.1, true)
+        } else {
+            let res = limvds(vnew, vold);
+            (res, res != vnew)
+        }
+    })
+}
+
+/// Limits the change of the voltage across a pn junction so that the exponential current does
+/// not overflow. Returns the limited voltage and whether limiting was applied.
+fn pnjlim(vnew: f64, vold: f64, vt: f64, vcrit: f64) -> (f64, bool) {
+    if vnew > vcrit && (vnew - vold).abs() > vt + vt {
+        let vnew = if vold > 0.0 {
+            let arg = 1.0 + (vnew - vold) / vt;
+            if arg > 0.0 {
+                vold + vt * arg.ln()
+            } else {
+                vcrit
+            }
+        } else {
+            vt * (vnew / vt).ln()
+        };
+        (vnew, true)
+    } else {
+        (vnew, false)
+    }
+}
+
+/// Limits the change of the gate voltage of a FET relative to the threshold voltage `vto`.
+fn fetlim(vnew: f64, vold: f64, vto: f64) -> f64 {
+    let vtsthi = (2.0 * (vold - vto)).abs() + 2.0;
+    let vtstlo = (vold - vto).abs() + 1.0;
+    let vtox = vto + 3.5;
+    let delv = vnew - vold;
+
+    if vold >= vto {
+        if vold >= vtox {
+            if delv <= 0.0 {
+                // going off
+                if vnew < vtox {
+                    vnew.max(vto + 2.0)
+                } else if -delv > vtstlo {
+                    vold - vtstlo
+                } else {
+                    vnew
+                }
+            } else if delv >= vtsthi {
+                // staying on
+                vold + vtsthi
+            } else {
+                vnew
+            }
+        } else if delv <= 0.0 {
+            // middle region, decreasing
+            vnew.max(vto - 0.5)
+        } else {
+            // middle region, increasing
+            vnew.min(vto + 4.0)
+        }
+    } else if delv <= 0.0 {
+        // off
+        if -delv > vtsthi {
+            vold - vtsthi
+        } else {
+            vnew
+        }
+    } else {
+        let vtemp = vto + 0.5;
+        if vnew <= vtemp {
+            if delv > vtstlo {
+                vold + vtstlo
+            } else {
+                vnew
+            }
+        } else {
+            vtemp
+        }
+    }
+}
+
+/// Limits the change of the drain-source voltage of a FET.
+fn limvds(vnew: f64, vold: f64) -> f64 {
+    if vold >= 3.5 {
+        if vnew > vold {
+            vnew.min(3.0 * vold + 2.0)
+        } else if vnew < 3.5 {
+            vnew.max(2.0)
+        } else {
+            vnew
+        }
+    } else if vnew > vold {
+        vnew.min(4.0)
+    } else {
+        vnew.max(-0.5)
+    }
+}
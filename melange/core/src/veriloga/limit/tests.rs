@@ -0,0 +1,80 @@
+use super::{fetlim, limvds, osdi_fetlim, osdi_limvds, osdi_pnjlim, pnjlim};
+
+const VT: f64 = 0.025852;
+const VCRIT: f64 = 0.6;
+
+fn assert_close(val: f64, ref_val: f64) {
+    assert!((val - ref_val).abs() <= 1e-12, "{val} != {ref_val}");
+}
+
+#[test]
+fn pnjlim_reference() {
+    // below vcrit and small steps are never limited
+    assert_eq!(pnjlim(0.5, 0.0, VT, VCRIT), (0.5, false));
+    assert_eq!(pnjlim(0.72, 0.7, VT, VCRIT), (0.72, false));
+
+    // forward biased: vold + vt * ln(1 + (vnew - vold) / vt)
+    let (res, limited) = pnjlim(1.0, 0.7, VT, VCRIT);
+    assert!(limited);
+    assert_close(res, 0.7655103982141349);
+
+    // starting from zero: vt * ln(vnew / vt)
+    let (res, limited) = pnjlim(1.0, 0.0, VT, VCRIT);
+    assert!(limited);
+    assert_close(res, 0.09449855573900463);
+
+    // large negative step falls back to vcrit
+    assert_eq!(pnjlim(0.7, 1.0, VT, VCRIT), (VCRIT, true));
+}
+
+#[test]
+fn fetlim_reference() {
+    let vto = 1.0;
+    // strongly on, going off
+    assert_eq!(fetlim(3.0, 5.0, vto), 3.0);
+    assert_eq!(fetlim(2.0, 5.0, vto), 3.0);
+    assert_eq!(fetlim(5.0, 6.0, vto), 5.0);
+    // strongly on, staying on
+    assert_eq!(fetlim(20.0, 5.0, vto), 15.0);
+    assert_eq!(fetlim(10.0, 5.0, vto), 10.0);
+    // middle region
+    assert_eq!(fetlim(0.0, 2.0, vto), 0.5);
+    assert_eq!(fetlim(8.0, 2.0, vto), 5.0);
+    // off
+    assert_eq!(fetlim(-10.0, 0.0, vto), -4.0);
+    assert_eq!(fetlim(-3.0, 0.0, vto), -3.0);
+    assert_eq!(fetlim(1.2, 0.0, vto), 1.2);
+    assert_eq!(fetlim(3.0, 0.0, vto), 1.5);
+}
+
+#[test]
+fn limvds_reference() {
+    assert_eq!(limvds(20.0, 4.0), 14.0);
+    assert_eq!(limvds(3.8, 4.0), 3.8);
+    assert_eq!(limvds(3.0, 4.0), 3.0);
+    assert_eq!(limvds(1.0, 4.0), 2.0);
+    assert_eq!(limvds(6.0, 1.0), 4.0);
+    assert_eq!(limvds(0.0, 1.0), 0.0);
+    assert_eq!(limvds(-2.0, 1.0), -0.5);
+}
+
+#[test]
+fn ffi_check_flag() {
+    let mut check = false;
+    assert_eq!(unsafe { osdi_pnjlim(true, &mut check, 1.0, 0.0, VT, VCRIT) }, VCRIT);
+    assert!(check);
+
+    let mut check = false;
+    assert_eq!(unsafe { osdi_pnjlim(false, &mut check, 0.5, 0.0, VT, VCRIT) }, 0.5);
+    assert!(!check);
+
+    let mut check = false;
+    assert_eq!(unsafe { osdi_fetlim(false, &mut check, 1.2, 0.0, 1.0) }, 1.2);
+    assert!(!check);
+    assert_eq!(unsafe { osdi_fetlim(false, &mut check, 3.0, 0.0, 1.0) }, 1.5);
+    assert!(check);
+
+    let mut check = false;
+    assert_eq!(unsafe { osdi_limvds(true, &mut check, 5.0, 0.0) }, 0.1);
+    assert!(check);
+}
@@ -36,6 +36,8 @@ pub const ANALYSIS_TRAN: u32 = 8192;
 pub const ANALYSIS_IC: u32 = 16384;
 pub const ANALYSIS_STATIC: u32 = 32768;
 pub const ANALYSIS_NODESET: u32 = 65536;
+pub const CALC_PARAM_SENSITIVITY: u32 = 131072;
+pub const CALC_HESSIAN: u32 = 262144;
 pub const EVAL_RET_FLAG_LIM: u32 = 1;
 pub const EVAL_RET_FLAG_FATAL: u32 = 2;
 pub const EVAL_RET_FLAG_FINISH: u32 = 4;
@@ -190,6 +192,9 @@ pub struct OsdiDescriptor {
     pub load_jacobian_with_offset_react: fn(*mut c_void, *mut c_void, usize),
     pub unknown_nature: *mut OsdiNatureRef,
     pub residual_nature: *mut OsdiNatureRef,
+    pub load_param_sensitivity: fn(*mut c_void, *mut c_void, u32, *mut f64, *mut f64),
+    pub load_hessian_vector_product: fn(*mut c_void, *mut c_void, *mut f64, *mut f64, *mut f64),
+    pub load_opvar_sensitivity: fn(*mut c_void, *mut c_void, u32, *mut f64),
 }
 impl OsdiDescriptor {
     pub fn access(
@@ -318,6 +323,35 @@ impl OsdiDescriptor {
     ) {
         (self.load_jacobian_with_offset_react)(inst, model, offset)
     }
+    pub fn load_param_sensitivity(
+        &self,
+        inst: *mut c_void,
+        model: *mut c_void,
+        param_id: u32,
+        dst_resist: *mut f64,
+        dst_react: *mut f64,
+    ) {
+        (self.load_param_sensitivity)(inst, model, param_id, dst_resist, dst_react)
+    }
+    pub fn load_hessian_vector_product(
+        &self,
+        inst: *mut c_void,
+        model: *mut c_void,
+        dir: *mut f64,
+        dst_resist: *mut f64,
+        dst_react: *mut f64,
+    ) {
+        (self.load_hessian_vector_product)(inst, model, dir, dst_resist, dst_react)
+    }
+    pub fn load_opvar_sensitivity(
+        &self,
+        inst: *mut c_void,
+        model: *mut c_void,
+        param_id: u32,
+        dst: *mut f64,
+    ) {
+        (self.load_opvar_sensitivity)(inst, model, param_id, dst)
+    }
 }
 #[repr(C)]
 pub struct OsdiNature {
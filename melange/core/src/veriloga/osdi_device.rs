@@ -12,12 +12,13 @@ use stdx::iter::zip;
 use typed_index_collections::TiSlice;
 
 use crate::circuit::Node;
-use crate::devices::{DeviceImpl, DeviceParams, InstanceImpl, ModelImpl, ParamId, Type};
-use crate::simulation::{MatrixEntryIter, SimBuilder, SimInfo};
+use crate::devices::{DeviceImpl, DeviceParams, InstanceImpl, ModelImpl, OpVar, ParamId, Type};
+use crate::simulation::{MatrixEntryIter, SimBuilder, SimInfo, SimParams};
 use crate::veriloga::osdi_0_4::{
     OsdiDescriptor, OsdiInitInfo, OsdiJacobianEntry, OsdiNode, OsdiNodePair, OsdiParamOpvar,
-    OsdiSimInfo, OsdiSimParas, ACCESS_FLAG_SET, EVAL_RET_FLAG_FATAL, INIT_ERR_OUT_OF_BOUNDS,
-    PARA_KIND_INST, PARA_TY_INT, PARA_TY_MASK, PARA_TY_REAL, PARA_TY_STR,
+    OsdiSimInfo, OsdiSimParas, ACCESS_FLAG_INSTANCE, ACCESS_FLAG_READ, ACCESS_FLAG_SET,
+    EVAL_RET_FLAG_FATAL, EVAL_RET_FLAG_LIM, INIT_ERR_OUT_OF_BOUNDS, PARA_KIND_INST, PARA_TY_INT,
+    PARA_TY_MASK, PARA_TY_REAL, PARA_TY_STR,
 };
 
 impl OsdiDescriptor {
@@ -37,6 +38,18 @@ impl OsdiDescriptor {
         unsafe { slice::from_raw_parts(self.param_opvar, self.num_params as usize) }
     }
 
+    fn opvars(&self) -> &[OsdiParamOpvar] {
+        // # SAFETY: OsdiDescriptor can only be constructed from FFI and is assumed to contain
+        // valid data
+        unsafe {
+            let params_opvars = slice::from_raw_parts(
+                self.param_opvar,
+                (self.num_params + self.num_opvars) as usize,
+            );
+            &params_opvars[self.num_params as usize..]
+        }
+    }
+
     fn collapsible(&self) -> &[OsdiNodePair] {
         // SAFETY: self.data is a valid allocation and the descriptor is assumed valid
         unsafe { slice::from_raw_parts(self.collapsible, self.num_collapsible as usize) }
@@ -89,7 +102,16 @@ impl Drop for OsdiInitInfo {
     }
 }
 
-unsafe fn osdi_str(raw: *mut c_char) -> &'static str {
+fn osdi_sim_params(sim_params: &SimParams) -> OsdiSimParas {
+    OsdiSimParas {
+        names: sim_params.names_ffi_ptr(),
+        vals: sim_params.values_ffi_ptr(),
+        names_str: sim_params.str_names_ffi_ptr(),
+        vals_str: ptr::null_mut(),
+    }
+}
+
+pub(super) unsafe fn osdi_str(raw: *mut c_char) -> &'static str {
     CStr::from_ptr(raw).to_str().expect("All OSDI strings must be encoded in UTF-8")
 }
 
@@ -142,6 +164,8 @@ unsafe fn dealloc(ptr: *mut c_void, size: usize) {
 
 pub(super) struct OsdiDevice {
     pub descriptor: &'static OsdiDescriptor,
+    /// whether the model was compiled with parameter sensitivities
+    pub param_sensitivities: bool,
 }
 impl DeviceImpl for OsdiDevice {
     fn get_name(&self) -> &'static str {
@@ -178,6 +202,7 @@ impl DeviceImpl for OsdiDevice {
         Rc::new(OsdiModel {
             data: alloc(self.descriptor.model_size as usize),
             descriptor: self.descriptor,
+            param_sensitivities: self.param_sensitivities,
         })
     }
 }
@@ -185,6 +210,7 @@ impl DeviceImpl for OsdiDevice {
 struct OsdiModel {
     descriptor: &'static OsdiDescriptor,
     data: *mut c_void,
+    param_sensitivities: bool,
 }
 
 impl Drop for OsdiModel {
@@ -195,13 +221,8 @@ impl Drop for OsdiModel {
 }
 
 impl ModelImpl for OsdiModel {
-    fn process_params(&self) -> Result<()> {
-        let mut sim_params = OsdiSimParas {
-            names: &mut ptr::null_mut(),
-            vals: ptr::null_mut(),
-            names_str: &mut ptr::null_mut(),
-            vals_str: ptr::null_mut(),
-        };
+    fn process_params(&self, sim_params: &SimParams) -> Result<()> {
+        let mut sim_params = osdi_sim_params(sim_params);
 
         let mut res = OsdiInitInfo { flags: 0, num_errors: 0, errors: ptr::null_mut() };
         self.descriptor.setup_model(
@@ -243,12 +264,23 @@ impl ModelImpl for OsdiModel {
     }
 
     fn new_instance(self: Rc<Self>) -> Box<dyn crate::devices::InstanceImpl> {
-        Box::new(OsdiInstance {
+        let num_states = self.descriptor.num_states as usize;
+        let res = OsdiInstance {
             descriptor: self.descriptor,
             data: alloc(self.descriptor.instance_size as usize),
             model_data: self.data,
+            param_sensitivities: self.param_sensitivities,
+            connected_terminals: 0,
+            prev_state: vec![0f64; num_states].into_boxed_slice(),
+            next_state: vec![0f64; num_states].into_boxed_slice(),
+            limited: false,
             _model: self,
-        })
+        };
+        // each instance has its own state vectors so the states are simply numbered
+        for (i, idx) in res.state_idx().iter().enumerate() {
+            idx.set(i as u32)
+        }
+        Box::new(res)
     }
 }
 
@@ -256,6 +288,15 @@ struct OsdiInstance {
     descriptor: &'static OsdiDescriptor,
     data: *mut c_void,
     model_data: *mut c_void,
+    param_sensitivities: bool,
+    /// number of terminals that are connected in the circuit
+    connected_terminals: u32,
+    /// values of the `$limit` states in the previous Newton iteration
+    prev_state: Box<[f64]>,
+    /// values of the `$limit` states written by the current Newton iteration
+    next_state: Box<[f64]>,
+    /// whether `$limit` changed a value during the last call to `eval`
+    limited: bool,
     _model: Rc<OsdiModel>, // only kept to ensure the data stays live
 }
 
@@ -284,6 +325,15 @@ impl OsdiInstance {
         }
     }
 
+    fn state_idx(&self) -> &[Cell<u32>] {
+        let ptr = self.data as *mut u8;
+        // SAFETY: self.data is a valid allocation and the descriptor is assumed valid
+        unsafe {
+            let ptr = ptr.add(self.descriptor.state_idx_off as usize) as *mut Cell<u32>;
+            slice::from_raw_parts_mut(ptr, self.descriptor.num_states as usize)
+        }
+    }
+
     fn collapsed(&self) -> &[bool] {
         let ptr = self.data as *mut u8;
         // SAFETY: self.data is a valid allocation and the descriptor is assumed valid
@@ -293,6 +343,21 @@ impl OsdiInstance {
         }
     }
 
+    fn setup_instance(&self, temp: f64, sim_params: &SimParams) -> Result<()> {
+        let mut sim_params = osdi_sim_params(sim_params);
+        let mut res = OsdiInitInfo { flags: 0, num_errors: 0, errors: ptr::null_mut() };
+        self.descriptor.setup_instance(
+            b"foo\0".as_ptr() as *mut c_void,
+            self.data,
+            self.model_data,
+            temp,
+            self.connected_terminals,
+            &mut sim_params,
+            &mut res,
+        );
+        self.descriptor.check_init_result(res)
+    }
+
     fn collapse_nodes(&self, connected_terminals: u32) -> Vec<u32> {
         let collapsed = self.collapsed();
         let node_mapping = self.node_mapping();
@@ -359,24 +424,8 @@ impl InstanceImpl for OsdiInstance {
         sim_builder: &mut SimBuilder,
         terminals: &[Node],
     ) -> Result<()> {
-        let mut sim_params = OsdiSimParas {
-            names: &mut ptr::null_mut(),
-            vals: ptr::null_mut(),
-            names_str: &mut ptr::null_mut(),
-            vals_str: ptr::null_mut(),
-        };
-
-        let mut res = OsdiInitInfo { flags: 0, num_errors: 0, errors: ptr::null_mut() };
-        self.descriptor.setup_instance(
-            b"foo\0".as_ptr() as *mut c_void,
-            self.data,
-            self.model_data,
-            temp,
-            terminals.len() as u32,
-            &mut sim_params,
-            &mut res,
-        );
-        self.descriptor.check_init_result(res)?;
+        self.connected_terminals = terminals.len() as u32;
+        self.setup_instance(temp, sim_builder.sim_params)?;
         let mut internal_nodes = self.collapse_nodes(terminals.len() as u32);
 
         // create internal nodes
@@ -392,7 +441,9 @@ impl InstanceImpl for OsdiInstance {
             let name = unsafe { osdi_str(node_info.name) };
             let units = unsafe { osdi_str(node_info.units) };
             let residual_units = unsafe { osdi_str(node_info.residual_units) };
-            *node = sim_builder.new_internal_unknown(name, tol, units, residual_units).into();
+            *node = sim_builder
+                .new_internal_unknown(name, tol, units, residual_units, node_info.is_flow)
+                .into();
         }
 
         let node_mapping = self.node_mapping();
@@ -416,6 +467,15 @@ impl InstanceImpl for OsdiInstance {
         Ok(())
     }
 
+    fn update_temperature(&mut self, temp: f64, sim_params: &SimParams) -> Result<()> {
+        let collapsed = self.collapsed().to_vec();
+        self.setup_instance(temp, sim_params)?;
+        if self.collapsed() != collapsed {
+            bail!("the temperature changed which nodes of the instance are collapsed")
+        }
+        Ok(())
+    }
+
     fn set_real_param(&mut self, param: ParamId, val: f64) {
         let ptr = self.descriptor.access(ptr::null_mut(), self.data, param.into(), ACCESS_FLAG_SET);
         let ptr = ptr as *mut f64;
@@ -461,19 +521,12 @@ impl InstanceImpl for OsdiInstance {
     }
 
     fn eval(&mut self, sim_info: SimInfo<'_>) -> Result<()> {
-        let sim_params = OsdiSimParas {
-            names: &mut ptr::null_mut(),
-            vals: ptr::null_mut(),
-            names_str: &mut ptr::null_mut(),
-            vals_str: ptr::null_mut(),
-        };
-
         let mut info = OsdiSimInfo {
-            paras: sim_params,
+            paras: osdi_sim_params(sim_info.sim_params),
             abstime: sim_info.abstime,
             prev_solve: sim_info.prev_solve.as_ptr() as *mut f64,
-            prev_state: ptr::null_mut(),
-            next_state: ptr::null_mut(),
+            prev_state: self.prev_state.as_mut_ptr(),
+            next_state: self.next_state.as_mut_ptr(),
             flags: sim_info.flags.bits(),
         };
 
@@ -487,6 +540,10 @@ impl InstanceImpl for OsdiInstance {
             bail!("Simulation aborted with $fatal")
         }
 
+        // the states written by this iteration are the previous states of the next iteration
+        swap(&mut self.prev_state, &mut self.next_state);
+        self.limited = (ret_flags & EVAL_RET_FLAG_LIM) != 0;
+
         // TODO only during tran
         // if (ret_flags & EVAL_RET_FLAG_FINISH) != 0 {
         //     bail!("Simulation aborted with $finish")
@@ -503,6 +560,14 @@ impl InstanceImpl for OsdiInstance {
         self.descriptor.load_jacobian_react(self.data, self.model_data, alpha)
     }
 
+    unsafe fn load_matrix_resist_with_offset(&self, offset: usize) {
+        self.descriptor.load_jacobian_with_offset_resist(self.data, self.model_data, offset)
+    }
+
+    unsafe fn load_matrix_react_with_offset(&self, offset: usize) {
+        self.descriptor.load_jacobian_with_offset_react(self.data, self.model_data, offset)
+    }
+
     fn load_residual_react(
         &self,
         _prev_solve: &TiSlice<Node, f64>,
@@ -519,6 +584,18 @@ impl InstanceImpl for OsdiInstance {
         self.descriptor.load_residual_resist(self.data, self.model_data, residual.as_mut_ptr())
     }
 
+    fn load_limit_rhs_resist(&self, lim_rhs: &mut TiSlice<Node, f64>) {
+        self.descriptor.load_limit_rhs_resist(self.data, self.model_data, lim_rhs.as_mut_ptr())
+    }
+
+    fn load_limit_rhs_react(&self, lim_rhs: &mut TiSlice<Node, f64>) {
+        self.descriptor.load_limit_rhs_react(self.data, self.model_data, lim_rhs.as_mut_ptr())
+    }
+
+    fn limited(&self) -> bool {
+        self.limited
+    }
+
     fn load_lead_current_resist(&self, _dc_solve: &TiSlice<Node, f64>, dst: &mut [f64]) {
         for (node, dst) in zip(self.descriptor.terminals(), dst) {
             unsafe {
@@ -538,4 +615,54 @@ impl InstanceImpl for OsdiInstance {
             };
         }
     }
+
+    fn op_vars(&self) -> Vec<OpVar> {
+        let mut res = Vec::with_capacity(self.descriptor.num_opvars as usize);
+        for (i, opvar) in self.descriptor.opvars().iter().enumerate() {
+            // string and array opvars can not be represented as a single number
+            if opvar.len != 0 {
+                continue;
+            }
+            let id = self.descriptor.num_params + i as u32;
+            let ptr = self.descriptor.access(
+                self.data,
+                self.model_data,
+                id,
+                ACCESS_FLAG_READ | ACCESS_FLAG_INSTANCE,
+            );
+            if ptr.is_null() {
+                unreachable!("invalid opvar access")
+            }
+            let value = match osdi_param_ty(opvar.flags) {
+                Type::Real => unsafe { *(ptr as *const f64) },
+                Type::Int => unsafe { *(ptr as *const i32) as f64 },
+                Type::String => continue,
+            };
+            unsafe {
+                res.push(OpVar {
+                    name: osdi_str(*opvar.name),
+                    value,
+                    units: osdi_str(opvar.units),
+                    description: osdi_str(opvar.description),
+                })
+            }
+        }
+        res
+    }
+
+    fn has_param_sensitivities(&self) -> bool {
+        self.param_sensitivities
+    }
+
+    fn load_param_sensitivity(&self, param: ParamId, dst: &mut TiSlice<Node, f64>) {
+        // only the resistive part is needed for DC, the reactive part is discarded
+        let mut react = vec![0f64; dst.len()];
+        self.descriptor.load_param_sensitivity(
+            self.data,
+            self.model_data,
+            param.into(),
+            dst.as_mut_ptr(),
+            react.as_mut_ptr(),
+        )
+    }
 }
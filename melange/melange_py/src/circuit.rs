@@ -0,0 +1,477 @@
+use std::os::raw::c_char;
+use std::ptr;
+
+use camino::Utf8PathBuf;
+use melange_core::circuit::{InstanceId, Node};
+use melange_core::devices::Type;
+use melange_core::simulation::SimConfig;
+use melange_core::veriloga::Opts;
+use melange_core::{Arena, Circuit, CircuitParam, Expr, ExprEvalCtx};
+use pyo3_ffi::*;
+
+use crate::ffi::{free_object, new_type};
+use crate::numpy::{is_array, NumpyArray};
+use crate::simulation::PySimulation;
+use crate::util::{
+    parse_args, py_to_f64, py_to_str, raise_error, raise_runtime_exception, raise_type_exception,
+};
+
+/// Temperature (in Kelvin) used by `prepare_sim` if `temp` is not specified
+const DEFAULT_TEMP: f64 = 300.15;
+
+pub static mut CIRCUIT_TY: PyTypeObject = {
+    let mut res = new_type::<PyCircuit>();
+    res.tp_name = "melange.Circuit\0".as_ptr() as *const c_char;
+    res.tp_doc = "A circuit that can be simulated with melange\0".as_ptr() as *const c_char;
+    res.tp_methods = std::ptr::addr_of_mut!(CIRCUIT_METHODS) as *mut _;
+    res.tp_new = Some(PyCircuit::new);
+    res.tp_dealloc = Some(PyCircuit::dealloc);
+    res
+};
+
+static mut CIRCUIT_METHODS: [PyMethodDef; 3] = [
+    PyMethodDef {
+        ml_name: "load_veriloga_file\0".as_ptr() as *const c_char,
+        ml_meth: PyMethodDefPointer { PyCFunctionWithKeywords: PyCircuit::load_veriloga_file },
+        ml_flags: METH_VARARGS | METH_KEYWORDS,
+        ml_doc: "compiles a Verilog-A file and registers all modules within as devices\0".as_ptr()
+            as *const c_char,
+    },
+    PyMethodDef {
+        ml_name: "prepare_sim\0".as_ptr() as *const c_char,
+        ml_meth: PyMethodDefPointer { PyCFunctionWithKeywords: PyCircuit::prepare_sim },
+        ml_flags: METH_VARARGS | METH_KEYWORDS,
+        ml_doc: "sets the circuit parameters (and `temp`) and prepares a simulation\nParameters passed as numpy arrays are swept: one simulation runs for each element\0"
+            .as_ptr() as *const c_char,
+    },
+    unsafe { zero!(PyMethodDef) },
+];
+
+pub struct CircuitData {
+    pub circ: Circuit,
+    pub arena: Arena,
+    pub va_opts: Opts,
+}
+
+#[repr(C)]
+pub struct PyCircuit {
+    ob_base: PyObject,
+    data: Box<CircuitData>,
+    /// number of simulations that borrow `data`, the circuit can not be modified while
+    /// any simulations are alive
+    pub sims: usize,
+}
+
+impl PyCircuit {
+    unsafe extern "C" fn new(
+        subtype: *mut PyTypeObject,
+        args: *mut PyObject,
+        kwds: *mut PyObject,
+    ) -> *mut PyObject {
+        let [name] = match parse_args("Circuit", args, kwds, ["name"], 1) {
+            Some(args) => args,
+            None => return ptr::null_mut(),
+        };
+        let name = match py_to_str(name) {
+            Some(name) => name.to_owned(),
+            None => return raise_type_exception("Circuit() argument 'name' must be a str"),
+        };
+
+        let ptr = (*subtype).tp_alloc.unwrap()(subtype, 0);
+        if ptr.is_null() {
+            return ptr::null_mut();
+        }
+
+        let mut arena = Arena::new();
+        let circ = Circuit::new(name, &mut arena);
+        let data = Box::new(CircuitData { circ, arena, va_opts: Opts::default() });
+        ptr::write(ptr::addr_of_mut!((*(ptr as *mut Self)).data), data);
+        (*(ptr as *mut Self)).sims = 0;
+        ptr
+    }
+
+    unsafe extern "C" fn dealloc(sel: *mut PyObject) {
+        ptr::drop_in_place(ptr::addr_of_mut!((*(sel as *mut Self)).data));
+        free_object(sel)
+    }
+
+    pub fn data(&self) -> &CircuitData {
+        &self.data
+    }
+
+    /// Returns the circuit for modification. Raises an exception if any simulations
+    /// of the circuit are alive.
+    fn data_mut(&mut self, fun: &str) -> Option<&mut CircuitData> {
+        if self.sims != 0 {
+            raise_runtime_exception(&format!(
+                "{fun}() can not modify a circuit while simulations of it are alive"
+            ));
+            return None;
+        }
+        Some(&mut self.data)
+    }
+
+    unsafe extern "C" fn load_veriloga_file(
+        self_: *mut PyObject,
+        args: *mut PyObject,
+        kwds: *mut PyObject,
+    ) -> *mut PyObject {
+        let self_ = &mut *(self_ as *mut Self);
+        let [path] = match parse_args("load_veriloga_file", args, kwds, ["path"], 1) {
+            Some(args) => args,
+            None => return ptr::null_mut(),
+        };
+
+        let path = PyOS_FSPath(path);
+        if path.is_null() {
+            return ptr::null_mut();
+        }
+        let path_str = py_to_str(path).map(Utf8PathBuf::from);
+        Py_DECREF(path);
+        let path = match path_str {
+            Some(path) => path,
+            None => {
+                return raise_type_exception(
+                    "load_veriloga_file() argument 'path' must be a pathlib Path or str",
+                )
+            }
+        };
+
+        let data = match self_.data_mut("load_veriloga_file") {
+            Some(data) => data,
+            None => return ptr::null_mut(),
+        };
+        let devices = match data.circ.load_veriloga_file(path, &data.va_opts) {
+            Ok(devices) => devices,
+            Err(err) => return raise_error(err),
+        };
+
+        let res = PyList_New(devices.len() as isize);
+        for (i, dev) in devices.into_iter().enumerate() {
+            let name = data.circ[dev].name;
+            let name =
+                PyUnicode_FromStringAndSize(name.as_ptr() as *const c_char, name.len() as isize);
+            PyList_SetItem(res, i as isize, name);
+        }
+        res
+    }
+
+    unsafe extern "C" fn prepare_sim(
+        self_ptr: *mut PyObject,
+        args: *mut PyObject,
+        kwds: *mut PyObject,
+    ) -> *mut PyObject {
+        let self_ = &mut *(self_ptr as *mut Self);
+        if PyTuple_GET_SIZE(args) != 0 {
+            return raise_type_exception("prepare_sim() only accepts keyword arguments");
+        }
+
+        // the simulation keeps the circuit alive and prevents any modifications
+        let data: &'static CircuitData = &*(&*self_.data as *const CircuitData);
+        let mut ctx = ExprEvalCtx::new(&data.arena);
+        ctx.set_param(CircuitParam::TEMPERATURE, DEFAULT_TEMP.into());
+
+        let mut sweeps = Vec::new();
+        let mut len = 1;
+        if !kwds.is_null() {
+            let mut pos = 0;
+            let mut key = ptr::null_mut();
+            let mut val = ptr::null_mut();
+            while PyDict_Next(kwds, &mut pos, &mut key, &mut val) != 0 {
+                let name = py_to_str(key).unwrap_or_default();
+                let param = if name == "temp" {
+                    CircuitParam::TEMPERATURE
+                } else if let Some((param, _)) = data.circ.lookup_param(name, &data.arena) {
+                    param
+                } else {
+                    return raise_type_exception(&format!(
+                        "prepare_sim() got an unexpected keyword argument '{name}'\nhelp: circuit parameters are created with CircuitInstance.set_param"
+                    ));
+                };
+
+                if is_array(val) {
+                    let vals = match NumpyArray::new(val) {
+                        Ok(arr) => arr.to_f64(),
+                        Err(_) => {
+                            return raise_type_exception(&format!(
+                                "prepare_sim() received numpy array with unsupported data type for '{name}'"
+                            ))
+                        }
+                    };
+                    if vals.len() == 1 {
+                        ctx.set_param(param, vals[0].into());
+                        continue;
+                    }
+                    if len == 1 {
+                        len = vals.len();
+                    } else if len != vals.len() {
+                        return raise_type_exception(&format!(
+                            "prepare_sim() all arrays must have the same length but '{name}' has length {}\n\thelp: all previous arrays have length {len}",
+                            vals.len()
+                        ));
+                    }
+                    sweeps.push((param, vals));
+                } else if let Some(val) = py_to_f64(val) {
+                    ctx.set_param(param, val.into());
+                } else {
+                    return raise_type_exception(&format!(
+                        "prepare_sim() argument '{name}' must be a number or a numpy array"
+                    ));
+                }
+            }
+        }
+
+        let sim = match data.circ.setup_simulation(SimConfig::default()) {
+            Ok(sim) => sim,
+            Err(err) => return raise_error(err),
+        };
+        PySimulation::new(self_ptr, sim, ctx, sweeps.into_boxed_slice(), len)
+    }
+}
+
+pub static mut CIRCUIT_INSTANCE_TY: PyTypeObject = {
+    let mut res = new_type::<PyCircuitInstance>();
+    res.tp_name = "melange.CircuitInstance\0".as_ptr() as *const c_char;
+    res.tp_doc = "An instance of a device within a circuit\0".as_ptr() as *const c_char;
+    res.tp_methods = std::ptr::addr_of_mut!(CIRCUIT_INSTANCE_METHODS) as *mut _;
+    res.tp_new = Some(PyCircuitInstance::new);
+    res.tp_dealloc = Some(PyCircuitInstance::dealloc);
+    res
+};
+
+static mut CIRCUIT_INSTANCE_METHODS: [PyMethodDef; 2] = [
+    PyMethodDef {
+        ml_name: "set_param\0".as_ptr() as *const c_char,
+        ml_meth: PyMethodDefPointer { PyCFunctionWithKeywords: PyCircuitInstance::set_param },
+        ml_flags: METH_VARARGS | METH_KEYWORDS,
+        ml_doc: "sets an instance or model parameter to a number\nStrings refer to a circuit parameter (that is set in `prepare_sim`) unless the parameter has type string\0"
+            .as_ptr() as *const c_char,
+    },
+    unsafe { zero!(PyMethodDef) },
+];
+
+#[repr(C)]
+pub struct PyCircuitInstance {
+    ob_base: PyObject,
+    circ: *mut PyObject,
+    pub instance: InstanceId,
+}
+
+impl PyCircuitInstance {
+    unsafe extern "C" fn new(
+        subtype: *mut PyTypeObject,
+        args: *mut PyObject,
+        kwds: *mut PyObject,
+    ) -> *mut PyObject {
+        let [circ_obj, name, device, ports] =
+            match parse_args("CircuitInstance", args, kwds, ["circ", "name", "device", "ports"], 3)
+            {
+                Some(args) => args,
+                None => return ptr::null_mut(),
+            };
+
+        if PyObject_TypeCheck(circ_obj, ptr::addr_of_mut!(CIRCUIT_TY)) == 0 {
+            return raise_type_exception("CircuitInstance() argument 'circ' must be a Circuit");
+        }
+        let name = match py_to_str(name) {
+            Some(name) => name.to_owned(),
+            None => return raise_type_exception("CircuitInstance() argument 'name' must be a str"),
+        };
+        let device = match py_to_str(device) {
+            Some(device) => device,
+            None => {
+                return raise_type_exception("CircuitInstance() argument 'device' must be a str")
+            }
+        };
+
+        let mut port_names = Vec::new();
+        if !ports.is_null() {
+            let len = PySequence_Size(ports);
+            if len < 0 {
+                PyErr_Clear();
+                return raise_type_exception(
+                    "CircuitInstance() argument 'ports' must be a list of str",
+                );
+            }
+            for i in 0..len {
+                let port = PySequence_GetItem(ports, i);
+                let port_name = py_to_str(port).map(str::to_owned);
+                Py_XDECREF(port);
+                match port_name {
+                    Some(port) => port_names.push(port),
+                    None => {
+                        return raise_type_exception(
+                            "CircuitInstance() argument 'ports' must be a list of str",
+                        )
+                    }
+                }
+            }
+        }
+
+        let circ = match (*(circ_obj as *mut PyCircuit)).data_mut("CircuitInstance") {
+            Some(data) => &mut data.circ,
+            None => return ptr::null_mut(),
+        };
+        let dev = match circ.lookup_device(device) {
+            Some(dev) => dev,
+            None => return raise_runtime_exception(&format!("unknown device '{device}'")),
+        };
+        let terminals = &circ[dev].terminals;
+        if port_names.len() > terminals.len() {
+            return raise_runtime_exception(&format!(
+                "device has terminals {} {:?} but {} terminals were connected",
+                terminals.len(),
+                terminals,
+                port_names.len(),
+            ));
+        }
+        let connections = port_names.into_iter().map(|port| node(circ, port)).collect();
+        let instance = match circ.new_device_instance(name, dev, connections) {
+            Ok((instance, _)) => instance,
+            Err(err) => return raise_error(err),
+        };
+
+        let ptr = (*subtype).tp_alloc.unwrap()(subtype, 0);
+        if ptr.is_null() {
+            return ptr::null_mut();
+        }
+        Py_INCREF(circ_obj);
+        let res = &mut *(ptr as *mut Self);
+        res.circ = circ_obj;
+        res.instance = instance;
+        ptr
+    }
+
+    unsafe extern "C" fn dealloc(sel: *mut PyObject) {
+        Py_XDECREF((*(sel as *mut Self)).circ);
+        free_object(sel)
+    }
+
+    unsafe extern "C" fn set_param(
+        self_: *mut PyObject,
+        args: *mut PyObject,
+        kwds: *mut PyObject,
+    ) -> *mut PyObject {
+        let self_ = &mut *(self_ as *mut Self);
+        let [name, val] = match parse_args("set_param", args, kwds, ["name", "value"], 2) {
+            Some(args) => args,
+            None => return ptr::null_mut(),
+        };
+        let name = match py_to_str(name) {
+            Some(name) => name,
+            None => return raise_type_exception("set_param() argument 'name' must be a str"),
+        };
+
+        let CircuitData { circ, arena, .. } =
+            match (*(self_.circ as *mut PyCircuit)).data_mut("set_param") {
+                Some(data) => data,
+                None => return ptr::null_mut(),
+            };
+
+        let dev = &circ[circ[circ[self_.instance].model].device];
+        let ty = match dev.parameters.lookup_param(name) {
+            Some((_, info)) => info.ty,
+            None => {
+                return raise_runtime_exception(&format!(
+                    "unknown parameter '{name}' for {}",
+                    dev.name
+                ))
+            }
+        };
+
+        let val: Expr = if let Some(str_val) = py_to_str(val) {
+            if ty == Type::String {
+                arena.str_value(str_val).into()
+            } else if let Some((_, read_expr)) = circ.lookup_param(str_val, arena) {
+                read_expr
+            } else {
+                match circ.def_param(str_val.to_owned(), None, arena) {
+                    Ok((_, read_expr)) => read_expr,
+                    Err(err) => return raise_error(err),
+                }
+            }
+        } else if let Some(val) = py_to_f64(val) {
+            val.into()
+        } else {
+            return raise_type_exception(&format!(
+                "set_param() value of '{name}' must be a number or a str"
+            ));
+        };
+
+        if let Err(err) = circ.set_param(self_.instance, name, val) {
+            return raise_error(err);
+        }
+
+        Py_INCREF(Py_None());
+        Py_None()
+    }
+}
+
+/// Looks up (or creates) the node `name`. `gnd` and `0` are aliases for the ground node.
+fn node(circ: &mut Circuit, name: String) -> Node {
+    match &*name {
+        "gnd" | "0" => Node::GROUND,
+        _ => circ.node(name),
+    }
+}
+
+/// Resolves the instance (a `CircuitInstance` or the name of an instance) and the terminal
+/// (a terminal name or index) passed to the function `fun`.
+pub unsafe fn lookup_terminal(
+    fun: &str,
+    circ: &Circuit,
+    instance: *mut PyObject,
+    terminal: *mut PyObject,
+) -> Option<(InstanceId, usize)> {
+    let instance = if PyObject_TypeCheck(instance, ptr::addr_of_mut!(CIRCUIT_INSTANCE_TY)) != 0 {
+        (*(instance as *mut PyCircuitInstance)).instance
+    } else if let Some(name) = py_to_str(instance) {
+        match circ.lookup_instance(name) {
+            Some(instance) => instance,
+            None => {
+                raise_runtime_exception(&format!("{fun}() instance '{name}' not found"));
+                return None;
+            }
+        }
+    } else {
+        raise_type_exception(&format!(
+            "{fun}() argument 'instance' must be a CircuitInstance or str"
+        ));
+        return None;
+    };
+
+    let info = &circ[instance];
+    let terminals = &circ[circ[info.model].device].terminals;
+    let terminal = if let Some(name) = py_to_str(terminal) {
+        match terminals.iter().position(|terminal| *terminal == name) {
+            Some(terminal) => terminal,
+            None => {
+                raise_runtime_exception(&format!(
+                    "{fun}() '{}' has no terminal '{name}'\n\thelp: the terminals are {terminals:?}",
+                    info.name
+                ));
+                return None;
+            }
+        }
+    } else {
+        let terminal = PyLong_AsSsize_t(terminal);
+        if terminal < 0 {
+            PyErr_Clear();
+            raise_type_exception(&format!(
+                "{fun}() argument 'terminal' must be a str or a positive int"
+            ));
+            return None;
+        }
+        terminal as usize
+    };
+
+    if terminal >= info.connections.len() {
+        raise_runtime_exception(&format!(
+            "{fun}() terminal {terminal} of '{}' is not connected",
+            info.name
+        ));
+        return None;
+    }
+
+    Some((instance, terminal))
+}
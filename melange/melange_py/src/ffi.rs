@@ -0,0 +1,37 @@
+use std::mem::size_of;
+use std::os::raw::c_ulong;
+
+use pyo3_ffi::*;
+
+#[cfg(Py_3_10)]
+const PY_TPFLAGS_IMMUTABLETYPE: c_ulong = pyo3_ffi::Py_TPFLAGS_IMMUTABLETYPE;
+#[cfg(not(Py_3_10))]
+const PY_TPFLAGS_IMMUTABLETYPE: c_ulong = 0;
+
+const TY_FLAGS: c_ulong = Py_TPFLAGS_DEFAULT | Py_TPFLAGS_BASETYPE | PY_TPFLAGS_IMMUTABLETYPE;
+
+macro_rules! zero {
+    ($ty:ty) => {{
+        union Init {
+            data: $ty,
+            raw: [u8; ::std::mem::size_of::<$ty>()],
+        }
+        Init { raw: [0; ::std::mem::size_of::<$ty>()] }.data
+    }};
+}
+
+// manual implementation of PyVarObject_HEAD_INIT macro
+pub const fn new_type<T>() -> PyTypeObject {
+    let mut res = unsafe { zero!(PyTypeObject) };
+    res.ob_base.ob_base.ob_refcnt = PyObjectObRefcnt { ob_refcnt: 1 };
+    res.tp_basicsize = size_of::<T>() as isize;
+    res.tp_flags = TY_FLAGS;
+
+    res
+}
+
+/// Frees the memory of an object created with `tp_alloc` after its fields have been dropped.
+pub unsafe fn free_object(obj: *mut PyObject) {
+    let free = (*Py_TYPE(obj)).tp_free.expect("types always have tp_free");
+    free(obj as *mut std::ffi::c_void)
+}
@@ -0,0 +1,81 @@
+#[macro_use]
+mod ffi;
+mod circuit;
+mod numpy;
+mod simulation;
+mod typeref;
+mod util;
+
+use std::os::raw::c_char;
+use std::ptr;
+
+use pyo3_ffi::*;
+
+use crate::circuit::{CIRCUIT_INSTANCE_TY, CIRCUIT_TY};
+use crate::simulation::SIMULATION_TY;
+use crate::typeref::init_typerefs;
+
+#[allow(clippy::missing_safety_doc)]
+#[allow(non_snake_case)]
+#[no_mangle]
+#[cold]
+pub unsafe extern "C" fn PyInit_melange() -> *mut PyObject {
+    let init = PyModuleDef {
+        m_base: PyModuleDef_HEAD_INIT,
+        m_name: "melange\0".as_ptr() as *const c_char,
+        m_doc: std::ptr::null(),
+        m_size: 0,
+        m_methods: std::ptr::null_mut(),
+        m_slots: std::ptr::null_mut(),
+        m_traverse: None,
+        m_clear: None,
+        m_free: None,
+    };
+
+    // all results are returned as numpy arrays
+    if !init_typerefs() {
+        PyErr_SetString(PyExc_ImportError, "melange requires numpy\0".as_ptr() as *const c_char);
+        return ptr::null_mut();
+    }
+
+    let types = [
+        ("Circuit\0", std::ptr::addr_of_mut!(CIRCUIT_TY)),
+        ("CircuitInstance\0", std::ptr::addr_of_mut!(CIRCUIT_INSTANCE_TY)),
+        ("Simulation\0", std::ptr::addr_of_mut!(SIMULATION_TY)),
+    ];
+
+    for (_, ty) in types {
+        if PyType_Ready(ty) < 0 {
+            return ptr::null_mut();
+        }
+    }
+
+    let mptr = PyModule_Create(Box::into_raw(Box::new(init)));
+    let version = env!("CARGO_PKG_VERSION");
+    PyModule_AddObject(
+        mptr,
+        "__version__\0".as_ptr() as *const c_char,
+        PyUnicode_FromStringAndSize(version.as_ptr() as *const c_char, version.len() as isize),
+    );
+
+    for (name, ty) in types {
+        // PyModule_AddObject steals a reference
+        Py_INCREF(ty as *mut PyObject);
+        PyModule_AddObject(mptr, name.as_ptr() as *const c_char, ty as *mut PyObject);
+    }
+
+    let all = ["__all__\0", "__version__\0", "Circuit\0", "CircuitInstance\0", "Simulation\0"];
+
+    let pyall = PyTuple_New(all.len() as isize);
+    for (i, obj) in all.iter().enumerate() {
+        PyTuple_SET_ITEM(
+            pyall,
+            i as isize,
+            PyUnicode_InternFromString(obj.as_ptr() as *const c_char),
+        )
+    }
+
+    PyModule_AddObject(mptr, "__all__\0".as_ptr() as *const c_char, pyall);
+
+    mptr
+}
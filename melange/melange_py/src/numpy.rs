@@ -0,0 +1,189 @@
+use std::ffi::c_void;
+use std::fmt::Display;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use num_complex::Complex64;
+use pyo3_ffi::{
+    PyObject, PyObject_GetAttr, PyTypeObject, Py_DECREF, Py_INCREF, Py_intptr_t, Py_ssize_t,
+};
+
+use crate::typeref::{
+    ARRAY_STRUCT_STR, NUMPY_API, NUMPY_ARR_TYPE, NUMPY_CDOUBLE_DESCR, NUMPY_DOUBLE_DESCR,
+};
+
+// https://docs.scipy.org/doc/numpy/reference/arrays.interface.html#c.__array_struct__
+
+#[repr(C)]
+struct PyArrayInterface {
+    pub two: c_int,
+    pub nd: c_int,
+    pub typekind: c_char,
+    pub itemsize: c_int,
+    pub flags: c_int,
+    pub shape: *mut Py_intptr_t,
+    pub strides: *mut Py_intptr_t,
+    pub data: *mut c_void,
+    pub descr: *mut PyObject,
+}
+
+#[repr(C)]
+struct PyCapsule {
+    pub ob_refcnt: Py_ssize_t,
+    pub ob_type: *mut PyTypeObject,
+    pub pointer: *mut c_void,
+    pub name: *const c_char,
+    pub context: *mut c_void,
+    pub destructor: *mut c_void, // should be typedef void (*PyCapsule_Destructor)(PyObject *);
+}
+
+// the first fields of PyArrayObject, the rest of the struct is never accessed
+#[repr(C)]
+struct PyArrayObject {
+    pub ob_base: PyObject,
+    pub data: *mut c_char,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ItemType {
+    Float,
+    Int,
+    Long,
+}
+
+impl Display for ItemType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ItemType::Float => write!(f, "float64"),
+            ItemType::Int => write!(f, "int32"),
+            ItemType::Long => write!(f, "int64"),
+        }
+    }
+}
+
+impl ItemType {
+    fn find(array: *mut PyArrayInterface) -> Option<ItemType> {
+        match unsafe { ((*array).typekind, (*array).itemsize) } {
+            (102, 8) => Some(ItemType::Float),
+            (105 | 117, 4) => Some(ItemType::Int),
+            (105 | 117, 8) => Some(ItemType::Long),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PyArrayError {
+    Malformed,
+    UnsupportedDataType,
+}
+
+pub struct NumpyArray {
+    array: *mut PyArrayInterface,
+    capsule: *mut PyCapsule,
+    pub kind: ItemType,
+}
+
+impl NumpyArray {
+    pub fn new(ptr: *mut PyObject) -> Result<Self, PyArrayError> {
+        let capsule = unsafe { PyObject_GetAttr(ptr, ARRAY_STRUCT_STR) };
+        let array = unsafe { (*(capsule as *mut PyCapsule)).pointer as *mut PyArrayInterface };
+        if unsafe { (*array).two != 2 } {
+            unsafe { Py_DECREF(capsule) };
+            Err(PyArrayError::Malformed)
+        } else {
+            let num_dimensions = unsafe { (*array).nd as usize };
+            if num_dimensions != 1 {
+                unsafe { Py_DECREF(capsule) };
+                return Err(PyArrayError::UnsupportedDataType);
+            }
+
+            let stride = unsafe { *(*array).strides };
+            let itemsize = unsafe { (*array).itemsize };
+
+            if stride % itemsize as isize != 0 {
+                unsafe { Py_DECREF(capsule) };
+                return Err(PyArrayError::UnsupportedDataType);
+            }
+
+            match ItemType::find(array) {
+                None => {
+                    unsafe { Py_DECREF(capsule) };
+                    Err(PyArrayError::UnsupportedDataType)
+                }
+                Some(kind) => {
+                    let pyarray = NumpyArray { array, capsule: capsule as *mut PyCapsule, kind };
+                    Ok(pyarray)
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe { *(*self.array).shape as usize }
+    }
+
+    fn stride(&self) -> isize {
+        unsafe { *(*self.array).strides / (*self.array).itemsize as isize }
+    }
+
+    /// Copies the contents of the array into a new slice of floats.
+    pub fn to_f64(&self) -> Box<[f64]> {
+        unsafe fn read<T: Copy>(arr: &NumpyArray, conv: impl Fn(T) -> f64) -> Box<[f64]> {
+            let data = (*arr.array).data as *const T;
+            (0..arr.len() as isize).map(|i| conv(*data.offset(i * arr.stride()))).collect()
+        }
+
+        unsafe {
+            match self.kind {
+                ItemType::Float => read(self, |val: f64| val),
+                ItemType::Int => read(self, |val: i32| val as f64),
+                ItemType::Long => read(self, |val: i64| val as f64),
+            }
+        }
+    }
+}
+
+impl Drop for NumpyArray {
+    fn drop(&mut self) {
+        unsafe { Py_DECREF(self.capsule as *mut pyo3_ffi::PyObject) }
+    }
+}
+
+#[inline(always)]
+pub unsafe fn is_array(obj: *mut PyObject) -> bool {
+    !NUMPY_ARR_TYPE.is_null() && (*obj).ob_type == NUMPY_ARR_TYPE
+}
+
+unsafe fn new_array<T: Copy>(descr: *mut PyObject, vals: &[T]) -> *mut PyObject {
+    let new_arr = NUMPY_API.expect("numpy is loaded during module initialization");
+    let mut len = vals.len() as Py_intptr_t;
+    let mut stride = std::mem::size_of::<T>() as Py_intptr_t;
+    // PyArray_NewFromDescr steals a reference to the descriptor
+    Py_INCREF(descr);
+    let res = new_arr(
+        NUMPY_ARR_TYPE,  // base_type (normal numpy array)
+        descr,           // type descriptor
+        1,               // nd
+        &mut len,        // dims
+        &mut stride,     // strides
+        ptr::null_mut(), // data (to be allocated)
+        0,               // flags
+        ptr::null_mut(), // obj (to be created)
+    );
+    if !res.is_null() {
+        let data = (*(res as *mut PyArrayObject)).data as *mut T;
+        ptr::copy_nonoverlapping(vals.as_ptr(), data, vals.len());
+    }
+    res
+}
+
+/// Creates a new float64 numpy array with the contents of `vals`
+pub unsafe fn new_f64_array(vals: &[f64]) -> *mut PyObject {
+    new_array(NUMPY_DOUBLE_DESCR, vals)
+}
+
+/// Creates a new complex128 numpy array with the contents of `vals`
+pub unsafe fn new_complex_array(vals: &[Complex64]) -> *mut PyObject {
+    new_array(NUMPY_CDOUBLE_DESCR, vals)
+}
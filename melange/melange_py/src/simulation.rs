@@ -0,0 +1,295 @@
+use std::f64::consts::TAU;
+use std::os::raw::c_char;
+use std::ptr;
+
+use anyhow::{bail, Context, Result};
+use melange_core::circuit::InstanceId;
+use melange_core::simulation::Simulation;
+use melange_core::{Arena, CircuitParam, ExprEvalCtx};
+use num_complex::Complex64;
+use pyo3_ffi::*;
+use typed_index_collections::TiSlice;
+
+use crate::circuit::{lookup_terminal, PyCircuit};
+use crate::ffi::{free_object, new_type};
+use crate::numpy::{new_complex_array, new_f64_array};
+use crate::util::{parse_args, py_to_f64, raise_error, raise_type_exception};
+
+pub static mut SIMULATION_TY: PyTypeObject = {
+    let mut res = new_type::<PySimulation>();
+    res.tp_name = "melange.Simulation\0".as_ptr() as *const c_char;
+    res.tp_doc =
+        "A simulation of a circuit created with Circuit.prepare_sim\0".as_ptr() as *const c_char;
+    res.tp_methods = std::ptr::addr_of_mut!(SIMULATION_METHODS) as *mut _;
+    res.tp_dealloc = Some(PySimulation::dealloc);
+    res
+};
+
+static mut SIMULATION_METHODS: [PyMethodDef; 5] = [
+    PyMethodDef {
+        ml_name: "dc_op\0".as_ptr() as *const c_char,
+        ml_meth: PyMethodDefPointer { PyCFunction: PySimulation::dc_op },
+        ml_flags: METH_NOARGS,
+        ml_doc: "calculates the DC operating point\nReturns a dict with the voltage of every circuit node\0"
+            .as_ptr() as *const c_char,
+    },
+    PyMethodDef {
+        ml_name: "lead_current\0".as_ptr() as *const c_char,
+        ml_meth: PyMethodDefPointer { PyCFunctionWithKeywords: PySimulation::lead_current },
+        ml_flags: METH_VARARGS | METH_KEYWORDS,
+        ml_doc: "returns the current flowing into a terminal of an instance at the DC operating point\0"
+            .as_ptr() as *const c_char,
+    },
+    PyMethodDef {
+        ml_name: "ac\0".as_ptr() as *const c_char,
+        ml_meth: PyMethodDefPointer { PyCFunctionWithKeywords: PySimulation::ac },
+        ml_flags: METH_VARARGS | METH_KEYWORDS,
+        ml_doc: "sets up a small signal simulation at the frequency `freq` around the DC operating points\0"
+            .as_ptr() as *const c_char,
+    },
+    PyMethodDef {
+        ml_name: "ac_lead_current\0".as_ptr() as *const c_char,
+        ml_meth: PyMethodDefPointer { PyCFunctionWithKeywords: PySimulation::ac_lead_current },
+        ml_flags: METH_VARARGS | METH_KEYWORDS,
+        ml_doc: "returns the small signal current flowing into a terminal of an instance\0"
+            .as_ptr() as *const c_char,
+    },
+    unsafe { zero!(PyMethodDef) },
+];
+
+struct SimData {
+    sim: Simulation<'static>,
+    ctx: ExprEvalCtx<'static>,
+    arena: &'static Arena,
+    /// circuit parameters that have a different value for each simulation
+    sweeps: Box<[(CircuitParam, Box<[f64]>)]>,
+    len: usize,
+    /// the DC operating point of each simulation
+    ops: Vec<Box<[f64]>>,
+    omega: Option<f64>,
+}
+
+impl SimData {
+    /// Sets the circuit parameters of the `i`th simulation
+    fn prepare(&mut self, i: usize) -> Result<()> {
+        for (param, vals) in &*self.sweeps {
+            self.ctx.set_param(*param, vals[i].into());
+        }
+        self.sim.prepare_solver(self.ctx.borrow(), self.arena)
+    }
+
+    fn context(&self, i: usize) -> impl Fn() -> String + '_ {
+        move || {
+            let mut res = format!("simulation {i} failed");
+            for (param, vals) in &*self.sweeps {
+                let (name, _) = self.arena.lookup_param_info(*param).unwrap();
+                res.push_str(&format!(" ({name} = {})", vals[i]));
+            }
+            res
+        }
+    }
+
+    fn dc_op(&mut self) -> Result<()> {
+        let mut ops: Vec<Box<[f64]>> = Vec::with_capacity(self.len);
+        for i in 0..self.len {
+            self.prepare(i).with_context(self.context(i))?;
+            // the previous operating point is usually a good initial guess for a sweep
+            if let Some(prev) = ops.last() {
+                self.sim.set_initial_guess(TiSlice::from_ref(&**prev));
+            }
+            let op = match self.sim.dc_op() {
+                Ok(op) => op.raw.to_vec().into_boxed_slice(),
+                Err(err) => return Err(err.context(self.context(i)())),
+            };
+            ops.push(op);
+        }
+        self.ops = ops;
+        Ok(())
+    }
+
+    /// Prepares the `i`th simulation starting at its DC operating point
+    fn restore_op(&mut self, i: usize) -> Result<()> {
+        self.prepare(i).with_context(self.context(i))?;
+        self.sim.set_initial_guess(TiSlice::from_ref(&*self.ops[i]));
+        Ok(())
+    }
+
+    fn lead_current(&mut self, instance: InstanceId, terminal: usize) -> Result<Vec<f64>> {
+        if self.ops.is_empty() {
+            bail!("dc_op() must be called before lead_current()")
+        }
+        (0..self.len)
+            .map(|i| {
+                self.restore_op(i)?;
+                let res = self.sim.dc_lead_current(instance).with_context(self.context(i))?;
+                Ok(res[terminal])
+            })
+            .collect()
+    }
+
+    fn ac_lead_current(&mut self, instance: InstanceId, terminal: usize) -> Result<Vec<Complex64>> {
+        let omega = self.omega.context("ac() must be called before ac_lead_current()")?;
+        (0..self.len)
+            .map(|i| {
+                self.restore_op(i)?;
+                self.sim.set_omega(omega);
+                let res = self.sim.ac_lead_current(instance).with_context(self.context(i))?;
+                Ok(res[terminal])
+            })
+            .collect()
+    }
+}
+
+#[repr(C)]
+pub struct PySimulation {
+    ob_base: PyObject,
+    circ: *mut PyObject,
+    data: Box<SimData>,
+}
+
+impl PySimulation {
+    #[allow(clippy::new_ret_no_self)]
+    pub unsafe fn new(
+        circ: *mut PyObject,
+        sim: Simulation<'static>,
+        ctx: ExprEvalCtx<'static>,
+        sweeps: Box<[(CircuitParam, Box<[f64]>)]>,
+        len: usize,
+    ) -> *mut PyObject {
+        let arena = &*(&(*(circ as *mut PyCircuit)).data().arena as *const Arena);
+        let mut data =
+            Box::new(SimData { sim, ctx, arena, sweeps, len, ops: Vec::new(), omega: None });
+        // report missing parameters right away
+        if let Err(err) = data.prepare(0) {
+            return raise_error(err);
+        }
+
+        let ptr = SIMULATION_TY.tp_alloc.unwrap()(std::ptr::addr_of_mut!(SIMULATION_TY), 0);
+        if ptr.is_null() {
+            return ptr::null_mut();
+        }
+        Py_INCREF(circ);
+        (*(circ as *mut PyCircuit)).sims += 1;
+        let res = ptr as *mut Self;
+        ptr::write(ptr::addr_of_mut!((*res).circ), circ);
+        ptr::write(ptr::addr_of_mut!((*res).data), data);
+        ptr
+    }
+
+    unsafe extern "C" fn dealloc(sel: *mut PyObject) {
+        let res = sel as *mut Self;
+        // the simulation borrows the circuit so it must be dropped first
+        ptr::drop_in_place(ptr::addr_of_mut!((*res).data));
+        let circ = (*res).circ;
+        (*(circ as *mut PyCircuit)).sims -= 1;
+        Py_DECREF(circ);
+        free_object(sel)
+    }
+
+    /// Whether results are returned as numpy arrays instead of scalars
+    fn is_sweep(&self) -> bool {
+        !self.data.sweeps.is_empty()
+    }
+
+    unsafe extern "C" fn dc_op(self_: *mut PyObject, _args: *mut PyObject) -> *mut PyObject {
+        let self_ = &mut *(self_ as *mut Self);
+        if let Err(err) = self_.data.dc_op() {
+            return raise_error(err);
+        }
+
+        let circ = &(*(self_.circ as *mut PyCircuit)).data().circ;
+        let res = PyDict_New();
+        for node in circ.nodes() {
+            let idx = usize::from(node);
+            let val = if self_.is_sweep() {
+                let vals: Vec<_> = self_.data.ops.iter().map(|op| op[idx]).collect();
+                new_f64_array(&vals)
+            } else {
+                PyFloat_FromDouble(self_.data.ops[0][idx])
+            };
+            let name = circ.node_name(node);
+            let name =
+                PyUnicode_FromStringAndSize(name.as_ptr() as *const c_char, name.len() as isize);
+            PyDict_SetItem(res, name, val);
+            Py_DECREF(name);
+            Py_DECREF(val);
+        }
+        res
+    }
+
+    unsafe extern "C" fn lead_current(
+        self_: *mut PyObject,
+        args: *mut PyObject,
+        kwds: *mut PyObject,
+    ) -> *mut PyObject {
+        let self_ = &mut *(self_ as *mut Self);
+        let [instance, terminal] =
+            match parse_args("lead_current", args, kwds, ["instance", "terminal"], 2) {
+                Some(args) => args,
+                None => return ptr::null_mut(),
+            };
+        let circ = &(*(self_.circ as *mut PyCircuit)).data().circ;
+        let (instance, terminal) = match lookup_terminal("lead_current", circ, instance, terminal) {
+            Some(res) => res,
+            None => return ptr::null_mut(),
+        };
+
+        match self_.data.lead_current(instance, terminal) {
+            Ok(res) if self_.is_sweep() => new_f64_array(&res),
+            Ok(res) => PyFloat_FromDouble(res[0]),
+            Err(err) => raise_error(err),
+        }
+    }
+
+    unsafe extern "C" fn ac(
+        self_: *mut PyObject,
+        args: *mut PyObject,
+        kwds: *mut PyObject,
+    ) -> *mut PyObject {
+        let self_ = &mut *(self_ as *mut Self);
+        let [freq] = match parse_args("ac", args, kwds, ["freq"], 1) {
+            Some(args) => args,
+            None => return ptr::null_mut(),
+        };
+        let freq = match py_to_f64(freq) {
+            Some(freq) => freq,
+            None => return raise_type_exception("ac() argument 'freq' must be a number"),
+        };
+
+        // the small signal simulation is performed around the operating points
+        if self_.data.ops.is_empty() {
+            if let Err(err) = self_.data.dc_op() {
+                return raise_error(err);
+            }
+        }
+        self_.data.omega = Some(TAU * freq);
+
+        Py_INCREF(Py_None());
+        Py_None()
+    }
+
+    unsafe extern "C" fn ac_lead_current(
+        self_: *mut PyObject,
+        args: *mut PyObject,
+        kwds: *mut PyObject,
+    ) -> *mut PyObject {
+        let self_ = &mut *(self_ as *mut Self);
+        let [instance, terminal] =
+            match parse_args("ac_lead_current", args, kwds, ["instance", "terminal"], 2) {
+                Some(args) => args,
+                None => return ptr::null_mut(),
+            };
+        let circ = &(*(self_.circ as *mut PyCircuit)).data().circ;
+        let (instance, terminal) =
+            match lookup_terminal("ac_lead_current", circ, instance, terminal) {
+                Some(res) => res,
+                None => return ptr::null_mut(),
+            };
+
+        match self_.data.ac_lead_current(instance, terminal) {
+            Ok(res) if self_.is_sweep() => new_complex_array(&res),
+            Ok(res) => PyComplex_FromDoubles(res[0].re, res[0].im),
+            Err(err) => raise_error(err),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::ffi::c_void;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::sync::Once;
+
+use pyo3_ffi::{
+    PyCapsule_GetPointer, PyErr_Clear, PyImport_ImportModule, PyMapping_GetItemString, PyObject,
+    PyObject_GenericGetDict, PyObject_GetAttrString, PyTypeObject, PyUnicode_InternFromString,
+    Py_XDECREF, Py_intptr_t,
+};
+
+const NPY_DOUBLE: c_int = 12;
+const NPY_CDOUBLE: c_int = 15;
+
+pub type PyArrayNew = extern "C" fn(
+    subtype: *mut PyTypeObject,
+    descr: *mut PyObject,
+    nd: c_int,
+    dims: *mut Py_intptr_t,
+    strides: *mut Py_intptr_t,
+    data: *mut c_void,
+    flags: c_int,
+    obj: *mut PyObject,
+) -> *mut PyObject;
+
+pub static mut NUMPY_ARR_TYPE: *mut PyTypeObject = 0 as *mut PyTypeObject;
+pub static mut NUMPY_API: Option<PyArrayNew> = None;
+pub static mut NUMPY_DOUBLE_DESCR: *mut PyObject = 0 as *mut PyObject;
+pub static mut NUMPY_CDOUBLE_DESCR: *mut PyObject = 0 as *mut PyObject;
+pub static mut ARRAY_STRUCT_STR: *mut PyObject = 0 as *mut PyObject;
+
+static INIT: Once = Once::new();
+
+/// Looks up the numpy types and API. Returns `false` if numpy is not available.
+#[cold]
+pub fn init_typerefs() -> bool {
+    INIT.call_once(|| unsafe {
+        ARRAY_STRUCT_STR =
+            PyUnicode_InternFromString("__array_struct__\0".as_ptr() as *const c_char);
+
+        let numpy = PyImport_ImportModule("numpy\0".as_ptr() as *const c_char);
+        if numpy.is_null() {
+            PyErr_Clear();
+            return;
+        }
+        NUMPY_ARR_TYPE = lookup_module_type(numpy, "ndarray\0");
+        Py_XDECREF(numpy);
+
+        if let Some(numpy_api) = get_numpy_api() {
+            let api = *(numpy_api.offset(94) as *const PyArrayNew);
+            let py_array_descr_from_type =
+                *(numpy_api.offset(45) as *const fn(type_: c_int) -> *mut PyObject);
+
+            NUMPY_DOUBLE_DESCR = py_array_descr_from_type(NPY_DOUBLE);
+            assert!(!NUMPY_DOUBLE_DESCR.is_null());
+            NUMPY_CDOUBLE_DESCR = py_array_descr_from_type(NPY_CDOUBLE);
+            assert!(!NUMPY_CDOUBLE_DESCR.is_null());
+
+            NUMPY_API = Some(api);
+        }
+    });
+    unsafe { NUMPY_API.is_some() }
+}
+
+#[cold]
+unsafe fn lookup_module_type(module: *mut PyObject, name: &str) -> *mut PyTypeObject {
+    let mod_dict = PyObject_GenericGetDict(module, std::ptr::null_mut());
+    let ptr = PyMapping_GetItemString(mod_dict, name.as_ptr() as *const c_char);
+    Py_XDECREF(ptr);
+    Py_XDECREF(mod_dict);
+    ptr as *mut PyTypeObject
+}
+
+#[cold]
+fn get_numpy_api() -> Option<*const *const c_void> {
+    unsafe {
+        let numpy = PyImport_ImportModule("numpy.core.multiarray\0".as_ptr() as *const c_char);
+        if numpy.is_null() {
+            PyErr_Clear();
+            return None;
+        }
+        let capsule = PyObject_GetAttrString(numpy as _, "_ARRAY_API\0".as_ptr() as *const c_char);
+        if capsule.is_null() {
+            PyErr_Clear();
+            return None;
+        }
+        Some(PyCapsule_GetPointer(capsule, ptr::null_mut()) as _)
+    }
+}
@@ -0,0 +1,102 @@
+use std::os::raw::c_char;
+use std::{ptr, slice, str};
+
+use pyo3_ffi::*;
+
+#[cold]
+#[inline(never)]
+fn raise_exception(ty: *mut PyObject, msg: &str) -> *mut PyObject {
+    unsafe {
+        let err_msg =
+            PyUnicode_FromStringAndSize(msg.as_ptr() as *const c_char, msg.len() as isize);
+        PyErr_SetObject(ty, err_msg);
+        Py_DECREF(err_msg);
+    };
+    ptr::null_mut()
+}
+
+pub fn raise_type_exception(msg: &str) -> *mut PyObject {
+    raise_exception(unsafe { PyExc_TypeError }, msg)
+}
+
+pub fn raise_runtime_exception(msg: &str) -> *mut PyObject {
+    raise_exception(unsafe { PyExc_RuntimeError }, msg)
+}
+
+/// Raises a `RuntimeError` with the complete context chain of `err`
+pub fn raise_error(err: anyhow::Error) -> *mut PyObject {
+    raise_runtime_exception(&format!("{err:#}"))
+}
+
+/// Reads a python `str`. The returned string is valid as long as `obj` is alive.
+pub unsafe fn py_to_str<'a>(obj: *mut PyObject) -> Option<&'a str> {
+    let mut size = 0;
+    let data = PyUnicode_AsUTF8AndSize(obj, &mut size) as *const u8;
+    if data.is_null() {
+        PyErr_Clear();
+        return None;
+    }
+    Some(str::from_utf8_unchecked(slice::from_raw_parts(data, size as usize)))
+}
+
+/// Reads a python `float` or `int`.
+pub unsafe fn py_to_f64(obj: *mut PyObject) -> Option<f64> {
+    let res = PyFloat_AsDouble(obj);
+    if res == -1.0 && !PyErr_Occurred().is_null() {
+        PyErr_Clear();
+        return None;
+    }
+    Some(res)
+}
+
+/// Matches positional and keyword arguments of a function call to the parameter `names`.
+/// The first `required` parameters must be provided, any other missing parameters are null.
+/// If the arguments do not match the parameters an exception is raised and `None` is returned.
+pub unsafe fn parse_args<const N: usize>(
+    fun: &str,
+    args: *mut PyObject,
+    kwds: *mut PyObject,
+    names: [&str; N],
+    required: usize,
+) -> Option<[*mut PyObject; N]> {
+    let mut res = [ptr::null_mut(); N];
+    let num_args = PyTuple_GET_SIZE(args) as usize;
+    if num_args > N {
+        raise_type_exception(&format!("{fun}() takes at most {N} arguments ({num_args} given)"));
+        return None;
+    }
+    for (i, dst) in res.iter_mut().enumerate().take(num_args) {
+        *dst = PyTuple_GET_ITEM(args, i as Py_ssize_t);
+    }
+
+    if !kwds.is_null() {
+        let mut pos = 0;
+        let mut key = ptr::null_mut();
+        let mut val = ptr::null_mut();
+        while PyDict_Next(kwds, &mut pos, &mut key, &mut val) != 0 {
+            let key = py_to_str(key).unwrap_or_default();
+            match names.iter().position(|name| *name == key) {
+                Some(i) if res[i].is_null() => res[i] = val,
+                Some(_) => {
+                    raise_type_exception(&format!(
+                        "{fun}() got multiple values for argument '{key}'"
+                    ));
+                    return None;
+                }
+                None => {
+                    raise_type_exception(&format!(
+                        "{fun}() got an unexpected keyword argument '{key}'"
+                    ));
+                    return None;
+                }
+            }
+        }
+    }
+
+    if let Some(i) = res[..required].iter().position(|arg| arg.is_null()) {
+        raise_type_exception(&format!("{fun}() missing required argument '{}'", names[i]));
+        return None;
+    }
+
+    Some(res)
+}
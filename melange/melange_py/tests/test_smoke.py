@@ -0,0 +1,36 @@
+# Runs an operating point and an AC analysis of a voltage divider through the python bindings
+import numpy as np
+from melange import Circuit, CircuitInstance
+
+circ = Circuit("divider")
+vsrc = CircuitInstance(circ, "vsrc", "vsource", ports=["in", "gnd"])
+vsrc.set_param("dc", "vdc")
+vsrc.set_param("mag", 1)
+r1 = CircuitInstance(circ, "r1", "resistor", ports=["in", "out"])
+r1.set_param("r", 1e3)
+r2 = CircuitInstance(circ, "r2", "resistor", ports=["out", "gnd"])
+r2.set_param("r", 3e3)
+
+# a single operating point returns floats
+sim = circ.prepare_sim(vdc=2.0)
+op = sim.dc_op()
+assert np.isclose(op["in"], 2.0)
+assert np.isclose(op["out"], 1.5)
+curr = sim.lead_current("vsrc", "A")
+assert isinstance(curr, float)
+assert np.isclose(curr, -0.5e-3)
+
+sim.ac(freq=1e3)
+ac_curr = sim.ac_lead_current("vsrc", "A")
+assert isinstance(ac_curr, complex)
+assert np.isclose(ac_curr, -0.25e-3)
+
+# sweeps return one value per parameter value
+vdc = np.linspace(0.0, 4.0, 5)
+sim = circ.prepare_sim(vdc=vdc)
+op = sim.dc_op()
+assert np.allclose(op["out"], 0.75 * vdc)
+assert np.allclose(sim.lead_current("vsrc", "A"), -vdc / 4e3)
+
+sim.ac(freq=1e3)
+assert np.allclose(sim.ac_lead_current("vsrc", "A"), np.full(len(vdc), -0.25e-3))
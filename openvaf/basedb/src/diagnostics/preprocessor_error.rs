@@ -2,6 +2,7 @@ use std::fmt::Write;
 
 use ahash::AHashSet;
 use stdx::iter::zip;
+use syntax::sourcemap::{CtxSpan, SourceMap};
 use syntax::PreprocessorDiagnostic;
 use vfs::FileId;
 
@@ -51,7 +52,34 @@ impl Diagnostic for PreprocessorDiagnostic {
                     message: "macro not defined here".to_owned(),
                 }])
             }
-            PreprocessorDiagnostic::MacroRecursion { .. } => todo!(),
+            PreprocessorDiagnostic::MacroRecursion { ref name, span, ref expansion } => {
+                let span = span.to_file_span(&sm);
+                let mut labels = vec![Label {
+                    style: LabelStyle::Primary,
+                    file_id: span.file,
+                    range: span.range.into(),
+                    message: format!("'`{}' is called recursively here", name),
+                }];
+                labels.extend(expansion_labels(expansion, &sm));
+                Report::error()
+                    .with_labels(labels)
+                    .with_notes(vec![expansion_chain_note(expansion, name)])
+            }
+            PreprocessorDiagnostic::MacroExpansionLimit { ref name, span, ref expansion } => {
+                let span = span.to_file_span(&sm);
+                // only show the innermost expansions, the full chain is far too long to be useful
+                let shown = &expansion[expansion.len().saturating_sub(8)..];
+                let mut labels = vec![Label {
+                    style: LabelStyle::Primary,
+                    file_id: span.file,
+                    range: span.range.into(),
+                    message: format!("'`{}' is called here", name),
+                }];
+                labels.extend(expansion_labels(shown, &sm));
+                Report::error().with_labels(labels).with_notes(vec![
+                    "help: check for macros that (indirectly) expand to each other".to_owned(),
+                ])
+            }
             PreprocessorDiagnostic::UnsupportedCompDir { span, .. } => {
                 let span = span.to_file_span(&sm);
 
@@ -275,3 +303,27 @@ impl Diagnostic for PreprocessorDiagnostic {
         report.with_message(self.to_string())
     }
 }
+
+fn expansion_labels<'a>(
+    expansion: &'a [(String, CtxSpan)],
+    sm: &'a SourceMap,
+) -> impl Iterator<Item = Label> + 'a {
+    expansion.iter().map(|(name, span)| {
+        let span = span.to_file_span(sm);
+        Label {
+            style: LabelStyle::Secondary,
+            file_id: span.file,
+            range: span.range.into(),
+            message: format!("while expanding '`{}' called here", name),
+        }
+    })
+}
+
+fn expansion_chain_note(expansion: &[(String, CtxSpan)], name: &str) -> String {
+    let mut note = "info: expansion chain: ".to_owned();
+    for (macro_name, _) in expansion {
+        write!(note, "`{macro_name} -> ").unwrap();
+    }
+    write!(note, "`{name}").unwrap();
+    note
+}
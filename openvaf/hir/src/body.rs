@@ -11,35 +11,68 @@ pub use syntax::ast::{BinaryOp, UnaryOp};
 
 use crate::{
     Branch, BranchWrite, CompilationDB, Function, FunctionArg, NatureAttribute, Node, Parameter,
-    Variable,
+    SourcePos, Variable,
 };
 
 #[derive(Debug, Clone)]
 pub struct Body {
+    id: DefWithBodyId,
     body: Arc<hir_def::body::Body>,
     infere: Arc<inference::InferenceResult>,
 }
 impl Body {
     pub(crate) fn new(id: DefWithBodyId, db: &CompilationDB) -> Body {
-        Body { body: db.body(id), infere: db.inference_result(id) }
+        Body { id, body: db.body(id), infere: db.inference_result(id) }
     }
 
     pub fn borrow(&self) -> BodyRef<'_> {
-        BodyRef { body: &self.body, infere: &self.infere }
+        BodyRef { id: self.id, body: &self.body, infere: &self.infere }
+    }
+}
+
+/// Identifies the body of a module, function or declaration. Expressions ([`ExprId`]) are
+/// only unique within a single body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BodyId(DefWithBodyId);
+
+impl BodyId {
+    /// Returns the source position of `expr`. Expressions that were created during lowering
+    /// (and therefore have no syntax) return `None`.
+    pub fn expr_pos(self, db: &CompilationDB, expr: ExprId) -> Option<SourcePos> {
+        let ptr = db.body_source_map(self.0).expr_map_back[expr].clone()?;
+        Some(SourcePos::new(db, self.0.file(db), ptr.range()))
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct BodyRef<'a> {
+    id: DefWithBodyId,
     body: &'a hir_def::body::Body,
     infere: &'a inference::InferenceResult,
 }
 
 impl<'a> BodyRef<'a> {
+    pub fn id(&self) -> BodyId {
+        BodyId(self.id)
+    }
+
+    /// Number of expressions in this body. All [`ExprId`]s of this body are smaller.
+    pub fn num_exprs(&self) -> usize {
+        self.body.exprs.len()
+    }
+
     pub fn entry(&self) -> &'a [StmtId] {
         &self.body.entry_stmts
     }
 
+    /// Returns all variables that are assigned somewhere in this body.
+    pub fn assigned_vars(&self) -> impl Iterator<Item = Variable> + 'a {
+        self.infere.assignment_destination.values().filter_map(|dst| match *dst {
+            inference::AssignDst::Var(id) => Some(Variable { id }),
+            _ => None,
+        })
+    }
+
     /// Returns the type that was inferred for this expression
     pub fn expr_type(&self, expr: ExprId) -> Type {
         self.infere.expr_types[expr].to_value().unwrap()
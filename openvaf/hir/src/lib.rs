@@ -14,6 +14,7 @@ use std::sync::Arc;
 use basedb::diagnostics::sink::Buffer;
 use basedb::diagnostics::ConsoleSink;
 pub use basedb::diagnostics::DiagnosticSink;
+use basedb::line_index::LineCol;
 use basedb::{BaseDB, FileId};
 pub use hir_def::body::{ConstraintValue, ParamConstraint};
 use hir_def::db::HirDefDB;
@@ -34,10 +35,13 @@ use salsa::InternKey;
 use smol_str::SmolStr;
 use syntax::ast;
 pub use syntax::name::Name;
+use syntax::sourcemap::FileSpan;
+use syntax::TextRange;
 
 pub use crate::attributes::AstCache;
 pub use crate::body::{
-    AssignmentLhs, Body, BodyRef, ContributeKind, Expr, ExprId, Ref, ResolvedFun, Stmt, StmtId,
+    AssignmentLhs, Body, BodyId, BodyRef, ContributeKind, Expr, ExprId, Ref, ResolvedFun, Stmt,
+    StmtId,
 };
 pub use crate::db::CompilationDB;
 
@@ -58,6 +62,29 @@ pub mod signatures {
     pub use hir_ty::types::{BOOL_EQ, INT_EQ, INT_OP, REAL_EQ, REAL_OP, STR_EQ};
 }
 
+/// A position in a Verilog-A source file. Lines and columns are zero based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourcePos {
+    pub file: FileId,
+    pub line: u32,
+    pub col: u32,
+}
+
+impl SourcePos {
+    /// Position of the start of `range` which is a range in the preprocessed source of
+    /// `root_file`.
+    fn new(db: &CompilationDB, root_file: FileId, range: TextRange) -> SourcePos {
+        let FileSpan { range, file } =
+            db.parse(root_file).to_file_span(range, &db.sourcemap(root_file));
+        let LineCol { line, col } = db.line_index(file).line_col(range.start());
+        SourcePos { file, line, col }
+    }
+
+    pub fn file_path(&self, db: &CompilationDB) -> String {
+        db.file_path(self.file).to_string()
+    }
+}
+
 /// A root file represents a compilation root file.
 /// A phsical file may be part of multiple root filer trough
 /// include statements. This is however not the case here
@@ -139,6 +166,12 @@ impl Module {
         self.id.lookup(db)
     }
 
+    /// Position of the module declaration.
+    pub fn decl_pos(self, db: &CompilationDB) -> SourcePos {
+        let loc = self.lookup(db);
+        SourcePos::new(db, loc.scope.root_file, loc.ast_ptr(db).range())
+    }
+
     /// list of all child scopes.
     pub fn child_scopes(self, db: &CompilationDB) -> Vec<Scope> {
         Scope::Module(self).children(db)
@@ -438,6 +471,12 @@ impl Variable {
         Body::new(self.id.into(), db)
     }
 
+    /// Position of the variable declaration.
+    pub fn decl_pos(self, db: &CompilationDB) -> SourcePos {
+        let loc = self.id.lookup(db);
+        SourcePos::new(db, loc.scope.root_file, loc.ast_ptr(db).range())
+    }
+
     pub fn get_attr(&self, db: &CompilationDB, ast: &AstCache, name: &str) -> Option<ast::Attr> {
         ast.resolve_attribute(name, self.id.lookup(db).ast_id(db).erased())
     }
@@ -25,7 +25,8 @@ use crate::{
 impl BodyLoweringCtx<'_, '_, '_> {
     pub fn lower_expr(&mut self, expr: ExprId) -> Value {
         let old_loc = self.ctx.get_srcloc();
-        self.ctx.set_srcloc(mir::SourceLoc::new(u32::from(expr) as i32 + 1));
+        let loc = self.ctx.intern.srclocs.srcloc(self.body, expr);
+        self.ctx.set_srcloc(loc);
 
         let mut res = match self.body.get_expr(expr) {
             Expr::Read(Ref::Variable(var)) => self.ctx.read_variable(var),
@@ -11,6 +11,7 @@ use lasso::Rodeo;
 use mir::builder::InstBuilder;
 use mir::{DataFlowGraph, FuncRef, Function, Inst, KnownDerivatives, Param, Unknown, Value};
 use mir_build::{FunctionBuilder, FunctionBuilderContext, RetBuilder};
+pub use srclocs::SourceLocMap;
 use stdx::packed_option::PackedOption;
 use stdx::{impl_debug_display, impl_idx_from};
 use typed_index_collections::TiVec;
@@ -35,6 +36,7 @@ mod ctx;
 mod expr;
 pub mod fmt;
 mod parameters;
+mod srclocs;
 mod state;
 mod stmt;
 
@@ -229,6 +231,7 @@ pub struct HirInterner {
     pub tagged_reads: IndexMap<Value, Variable, ahash::RandomState>,
     pub implicit_equations: TiVec<ImplicitEquation, ImplicitEquationKind>,
     pub lim_state: TiMap<LimitState, Value, Vec<(Value, bool)>>,
+    pub srclocs: SourceLocMap,
 }
 
 pub type LiveParams<'a> = FilterMap<
@@ -344,6 +347,28 @@ impl HirInterner {
         KnownDerivatives { unknowns, ddx_calls }
     }
 
+    /// Marks the values of (live) model parameters as unknowns so that the sensitivities
+    /// of `func` to these parameters can be calculated with `auto_diff`.
+    pub fn param_unknowns(
+        &self,
+        func: impl AsRef<Function>,
+        derivatives: &mut KnownDerivatives,
+        params: impl IntoIterator<Item = Parameter>,
+    ) -> Vec<(Parameter, Unknown)> {
+        let func = func.as_ref();
+        params
+            .into_iter()
+            .filter_map(|param| {
+                let val = *self.params.raw.get(&ParamKind::Param(param))?;
+                if func.dfg.value_dead(val) {
+                    return None;
+                }
+                let (unknown, _) = derivatives.unknowns.ensure(val);
+                Some((param, unknown))
+            })
+            .collect()
+    }
+
     pub fn is_param_live(&self, func: impl AsRef<Function>, kind: &ParamKind) -> bool {
         let func = func.as_ref();
         if let Some(val) = self.params.raw.get(kind) {
@@ -481,6 +506,9 @@ impl<'a> MirBuilder<'a> {
         let analog_initial_body = self.module.analog_initial_block(self.db);
         let analog_body = self.module.analog_block(self.db);
 
+        // the analog block is registered first so that its source locations are
+        // just the expression ids
+        interner.srclocs.body_start(analog_body.borrow());
         let mut ctx = LoweringCtx::new(self.db, builder, !self.lower_equations, &mut interner)
             .with_tagged_vars(self.tagged_reads);
         let mut body_ctx =
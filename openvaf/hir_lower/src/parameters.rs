@@ -4,7 +4,7 @@ use std::mem::replace;
 use hir::{CompilationDB, ConstraintValue, ParamConstraint, Parameter, Type};
 use lasso::Rodeo;
 use mir::builder::InstBuilder;
-use mir::{Block, FuncRef, Function, Opcode, Value, FALSE, GRAVESTONE, INFINITY};
+use mir::{Block, FuncRef, Function, Opcode, Value, FALSE, GRAVESTONE, INFINITY, TRUE};
 use mir_build::{FunctionBuilder, FunctionBuilderContext};
 use stdx::packed_option::ReservedValue;
 use syntax::ast::ConstraintKind;
@@ -311,6 +311,91 @@ impl HirInterner {
                 .unwrap_unchecked();
         }
     }
+
+    /// Builds `$param_given(param) ? param : default` for each of `params`, mirroring the
+    /// parameter processing in `model_param_setup`. The default values are lowered in terms
+    /// of the returned values instead of the parameters themselves. Derivatives by the
+    /// returned values therefore include the dependencies between parameter defaults.
+    ///
+    /// Uses of the parameters are **not** replaced; the returned values are only intended
+    /// to be differentiated.
+    pub fn insert_param_defaults(
+        &mut self,
+        db: &CompilationDB,
+        func: &mut Function,
+        literals: &mut Rodeo,
+        params: &[Parameter],
+    ) -> Vec<Value> {
+        let mut param_vals = Vec::with_capacity(params.len());
+
+        let mut ctx = FunctionBuilderContext::default();
+        let (builder, term) = FunctionBuilder::edit(func, literals, &mut ctx, false);
+        let mut ctx = LoweringCtx::new(db, builder, true, self);
+
+        let res = params
+            .iter()
+            .map(|&param| {
+                let param_val = ctx.use_param(ParamKind::Param(param));
+                let param_given = ctx.use_param(ParamKind::ParamGiven { param });
+                let body = param.init(db);
+                let (then_src, else_src) = ctx.make_cond(param_given, |ctx, param_given| {
+                    if param_given {
+                        param_val
+                    } else {
+                        ctx.lower_expr_body(body.borrow(), 0)
+                    }
+                });
+                let val = ctx.ins().phi(&[then_src, else_src]);
+                // the defaults of the following parameters are lowered in terms of `val`
+                ctx.def_param(ParamKind::Param(param), val);
+                param_vals.push(param_val);
+                val
+            })
+            .collect();
+
+        ctx.ensured_sealed();
+        ctx.func.func.layout.append_inst_to_bb(term, ctx.current_block());
+
+        for (&param, val) in params.iter().zip(param_vals) {
+            self.params.raw[&ParamKind::Param(param)] = val;
+        }
+        res
+    }
+
+    /// Replaces the parameters for which `fixed` returns a value by constants. Reads of
+    /// `$param_given` for these parameters are replaced by `true`. The parameters are removed
+    /// from [`HirInterner::params`] and the remaining function parameters are renumbered.
+    pub fn specialize_params(
+        &mut self,
+        db: &CompilationDB,
+        func: &mut Function,
+        fixed: impl Fn(Parameter) -> Option<f64>,
+    ) {
+        self.params.raw.retain(|kind, val| {
+            let (param, given) = match *kind {
+                ParamKind::Param(param) => (param, false),
+                ParamKind::ParamGiven { param } => (param, true),
+                _ => return true,
+            };
+            let fixed_val = match fixed(param) {
+                Some(fixed_val) => fixed_val,
+                None => return true,
+            };
+            let const_val = if given {
+                TRUE
+            } else if param.ty(db) == Type::Integer {
+                func.dfg.iconst(fixed_val as i32)
+            } else {
+                func.dfg.fconst(fixed_val.into())
+            };
+            func.dfg.replace_uses(*val, const_val);
+            false
+        });
+
+        for (i, &val) in self.params.raw.values().enumerate() {
+            func.dfg.values.make_param_at(i.into(), val);
+        }
+    }
 }
 
 impl BodyLoweringCtx<'_, '_, '_> {
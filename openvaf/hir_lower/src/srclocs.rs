@@ -0,0 +1,55 @@
+use ahash::AHashMap;
+use hir::{BodyId, BodyRef, CompilationDB, ExprId, SourcePos};
+use mir::SourceLoc;
+
+/// Maps the [`SourceLoc`]s of lowered instructions back to the HIR expressions they were
+/// created for.
+///
+/// Expressions are only unique within a single body but a function usually contains code
+/// from multiple bodies (analog blocks, user defined functions, parameter defaults...).
+/// Therefore each body is assigned a consecutive range of source locations (one for each
+/// expression) when it is lowered for the first time. The source location `0` is reserved
+/// for instructions without a location. Derivatives created by `mir_autodiff` negate the
+/// source location so the absolute value is used for the lookup.
+#[derive(Debug, PartialEq, Eq, Default, Clone)]
+pub struct SourceLocMap {
+    /// the first source location assigned to each body
+    starts: AHashMap<BodyId, u32>,
+    /// all bodies sorted by their first source location
+    bodies: Vec<(u32, BodyId)>,
+    end: u32,
+}
+
+impl SourceLocMap {
+    pub(crate) fn srcloc(&mut self, body: BodyRef<'_>, expr: ExprId) -> SourceLoc {
+        let start = self.body_start(body);
+        SourceLoc::new((start + u32::from(expr) + 1) as i32)
+    }
+
+    /// Returns the first source location of `body` and assigns a range of source locations
+    /// to `body` if it hasn't been lowered before.
+    pub(crate) fn body_start(&mut self, body: BodyRef<'_>) -> u32 {
+        if let Some(&start) = self.starts.get(&body.id()) {
+            return start;
+        }
+        let start = self.end;
+        self.end += body.num_exprs() as u32;
+        self.starts.insert(body.id(), start);
+        self.bodies.push((start, body.id()));
+        start
+    }
+
+    /// Returns the expression `loc` was created for.
+    pub fn resolve(&self, loc: SourceLoc) -> Option<(BodyId, ExprId)> {
+        let loc = loc.bits().unsigned_abs().checked_sub(1)?;
+        let idx = self.bodies.partition_point(|&(start, _)| start <= loc).checked_sub(1)?;
+        let (start, body) = self.bodies[idx];
+        Some((body, ExprId::from(loc - start)))
+    }
+
+    /// Returns the source position of the expression `loc` was created for.
+    pub fn source_pos(&self, db: &CompilationDB, loc: SourceLoc) -> Option<SourcePos> {
+        let (body, expr) = self.resolve(loc)?;
+        body.expr_pos(db, expr)
+    }
+}
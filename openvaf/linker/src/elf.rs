@@ -0,0 +1,1069 @@
+//! A minimal linker that turns the relocatable ELF objects emitted by LLVM into an `ET_DYN`
+//! shared object without invoking any external tools.
+//!
+//! Only the features required by OpenVAF are supported: x86_64 PIC objects containing code,
+//! (read-only) data and bss. The output consists of a read-only, a read-only/executable and a
+//! writable `PT_LOAD` segment. All global symbols with default visibility are exported
+//! (`OSDI_*`), everything else that remains undefined (libm functions and `snprintf`) is
+//! imported from `libm.so.6` and `libc.so.6`.
+//! Lazy binding is not supported: all relocations are resolved by the dynamic loader when the
+//! library is opened.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+
+use anyhow::{bail, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use target::spec::Target;
+
+#[cfg(test)]
+mod tests;
+
+const PAGE_SIZE: u64 = 0x1000;
+
+const EHDR_SIZE: u64 = 64;
+const PHDR_SIZE: u64 = 56;
+const SHDR_SIZE: u64 = 64;
+const SYM_SIZE: u64 = 24;
+const RELA_SIZE: u64 = 24;
+const DYN_SIZE: u64 = 16;
+const PLT_ENTRY_SIZE: u64 = 16;
+/// read-only, read-only/executable and writable `PT_LOAD`, `PT_DYNAMIC` and `PT_GNU_STACK`
+const NUM_PHDRS: u64 = 5;
+
+const ET_REL: u16 = 1;
+const ET_DYN: u16 = 3;
+const EM_X86_64: u16 = 62;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_HASH: u32 = 5;
+const SHT_DYNAMIC: u32 = 6;
+const SHT_NOBITS: u32 = 8;
+const SHT_DYNSYM: u32 = 11;
+const SHT_X86_64_UNWIND: u32 = 0x7000_0001;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_TLS: u64 = 0x400;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_ABS: u16 = 0xfff1;
+const SHN_COMMON: u16 = 0xfff2;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+const STV_DEFAULT: u8 = 0;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_GNU_STACK: u32 = 0x6474_e551;
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+const PF_R: u32 = 0x4;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_HASH: u64 = 4;
+const DT_STRTAB: u64 = 5;
+const DT_SYMTAB: u64 = 6;
+const DT_RELA: u64 = 7;
+const DT_RELASZ: u64 = 8;
+const DT_RELAENT: u64 = 9;
+const DT_STRSZ: u64 = 10;
+const DT_SYMENT: u64 = 11;
+const DT_SONAME: u64 = 14;
+const DT_FLAGS: u64 = 30;
+const DF_BIND_NOW: u64 = 0x8;
+
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+const R_X86_64_GLOB_DAT: u32 = 6;
+const R_X86_64_RELATIVE: u32 = 8;
+const R_X86_64_GOTPCREL: u32 = 9;
+const R_X86_64_PC64: u32 = 24;
+const R_X86_64_GOTOFF64: u32 = 25;
+const R_X86_64_GOTPC32: u32 = 26;
+const R_X86_64_GOTPCRELX: u32 = 41;
+const R_X86_64_REX_GOTPCRELX: u32 = 42;
+
+/// Whether [`link_shared_object`] can produce a library for `target`.
+pub fn is_supported(target: &Target) -> bool {
+    target.arch == "x86_64" && !target.options.is_like_windows && !target.options.is_like_osx
+}
+
+/// Links the relocatable `objects` into the shared object `out_filename`.
+pub fn link_shared_object(
+    target: &Target,
+    objects: &[Utf8PathBuf],
+    out_filename: &Utf8Path,
+) -> Result<()> {
+    if !is_supported(target) {
+        bail!("the builtin linker does not support the target {}", target.llvm_target)
+    }
+    let objects = objects
+        .iter()
+        .map(|path| {
+            let data = fs::read(path).with_context(|| format!("failed to read {path}"))?;
+            Object::parse(path.clone(), data).with_context(|| format!("failed to parse {path}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let soname = out_filename.file_name().unwrap_or("");
+    let lib = ElfLinker::new(&objects)?.link(soname)?;
+    fs::write(out_filename, lib).with_context(|| format!("failed to write {out_filename}"))
+}
+
+struct Section {
+    name: String,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    info: u32,
+    align: u64,
+}
+
+struct Symbol {
+    name: String,
+    info: u8,
+    other: u8,
+    shndx: u16,
+    value: u64,
+    size: u64,
+}
+
+impl Symbol {
+    fn bind(&self) -> u8 {
+        self.info >> 4
+    }
+
+    fn kind(&self) -> u8 {
+        self.info & 0xf
+    }
+}
+
+struct Object {
+    path: Utf8PathBuf,
+    data: Vec<u8>,
+    sections: Vec<Section>,
+    symbols: Vec<Symbol>,
+}
+
+fn read<const N: usize>(data: &[u8], off: u64) -> Result<[u8; N]> {
+    let bytes = usize::try_from(off).ok().and_then(|off| data.get(off..off.checked_add(N)?));
+    match bytes {
+        Some(bytes) => Ok(bytes.try_into().unwrap()),
+        None => bail!("unexpected end of file"),
+    }
+}
+
+fn read_u8(data: &[u8], off: u64) -> Result<u8> {
+    read(data, off).map(u8::from_le_bytes)
+}
+
+fn read_u16(data: &[u8], off: u64) -> Result<u16> {
+    read(data, off).map(u16::from_le_bytes)
+}
+
+fn read_u32(data: &[u8], off: u64) -> Result<u32> {
+    read(data, off).map(u32::from_le_bytes)
+}
+
+fn read_u64(data: &[u8], off: u64) -> Result<u64> {
+    read(data, off).map(u64::from_le_bytes)
+}
+
+fn read_str(data: &[u8], off: u64) -> Result<String> {
+    let tail = data.get(off as usize..).context("string out of bounds")?;
+    let len = tail.iter().position(|&c| c == 0).context("unterminated string")?;
+    Ok(String::from_utf8_lossy(&tail[..len]).into_owned())
+}
+
+impl Object {
+    fn parse(path: Utf8PathBuf, data: Vec<u8>) -> Result<Object> {
+        if data.get(..4) != Some(b"\x7fELF") || read::<2>(&data, 4)? != [2, 1] {
+            bail!("not a 64-bit little endian ELF file")
+        }
+        if read_u16(&data, 16)? != ET_REL || read_u16(&data, 18)? != EM_X86_64 {
+            bail!("not a x86_64 relocatable object")
+        }
+        let shoff = read_u64(&data, 40)?;
+        let shnum = read_u16(&data, 60)? as u64;
+        let shstrndx = read_u16(&data, 62)? as u64;
+
+        let mut sections = (0..shnum)
+            .map(|i| {
+                let hdr = shoff + i * SHDR_SIZE;
+                let section = Section {
+                    // resolved below once the section name table is known
+                    name: String::new(),
+                    kind: read_u32(&data, hdr + 4)?,
+                    flags: read_u64(&data, hdr + 8)?,
+                    offset: read_u64(&data, hdr + 24)?,
+                    size: read_u64(&data, hdr + 32)?,
+                    info: read_u32(&data, hdr + 44)?,
+                    align: read_u64(&data, hdr + 48)?.max(1),
+                };
+                Ok((read_u32(&data, hdr)?, read_u32(&data, hdr + 40)?, section))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let shstrtab = sections.get(shstrndx as usize).context("invalid e_shstrndx")?.2.offset;
+        let mut symbols = Vec::new();
+        for i in 0..sections.len() {
+            let (name, link, ref section) = sections[i];
+            let name = read_str(&data, shstrtab + name as u64)?;
+            if section.kind == SHT_SYMTAB {
+                let strtab = sections.get(link as usize).context("invalid symtab link")?.2.offset;
+                for j in 1..section.size / SYM_SIZE {
+                    let sym = section.offset + j * SYM_SIZE;
+                    symbols.push(Symbol {
+                        name: read_str(&data, strtab + read_u32(&data, sym)? as u64)?,
+                        info: read_u8(&data, sym + 4)?,
+                        other: read_u8(&data, sym + 5)?,
+                        shndx: read_u16(&data, sym + 6)?,
+                        value: read_u64(&data, sym + 8)?,
+                        size: read_u64(&data, sym + 16)?,
+                    })
+                }
+            }
+            sections[i].2.name = name;
+        }
+        // symbol indices in relocations are 1-based (index 0 is the null symbol)
+        symbols.insert(
+            0,
+            Symbol { name: String::new(), info: 0, other: 0, shndx: SHN_UNDEF, value: 0, size: 0 },
+        );
+
+        let sections = sections.into_iter().map(|(_, _, section)| section).collect();
+        Ok(Object { path, data, sections, symbols })
+    }
+
+    fn section_data(&self, section: &Section) -> Result<&[u8]> {
+        let end = section.offset.checked_add(section.size).context("section out of bounds")?;
+        self.data.get(section.offset as usize..end as usize).context("section out of bounds")
+    }
+
+    fn relocations(&self, section: &Section) -> Result<impl Iterator<Item = Reloc> + '_> {
+        let data = self.section_data(section)?;
+        let relocs = data.chunks_exact(RELA_SIZE as usize).map(|rela| {
+            let info = read_u64(rela, 8).unwrap();
+            Reloc {
+                offset: read_u64(rela, 0).unwrap(),
+                kind: info as u32,
+                sym: (info >> 32) as usize,
+                addend: read_u64(rela, 16).unwrap() as i64,
+            }
+        });
+        Ok(relocs)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Reloc {
+    offset: u64,
+    kind: u32,
+    sym: usize,
+    addend: i64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum OutputKind {
+    Rodata,
+    Text,
+    Data,
+    Bss,
+}
+
+impl OutputKind {
+    fn of(section: &Section) -> Option<OutputKind> {
+        if section.flags & SHF_ALLOC == 0
+            || section.kind == SHT_X86_64_UNWIND
+            || section.name == ".eh_frame"
+            || section.name.starts_with(".note")
+        {
+            return None;
+        }
+        let kind = if section.kind == SHT_NOBITS {
+            OutputKind::Bss
+        } else if section.flags & SHF_EXECINSTR != 0 {
+            OutputKind::Text
+        } else if section.flags & SHF_WRITE != 0 {
+            OutputKind::Data
+        } else {
+            OutputKind::Rodata
+        };
+        Some(kind)
+    }
+
+    /// Index of the corresponding section header in the output
+    fn shndx(self) -> u16 {
+        match self {
+            OutputKind::Rodata => 5,
+            OutputKind::Text => 6,
+            OutputKind::Data => 8,
+            OutputKind::Bss => 11,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            OutputKind::Rodata => ".rodata",
+            OutputKind::Text => ".text",
+            OutputKind::Data => ".data",
+            OutputKind::Bss => ".bss",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Definition {
+    Section { obj: usize, section: usize, value: u64, weak: bool },
+    Common { size: u64, align: u64 },
+    Abs { value: u64 },
+}
+
+/// The value a symbol reference resolves to.
+#[derive(Clone, Copy)]
+enum Resolved {
+    Addr(u64),
+    /// Index into the dynamic symbol table
+    Import(u32),
+}
+
+struct ElfLinker<'a> {
+    objects: &'a [Object],
+    globals: HashMap<&'a str, Definition>,
+    /// Start address of each input section (`None` for discarded sections)
+    section_addrs: Vec<Vec<Option<u64>>>,
+    common_addrs: HashMap<&'a str, u64>,
+    imports: Vec<&'a Symbol>,
+    import_ids: HashMap<&'a str, u32>,
+    /// Symbols that require a GOT entry (addressed by name for globals and
+    /// by object and symbol index for locals)
+    got: Vec<GotEntry<'a>>,
+    got_ids: HashMap<GotEntry<'a>, u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum GotEntry<'a> {
+    Global(&'a str),
+    Local { obj: usize, sym: usize },
+}
+
+/// Address layout of the generated library. Every allocated section is placed at a file offset
+/// that is equal to its virtual address.
+#[derive(Default)]
+struct Layout {
+    hash: (u64, u64),
+    dynsym: (u64, u64),
+    dynstr: (u64, u64),
+    rela_dyn: (u64, u64),
+    outputs: [(u64, u64); 4],
+    plt: (u64, u64),
+    got: (u64, u64),
+    dynamic: (u64, u64),
+    r_end: u64,
+    rx_start: u64,
+    rx_end: u64,
+    rw_start: u64,
+    rw_file_end: u64,
+    rw_mem_end: u64,
+}
+
+impl<'a> ElfLinker<'a> {
+    fn new(objects: &'a [Object]) -> Result<ElfLinker<'a>> {
+        let mut globals: HashMap<&str, Definition> = HashMap::new();
+        for (obj_idx, obj) in objects.iter().enumerate() {
+            for sym in &obj.symbols {
+                if !matches!(sym.bind(), STB_GLOBAL | STB_WEAK) || sym.shndx == SHN_UNDEF {
+                    continue;
+                }
+                if sym.shndx == SHN_COMMON {
+                    let (size, align) = match globals.get(&*sym.name) {
+                        Some(Definition::Common { size, align }) => (*size, *align),
+                        Some(_) => continue,
+                        None => (0, 1),
+                    };
+                    let def = Definition::Common {
+                        size: size.max(sym.size),
+                        align: align.max(sym.value),
+                    };
+                    globals.insert(&sym.name, def);
+                    continue;
+                }
+                let weak = sym.bind() == STB_WEAK;
+                let def = if sym.shndx == SHN_ABS {
+                    Definition::Abs { value: sym.value }
+                } else {
+                    let section = sym.shndx as usize;
+                    Definition::Section { obj: obj_idx, section, value: sym.value, weak }
+                };
+                match globals.entry(&sym.name) {
+                    Entry::Occupied(mut entry) => match *entry.get() {
+                        Definition::Section { weak: true, .. } | Definition::Common { .. } => {
+                            entry.insert(def);
+                        }
+                        _ if weak => (),
+                        Definition::Section { obj: prev, .. } => bail!(
+                            "duplicate symbol {} (defined in {} and {})",
+                            sym.name,
+                            objects[prev].path,
+                            obj.path
+                        ),
+                        Definition::Abs { .. } => bail!("duplicate symbol {}", sym.name),
+                    },
+                    Entry::Vacant(entry) => {
+                        entry.insert(def);
+                    }
+                }
+            }
+        }
+
+        Ok(ElfLinker {
+            objects,
+            globals,
+            section_addrs: Vec::new(),
+            common_addrs: HashMap::new(),
+            imports: Vec::new(),
+            import_ids: HashMap::new(),
+            got: Vec::new(),
+            got_ids: HashMap::new(),
+        })
+    }
+
+    fn symbol(&self, obj: usize, sym: usize) -> Result<&'a Symbol> {
+        let obj = &self.objects[obj];
+        obj.symbols.get(sym).with_context(|| format!("invalid symbol index in {}", obj.path))
+    }
+
+    fn is_import(&self, obj: usize, sym: usize) -> Result<bool> {
+        let sym = self.symbol(obj, sym)?;
+        Ok(sym.bind() != STB_LOCAL && !self.globals.contains_key(&*sym.name))
+    }
+
+    /// All relocation sections that apply to sections which are part of the output.
+    fn relocation_sections(&self) -> impl Iterator<Item = (usize, &'a Section, usize)> + '_ {
+        self.objects.iter().enumerate().flat_map(|(obj_idx, obj)| {
+            obj.sections
+                .iter()
+                .filter(move |section| {
+                    section.kind == SHT_RELA
+                        && obj
+                            .sections
+                            .get(section.info as usize)
+                            .map_or(false, |target| OutputKind::of(target).is_some())
+                })
+                .map(move |section| (obj_idx, section, section.info as usize))
+        })
+    }
+
+    /// Collects imported symbols and GOT entries and returns the number of
+    /// dynamic relocations.
+    fn scan_relocations(&mut self) -> Result<u64> {
+        let mut num_dyn_relocs = 0;
+        for (obj, section, _) in self.relocation_sections().collect::<Vec<_>>() {
+            for reloc in self.objects[obj].relocations(section)? {
+                let sym = self.symbol(obj, reloc.sym)?;
+                if reloc.sym != 0 && self.is_import(obj, reloc.sym)? {
+                    // imports are always accessed through the GOT (directly or with a PLT stub)
+                    self.add_import(sym);
+                    self.add_got_entry(obj, reloc.sym)?;
+                }
+                match reloc.kind {
+                    R_X86_64_64 => num_dyn_relocs += 1,
+                    R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX => {
+                        self.add_got_entry(obj, reloc.sym)?
+                    }
+                    R_X86_64_NONE | R_X86_64_PC32 | R_X86_64_PLT32 | R_X86_64_PC64
+                    | R_X86_64_GOTOFF64 | R_X86_64_GOTPC32 => (),
+                    kind => bail!(
+                        "unsupported relocation type {kind} in {} (was the object compiled as PIC?)",
+                        self.objects[obj].path
+                    ),
+                }
+            }
+        }
+        Ok(num_dyn_relocs + self.got.len() as u64)
+    }
+
+    fn add_import(&mut self, sym: &'a Symbol) {
+        if let Entry::Vacant(entry) = self.import_ids.entry(&sym.name) {
+            // index 0 is the null symbol
+            entry.insert(self.imports.len() as u32 + 1);
+            self.imports.push(sym);
+        }
+    }
+
+    fn add_got_entry(&mut self, obj: usize, sym: usize) -> Result<()> {
+        let symbol = self.symbol(obj, sym)?;
+        let entry = if symbol.bind() == STB_LOCAL {
+            GotEntry::Local { obj, sym }
+        } else {
+            GotEntry::Global(&symbol.name)
+        };
+        if let Entry::Vacant(id) = self.got_ids.entry(entry) {
+            id.insert(self.got.len() as u64);
+            self.got.push(entry);
+        }
+        Ok(())
+    }
+
+    fn exports(&self) -> Vec<(&'a str, &'a Symbol)> {
+        let mut exports: Vec<_> = self
+            .objects
+            .iter()
+            .flat_map(|obj| &obj.symbols)
+            .filter(|sym| {
+                matches!(sym.bind(), STB_GLOBAL | STB_WEAK)
+                    && sym.shndx != SHN_UNDEF
+                    && sym.other & 0x3 == STV_DEFAULT
+                    && !matches!(sym.kind(), STT_SECTION | STT_FILE)
+            })
+            .map(|sym| (&*sym.name, sym))
+            .collect();
+        // only export the definition that won symbol resolution
+        exports.sort_by_key(|(name, _)| *name);
+        exports.dedup_by_key(|(name, _)| *name);
+        exports
+    }
+
+    fn resolve_global(&self, name: &str) -> Result<Resolved> {
+        let addr = match self.globals.get(name) {
+            Some(Definition::Section { obj, section, value, .. }) => {
+                self.section_addr(*obj, *section)? + value
+            }
+            Some(Definition::Common { .. }) => self.common_addrs[name],
+            Some(Definition::Abs { value }) => *value,
+            None => return Ok(Resolved::Import(self.import_ids[name])),
+        };
+        Ok(Resolved::Addr(addr))
+    }
+
+    fn resolve(&self, obj: usize, sym: usize) -> Result<Resolved> {
+        let symbol = self.symbol(obj, sym)?;
+        if symbol.bind() != STB_LOCAL {
+            return self.resolve_global(&symbol.name);
+        }
+        let addr = match symbol.shndx {
+            SHN_ABS => symbol.value,
+            SHN_UNDEF => 0,
+            section => self.section_addr(obj, section as usize)? + symbol.value,
+        };
+        Ok(Resolved::Addr(addr))
+    }
+
+    fn section_addr(&self, obj: usize, section: usize) -> Result<u64> {
+        match self.section_addrs[obj].get(section) {
+            Some(Some(addr)) => Ok(*addr),
+            _ => {
+                let obj = &self.objects[obj];
+                let name = obj.sections.get(section).map_or("<invalid>", |s| &s.name);
+                bail!("reference to discarded section {name} in {}", obj.path)
+            }
+        }
+    }
+
+    fn link(mut self, soname: &str) -> Result<Vec<u8>> {
+        let num_dyn_relocs = self.scan_relocations()?;
+        let exports = self.exports();
+
+        // .dynstr
+        let mut dynstr = vec![0u8];
+        let mut add_str = |name: &str| {
+            let off = dynstr.len() as u32;
+            dynstr.extend_from_slice(name.as_bytes());
+            dynstr.push(0);
+            off
+        };
+        // the imports are not resolved here, so all libraries that may define them are needed
+        let needed: Vec<_> = if self.imports.is_empty() {
+            Vec::new()
+        } else {
+            vec![add_str("libm.so.6"), add_str("libc.so.6")]
+        };
+        let soname = add_str(soname);
+        let import_names: Vec<_> = self.imports.iter().map(|sym| add_str(&sym.name)).collect();
+        let export_names: Vec<_> = exports.iter().map(|(name, _)| add_str(name)).collect();
+        let num_dynsyms = 1 + self.imports.len() + exports.len();
+
+        // address layout
+        let mut layout = Layout::default();
+        let mut pos = EHDR_SIZE + PHDR_SIZE * NUM_PHDRS;
+        let alloc = |pos: &mut u64, size: u64, align: u64| {
+            let start = align_to(*pos, align);
+            *pos = start + size;
+            (start, size)
+        };
+        let nbucket = (num_dynsyms as u64 / 2).max(1);
+        layout.hash = alloc(&mut pos, 4 * (2 + nbucket + num_dynsyms as u64), 8);
+        layout.dynsym = alloc(&mut pos, SYM_SIZE * num_dynsyms as u64, 8);
+        layout.dynstr = alloc(&mut pos, dynstr.len() as u64, 1);
+        layout.rela_dyn = alloc(&mut pos, RELA_SIZE * num_dyn_relocs, 8);
+
+        let mut inputs: Vec<(OutputKind, usize, usize)> = self
+            .objects
+            .iter()
+            .enumerate()
+            .flat_map(|(obj_idx, obj)| {
+                obj.sections.iter().enumerate().filter_map(move |(idx, section)| {
+                    Some((OutputKind::of(section)?, obj_idx, idx))
+                })
+            })
+            .collect();
+        for &(kind, obj, idx) in &inputs {
+            let section = &self.objects[obj].sections[idx];
+            if section.flags & SHF_TLS != 0 {
+                bail!("thread local storage is not supported ({})", self.objects[obj].path)
+            }
+            if section.kind != SHT_PROGBITS && kind != OutputKind::Bss {
+                bail!("unsupported section {} in {}", section.name, self.objects[obj].path)
+            }
+        }
+        inputs.sort_by_key(|(kind, _, _)| *kind);
+
+        self.section_addrs =
+            self.objects.iter().map(|obj| vec![None; obj.sections.len()]).collect();
+        let mut place_inputs = |pos: &mut u64, kind: OutputKind| {
+            let start = *pos;
+            for &(_, obj, idx) in inputs.iter().filter(|(it, _, _)| *it == kind) {
+                let section = &self.objects[obj].sections[idx];
+                let (addr, _) = alloc(pos, section.size, section.align);
+                self.section_addrs[obj][idx] = Some(addr);
+            }
+            (start, *pos - start)
+        };
+
+        layout.outputs[OutputKind::Rodata as usize] = place_inputs(&mut pos, OutputKind::Rodata);
+        layout.r_end = pos;
+
+        // code is placed on its own pages so that data can not be executed
+        pos = align_to(pos, PAGE_SIZE);
+        layout.rx_start = pos;
+        layout.outputs[OutputKind::Text as usize] = place_inputs(&mut pos, OutputKind::Text);
+        layout.plt = alloc(&mut pos, PLT_ENTRY_SIZE * self.imports.len() as u64, 16);
+        layout.rx_end = pos;
+
+        pos = align_to(pos, PAGE_SIZE);
+        layout.rw_start = pos;
+        layout.outputs[OutputKind::Data as usize] = place_inputs(&mut pos, OutputKind::Data);
+        layout.got = alloc(&mut pos, 8 * self.got.len() as u64, 8);
+        let num_dyn_entries = 8 + needed.len() as u64 + 3 * (num_dyn_relocs != 0) as u64;
+        layout.dynamic = alloc(&mut pos, DYN_SIZE * num_dyn_entries, 8);
+        layout.rw_file_end = pos;
+        layout.outputs[OutputKind::Bss as usize] = place_inputs(&mut pos, OutputKind::Bss);
+        let mut commons: Vec<_> = self
+            .globals
+            .iter()
+            .filter_map(|(name, def)| match def {
+                Definition::Common { size, align } => Some((*name, *size, *align)),
+                _ => None,
+            })
+            .collect();
+        commons.sort_by_key(|(name, _, _)| *name);
+        for (name, size, align) in commons {
+            let (addr, _) = alloc(&mut pos, size, align);
+            self.common_addrs.insert(name, addr);
+        }
+        let bss = &mut layout.outputs[OutputKind::Bss as usize];
+        bss.1 = pos - bss.0;
+        layout.rw_mem_end = pos;
+
+        // contents
+        let mut out = vec![0u8; layout.rw_file_end as usize];
+        for &(kind, obj, idx) in &inputs {
+            if kind == OutputKind::Bss {
+                continue;
+            }
+            let object = &self.objects[obj];
+            let data = object.section_data(&object.sections[idx])?;
+            let addr = self.section_addrs[obj][idx].unwrap() as usize;
+            out[addr..addr + data.len()].copy_from_slice(data);
+        }
+
+        let mut dyn_relocs = Vec::with_capacity(num_dyn_relocs as usize);
+        for (i, entry) in self.got.iter().enumerate() {
+            let addr = layout.got.0 + 8 * i as u64;
+            let sym = match *entry {
+                GotEntry::Local { obj, sym } => self.resolve(obj, sym)?,
+                GotEntry::Global(name) => self.resolve_global(name)?,
+            };
+            match sym {
+                Resolved::Addr(val) => {
+                    write_u64(&mut out, addr, val);
+                    dyn_relocs.push((addr, R_X86_64_RELATIVE, 0, val as i64));
+                }
+                Resolved::Import(id) => dyn_relocs.push((addr, R_X86_64_GLOB_DAT, id, 0)),
+            }
+        }
+
+        for (i, sym) in self.imports.iter().enumerate() {
+            // jmp *got_entry(%rip)
+            let stub = layout.plt.0 + PLT_ENTRY_SIZE * i as u64;
+            let got_entry = layout.got.0 + 8 * self.got_ids[&GotEntry::Global(&sym.name)];
+            out[stub as usize..stub as usize + 2].copy_from_slice(&[0xff, 0x25]);
+            write_u32(&mut out, stub + 2, got_entry.wrapping_sub(stub + 6) as u32);
+            out[stub as usize + 6..(stub + PLT_ENTRY_SIZE) as usize].fill(0xcc);
+        }
+
+        for (obj, section, target) in self.relocation_sections().collect::<Vec<_>>() {
+            let base = self.section_addr(obj, target)?;
+            let object = &self.objects[obj];
+            let target = &object.sections[target];
+            if OutputKind::of(target) == Some(OutputKind::Bss) {
+                bail!("relocation in section {} without data in {}", target.name, object.path)
+            }
+            for reloc in object.relocations(section)? {
+                let width = match reloc.kind {
+                    R_X86_64_NONE => 0,
+                    R_X86_64_64 | R_X86_64_PC64 | R_X86_64_GOTOFF64 => 8,
+                    _ => 4,
+                };
+                if !matches!(reloc.offset.checked_add(width), Some(end) if end <= target.size) {
+                    bail!(
+                        "relocation at {:#x} is out of bounds of section {} in {}",
+                        reloc.offset,
+                        target.name,
+                        object.path
+                    )
+                }
+                let place = base + reloc.offset;
+                let sym =
+                    if reloc.sym == 0 { Resolved::Addr(0) } else { self.resolve(obj, reloc.sym)? };
+                let got_entry = || {
+                    let symbol = self.symbol(obj, reloc.sym)?;
+                    let entry = if symbol.bind() == STB_LOCAL {
+                        GotEntry::Local { obj, sym: reloc.sym }
+                    } else {
+                        GotEntry::Global(&symbol.name)
+                    };
+                    Ok::<_, anyhow::Error>(layout.got.0 + 8 * self.got_ids[&entry])
+                };
+                // functions are called through the PLT, data can not be referenced
+                // pc-relative across library boundaries
+                let direct_addr = || match sym {
+                    Resolved::Addr(addr) => addr,
+                    Resolved::Import(id) => layout.plt.0 + PLT_ENTRY_SIZE * (id as u64 - 1),
+                };
+                let pc_rel = |val: u64| val.wrapping_add(reloc.addend as u64).wrapping_sub(place);
+                match reloc.kind {
+                    R_X86_64_NONE => (),
+                    R_X86_64_64 => match sym {
+                        Resolved::Addr(addr) => {
+                            let val = addr.wrapping_add(reloc.addend as u64);
+                            write_u64(&mut out, place, val);
+                            dyn_relocs.push((place, R_X86_64_RELATIVE, 0, val as i64));
+                        }
+                        Resolved::Import(id) => {
+                            dyn_relocs.push((place, R_X86_64_64, id, reloc.addend))
+                        }
+                    },
+                    R_X86_64_PC32 | R_X86_64_PLT32 => {
+                        write_i32(&mut out, place, pc_rel(direct_addr()))?
+                    }
+                    R_X86_64_PC64 => write_u64(&mut out, place, pc_rel(direct_addr())),
+                    R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX => {
+                        write_i32(&mut out, place, pc_rel(got_entry()?))?
+                    }
+                    R_X86_64_GOTPC32 => write_i32(&mut out, place, pc_rel(layout.got.0))?,
+                    R_X86_64_GOTOFF64 => {
+                        let val = direct_addr().wrapping_add(reloc.addend as u64);
+                        write_u64(&mut out, place, val.wrapping_sub(layout.got.0))
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        // .rela.dyn
+        for (i, (place, kind, sym, addend)) in dyn_relocs.into_iter().enumerate() {
+            let rela = layout.rela_dyn.0 + RELA_SIZE * i as u64;
+            write_u64(&mut out, rela, place);
+            write_u64(&mut out, rela + 8, (sym as u64) << 32 | kind as u64);
+            write_u64(&mut out, rela + 16, addend as u64);
+        }
+
+        // .dynsym (the null symbol is already zeroed)
+        let mut dynsym = layout.dynsym.0 + SYM_SIZE;
+        for (sym, name) in self.imports.iter().zip(import_names) {
+            write_u32(&mut out, dynsym, name);
+            out[dynsym as usize + 4] = sym.info;
+            dynsym += SYM_SIZE;
+        }
+        for ((name, sym), name_off) in exports.iter().zip(export_names) {
+            let (addr, shndx) = match self.globals[name] {
+                Definition::Section { obj, section, value, .. } => {
+                    let kind = OutputKind::of(&self.objects[obj].sections[section]).unwrap();
+                    (self.section_addr(obj, section)? + value, kind.shndx())
+                }
+                Definition::Common { .. } => (self.common_addrs[name], OutputKind::Bss.shndx()),
+                Definition::Abs { value } => (value, SHN_ABS),
+            };
+            write_u32(&mut out, dynsym, name_off);
+            out[dynsym as usize + 4] = sym.info;
+            write_u16(&mut out, dynsym + 6, shndx);
+            write_u64(&mut out, dynsym + 8, addr);
+            write_u64(&mut out, dynsym + 16, sym.size);
+            dynsym += SYM_SIZE;
+        }
+
+        out[layout.dynstr.0 as usize..(layout.dynstr.0 + layout.dynstr.1) as usize]
+            .copy_from_slice(&dynstr);
+
+        // .hash
+        let mut buckets = vec![0u32; nbucket as usize];
+        let mut chains = vec![0u32; num_dynsyms];
+        let imports = self.imports.iter().map(|sym| &*sym.name);
+        let names = imports.chain(exports.iter().map(|(name, _)| *name));
+        for (i, name) in names.enumerate() {
+            let sym = i as u32 + 1;
+            let bucket = &mut buckets[(elf_hash(name) % nbucket as u32) as usize];
+            chains[sym as usize] = *bucket;
+            *bucket = sym;
+        }
+        let hash = layout.hash.0;
+        write_u32(&mut out, hash, nbucket as u32);
+        write_u32(&mut out, hash + 4, num_dynsyms as u32);
+        for (i, val) in buckets.into_iter().chain(chains).enumerate() {
+            write_u32(&mut out, hash + 8 + 4 * i as u64, val);
+        }
+
+        // .dynamic
+        let mut dynamic = Vec::new();
+        dynamic.extend(needed.into_iter().map(|needed| (DT_NEEDED, needed as u64)));
+        dynamic.extend([
+            (DT_SONAME, soname as u64),
+            (DT_HASH, layout.hash.0),
+            (DT_STRTAB, layout.dynstr.0),
+            (DT_SYMTAB, layout.dynsym.0),
+            (DT_STRSZ, layout.dynstr.1),
+            (DT_SYMENT, SYM_SIZE),
+        ]);
+        if num_dyn_relocs != 0 {
+            dynamic.extend([
+                (DT_RELA, layout.rela_dyn.0),
+                (DT_RELASZ, layout.rela_dyn.1),
+                (DT_RELAENT, RELA_SIZE),
+            ]);
+        }
+        dynamic.extend([(DT_FLAGS, DF_BIND_NOW), (DT_NULL, 0)]);
+        debug_assert_eq!(dynamic.len() as u64, num_dyn_entries);
+        for (i, (tag, val)) in dynamic.into_iter().enumerate() {
+            let entry = layout.dynamic.0 + DYN_SIZE * i as u64;
+            write_u64(&mut out, entry, tag);
+            write_u64(&mut out, entry + 8, val);
+        }
+
+        self.write_headers(&mut out, &layout);
+        Ok(out)
+    }
+
+    /// Writes the ELF header, program headers and section headers. The section headers are not
+    /// required by the dynamic loader but make the output usable with tools like `readelf`.
+    fn write_headers(&self, out: &mut Vec<u8>, layout: &Layout) {
+        // section headers
+        let sections = [
+            (".hash", SHT_HASH, SHF_ALLOC, layout.hash, 2, 0, 8, 4),
+            (".dynsym", SHT_DYNSYM, SHF_ALLOC, layout.dynsym, 3, 1, 8, SYM_SIZE),
+            (".dynstr", SHT_STRTAB, SHF_ALLOC, layout.dynstr, 0, 0, 1, 0),
+            (".rela.dyn", SHT_RELA, SHF_ALLOC, layout.rela_dyn, 2, 0, 8, RELA_SIZE),
+            (
+                OutputKind::Rodata.name(),
+                SHT_PROGBITS,
+                SHF_ALLOC,
+                layout.outputs[OutputKind::Rodata as usize],
+                0,
+                0,
+                16,
+                0,
+            ),
+            (
+                OutputKind::Text.name(),
+                SHT_PROGBITS,
+                SHF_ALLOC | SHF_EXECINSTR,
+                layout.outputs[OutputKind::Text as usize],
+                0,
+                0,
+                16,
+                0,
+            ),
+            (".plt", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, layout.plt, 0, 0, 16, PLT_ENTRY_SIZE),
+            (
+                OutputKind::Data.name(),
+                SHT_PROGBITS,
+                SHF_ALLOC | SHF_WRITE,
+                layout.outputs[OutputKind::Data as usize],
+                0,
+                0,
+                16,
+                0,
+            ),
+            (".got", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, layout.got, 0, 0, 8, 8),
+            (".dynamic", SHT_DYNAMIC, SHF_ALLOC | SHF_WRITE, layout.dynamic, 3, 0, 8, DYN_SIZE),
+            (
+                OutputKind::Bss.name(),
+                SHT_NOBITS,
+                SHF_ALLOC | SHF_WRITE,
+                layout.outputs[OutputKind::Bss as usize],
+                0,
+                0,
+                16,
+                0,
+            ),
+        ];
+
+        let mut shstrtab = vec![0u8];
+        let mut names = Vec::new();
+        for (name, ..) in &sections {
+            names.push(shstrtab.len() as u32);
+            shstrtab.extend_from_slice(name.as_bytes());
+            shstrtab.push(0);
+        }
+        let shstrtab_name = shstrtab.len() as u32;
+        shstrtab.extend_from_slice(b".shstrtab\0");
+
+        let shstrtab_off = out.len() as u64;
+        out.extend_from_slice(&shstrtab);
+        out.resize(align_to(out.len() as u64, 8) as usize, 0);
+        let shoff = out.len() as u64;
+        out.resize((shoff + SHDR_SIZE * (sections.len() as u64 + 2)) as usize, 0);
+        let mut shdr = shoff + SHDR_SIZE;
+        for ((_, kind, flags, (addr, size), link, info, align, entsize), name) in
+            sections.into_iter().zip(names)
+        {
+            write_shdr(out, shdr, name, kind, flags, addr, addr, size, link, info, align, entsize);
+            shdr += SHDR_SIZE;
+        }
+        let shstrtab_size = shstrtab.len() as u64;
+        write_shdr(
+            out,
+            shdr,
+            shstrtab_name,
+            SHT_STRTAB,
+            0,
+            0,
+            shstrtab_off,
+            shstrtab_size,
+            0,
+            0,
+            1,
+            0,
+        );
+
+        // ELF header
+        out[..16].copy_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+        write_u16(out, 16, ET_DYN);
+        write_u16(out, 18, EM_X86_64);
+        write_u32(out, 20, 1);
+        write_u64(out, 24, 0);
+        write_u64(out, 32, EHDR_SIZE);
+        write_u64(out, 40, shoff);
+        write_u32(out, 48, 0);
+        write_u16(out, 52, EHDR_SIZE as u16);
+        write_u16(out, 54, PHDR_SIZE as u16);
+        write_u16(out, 56, NUM_PHDRS as u16);
+        write_u16(out, 58, SHDR_SIZE as u16);
+        write_u16(out, 60, sections.len() as u16 + 2);
+        write_u16(out, 62, sections.len() as u16 + 1);
+
+        // program headers
+        let rw_file_size = layout.rw_file_end - layout.rw_start;
+        let rw_mem_size = layout.rw_mem_end - layout.rw_start;
+        let rx_size = layout.rx_end - layout.rx_start;
+        let phdrs = [
+            (PT_LOAD, PF_R, 0, layout.r_end, layout.r_end, PAGE_SIZE),
+            (PT_LOAD, PF_R | PF_X, layout.rx_start, rx_size, rx_size, PAGE_SIZE),
+            (PT_LOAD, PF_R | PF_W, layout.rw_start, rw_file_size, rw_mem_size, PAGE_SIZE),
+            (PT_DYNAMIC, PF_R | PF_W, layout.dynamic.0, layout.dynamic.1, layout.dynamic.1, 8),
+            (PT_GNU_STACK, PF_R | PF_W, 0, 0, 0, 16),
+        ];
+        debug_assert_eq!(phdrs.len() as u64, NUM_PHDRS);
+        for (i, (kind, flags, addr, file_size, mem_size, align)) in phdrs.into_iter().enumerate() {
+            let phdr = EHDR_SIZE + PHDR_SIZE * i as u64;
+            write_u32(out, phdr, kind);
+            write_u32(out, phdr + 4, flags);
+            write_u64(out, phdr + 8, addr);
+            write_u64(out, phdr + 16, addr);
+            write_u64(out, phdr + 24, addr);
+            write_u64(out, phdr + 32, file_size);
+            write_u64(out, phdr + 40, mem_size);
+            write_u64(out, phdr + 48, align);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_shdr(
+    out: &mut [u8],
+    shdr: u64,
+    name: u32,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+) {
+    write_u32(out, shdr, name);
+    write_u32(out, shdr + 4, kind);
+    write_u64(out, shdr + 8, flags);
+    write_u64(out, shdr + 16, addr);
+    write_u64(out, shdr + 24, offset);
+    write_u64(out, shdr + 32, size);
+    write_u32(out, shdr + 40, link);
+    write_u32(out, shdr + 44, info);
+    write_u64(out, shdr + 48, align);
+    write_u64(out, shdr + 56, entsize);
+}
+
+fn align_to(pos: u64, align: u64) -> u64 {
+    let align = align.max(1);
+    (pos + align - 1) / align * align
+}
+
+fn write_u16(out: &mut [u8], off: u64, val: u16) {
+    out[off as usize..off as usize + 2].copy_from_slice(&val.to_le_bytes())
+}
+
+fn write_u32(out: &mut [u8], off: u64, val: u32) {
+    out[off as usize..off as usize + 4].copy_from_slice(&val.to_le_bytes())
+}
+
+fn write_u64(out: &mut [u8], off: u64, val: u64) {
+    out[off as usize..off as usize + 8].copy_from_slice(&val.to_le_bytes())
+}
+
+fn write_i32(out: &mut [u8], off: u64, val: u64) -> Result<()> {
+    let val = val as i64;
+    if i32::try_from(val).is_err() {
+        bail!("relocation overflow at {off:#x}")
+    }
+    write_u32(out, off, val as u32);
+    Ok(())
+}
+
+/// The hash function used by the `DT_HASH` table (see the System V ABI)
+fn elf_hash(name: &str) -> u32 {
+    let mut h: u32 = 0;
+    for &c in name.as_bytes() {
+        h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xf000_0000;
+        if g != 0 {
+            h ^= g >> 24;
+        }
+        h &= !g;
+    }
+    h
+}
@@ -0,0 +1,325 @@
+use super::*;
+
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+/// A relocatable object that is assembled in memory so that the linker can be tested
+/// without a compiler.
+#[derive(Default)]
+struct TestObject {
+    /// name, type, flags and contents (only the size for `SHT_NOBITS`)
+    sections: Vec<(&'static str, u32, u64, Vec<u8>)>,
+    /// name, binding, type, section index and value
+    symbols: Vec<(&'static str, u8, u8, u16, u64)>,
+    /// target section, offset, type, symbol index and addend
+    relocs: Vec<(u16, u64, u32, usize, i64)>,
+}
+
+impl TestObject {
+    fn section(&mut self, name: &'static str, kind: u32, flags: u64, data: Vec<u8>) -> u16 {
+        self.sections.push((name, kind, flags, data));
+        // index 0 is the null section
+        self.sections.len() as u16
+    }
+
+    fn text(&mut self, size: usize) -> u16 {
+        self.section(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, vec![0; size])
+    }
+
+    fn data(&mut self, size: usize) -> u16 {
+        self.section(".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, vec![0; size])
+    }
+
+    fn symbol(&mut self, name: &'static str, bind: u8, kind: u8, shndx: u16, value: u64) -> usize {
+        self.symbols.push((name, bind, kind, shndx, value));
+        // index 0 is the null symbol
+        self.symbols.len()
+    }
+
+    fn reloc(&mut self, section: u16, offset: u64, kind: u32, sym: usize, addend: i64) {
+        self.relocs.push((section, offset, kind, sym, addend))
+    }
+
+    fn build(&self) -> Object {
+        fn add_str(tab: &mut Vec<u8>, name: &str) -> u32 {
+            let off = tab.len() as u32;
+            tab.extend_from_slice(name.as_bytes());
+            tab.push(0);
+            off
+        }
+
+        let mut targets: Vec<_> = self.relocs.iter().map(|reloc| reloc.0).collect();
+        targets.sort_unstable();
+        targets.dedup();
+        let symtab = (self.sections.len() + targets.len() + 1) as u32;
+
+        // name, type, flags, contents, size, link, info, entsize
+        let mut sections = Vec::new();
+        for (name, kind, flags, data) in &self.sections {
+            let size = data.len() as u64;
+            let data = if *kind == SHT_NOBITS { Vec::new() } else { data.clone() };
+            sections.push((name.to_string(), *kind, *flags, data, size, 0, 0, 0));
+        }
+        for &target in &targets {
+            let mut data = Vec::new();
+            for &(_, offset, kind, sym, addend) in self.relocs.iter().filter(|it| it.0 == target) {
+                data.extend_from_slice(&offset.to_le_bytes());
+                data.extend_from_slice(&((sym as u64) << 32 | kind as u64).to_le_bytes());
+                data.extend_from_slice(&addend.to_le_bytes());
+            }
+            let name = format!(".rela{}", self.sections[target as usize - 1].0);
+            let size = data.len() as u64;
+            sections.push((name, SHT_RELA, 0, data, size, symtab, target as u32, RELA_SIZE));
+        }
+        let mut strtab = vec![0u8];
+        let mut symbols = vec![0u8; SYM_SIZE as usize];
+        for &(name, bind, kind, shndx, value) in &self.symbols {
+            symbols.extend_from_slice(&add_str(&mut strtab, name).to_le_bytes());
+            symbols.extend_from_slice(&[bind << 4 | kind, STV_DEFAULT]);
+            symbols.extend_from_slice(&shndx.to_le_bytes());
+            symbols.extend_from_slice(&value.to_le_bytes());
+            symbols.extend_from_slice(&0u64.to_le_bytes());
+        }
+        let size = symbols.len() as u64;
+        sections.push((
+            ".symtab".to_owned(),
+            SHT_SYMTAB,
+            0,
+            symbols,
+            size,
+            symtab + 1,
+            1,
+            SYM_SIZE,
+        ));
+        let size = strtab.len() as u64;
+        sections.push((".strtab".to_owned(), SHT_STRTAB, 0, strtab, size, 0, 0, 0));
+        let mut shstrtab = vec![0u8];
+        let mut names: Vec<_> =
+            sections.iter().map(|section| add_str(&mut shstrtab, &section.0)).collect();
+        names.push(add_str(&mut shstrtab, ".shstrtab"));
+        let size = shstrtab.len() as u64;
+        sections.push((".shstrtab".to_owned(), SHT_STRTAB, 0, shstrtab, size, 0, 0, 0));
+
+        let mut out = vec![0u8; EHDR_SIZE as usize];
+        let mut offsets = Vec::new();
+        for section in &sections {
+            out.resize(align_to(out.len() as u64, 16) as usize, 0);
+            offsets.push(out.len() as u64);
+            out.extend_from_slice(&section.3);
+        }
+        out.resize(align_to(out.len() as u64, 8) as usize, 0);
+        let shoff = out.len() as u64;
+        out.resize((shoff + SHDR_SIZE * (sections.len() as u64 + 1)) as usize, 0);
+        let mut shdr = shoff + SHDR_SIZE;
+        for (((_, kind, flags, _, size, link, info, entsize), name), offset) in
+            sections.into_iter().zip(names).zip(offsets)
+        {
+            write_shdr(&mut out, shdr, name, kind, flags, 0, offset, size, link, info, 16, entsize);
+            shdr += SHDR_SIZE;
+        }
+
+        out[..16].copy_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+        write_u16(&mut out, 16, ET_REL);
+        write_u16(&mut out, 18, EM_X86_64);
+        write_u64(&mut out, 40, shoff);
+        let shnum = (out.len() as u64 - shoff) / SHDR_SIZE;
+        write_u16(&mut out, 60, shnum as u16);
+        write_u16(&mut out, 62, shnum as u16 - 1);
+        Object::parse("test.o".into(), out).unwrap()
+    }
+}
+
+fn link(obj: &TestObject) -> Result<Vec<u8>> {
+    let objects = [obj.build()];
+    ElfLinker::new(&objects)?.link("libtest.so")
+}
+
+fn link_err(obj: &TestObject) -> String {
+    link(obj).unwrap_err().to_string()
+}
+
+/// The address and size of the output section `name`.
+fn output_section(lib: &[u8], name: &str) -> (u64, u64) {
+    let shoff = read_u64(lib, 40).unwrap();
+    let shnum = read_u16(lib, 60).unwrap() as u64;
+    let shstrndx = read_u16(lib, 62).unwrap() as u64;
+    let shstrtab = read_u64(lib, shoff + shstrndx * SHDR_SIZE + 24).unwrap();
+    (0..shnum)
+        .map(|i| shoff + i * SHDR_SIZE)
+        .find(|&hdr| read_str(lib, shstrtab + read_u32(lib, hdr).unwrap() as u64).unwrap() == name)
+        .map(|hdr| (read_u64(lib, hdr + 16).unwrap(), read_u64(lib, hdr + 32).unwrap()))
+        .unwrap_or_else(|| panic!("missing output section {name}"))
+}
+
+/// The place, type, symbol and addend of all dynamic relocations.
+fn dyn_relocs(lib: &[u8]) -> Vec<(u64, u32, u32, i64)> {
+    let (rela_dyn, size) = output_section(lib, ".rela.dyn");
+    (0..size / RELA_SIZE)
+        .map(|i| {
+            let rela = rela_dyn + i * RELA_SIZE;
+            let info = read_u64(lib, rela + 8).unwrap();
+            let addend = read_u64(lib, rela + 16).unwrap() as i64;
+            (read_u64(lib, rela).unwrap(), info as u32, (info >> 32) as u32, addend)
+        })
+        .collect()
+}
+
+/// The index of `name` in the dynamic symbol table.
+fn dynsym(lib: &[u8], name: &str) -> u32 {
+    let (dynsym, size) = output_section(lib, ".dynsym");
+    let (dynstr, _) = output_section(lib, ".dynstr");
+    (1..size / SYM_SIZE)
+        .find(|i| {
+            let name_off = read_u32(lib, dynsym + i * SYM_SIZE).unwrap() as u64;
+            read_str(lib, dynstr + name_off).unwrap() == name
+        })
+        .unwrap_or_else(|| panic!("missing dynamic symbol {name}")) as u32
+}
+
+fn read_i32(lib: &[u8], off: u64) -> i64 {
+    read_u32(lib, off).unwrap() as i32 as i64
+}
+
+#[test]
+fn relocations() {
+    let mut obj = TestObject::default();
+    let text = obj.text(32);
+    let data = obj.data(16);
+    let var = obj.symbol("var", STB_LOCAL, STT_OBJECT, data, 4);
+    let func = obj.symbol("func", STB_GLOBAL, STT_FUNC, text, 0);
+    obj.reloc(text, 0, R_X86_64_PC32, var, -4);
+    obj.reloc(text, 4, R_X86_64_PLT32, func, -4);
+    obj.reloc(text, 8, R_X86_64_PC64, var, 0);
+    obj.reloc(text, 16, R_X86_64_GOTOFF64, var, 2);
+    obj.reloc(text, 24, R_X86_64_GOTPC32, 0, 3);
+    obj.reloc(text, 28, R_X86_64_GOTPCREL, var, -4);
+    obj.reloc(data, 0, R_X86_64_NONE, 0, 0);
+    obj.reloc(data, 8, R_X86_64_64, func, 1);
+    let lib = link(&obj).unwrap();
+
+    let (text, _) = output_section(&lib, ".text");
+    let (data, _) = output_section(&lib, ".data");
+    let (got, got_size) = output_section(&lib, ".got");
+    let (_, plt_size) = output_section(&lib, ".plt");
+    let var = data + 4;
+    assert_eq!(got_size, 8);
+    assert_eq!(plt_size, 0);
+
+    assert_eq!(read_i32(&lib, text), var as i64 - 4 - text as i64);
+    assert_eq!(read_i32(&lib, text + 4), -8);
+    assert_eq!(read_u64(&lib, text + 8).unwrap(), var.wrapping_sub(text + 8));
+    assert_eq!(read_u64(&lib, text + 16).unwrap(), (var + 2).wrapping_sub(got));
+    assert_eq!(read_i32(&lib, text + 24), got as i64 + 3 - (text + 24) as i64);
+    assert_eq!(read_i32(&lib, text + 28), got as i64 - 4 - (text + 28) as i64);
+    assert_eq!(read_u64(&lib, got).unwrap(), var);
+    assert_eq!(read_u64(&lib, data).unwrap(), 0);
+    assert_eq!(read_u64(&lib, data + 8).unwrap(), text + 1);
+    assert_eq!(
+        dyn_relocs(&lib),
+        vec![
+            (got, R_X86_64_RELATIVE, 0, var as i64),
+            (data + 8, R_X86_64_RELATIVE, 0, (text + 1) as i64)
+        ]
+    );
+    dynsym(&lib, "func");
+}
+
+#[test]
+fn plt_and_got_imports() {
+    let mut obj = TestObject::default();
+    let text = obj.text(16);
+    let data = obj.data(8);
+    let exp = obj.symbol("exp", STB_GLOBAL, STT_NOTYPE, SHN_UNDEF, 0);
+    let sin = obj.symbol("sin", STB_GLOBAL, STT_NOTYPE, SHN_UNDEF, 0);
+    let counter = obj.symbol("counter", STB_GLOBAL, STT_NOTYPE, SHN_UNDEF, 0);
+    obj.reloc(text, 0, R_X86_64_PLT32, exp, -4);
+    obj.reloc(text, 4, R_X86_64_PLT32, sin, -4);
+    obj.reloc(text, 8, R_X86_64_REX_GOTPCRELX, exp, -4);
+    obj.reloc(text, 12, R_X86_64_GOTPCRELX, counter, -4);
+    obj.reloc(data, 0, R_X86_64_64, counter, 8);
+    let lib = link(&obj).unwrap();
+
+    let (text, _) = output_section(&lib, ".text");
+    let (data, _) = output_section(&lib, ".data");
+    let (plt, plt_size) = output_section(&lib, ".plt");
+    let (_, got_size) = output_section(&lib, ".got");
+    assert_eq!(plt_size, 3 * PLT_ENTRY_SIZE);
+    assert_eq!(got_size, 3 * 8);
+
+    let relocs = dyn_relocs(&lib);
+    assert_eq!(relocs.len(), 4);
+    let imports = ["exp", "sin", "counter"].map(|name| {
+        let id = dynsym(&lib, name);
+        let got_entry = relocs
+            .iter()
+            .find(|reloc| reloc.1 == R_X86_64_GLOB_DAT && reloc.2 == id)
+            .unwrap_or_else(|| panic!("missing GOT entry for {name}"))
+            .0;
+        assert_eq!(read_u64(&lib, got_entry).unwrap(), 0);
+
+        // jmp *got_entry(%rip)
+        let stub = plt + PLT_ENTRY_SIZE * (id as u64 - 1);
+        assert_eq!(lib[stub as usize..stub as usize + 2], [0xff, 0x25]);
+        assert_eq!(read_i32(&lib, stub + 2), got_entry as i64 - (stub + 6) as i64);
+        assert!(lib[stub as usize + 6..(stub + PLT_ENTRY_SIZE) as usize]
+            .iter()
+            .all(|&b| b == 0xcc));
+        (id, stub, got_entry)
+    });
+    let [(_, exp_stub, exp_got), (_, sin_stub, _), (counter, _, counter_got)] = imports;
+
+    assert_eq!(read_i32(&lib, text), exp_stub as i64 - 4 - text as i64);
+    assert_eq!(read_i32(&lib, text + 4), sin_stub as i64 - 4 - (text + 4) as i64);
+    assert_eq!(read_i32(&lib, text + 8), exp_got as i64 - 4 - (text + 8) as i64);
+    assert_eq!(read_i32(&lib, text + 12), counter_got as i64 - 4 - (text + 12) as i64);
+    assert_eq!(read_u64(&lib, data).unwrap(), 0);
+    assert!(relocs.contains(&(data, R_X86_64_64, counter, 8)));
+}
+
+#[test]
+fn relocation_overflow() {
+    let mut obj = TestObject::default();
+    let text = obj.text(8);
+    let far = obj.symbol("far", STB_GLOBAL, STT_NOTYPE, SHN_ABS, 1 << 40);
+    obj.reloc(text, 0, R_X86_64_PC32, far, -4);
+    assert!(link_err(&obj).starts_with("relocation overflow"));
+}
+
+#[test]
+fn relocation_out_of_bounds() {
+    let mut obj = TestObject::default();
+    let text = obj.text(8);
+    let func = obj.symbol("func", STB_GLOBAL, STT_FUNC, text, 0);
+    obj.reloc(text, 6, R_X86_64_PC32, func, -4);
+    assert!(link_err(&obj).contains("out of bounds of section .text"));
+
+    let mut obj = TestObject::default();
+    let text = obj.text(8);
+    let data = obj.data(8);
+    let func = obj.symbol("func", STB_GLOBAL, STT_FUNC, text, 0);
+    obj.reloc(data, 4, R_X86_64_64, func, 0);
+    assert!(link_err(&obj).contains("out of bounds of section .data"));
+
+    let mut obj = TestObject::default();
+    let text = obj.text(8);
+    obj.reloc(text, u64::MAX - 1, R_X86_64_GOTPC32, 0, 0);
+    assert!(link_err(&obj).contains("out of bounds of section .text"));
+
+    let mut obj = TestObject::default();
+    let text = obj.text(8);
+    let bss = obj.section(".bss", SHT_NOBITS, SHF_ALLOC | SHF_WRITE, vec![0; 8]);
+    let func = obj.symbol("func", STB_GLOBAL, STT_FUNC, text, 0);
+    obj.reloc(bss, 0, R_X86_64_64, func, 0);
+    assert!(link_err(&obj).contains("without data"));
+}
+
+#[test]
+fn unsupported_relocation() {
+    const R_X86_64_32: u32 = 10;
+    let mut obj = TestObject::default();
+    let text = obj.text(8);
+    let func = obj.symbol("func", STB_GLOBAL, STT_FUNC, text, 0);
+    obj.reloc(text, 0, R_X86_64_32, func, 0);
+    assert!(link_err(&obj).starts_with("unsupported relocation type 10"));
+}
@@ -11,13 +11,33 @@ use camino::{Utf8Path, Utf8PathBuf};
 use cc::windows_registry;
 use target::spec::{LinkerFlavor, Target};
 
+mod elf;
+
+/// Links the object files added by `add_objects` into a shared library.
+///
+/// If the target uses [`LinkerFlavor::Builtin`] (or no external linker was specified and
+/// the system linker can not be found) the library is produced by the builtin ELF linker
+/// instead of invoking an external tool.
 pub fn link(
     path: Option<Utf8PathBuf>,
     target: &Target,
     out_filename: &Utf8Path,
     add_objects: impl FnOnce(&mut dyn Linker),
 ) -> Result<()> {
-    let mut linker = linker_with_args(path, target, out_filename, add_objects);
+    let mut objects = BuiltinLinker { objects: Vec::new() };
+    add_objects(&mut objects);
+    let objects = objects.objects;
+
+    if target.options.linker_flavor == LinkerFlavor::Builtin {
+        return elf::link_shared_object(target, &objects, out_filename);
+    }
+
+    let fallback_to_builtin = path.is_none() && elf::is_supported(target);
+    let mut linker = linker_with_args(path, target, out_filename, |linker| {
+        for obj in &objects {
+            linker.add_object(obj)
+        }
+    });
 
     let import_lib_path = out_filename.with_file_name("__openvaf__import.lib");
     if !target.options.import_lib.is_empty() {
@@ -38,6 +58,9 @@ pub fn link(
             bail!("linking failed (see linker output for details)")
         }
         Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound && fallback_to_builtin => {
+            elf::link_shared_object(target, &objects, out_filename)
+        }
         Err(err) => bail!("linker not found: {}", err),
     }
 }
@@ -151,6 +174,7 @@ fn get_linker<'a>(
             });
             Box::new(LdLinker { cmd: Command::new(path), target }) as Box<dyn Linker>
         }
+        LinkerFlavor::Builtin => unreachable!("the builtin linker is not an external command"),
     }
 }
 
@@ -302,6 +326,25 @@ impl Linker for MsvcLinker {
     }
 }
 
+/// Collects the objects for the builtin linker, which does not use a command line.
+struct BuiltinLinker {
+    objects: Vec<Utf8PathBuf>,
+}
+
+impl Linker for BuiltinLinker {
+    fn cmd(&mut self) -> &mut Command {
+        unreachable!("the builtin linker is not an external command")
+    }
+
+    fn output_filename(&mut self, _path: &Utf8Path) {}
+
+    fn add_object(&mut self, path: &Utf8Path) {
+        self.objects.push(path.to_owned());
+    }
+
+    fn set_output_kind(&mut self) {}
+}
+
 pub struct Command {
     command: PathBuf,
     args: Vec<OsString>,
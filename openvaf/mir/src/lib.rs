@@ -50,7 +50,7 @@ pub use crate::dfg::{
     ValueDef,
 };
 pub use crate::dominators::DominatorTree;
-pub use crate::entities::{AnyEntity, Block, FuncRef, Inst, Param, Use, Value};
+pub use crate::entities::{AnyEntity, Block, FuncRef, Inst, Param, Tag, Use, Value};
 pub use crate::flowgraph::ControlFlowGraph;
 pub use crate::instructions::{
     InstructionData, InstructionFormat, Opcode, PhiMap, PhiNode, ValueList, ValueListPool,
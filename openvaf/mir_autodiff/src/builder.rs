@@ -340,6 +340,7 @@ impl<'a, 'u> DerivativeBuilder<'a, 'u> {
     ) {
         // add the original instruction
         bcache.resolved_derivatives.insert(None, ResolvedDerivative::root_instr(self.dst.0));
+        let generated_start: Inst = self.func.dfg.num_insts().into();
 
         for derivative in derivatives.iter() {
             let prev_order = self.intern.previous_order(derivative);
@@ -350,27 +351,59 @@ impl<'a, 'u> DerivativeBuilder<'a, 'u> {
 
             let inst_start = self.func.dfg.num_insts().into();
 
+            let mut reused = Vec::new();
             for (inst, cache_data_i) in zip(origin.instructions(), cache.data) {
                 if !self.func.dfg.has_results(inst) {
                     continue;
                 }
                 let res = self.func.dfg.first_result(inst);
-                if !self.derivative_values.contains_key(&(res, base)) {
+                if let Some(&val) = self.derivative_values.get(&(res, base)) {
+                    reused.push(val);
+                } else {
                     self.inst_derivative(inst, base, bcache.cache_data[cache_data_i]);
                 }
             }
 
             let inst_end = self.func.dfg.num_insts().into();
 
+            // Mixed derivatives (d^2f/dxdy and d^2f/dydx) share values that were generated for
+            // another derivative of this instruction. These values (and the instructions they
+            // depend on) must also be derived to obtain the next higher order.
+            let reused = self.generated_insts(reused, generated_start..inst_start, &cache.instrs);
             bcache.resolved_derivatives.insert(
                 Some(derivative),
-                ResolvedDerivative { instrs: inst_start..inst_end, cache_instrs: cache.instrs },
+                ResolvedDerivative {
+                    instrs: inst_start..inst_end,
+                    cache_instrs: cache.instrs,
+                    reused,
+                },
             );
         }
 
         bcache.clear();
     }
 
+    /// Returns the instructions in `generated` (excluding `cache`) that `vals` depend on
+    /// in the order they were generated.
+    fn generated_insts(
+        &self,
+        mut vals: Vec<Value>,
+        generated: Range<Inst>,
+        cache: &Range<Inst>,
+    ) -> Vec<Inst> {
+        let mut insts = Vec::new();
+        while let Some(val) = vals.pop() {
+            if let Some(inst) = self.func.dfg.value_def(val).inst() {
+                if generated.contains(&inst) && !cache.contains(&inst) && !insts.contains(&inst) {
+                    insts.push(inst);
+                    vals.extend_from_slice(self.func.dfg.instr_args(inst));
+                }
+            }
+        }
+        insts.sort_unstable();
+        insts
+    }
+
     fn insert_conversions(&mut self, inst: Inst) {
         if let Some(conversion) = self.live_derivatives.conversions.get(&inst) {
             for chain_rule in conversion.iter().rev() {
@@ -896,6 +929,8 @@ pub struct CacheInfo {
 pub struct ResolvedDerivative {
     instrs: Range<Inst>,
     cache_instrs: Range<Inst>,
+    /// instructions generated for another derivative whose results are reused
+    reused: Vec<Inst>,
 }
 
 impl ResolvedDerivative {
@@ -905,13 +940,14 @@ impl ResolvedDerivative {
         ResolvedDerivative {
             instrs: pos..Inst::from(u32::from(pos) + 1),
             cache_instrs: pos..Inst::from(u32::from(pos)),
+            reused: Vec::new(),
         }
     }
 
-    fn instructions(&self) -> impl Iterator<Item = Inst> {
+    fn instructions(&self) -> impl Iterator<Item = Inst> + '_ {
         let instrs: Range<u32> = self.instrs.start.into()..self.instrs.end.into();
         let cache_instrs: Range<u32> = self.cache_instrs.start.into()..self.cache_instrs.end.into();
-        cache_instrs.chain(instrs).map(Inst::from)
+        self.reused.iter().copied().chain(cache_instrs.chain(instrs).map(Inst::from))
     }
 }
 
@@ -6,7 +6,7 @@ use mir_interpret::{Data, Interpreter};
 use mir_reader::parse_function;
 use typed_index_collections::TiSlice;
 
-use crate::auto_diff;
+use crate::{auto_diff, auto_diff_higher_order, derivative_of};
 
 fn check_simple(src: &str, data_flow_result: Expect) {
     let (mut func, _) = parse_function(src).unwrap();
@@ -996,3 +996,58 @@ fn subgraph() {
 
     check_simple(src, expect);
 }
+
+#[test]
+fn higher_order_extra_derivatives() {
+    let src = r##"
+        function %bar(v10, v11) {
+        block0:
+            v12 = fmul v10, v11
+            v13 = exp v12
+            v14 = fmul v13, v10
+            v100 = optbarrier v14
+        }"##;
+    let (mut func, _) = parse_function(src).unwrap();
+    let mut cfg = ControlFlowGraph::new();
+    cfg.compute(&func);
+    let mut dom_tree = DominatorTree::default();
+    dom_tree.compute(&func, &cfg, true, false, true);
+
+    let unknowns = [10u32.into(), 11u32.into()].into_iter().collect();
+    let unknowns = KnownDerivatives { unknowns, ddx_calls: Default::default() };
+    let (x, y) = (0u32.into(), 1u32.into());
+    let val = 14u32.into();
+    let derivatives = auto_diff_higher_order(
+        &mut func,
+        &dom_tree,
+        &unknowns,
+        &[],
+        &[(val, &[x, y]), (val, &[x, x]), (val, &[y, y, x])],
+    );
+    assert!(func.validate());
+
+    let (a, b) = (0.7f64, 1.3f64);
+    let mut interpret = Interpreter::new(
+        &func,
+        TiSlice::from_ref(&[]),
+        TiSlice::from_ref(Data::from_f64_slice(&[a, b])),
+    );
+    interpret.run();
+    let read = |unknowns: &[_]| -> f64 {
+        interpret.state.read(derivative_of(&derivatives, val, unknowns))
+    };
+
+    // f = x*exp(x*y)
+    let e = (a * b).exp();
+    let expected = [
+        (&[x][..], e + a * b * e),
+        (&[x, y][..], 2.0 * a * e + a * a * b * e),
+        (&[x, x][..], 2.0 * b * e + a * b * b * e),
+        (&[y, y, x][..], 3.0 * a * a * e + a * a * a * b * e),
+    ];
+    let margin = F64Margin::default().epsilon(10f64 * f64::EPSILON);
+    for (unknowns, expected) in expected {
+        let res = read(unknowns);
+        assert!(res.approx_eq(expected, margin), "{unknowns:?}: {res} != {expected}");
+    }
+}
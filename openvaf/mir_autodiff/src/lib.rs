@@ -8,23 +8,59 @@ use ahash::AHashMap;
 pub use builder::build_derivatives;
 pub use live_derivatives::LiveDerivatives;
 use mir::{
-    DataFlowGraph, DominatorTree, Function, Inst, InstructionData, KnownDerivatives, Opcode, Value,
+    DataFlowGraph, DominatorTree, Function, Inst, InstructionData, KnownDerivatives, Opcode,
+    Unknown, Value, F_ZERO,
 };
 
 use crate::intern::{Derivative, DerivativeIntern};
 
 pub fn auto_diff(
+    func: impl AsMut<Function>,
+    dom_tree: &DominatorTree,
+    derivatives: &KnownDerivatives,
+    extra_derivatives: &[(Value, mir::Unknown)],
+) -> AHashMap<(Value, mir::Unknown), Value> {
+    auto_diff_higher_order(func, dom_tree, derivatives, extra_derivatives, &[])
+}
+
+/// Same as [`auto_diff`] but additionally builds the higher order derivatives in
+/// `higher_order_derivatives`. An entry `(val, [x, y])` requests the derivative of `val` by `x`
+/// which is then derived by `y`. The result is retrieved with [`derivative_of`].
+///
+/// Only those lower order derivatives that are required to build the higher order derivatives
+/// are generated (just like for nested `ddx` calls).
+pub fn auto_diff_higher_order(
     mut func: impl AsMut<Function>,
     dom_tree: &DominatorTree,
     derivatives: &KnownDerivatives,
     extra_derivatives: &[(Value, mir::Unknown)],
+    higher_order_derivatives: &[(Value, &[mir::Unknown])],
 ) -> AHashMap<(Value, mir::Unknown), Value> {
     let func = func.as_mut();
     let mut intern = DerivativeIntern::new(derivatives);
-    let live_derivative = LiveDerivatives::build(func, &mut intern, extra_derivatives, dom_tree);
+    let live_derivative = LiveDerivatives::build(
+        func,
+        &mut intern,
+        extra_derivatives,
+        higher_order_derivatives,
+        dom_tree,
+    );
     build_derivatives(func, &mut intern, &live_derivative, dom_tree.cfg_postorder())
 }
 
+/// Looks up the (higher order) derivative of `val` by `unknowns` in the result of
+/// [`auto_diff_higher_order`]. Derivatives that were not generated are zero.
+pub fn derivative_of(
+    derivatives: &AHashMap<(Value, Unknown), Value>,
+    val: Value,
+    unknowns: &[Unknown],
+) -> Value {
+    unknowns
+        .iter()
+        .try_fold(val, |val, &unknown| derivatives.get(&(val, unknown)).copied())
+        .unwrap_or(F_ZERO)
+}
+
 fn is_zero_call(dfg: &DataFlowGraph, inst: Inst, intern: &DerivativeIntern) -> bool {
     if let InstructionData::Call { func_ref, .. } = dfg.insts[inst] {
         !intern.ddx_calls.contains_key(&func_ref)
@@ -19,6 +19,8 @@ struct LiveDerivativeBuilder<'a, 'b> {
     reachable_derivatives: SparseBitMatrix<Inst, Derivative>,
     post_order_parts: PostorderParts<'a>,
     visited: BitSet<Inst>,
+    /// derivatives that are requested explicitly and therefore always live
+    extra_derivatives: AHashMap<Inst, HybridBitSet<Derivative>>,
 }
 
 impl<'a, 'b> LiveDerivativeBuilder<'a, 'b> {
@@ -38,6 +40,7 @@ impl<'a, 'b> LiveDerivativeBuilder<'a, 'b> {
             reachable_derivatives: mat,
             post_order_parts,
             visited: BitSet::default(),
+            extra_derivatives: AHashMap::default(),
         }
     }
 
@@ -147,6 +150,8 @@ impl<'a, 'b> LiveDerivativeBuilder<'a, 'b> {
     fn initial_live_derivative_workque(&mut self) -> WorkQueue<Inst> {
         let mut post_order =
             Postorder::from_parts(&self.func.dfg, take(&mut self.post_order_parts), self.intern);
+        // higher order derivatives requested by the caller were already populated
+        post_order.clear();
         for param in self.intern.unknowns.iter() {
             post_order.populate(*param)
         }
@@ -177,6 +182,9 @@ impl<'a, 'b> LiveDerivativeBuilder<'a, 'b> {
         let func = self.func;
         while let Some(inst) = workqueue.pop() {
             let mut dst = self.live_derivatives.compute_inst(inst, func, self.intern);
+            if let Some(extra) = self.extra_derivatives.get(&inst) {
+                dst.union(extra, self.intern.num_derivatives());
+            }
             if let InstructionData::Call { func_ref, .. } = func.dfg.insts[inst] {
                 if self.intern.ddx_calls.contains_key(&func_ref) {
                     let old = dst.clone();
@@ -230,10 +238,62 @@ impl<'a, 'b> LiveDerivativeBuilder<'a, 'b> {
         self.live_derivatives.mat.intersect(&reachable_derivatives);
     }
 
-    fn insert_extra_derivative(&mut self, extra_derivatives: &[(Value, mir::Unknown)]) {
+    fn insert_extra_derivative(&mut self, inst: Inst, derivative: Derivative) {
+        let num_derivatives = self.intern.num_derivatives();
+        self.live_derivatives.mat.ensure_row(inst).insert_growable(derivative, num_derivatives);
+        self.extra_derivatives
+            .entry(inst)
+            .or_default()
+            .insert_growable(derivative, num_derivatives);
+    }
+
+    fn insert_extra_derivatives(&mut self, extra_derivatives: &[(Value, mir::Unknown)]) {
         for (val, unknown) in extra_derivatives {
             if let ValueDef::Result(inst, _) = self.func.dfg.value_def(*val) {
-                self.live_derivatives.mat.insert(inst, self.intern.to_derivative(*unknown));
+                self.insert_extra_derivative(inst, self.intern.to_derivative(*unknown));
+            }
+        }
+    }
+
+    /// Higher order derivatives are inserted just like nested `ddx` calls: Starting from the
+    /// outermost derivative every live derivative is raised by the next unknown. This also
+    /// inserts all lower order derivatives required to calculate the requested derivative.
+    /// Derivatives that are not reachable are skipped so that no trivially zero derivatives
+    /// are generated.
+    fn insert_higher_order_derivatives(
+        &mut self,
+        higher_order_derivatives: &[(Value, &[mir::Unknown])],
+    ) {
+        let mut live = HybridBitSet::new_empty();
+        for &(val, unknowns) in higher_order_derivatives {
+            let inst = if let ValueDef::Result(inst, _) = self.func.dfg.value_def(val) {
+                inst
+            } else {
+                continue;
+            };
+
+            live.clear();
+            for &unknown in unknowns.iter().rev() {
+                let old = live.clone();
+                for derivative in old.iter() {
+                    let res = self.intern.raise_order_with(derivative, unknown, |derivative| {
+                        self.reachable_derivatives.contains(inst, derivative)
+                    });
+                    if let Some((derivative, created)) = res {
+                        live.insert_growable(derivative, self.intern.num_derivatives());
+                        if created {
+                            self.populate_reachable(derivative)
+                        }
+                    }
+                }
+                live.insert_growable(
+                    self.intern.to_derivative(unknown),
+                    self.intern.num_derivatives(),
+                );
+            }
+
+            for derivative in live.iter() {
+                self.insert_extra_derivative(inst, derivative)
             }
         }
     }
@@ -255,17 +315,23 @@ impl LiveDerivatives {
         func: &Function,
         intern: &mut DerivativeIntern,
         extra_derivatives: &[(Value, mir::Unknown)],
+        higher_order_derivatives: &[(Value, &[mir::Unknown])],
         dom_tree: &DominatorTree,
     ) -> LiveDerivatives {
         let mut builder = LiveDerivativeBuilder::new(func, intern);
         builder.populate_reachable_unknowns();
-        builder.insert_extra_derivative(extra_derivatives);
+        builder.insert_extra_derivatives(extra_derivatives);
+        builder.insert_higher_order_derivatives(higher_order_derivatives);
         let mut workqueue = builder.initial_live_derivative_workque();
         builder.live_derivative_fixpoint(&mut workqueue);
         builder.strip_unneeded_derivatives();
         let (mut res, buf) = builder.finish();
 
-        res.run_subgraph_opt(func, intern, extra_derivatives, dom_tree, buf);
+        let outputs = extra_derivatives
+            .iter()
+            .map(|&(val, _)| val)
+            .chain(higher_order_derivatives.iter().map(|&(val, _)| val));
+        res.run_subgraph_opt(func, intern, outputs, dom_tree, buf);
 
         res
     }
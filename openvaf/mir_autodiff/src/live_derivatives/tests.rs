@@ -69,7 +69,7 @@ fn check(src: &str, data_flow_result: Expect) {
     let mut dom_tree = DominatorTree::default();
     dom_tree.compute(&func, &cfg, true, false, true);
 
-    let res = LiveDerivatives::build(&func, &mut unknowns, &[], &dom_tree);
+    let res = LiveDerivatives::build(&func, &mut unknowns, &[], &[], &dom_tree);
     let printer = DerivativeFmt { func: &func, derivatives: &res.mat };
 
     let actual = format!("{:#?}", printer);
@@ -309,13 +309,13 @@ impl LiveDerivatives {
         &mut self,
         func: &Function,
         intern: &mut DerivativeIntern,
-        extra_derivatives: &[(Value, mir::Unknown)],
+        extra_derivatives: impl Iterator<Item = Value>,
         dom_tree: &DominatorTree,
         buf: BitSet<Inst>,
     ) {
         let mut outputs = buf;
-        for (val, _) in extra_derivatives {
-            if let Some(inst) = func.dfg.value_def(*val).inst() {
+        for val in extra_derivatives {
+            if let Some(inst) = func.dfg.value_def(val).inst() {
                 outputs.insert(inst);
             }
         }
@@ -14,6 +14,7 @@ use mir::{
 use typed_index_collections::TiVec;
 
 use crate::callbacks::CallbackFun;
+use crate::debug_info::FunctionDebugInfo;
 use crate::{CodegenCx, UNNAMED};
 
 #[derive(Clone)]
@@ -212,6 +213,9 @@ pub struct Builder<'a, 'cx, 'll> {
     // Initially the value in the Cell is None.
     // If None, nothing is stored at return.
     pub ret_store_ptr: Cell<Option<&'ll llvm_sys::LLVMValue>>,
+    // Source locations and variables for the debug info of this function.
+    // Set with set_debug_info.
+    pub debug_info: Option<FunctionDebugInfo<'a, 'll>>,
 }
 
 impl Drop for Builder<'_, '_, '_> {
@@ -277,8 +281,16 @@ impl<'a, 'cx, 'll> Builder<'a, 'cx, 'll> {
             ret_allocated,
             ret_alloc_type,
             ret_store_ptr: Cell::new(None),
+            debug_info: None,
         }
     }
+
+    /// Emits debug info for this function. Instructions built afterwards that have no source
+    /// location are attributed to the start of the function.
+    pub fn set_debug_info(&mut self, debug_info: FunctionDebugInfo<'a, 'll>) {
+        unsafe { self.set_debug_loc(debug_info.default_loc) };
+        self.debug_info = Some(debug_info);
+    }
 }
 
 use std::ptr::NonNull;
@@ -560,11 +572,47 @@ impl<'ll> Builder<'_, '_, 'll> {
         self.select_bb(bb);
 
         for inst in self.func.layout.block_insts(bb) {
-            let fast_math = self.func.srclocs.get(inst).map_or(false, |loc| loc.0 < 0);
+            let loc = self.func.srclocs.get(inst).copied().unwrap_or_default();
+            let debug_loc = self.debug_info.as_ref().map(|info| {
+                info.locations.get(&loc.0.unsigned_abs()).copied().unwrap_or(info.default_loc)
+            });
+            if let Some(debug_loc) = debug_loc {
+                self.set_debug_loc(debug_loc);
+            }
+            let fast_math = loc.0 < 0;
             self.build_inst(
                 inst,
                 if fast_math { FastMathMode::Partial } else { FastMathMode::Disabled },
-            )
+            );
+            if let Some(debug_loc) = debug_loc {
+                self.declare_variables(inst, debug_loc);
+            }
+        }
+    }
+
+    unsafe fn set_debug_loc(&self, loc: &'ll llvm_sys::LLVMOpaqueMetadata) {
+        llvm_sys::core::LLVMSetCurrentDebugLocation2(
+            self.llbuilder as *const _ as *mut _,
+            loc as *const _ as *mut _,
+        );
+    }
+
+    /// Emits `llvm.dbg.value` for results of `inst` that are assigned to a variable.
+    /// Phis are skipped because debug intrinsics can not be placed between them.
+    unsafe fn declare_variables(&self, inst: Inst, debug_loc: &'ll llvm_sys::LLVMOpaqueMetadata) {
+        let debug_info = self.debug_info.as_ref().unwrap();
+        if debug_info.variables.is_empty() || self.func.dfg.insts[inst].opcode() == Opcode::Phi {
+            return;
+        }
+        for &val in self.func.dfg.inst_results(inst) {
+            let var = match self.func.dfg.tag(val) {
+                Some(tag) => debug_info.variables.get(&tag),
+                None => None,
+            };
+            if let (Some(&var), BuilderVal::Eager(llval)) = (var, &self.values[val]) {
+                let bb = llvm_sys::core::LLVMGetInsertBlock(self.llbuilder as *const _ as *mut _);
+                debug_info.dbg_value(llval, var, debug_loc, bb);
+            }
         }
     }
 
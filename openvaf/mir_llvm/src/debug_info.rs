@@ -0,0 +1,296 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::ptr::{self, NonNull};
+
+use ahash::AHashMap;
+use libc::c_uint;
+use llvm_sys::debuginfo::{
+    LLVMDIBuilderCreateAutoVariable, LLVMDIBuilderCreateBasicType, LLVMDIBuilderCreateCompileUnit,
+    LLVMDIBuilderCreateDebugLocation, LLVMDIBuilderCreateExpression, LLVMDIBuilderCreateFile,
+    LLVMDIBuilderCreateFunction, LLVMDIBuilderCreateLexicalBlockFile,
+    LLVMDIBuilderCreateSubroutineType, LLVMDIBuilderFinalize, LLVMDIFlagZero,
+    LLVMDWARFEmissionKind, LLVMDWARFSourceLanguage, LLVMDebugMetadataVersion, LLVMDisposeDIBuilder,
+    LLVMSetSubprogram,
+};
+use llvm_sys::{LLVMModuleFlagBehavior, LLVMOpaqueDIBuilder, LLVMOpaqueMetadata as Metadata};
+use mir::{SourceLoc, Tag};
+
+use crate::CodegenCx;
+
+const DW_ATE_BOOLEAN: c_uint = 0x02;
+const DW_ATE_FLOAT: c_uint = 0x04;
+const DW_ATE_SIGNED: c_uint = 0x05;
+
+/// The types of Verilog-A variables that can be shown in a debugger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugType {
+    Real,
+    Integer,
+    Bool,
+}
+
+/// Emits DWARF debug info for a single LLVM module. Each module receives one compile unit for
+/// the Verilog-A file it was compiled from.
+pub struct DebugInfo<'ll> {
+    llcx: &'ll llvm_sys::LLVMContext,
+    builder: NonNull<LLVMOpaqueDIBuilder>,
+    compile_unit: &'ll Metadata,
+    files: RefCell<AHashMap<String, &'ll Metadata>>,
+    tys: [&'ll Metadata; 3],
+    empty_expr: &'ll Metadata,
+}
+
+impl<'ll> DebugInfo<'ll> {
+    /// Creates a compile unit for the Verilog-A file `path` in the module of `cx`.
+    pub fn new(cx: &CodegenCx<'_, 'll>, path: &str, optimized: bool) -> DebugInfo<'ll> {
+        unsafe {
+            let llmod = NonNull::from(cx.llmod).as_ptr();
+            let version = cx.const_unsigned_int(LLVMDebugMetadataVersion()) as *const _ as *mut _;
+            let key = "Debug Info Version";
+            llvm_sys::core::LLVMAddModuleFlag(
+                llmod,
+                LLVMModuleFlagBehavior::LLVMModuleFlagBehaviorWarning,
+                key.as_ptr() as *const _,
+                key.len(),
+                llvm_sys::core::LLVMValueAsMetadata(version),
+            );
+
+            let builder = NonNull::new(llvm_sys::debuginfo::LLVMCreateDIBuilder(llmod)).unwrap();
+            let file = create_file(builder, path);
+            let producer = "OpenVAF";
+            let compile_unit = &*LLVMDIBuilderCreateCompileUnit(
+                builder.as_ptr(),
+                // DWARF has no language code for Verilog-A
+                LLVMDWARFSourceLanguage::LLVMDWARFSourceLanguageC,
+                file as *const _ as *mut _,
+                producer.as_ptr() as *const _,
+                producer.len(),
+                optimized as i32,
+                ptr::null(),
+                0,
+                0,
+                ptr::null(),
+                0,
+                LLVMDWARFEmissionKind::LLVMDWARFEmissionKindFull,
+                0,
+                0,
+                0,
+                ptr::null(),
+                0,
+                ptr::null(),
+                0,
+            );
+
+            let basic_ty = |name: &str, bits: u64, encoding: c_uint| {
+                &*LLVMDIBuilderCreateBasicType(
+                    builder.as_ptr(),
+                    name.as_ptr() as *const _,
+                    name.len(),
+                    bits,
+                    encoding,
+                    LLVMDIFlagZero,
+                )
+            };
+            let tys = [
+                basic_ty("real", 64, DW_ATE_FLOAT),
+                basic_ty("integer", 32, DW_ATE_SIGNED),
+                basic_ty("bool", 8, DW_ATE_BOOLEAN),
+            ];
+            let empty_expr = &*LLVMDIBuilderCreateExpression(builder.as_ptr(), ptr::null_mut(), 0);
+
+            let mut files = AHashMap::new();
+            files.insert(path.to_owned(), file);
+            DebugInfo {
+                llcx: cx.llcx,
+                builder,
+                compile_unit,
+                files: RefCell::new(files),
+                tys,
+                empty_expr,
+            }
+        }
+    }
+
+    /// Returns the `DIFile` for `path`.
+    pub fn file(&self, path: &str) -> &'ll Metadata {
+        *self
+            .files
+            .borrow_mut()
+            .entry(path.to_owned())
+            .or_insert_with(|| unsafe { create_file(self.builder, path) })
+    }
+
+    /// Attaches a `DISubprogram` to `llfunc`. Its source locations and variables are added to
+    /// the returned [`FunctionDebugInfo`] which must then be passed to the [`Builder`] of the
+    /// function.
+    ///
+    /// [`Builder`]: crate::Builder
+    pub fn function<'a>(
+        &'a self,
+        llfunc: &'ll llvm_sys::LLVMValue,
+        name: &str,
+        path: &str,
+        line: u32,
+    ) -> FunctionDebugInfo<'a, 'll> {
+        let file = self.file(path);
+        let line = line + 1;
+        unsafe {
+            let fun_ty = LLVMDIBuilderCreateSubroutineType(
+                self.builder.as_ptr(),
+                file as *const _ as *mut _,
+                ptr::null_mut(),
+                0,
+                LLVMDIFlagZero,
+            );
+            let scope = &*LLVMDIBuilderCreateFunction(
+                self.builder.as_ptr(),
+                self.compile_unit as *const _ as *mut _,
+                name.as_ptr() as *const _,
+                name.len(),
+                name.as_ptr() as *const _,
+                name.len(),
+                file as *const _ as *mut _,
+                line,
+                fun_ty,
+                0,
+                1,
+                line,
+                LLVMDIFlagZero,
+                1,
+            );
+            LLVMSetSubprogram(NonNull::from(llfunc).as_ptr(), scope as *const _ as *mut _);
+            let default_loc = self.location(scope, line, 0);
+            let mut scopes = AHashMap::new();
+            scopes.insert(path.to_owned(), scope);
+            FunctionDebugInfo {
+                info: self,
+                scope,
+                scopes,
+                default_loc,
+                locations: AHashMap::new(),
+                variables: AHashMap::new(),
+            }
+        }
+    }
+
+    fn location(&self, scope: &'ll Metadata, line: u32, col: u32) -> &'ll Metadata {
+        unsafe {
+            &*LLVMDIBuilderCreateDebugLocation(
+                NonNull::from(self.llcx).as_ptr(),
+                line,
+                col,
+                scope as *const _ as *mut _,
+                ptr::null_mut(),
+            )
+        }
+    }
+
+    /// Must be called after all functions were built and before the module is optimized or
+    /// emitted.
+    pub fn finalize(&self) {
+        unsafe { LLVMDIBuilderFinalize(self.builder.as_ptr()) }
+    }
+}
+
+impl Drop for DebugInfo<'_> {
+    fn drop(&mut self) {
+        unsafe { LLVMDisposeDIBuilder(self.builder.as_ptr()) }
+    }
+}
+
+unsafe fn create_file<'ll>(builder: NonNull<LLVMOpaqueDIBuilder>, path: &str) -> &'ll Metadata {
+    let path = Path::new(path);
+    let name =
+        path.file_name().map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy());
+    let dir = path.parent().map_or_else(Default::default, |dir| dir.to_string_lossy());
+    &*LLVMDIBuilderCreateFile(
+        builder.as_ptr(),
+        name.as_ptr() as *const _,
+        name.len(),
+        dir.as_ptr() as *const _,
+        dir.len(),
+    )
+}
+
+/// Debug info of a single function, see [`DebugInfo::function`].
+pub struct FunctionDebugInfo<'a, 'll> {
+    info: &'a DebugInfo<'ll>,
+    /// the `DISubprogram`
+    scope: &'ll Metadata,
+    /// scopes for the files that contain the code of this function (include files require a
+    /// `DILexicalBlockFile`)
+    scopes: AHashMap<String, &'ll Metadata>,
+    /// location used for instructions without a source location
+    pub(crate) default_loc: &'ll Metadata,
+    /// locations by the absolute value of the source location of the instructions
+    pub(crate) locations: AHashMap<u32, &'ll Metadata>,
+    /// `DILocalVariable`s by the tag of the values assigned to the variable
+    pub(crate) variables: AHashMap<Tag, &'ll Metadata>,
+}
+
+impl<'ll> FunctionDebugInfo<'_, 'll> {
+    /// Sets the position (with zero based `line` and `col`) of all instructions with the
+    /// source location `loc`.
+    pub fn add_location(&mut self, loc: SourceLoc, path: &str, line: u32, col: u32) {
+        let scope = self.file_scope(path);
+        let loc_ = self.info.location(scope, line + 1, col + 1);
+        self.locations.insert(loc.bits().unsigned_abs(), loc_);
+    }
+
+    /// Declares a local variable. All values tagged with `tag` are shown as this variable.
+    pub fn add_variable(&mut self, tag: Tag, name: &str, path: &str, line: u32, ty: DebugType) {
+        let scope = self.file_scope(path);
+        let file = self.info.file(path);
+        let ty = self.info.tys[ty as usize];
+        let var = unsafe {
+            &*LLVMDIBuilderCreateAutoVariable(
+                self.info.builder.as_ptr(),
+                scope as *const _ as *mut _,
+                name.as_ptr() as *const _,
+                name.len(),
+                file as *const _ as *mut _,
+                line + 1,
+                ty as *const _ as *mut _,
+                1,
+                LLVMDIFlagZero,
+                0,
+            )
+        };
+        self.variables.insert(tag, var);
+    }
+
+    fn file_scope(&mut self, path: &str) -> &'ll Metadata {
+        if let Some(&scope) = self.scopes.get(path) {
+            return scope;
+        }
+        let file = self.info.file(path);
+        let scope = unsafe {
+            &*LLVMDIBuilderCreateLexicalBlockFile(
+                self.info.builder.as_ptr(),
+                self.scope as *const _ as *mut _,
+                file as *const _ as *mut _,
+                0,
+            )
+        };
+        self.scopes.insert(path.to_owned(), scope);
+        scope
+    }
+
+    /// Emits a `llvm.dbg.value` that assigns `val` to `var` at the end of `bb`.
+    pub(crate) unsafe fn dbg_value(
+        &self,
+        val: &'ll llvm_sys::LLVMValue,
+        var: &'ll Metadata,
+        loc: &'ll Metadata,
+        bb: *mut llvm_sys::LLVMBasicBlock,
+    ) {
+        llvm_sys::debuginfo::LLVMDIBuilderInsertDbgValueAtEnd(
+            self.info.builder.as_ptr(),
+            NonNull::from(val).as_ptr(),
+            var as *const _ as *mut _,
+            self.info.empty_expr as *const _ as *mut _,
+            loc as *const _ as *mut _,
+            bb,
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use llvm_sys::error::{LLVMDisposeErrorMessage, LLVMErrorRef, LLVMGetErrorMessage};
+use llvm_sys::orc2::lljit::{
+    LLVMOrcCreateLLJIT, LLVMOrcCreateLLJITBuilder, LLVMOrcDisposeLLJIT, LLVMOrcLLJITAddObjectFile,
+    LLVMOrcLLJITGetGlobalPrefix, LLVMOrcLLJITGetMainJITDylib, LLVMOrcLLJITLookup, LLVMOrcLLJITRef,
+};
+use llvm_sys::orc2::{
+    LLVMOrcCreateDynamicLibrarySearchGeneratorForProcess, LLVMOrcDefinitionGeneratorRef,
+    LLVMOrcExecutorAddress, LLVMOrcJITDylibAddGenerator,
+};
+use llvm_sys::prelude::LLVMMemoryBufferRef;
+
+use crate::LLVMString;
+
+/// An object file that was emitted into memory by [`ModuleLlvm::emit_object_buffer`].
+///
+/// [`ModuleLlvm::emit_object_buffer`]: crate::ModuleLlvm::emit_object_buffer
+pub struct ObjectBuffer {
+    raw: LLVMMemoryBufferRef,
+}
+
+// the buffer is never mutated after creation and LLVM does not attach it to any context
+unsafe impl Send for ObjectBuffer {}
+unsafe impl Sync for ObjectBuffer {}
+
+impl ObjectBuffer {
+    /// # Safety
+    /// `raw` must be a valid memory buffer that is not owned by anything else
+    pub(crate) unsafe fn from_raw(raw: LLVMMemoryBufferRef) -> ObjectBuffer {
+        ObjectBuffer { raw }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            let start = llvm_sys::core::LLVMGetBufferStart(self.raw);
+            let len = llvm_sys::core::LLVMGetBufferSize(self.raw);
+            slice::from_raw_parts(start as *const u8, len)
+        }
+    }
+
+    fn into_raw(self) -> LLVMMemoryBufferRef {
+        let raw = self.raw;
+        std::mem::forget(self);
+        raw
+    }
+}
+
+impl Drop for ObjectBuffer {
+    fn drop(&mut self) {
+        unsafe { llvm_sys::core::LLVMDisposeMemoryBuffer(self.raw) }
+    }
+}
+
+/// An in-process JIT (LLVMs ORC `LLJIT`) that links object files emitted by
+/// OpenVAF directly into the memory of the current process.
+///
+/// Undefined symbols (for example `libm` functions like `exp`) are resolved
+/// against the symbols of the host process. All code and data linked into
+/// the JIT is freed once the `OrcJit` is dropped.
+pub struct OrcJit {
+    raw: LLVMOrcLLJITRef,
+}
+
+// LLJIT is internally synchronized
+unsafe impl Send for OrcJit {}
+unsafe impl Sync for OrcJit {}
+
+impl OrcJit {
+    /// Creates a new JIT for the host machine.
+    ///
+    /// # Safety
+    ///
+    /// The native target and asm printer of LLVM must be initialized before calling this function.
+    pub unsafe fn new() -> Result<OrcJit, LLVMString> {
+        let mut raw = ptr::null_mut();
+        check_error(LLVMOrcCreateLLJIT(&mut raw, LLVMOrcCreateLLJITBuilder()))?;
+        let jit = OrcJit { raw };
+
+        let mut generator: LLVMOrcDefinitionGeneratorRef = ptr::null_mut();
+        check_error(LLVMOrcCreateDynamicLibrarySearchGeneratorForProcess(
+            &mut generator,
+            LLVMOrcLLJITGetGlobalPrefix(jit.raw),
+            None,
+            ptr::null_mut(),
+        ))?;
+        LLVMOrcJITDylibAddGenerator(LLVMOrcLLJITGetMainJITDylib(jit.raw), generator);
+
+        Ok(jit)
+    }
+
+    /// Adds an object file to the JIT. The object is only linked once
+    /// one of its symbols is looked up.
+    pub fn add_object(&self, obj: ObjectBuffer) -> Result<(), LLVMString> {
+        unsafe {
+            let dylib = LLVMOrcLLJITGetMainJITDylib(self.raw);
+            check_error(LLVMOrcLLJITAddObjectFile(self.raw, dylib, obj.into_raw()))
+        }
+    }
+
+    /// Returns the address of the (unmangled) symbol `name`.
+    /// All object files that are required to resolve `name` are linked by this function.
+    pub fn lookup(&self, name: &str) -> Result<*mut (), LLVMString> {
+        let name = CString::new(name).unwrap();
+        let mut addr: LLVMOrcExecutorAddress = 0;
+        unsafe { check_error(LLVMOrcLLJITLookup(self.raw, &mut addr, name.as_ptr()))? };
+        Ok(addr as usize as *mut ())
+    }
+}
+
+impl Drop for OrcJit {
+    fn drop(&mut self) {
+        let err = unsafe { LLVMOrcDisposeLLJIT(self.raw) };
+        if let Err(err) = unsafe { check_error(err) } {
+            log::error!("failed to tear down JIT: {err}")
+        }
+    }
+}
+
+unsafe fn check_error(err: LLVMErrorRef) -> Result<(), LLVMString> {
+    if err.is_null() {
+        return Ok(());
+    }
+    let msg: *mut c_char = LLVMGetErrorMessage(err);
+    let res = LLVMString::create_from_c_str(CStr::from_ptr(msg));
+    LLVMDisposeErrorMessage(msg);
+    Err(res)
+}
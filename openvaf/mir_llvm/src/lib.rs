@@ -87,8 +87,10 @@ use target::spec::Target;
 
 mod builder;
 mod context;
+mod debug_info;
 mod declarations;
 mod intrinsics;
+mod jit;
 mod types;
 
 mod callbacks;
@@ -98,6 +100,8 @@ mod tests;
 pub use builder::{Builder, BuilderVal, MemLoc};
 pub use callbacks::{BuiltCallbackFun, CallbackFun, InlineCallbackBuilder};
 pub use context::CodegenCx;
+pub use debug_info::{DebugInfo, DebugType, FunctionDebugInfo};
+pub use jit::{ObjectBuffer, OrcJit};
 pub struct LLVMBackend<'t> {
     target: &'t Target,
     target_cpu: String,
@@ -517,6 +521,30 @@ impl ModuleLlvm {
 
         Ok(())
     }
+
+    /// Emits this module as an object file into memory instead of a file.
+    /// The result can be loaded with [`OrcJit`] without invoking the system linker.
+    pub fn emit_object_buffer(&self) -> Result<ObjectBuffer, LLVMString> {
+        let mut err_string = MaybeUninit::uninit();
+        let mut buf = ptr::null_mut();
+        let return_code = unsafe {
+            llvm_sys::target_machine::LLVMTargetMachineEmitToMemoryBuffer(
+                self.tm,
+                NonNull::from(self.llmod()).as_ptr(),
+                llvm_sys::target_machine::LLVMCodeGenFileType::LLVMObjectFile,
+                err_string.as_mut_ptr(),
+                &mut buf,
+            )
+        };
+
+        if return_code == 1 {
+            unsafe {
+                return Err(LLVMString::new(err_string.assume_init()));
+            }
+        }
+
+        Ok(unsafe { ObjectBuffer::from_raw(buf) })
+    }
 }
 
 impl Drop for ModuleLlvm {
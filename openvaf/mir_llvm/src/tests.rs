@@ -1 +1,61 @@
+use std::ptr::NonNull;
 
+use lasso::Rodeo;
+use llvm_sys::target::LLVM_InitializeNativeTarget;
+use llvm_sys::target_machine::LLVMCodeGenOptLevel;
+use mir::builder::InstBuilder;
+use mir::cursor::{Cursor, FuncCursor};
+use mir::{Function, SourceLoc, Tag};
+use target::spec::Target;
+
+use crate::{Builder, BuilderVal, DebugInfo, DebugType, LLVMBackend};
+
+#[test]
+fn debug_info() {
+    // x = exp(v0) at line 3 (zero based: 2) of test.va
+    let mut func = Function::default();
+    let param = func.dfg.make_param(0u32.into());
+    let entry = func.layout.make_block();
+    func.layout.append_block(entry);
+    let loc = SourceLoc::new(1);
+    let mut cursor = FuncCursor::new(&mut func).with_srcloc(loc).at_bottom(entry);
+    let x = cursor.ins().exp(param);
+    func.dfg.set_tag(x, Some(Tag::from(0u32)));
+
+    assert_eq!(unsafe { LLVM_InitializeNativeTarget() }, 0);
+    let target = Target::host_target().unwrap();
+    let back = LLVMBackend::new(&[], &target, "native".to_owned(), &[]);
+    let literals = Rodeo::new();
+    let llmod = unsafe { back.new_module("debug_info", LLVMCodeGenOptLevel::LLVMCodeGenLevelNone) }
+        .unwrap();
+    let cx = unsafe { back.new_ctx(&literals, &llmod) };
+    let fun_ty = cx.ty_func(&[cx.ty_double()], cx.ty_void());
+    let llfunc = cx.declare_int_c_fn("test", fun_ty);
+
+    let debug_info = DebugInfo::new(&cx, "/models/test.va", false);
+    let mut func_info = debug_info.function(llfunc, "test", "/models/test.va", 0);
+    func_info.add_location(loc, "/models/test.va", 2, 4);
+    func_info.add_variable(Tag::from(0u32), "x", "/models/test.va", 1, DebugType::Real);
+
+    let mut builder = Builder::new(&cx, &func, llfunc, None, true);
+    builder.set_debug_info(func_info);
+    let llparam = unsafe { &*llvm_sys::core::LLVMGetParam(NonNull::from(llfunc).as_ptr(), 0) };
+    builder.params = vec![BuilderVal::Eager(llparam)].into();
+    unsafe {
+        builder.build_consts();
+        builder.build_func();
+        builder.select_bb(entry);
+        builder.ret_void();
+    }
+    drop(builder);
+    debug_info.finalize();
+    assert!(llmod.verify_and_print());
+
+    let ir = llmod.to_str().to_string();
+    assert!(ir.contains("!DICompileUnit(language: DW_LANG_C, file: "), "{ir}");
+    assert!(ir.contains("!DIFile(filename: \"test.va\", directory: \"/models\")"), "{ir}");
+    assert!(ir.contains("!DISubprogram(name: \"test\""), "{ir}");
+    assert!(ir.contains("!DILocation(line: 3, column: 5"), "{ir}");
+    assert!(ir.contains("!DILocalVariable(name: \"x\""), "{ir}");
+    assert!(ir.contains("call void @llvm.dbg.value(metadata double %"), "{ir}");
+}
@@ -1,28 +1,38 @@
 use std::mem::size_of_val;
 
-use mir::{Const, Function, Opcode, Value, FALSE, F_ONE, F_ZERO, ONE, TRUE, ZERO};
+use mir::{Const, Function, Opcode, Value};
 
 pub fn eval_binary(func: &mut Function, op: Opcode, lhs: Const, rhs: Const) -> Value {
+    func.dfg.values.make_const(fold_binary(op, lhs, rhs))
+}
+
+pub fn eval_unary(func: &mut Function, op: Opcode, val: Const) -> Option<Value> {
+    let val = fold_unary(op, val)?;
+    Some(func.dfg.values.make_const(val))
+}
+
+/// Evaluates a binary operation on constant operands.
+pub fn fold_binary(op: Opcode, lhs: Const, rhs: Const) -> Const {
     match (lhs, rhs) {
         (Const::Int(lhs), Const::Int(rhs)) => match op {
-            Opcode::Iadd => func.dfg.iconst(lhs + rhs),
-            Opcode::Isub => func.dfg.iconst(lhs - rhs),
-            Opcode::Imul => func.dfg.iconst(lhs * rhs),
-            Opcode::Idiv => func.dfg.iconst(lhs / rhs),
-            Opcode::Irem => func.dfg.iconst(lhs % rhs),
+            Opcode::Iadd => Const::Int(lhs + rhs),
+            Opcode::Isub => Const::Int(lhs - rhs),
+            Opcode::Imul => Const::Int(lhs * rhs),
+            Opcode::Idiv => Const::Int(lhs / rhs),
+            Opcode::Irem => Const::Int(lhs % rhs),
 
-            Opcode::Ishl => func.dfg.iconst(lhs << rhs),
-            Opcode::Ishr => func.dfg.iconst(lhs >> rhs),
-            Opcode::Ixor => func.dfg.iconst(lhs ^ rhs),
-            Opcode::Iand => func.dfg.iconst(lhs & rhs),
-            Opcode::Ior => func.dfg.iconst(lhs | rhs),
+            Opcode::Ishl => Const::Int(lhs << rhs),
+            Opcode::Ishr => Const::Int(lhs >> rhs),
+            Opcode::Ixor => Const::Int(lhs ^ rhs),
+            Opcode::Iand => Const::Int(lhs & rhs),
+            Opcode::Ior => Const::Int(lhs | rhs),
 
-            Opcode::Ilt => (lhs < rhs).into(),
-            Opcode::Igt => (lhs > rhs).into(),
-            Opcode::Ige => (lhs >= rhs).into(),
-            Opcode::Ile => (lhs <= rhs).into(),
-            Opcode::Ieq => (lhs == rhs).into(),
-            Opcode::Ine => (lhs != rhs).into(),
+            Opcode::Ilt => Const::Bool(lhs < rhs),
+            Opcode::Igt => Const::Bool(lhs > rhs),
+            Opcode::Ige => Const::Bool(lhs >= rhs),
+            Opcode::Ile => Const::Bool(lhs <= rhs),
+            Opcode::Ieq => Const::Bool(lhs == rhs),
+            Opcode::Ine => Const::Bool(lhs != rhs),
 
             _ => unreachable!("invalid int operation {}", op),
         },
@@ -31,34 +41,36 @@ pub fn eval_binary(func: &mut Function, op: Opcode, lhs: Const, rhs: Const) -> V
             let lhs: f64 = lhs.into();
             let rhs: f64 = rhs.into();
             match op {
-                Opcode::Fadd => func.dfg.f64const(lhs + rhs),
-                Opcode::Fsub => func.dfg.f64const(lhs - rhs),
-                Opcode::Fmul => func.dfg.f64const(lhs * rhs),
-                Opcode::Fdiv => func.dfg.f64const(lhs / rhs),
-                Opcode::Frem => func.dfg.f64const(lhs % rhs),
+                Opcode::Fadd => Const::Float((lhs + rhs).into()),
+                Opcode::Fsub => Const::Float((lhs - rhs).into()),
+                Opcode::Fmul => Const::Float((lhs * rhs).into()),
+                Opcode::Fdiv => Const::Float((lhs / rhs).into()),
+                Opcode::Frem => Const::Float((lhs % rhs).into()),
 
-                Opcode::Flt => (lhs < rhs).into(),
-                Opcode::Fgt => (lhs > rhs).into(),
-                Opcode::Fge => (lhs >= rhs).into(),
-                Opcode::Fle => (lhs <= rhs).into(),
-                Opcode::Feq => (lhs == rhs).into(),
-                Opcode::Fne => (lhs != rhs).into(),
+                Opcode::Flt => Const::Bool(lhs < rhs),
+                Opcode::Fgt => Const::Bool(lhs > rhs),
+                Opcode::Fge => Const::Bool(lhs >= rhs),
+                Opcode::Fle => Const::Bool(lhs <= rhs),
+                Opcode::Feq => Const::Bool(lhs == rhs),
+                Opcode::Fne => Const::Bool(lhs != rhs),
 
-                Opcode::Hypot => func.dfg.f64const(lhs.hypot(rhs)),
-                Opcode::Atan2 => func.dfg.f64const(lhs.atan2(rhs)),
-                Opcode::Pow => func.dfg.f64const(lhs.powf(rhs)),
+                Opcode::Hypot => Const::Float(lhs.hypot(rhs).into()),
+                Opcode::Atan2 => Const::Float(lhs.atan2(rhs).into()),
+                Opcode::Pow => Const::Float(lhs.powf(rhs).into()),
                 _ => unreachable!("invalid real operation  {}", op,),
             }
         }
         _ => match op {
-            Opcode::Seq | Opcode::Beq => (lhs == rhs).into(),
-            Opcode::Sne | Opcode::Bne => (lhs != rhs).into(),
+            Opcode::Seq | Opcode::Beq => Const::Bool(lhs == rhs),
+            Opcode::Sne | Opcode::Bne => Const::Bool(lhs != rhs),
             _ => unreachable!("invalid operation {} {:?} {:?}", op, lhs, rhs),
         },
     }
 }
 
-pub fn eval_unary(func: &mut Function, op: Opcode, val: Const) -> Option<Value> {
+/// Evaluates a unary operation on a constant operand. Returns `None` for operations that must
+/// not be folded (optbarriers).
+pub fn fold_unary(op: Opcode, val: Const) -> Option<Const> {
     if op == Opcode::OptBarrier {
         return None;
     }
@@ -66,52 +78,52 @@ pub fn eval_unary(func: &mut Function, op: Opcode, val: Const) -> Option<Value>
         mir::Const::Float(val) => {
             let val: f64 = val.into();
             match op {
-                Opcode::Sqrt => func.dfg.f64const(val.sqrt()),
-                Opcode::Exp => func.dfg.f64const(val.exp()),
-                Opcode::Ln => func.dfg.f64const(val.ln()),
-                Opcode::Log => func.dfg.f64const(val.log10()),
-                Opcode::Floor => func.dfg.f64const(val.floor()),
-                Opcode::Ceil => func.dfg.f64const(val.ceil()),
-                Opcode::Sin => func.dfg.f64const(val.sin()),
-                Opcode::Cos => func.dfg.f64const(val.cos()),
-                Opcode::Tan => func.dfg.f64const(val.tan()),
-                Opcode::Asin => func.dfg.f64const(val.asin()),
-                Opcode::Acos => func.dfg.f64const(val.acos()),
-                Opcode::Atan => func.dfg.f64const(val.atan()),
-                Opcode::Sinh => func.dfg.f64const(val.sinh()),
-                Opcode::Cosh => func.dfg.f64const(val.cosh()),
-                Opcode::Tanh => func.dfg.f64const(val.tanh()),
-                Opcode::Asinh => func.dfg.f64const(val.asinh()),
-                Opcode::Acosh => func.dfg.f64const(val.acosh()),
-                Opcode::Atanh => func.dfg.f64const(val.atanh()),
-                Opcode::FIcast => func.dfg.iconst(val.round() as i32),
-                Opcode::FBcast => (val.abs() != 0.0).into(),
-                Opcode::Fneg => func.dfg.f64const(-val),
+                Opcode::Sqrt => Const::Float(val.sqrt().into()),
+                Opcode::Exp => Const::Float(val.exp().into()),
+                Opcode::Ln => Const::Float(val.ln().into()),
+                Opcode::Log => Const::Float(val.log10().into()),
+                Opcode::Floor => Const::Float(val.floor().into()),
+                Opcode::Ceil => Const::Float(val.ceil().into()),
+                Opcode::Sin => Const::Float(val.sin().into()),
+                Opcode::Cos => Const::Float(val.cos().into()),
+                Opcode::Tan => Const::Float(val.tan().into()),
+                Opcode::Asin => Const::Float(val.asin().into()),
+                Opcode::Acos => Const::Float(val.acos().into()),
+                Opcode::Atan => Const::Float(val.atan().into()),
+                Opcode::Sinh => Const::Float(val.sinh().into()),
+                Opcode::Cosh => Const::Float(val.cosh().into()),
+                Opcode::Tanh => Const::Float(val.tanh().into()),
+                Opcode::Asinh => Const::Float(val.asinh().into()),
+                Opcode::Acosh => Const::Float(val.acosh().into()),
+                Opcode::Atanh => Const::Float(val.atanh().into()),
+                Opcode::FIcast => Const::Int(val.round() as i32),
+                Opcode::FBcast => Const::Bool(val.abs() != 0.0),
+                Opcode::Fneg => Const::Float((-val).into()),
                 _ => unreachable!("invalid real operation {}", op),
             }
         }
         mir::Const::Int(val) => match op {
-            Opcode::Inot => func.dfg.iconst(!val),
-            Opcode::Ineg => func.dfg.iconst(-val),
-            Opcode::IFcast => func.dfg.f64const(val as f64),
-            Opcode::IBcast => (val != 0).into(),
+            Opcode::Inot => Const::Int(!val),
+            Opcode::Ineg => Const::Int(-val),
+            Opcode::IFcast => Const::Float((val as f64).into()),
+            Opcode::IBcast => Const::Bool(val != 0),
             Opcode::Clog2 => {
                 let val = 8 * size_of_val(&val) as i32 - val.leading_zeros() as i32;
-                func.dfg.iconst(val)
+                Const::Int(val)
             }
             _ => unreachable!("invalid int operation {}", op),
         },
         mir::Const::Str(_) => unreachable!(),
         mir::Const::Bool(true) => match op {
-            Opcode::Bnot => FALSE,
-            Opcode::BIcast => ONE,
-            Opcode::BFcast => F_ONE,
+            Opcode::Bnot => Const::Bool(false),
+            Opcode::BIcast => Const::Int(1),
+            Opcode::BFcast => Const::Float(1f64.into()),
             _ => unreachable!(),
         },
         mir::Const::Bool(false) => match op {
-            Opcode::Bnot => TRUE,
-            Opcode::BIcast => ZERO,
-            Opcode::BFcast => F_ZERO,
+            Opcode::Bnot => Const::Bool(true),
+            Opcode::BIcast => Const::Int(0),
+            Opcode::BFcast => Const::Float(0f64.into()),
             _ => unreachable!(),
         },
     };
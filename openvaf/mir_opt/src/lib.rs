@@ -4,14 +4,22 @@ mod dead_code;
 mod dead_code_aggressive;
 mod global_value_numbering;
 mod inst_combine;
+mod licm;
+mod loop_unroll;
+mod loops;
 mod simplify;
 mod simplify_cfg;
 mod split_tainted;
+mod transcendental;
 
 pub use const_prop::sparse_conditional_constant_propagation;
 pub use dead_code::dead_code_elimination;
 pub use dead_code_aggressive::aggressive_dead_code_elimination;
 pub use global_value_numbering::{ClassId, GVN};
 pub use inst_combine::inst_combine;
+pub use licm::loop_invariant_code_motion;
+pub use loop_unroll::unroll_loops;
+pub use loops::{Loop, LoopInfo};
 pub use simplify_cfg::{simplify_cfg, simplify_cfg_init, simplify_cfg_no_phi_merge};
 pub use split_tainted::{propagate_direct_taint, propagate_taint};
+pub use transcendental::simplify_transcendental;
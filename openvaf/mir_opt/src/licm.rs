@@ -0,0 +1,82 @@
+use mir::{
+    Const, ControlFlowGraph, DominatorTree, Function, Inst, InstructionData, Opcode, ValueDef,
+};
+
+use crate::loops::{Loop, LoopInfo};
+
+#[cfg(test)]
+mod tests;
+
+/// Moves instructions whose operands are not changed within a loop into the preheader of the
+/// loop. Loops are processed from the inside out so that invariant code can be hoisted through
+/// multiple nested loops.
+///
+/// Only pure instructions that can not fail are moved. Calls are never moved as callbacks may
+/// have side effects. Returns whether any instruction was moved. The `cfg` is kept up to date
+/// while the `dom_tree` must be recomputed by the caller if any preheader was inserted.
+pub fn loop_invariant_code_motion(
+    func: &mut Function,
+    cfg: &mut ControlFlowGraph,
+    dom_tree: &DominatorTree,
+) -> bool {
+    let mut loops = LoopInfo::default();
+    loops.compute(func, cfg, dom_tree);
+
+    let mut changed = false;
+    let mut hoisted = Vec::new();
+    for idx in 0..loops.len() {
+        let blocks: Vec<_> = func.layout.blocks().filter(|&bb| loops[idx].contains(bb)).collect();
+        loop {
+            for &bb in &blocks {
+                for inst in func.layout.block_insts(bb) {
+                    if is_invariant(func, &loops[idx], inst) {
+                        hoisted.push(inst)
+                    }
+                }
+            }
+
+            if hoisted.is_empty() {
+                break;
+            }
+
+            let preheader = if let Some(preheader) = loops.ensure_preheader(idx, func, cfg) {
+                preheader
+            } else {
+                hoisted.clear();
+                break;
+            };
+            let term = func.layout.last_inst(preheader).unwrap();
+            for inst in hoisted.drain(..) {
+                func.layout.remove_inst(inst);
+                func.layout.prepend_inst(inst, term);
+            }
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+fn is_invariant(func: &Function, lp: &Loop, inst: Inst) -> bool {
+    let args = match func.dfg.insts[inst] {
+        InstructionData::Unary { opcode: Opcode::OptBarrier, .. } => return false,
+        InstructionData::Unary { arg, .. } => [arg, arg],
+        // division by zero must not be hoisted out of a loop that may not be executed
+        InstructionData::Binary { opcode: Opcode::Idiv | Opcode::Irem, args } => {
+            if !matches!(func.dfg.value_def(args[1]).as_const(), Some(Const::Int(val)) if val != 0)
+            {
+                return false;
+            }
+            args
+        }
+        InstructionData::Binary { args, .. } => args,
+        _ => return false,
+    };
+
+    args.iter().all(|&arg| match func.dfg.value_def(arg) {
+        ValueDef::Result(def, _) => {
+            !matches!(func.layout.inst_block(def), Some(bb) if lp.contains(bb))
+        }
+        _ => true,
+    })
+}
@@ -0,0 +1,69 @@
+use expect_test::{expect, Expect};
+use mir::{ControlFlowGraph, DominatorTree};
+use mir_reader::parse_function;
+
+use crate::loop_invariant_code_motion;
+
+fn check(src: &str, expect: Expect) {
+    let (mut func, _) = parse_function(src).unwrap();
+    let mut cfg = ControlFlowGraph::new();
+    cfg.compute(&func);
+    let mut dom_tree = DominatorTree::default();
+    dom_tree.compute(&func, &cfg, true, false, false);
+    loop_invariant_code_motion(&mut func, &mut cfg, &dom_tree);
+    expect.assert_eq(&func.to_debug_string());
+}
+
+#[test]
+fn hoist_invariant() {
+    let src = r#"
+        function %bar(v10, v11) {
+            v3 = fconst 0.0
+            v4 = iconst 0
+            v5 = iconst 1
+        block0:
+            jmp block1
+        block1:
+            v12 = phi [v4, block0], [v14, block2]
+            v13 = phi [v3, block0], [v16, block2]
+            v17 = ilt v12, v11
+            br v17, block2[loop], block3
+        block2:
+            v14 = iadd v12, v5
+            v15 = exp v10
+            v18 = fmul v15, v15
+            v16 = fadd v13, v18
+            jmp block1
+        block3:
+            v19 = optbarrier v13
+        }
+    "#;
+    check(
+        src,
+        expect![[r#"
+            function %bar(v10, v11) {
+                v3 = fconst 0.0
+                v4 = iconst 0
+                v5 = iconst 1
+            block0:
+                v15 = exp v10
+                v18 = fmul v15, v15
+                jmp block1
+
+            block1:
+                v12 = phi [v4, block0], [v14, block2]
+                v13 = phi [v3, block0], [v16, block2]
+                v17 = ilt v12, v11
+                br v17, block2[loop], block3
+
+            block2:
+                v14 = iadd v12, v5
+                v16 = fadd v13, v18
+                jmp block1
+
+            block3:
+                v19 = optbarrier v13
+            }
+        "#]],
+    );
+}
@@ -0,0 +1,358 @@
+use ahash::AHashMap;
+use mir::builder::InstBuilderBase;
+use mir::cursor::{Cursor, FuncCursor};
+use mir::{
+    Block, Const, ControlFlowGraph, DominatorTree, FuncRef, Function, Inst, InstructionData,
+    Opcode, PhiMap, PhiNode, SourceLoc, Value, ValueDef, ValueList,
+};
+use stdx::iter::zip;
+
+use crate::const_eval::{fold_binary, fold_unary};
+use crate::loops::{Loop, LoopInfo};
+
+#[cfg(test)]
+mod tests;
+
+/// Loops with more iterations are never unrolled.
+const MAX_TRIP_COUNT: usize = 64;
+/// Maximum number of instructions created by unrolling a single loop.
+const MAX_UNROLLED_INSTS: usize = 4096;
+
+/// Fully unrolls all loops whose trip count is known at compile time. Loops are unrolled from
+/// the inside out: an outer loop is unrolled once all loops nested inside it were removed.
+///
+/// Calls to callbacks for which `can_duplicate` returns `false` are never duplicated (loops
+/// containing such calls are kept). Returns whether any loop was unrolled. The `cfg` is
+/// kept up to date while the `dom_tree` must be recomputed by the caller.
+pub fn unroll_loops(
+    func: &mut Function,
+    cfg: &mut ControlFlowGraph,
+    dom_tree: &mut DominatorTree,
+    can_duplicate: &dyn Fn(FuncRef) -> bool,
+) -> bool {
+    let mut loops = LoopInfo::default();
+    let mut changed = false;
+    loop {
+        dom_tree.compute(func, cfg, true, false, false);
+        loops.compute(func, cfg, dom_tree);
+        let unroll = loops
+            .iter()
+            .filter(|lp| loops.is_innermost(lp))
+            .find_map(|lp| UnrollLoop::new(func, cfg, dom_tree, lp, can_duplicate));
+        if let Some(unroll) = unroll {
+            unroll.run(func);
+            cfg.compute(func);
+            changed = true;
+        } else {
+            return changed;
+        }
+    }
+}
+
+struct HeaderPhi {
+    inst: Inst,
+    val: Value,
+    init: Value,
+    next: Value,
+}
+
+struct UnrollLoop {
+    header: Block,
+    entry: Block,
+    /// successor of the header inside the loop
+    body: Block,
+    /// successor of the header outside the loop
+    exit: Block,
+    /// blocks of the loop in reverse postorder (starting with the header)
+    blocks: Vec<Block>,
+    phis: Vec<HeaderPhi>,
+    trip_count: usize,
+}
+
+impl UnrollLoop {
+    /// Checks whether `lp` can be unrolled. Only loops that are left from the header and
+    /// that have a single latch are considered.
+    fn new(
+        func: &Function,
+        cfg: &ControlFlowGraph,
+        dom_tree: &DominatorTree,
+        lp: &Loop,
+        can_duplicate: &dyn Fn(FuncRef) -> bool,
+    ) -> Option<UnrollLoop> {
+        let header = lp.header;
+        let latch = if let [latch] = *lp.latches { latch } else { return None };
+        let entry = lp.entry(cfg)?;
+
+        let term = func.layout.last_inst(header)?;
+        let (cond, then_dst, else_dst) = func.dfg.as_branch(term)?;
+        let (body, exit, continue_if) = match (lp.contains(then_dst), lp.contains(else_dst)) {
+            (true, false) => (then_dst, else_dst, true),
+            (false, true) => (else_dst, then_dst, false),
+            _ => return None,
+        };
+        if lp.exits(cfg) != [(header, exit)] {
+            return None;
+        }
+
+        let mut phis = Vec::new();
+        for inst in func.layout.block_insts(header) {
+            if let InstructionData::PhiNode(phi) = &func.dfg.insts[inst] {
+                phis.push(HeaderPhi {
+                    inst,
+                    val: func.dfg.first_result(inst),
+                    init: func.dfg.phi_edge_val(phi, entry)?,
+                    next: func.dfg.phi_edge_val(phi, latch)?,
+                });
+            } else {
+                break;
+            }
+        }
+
+        let blocks: Vec<_> =
+            dom_tree.cfg_postorder().iter().rev().copied().filter(|bb| lp.contains(*bb)).collect();
+        let mut size = 0;
+        for &bb in &blocks {
+            for inst in func.layout.block_insts(bb) {
+                if let Some(func_ref) = func.dfg.func_ref(inst) {
+                    if !can_duplicate(func_ref) {
+                        return None;
+                    }
+                }
+                size += 1;
+            }
+        }
+
+        let trip_count =
+            TripCount { func, lp, vals: AHashMap::new() }.solve(&phis, cond, continue_if)?;
+        if trip_count * size > MAX_UNROLLED_INSTS {
+            return None;
+        }
+
+        Some(UnrollLoop { header, entry, body, exit, blocks, phis, trip_count })
+    }
+
+    fn run(self, func: &mut Function) {
+        // create the blocks of all iterations upfront so that jumps to the next iteration can
+        // be resolved, the original header is reused to leave the loop after the last iteration
+        let iterations: Vec<AHashMap<Block, Block>> = (0..self.trip_count)
+            .map(|_| {
+                self.blocks
+                    .iter()
+                    .map(|&bb| {
+                        let new_bb = func.layout.make_block();
+                        func.layout.insert_block(new_bb, self.header);
+                        (bb, new_bb)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut vals: AHashMap<Value, Value> =
+            self.phis.iter().map(|phi| (phi.val, phi.init)).collect();
+        for (i, block_map) in iterations.iter().enumerate() {
+            let next_header = iterations.get(i + 1).map_or(self.header, |it| it[&self.header]);
+            let map_dst = |bb: Block| if bb == self.header { next_header } else { block_map[&bb] };
+            for &bb in &self.blocks {
+                let mut cursor = func.layout.block_inst_cursor(bb);
+                while let Some(inst) = cursor.next(&func.layout) {
+                    let data = match func.dfg.insts[inst].clone() {
+                        // header phis are resolved to the values of the previous iteration
+                        InstructionData::PhiNode(_) if bb == self.header => continue,
+                        InstructionData::PhiNode(phi) => {
+                            let edges: Vec<_> = func
+                                .dfg
+                                .phi_edges(&phi)
+                                .map(|(pred, val)| (block_map[&pred], map_val(&vals, val)))
+                                .collect();
+                            let mut args = ValueList::new();
+                            let mut blocks = PhiMap::new();
+                            for (pred, val) in edges {
+                                let i = args.push(val, &mut func.dfg.insts.value_lists) as u32;
+                                blocks.insert(pred, i, &mut func.dfg.phi_forest, &());
+                            }
+                            PhiNode { args, blocks }.into()
+                        }
+                        // the header always enters the body while the loop is unrolled
+                        InstructionData::Branch { .. } if bb == self.header => {
+                            InstructionData::Jump { destination: map_dst(self.body) }
+                        }
+                        InstructionData::Branch { cond, then_dst, else_dst, loop_entry } => {
+                            InstructionData::Branch {
+                                cond: map_val(&vals, cond),
+                                then_dst: map_dst(then_dst),
+                                else_dst: map_dst(else_dst),
+                                loop_entry,
+                            }
+                        }
+                        InstructionData::Jump { destination } => {
+                            InstructionData::Jump { destination: map_dst(destination) }
+                        }
+                        InstructionData::Unary { opcode, arg } => {
+                            InstructionData::Unary { opcode, arg: map_val(&vals, arg) }
+                        }
+                        InstructionData::Binary { opcode, args } => InstructionData::Binary {
+                            opcode,
+                            args: args.map(|arg| map_val(&vals, arg)),
+                        },
+                        InstructionData::Call { func_ref, args } => {
+                            let args: Vec<_> = args
+                                .as_slice(&func.dfg.insts.value_lists)
+                                .iter()
+                                .map(|&arg| map_val(&vals, arg))
+                                .collect();
+                            let args =
+                                ValueList::from_slice(&args, &mut func.dfg.insts.value_lists);
+                            InstructionData::Call { func_ref, args }
+                        }
+                        InstructionData::Exit => unreachable!("loops can not contain exits"),
+                    };
+
+                    let new_inst =
+                        FuncCursor::new(func).at_bottom(block_map[&bb]).ins().build(data).0;
+                    for (&old, &new) in
+                        zip(func.dfg.inst_results(inst), func.dfg.inst_results(new_inst))
+                    {
+                        vals.insert(old, new);
+                    }
+                    copy_tags_and_srcloc(func, inst, new_inst);
+                }
+            }
+
+            let next: Vec<_> = self.phis.iter().map(|phi| map_val(&vals, phi.next)).collect();
+            for (phi, next) in zip(&self.phis, next) {
+                vals.insert(phi.val, next);
+            }
+        }
+
+        // the original header is executed once more to leave the loop
+        for phi in &self.phis {
+            func.dfg.replace_uses(phi.val, vals[&phi.val]);
+            func.dfg.zap_inst(phi.inst);
+            func.layout.remove_inst(phi.inst);
+        }
+        let term = func.layout.last_inst(self.header).unwrap();
+        func.dfg.update_inst(term, InstructionData::Jump { destination: self.exit });
+
+        if let Some(block_map) = iterations.first() {
+            let term = func.layout.last_inst(self.entry).unwrap();
+            let first_header = block_map[&self.header];
+            match &mut func.dfg.insts[term] {
+                InstructionData::Jump { destination } => *destination = first_header,
+                InstructionData::Branch { then_dst, else_dst, .. } => {
+                    for dst in [then_dst, else_dst] {
+                        if *dst == self.header {
+                            *dst = first_header
+                        }
+                    }
+                }
+                _ => unreachable!("last instruction must be terminator"),
+            }
+        }
+
+        for &bb in &self.blocks[1..] {
+            for inst in func.layout.block_insts(bb) {
+                func.dfg.zap_inst(inst)
+            }
+            func.layout.remove_and_clear_block(bb);
+        }
+    }
+}
+
+fn map_val(vals: &AHashMap<Value, Value>, val: Value) -> Value {
+    vals.get(&val).copied().unwrap_or(val)
+}
+
+fn copy_tags_and_srcloc(func: &mut Function, old: Inst, new: Inst) {
+    let results = func.dfg.inst_results(old).len();
+    for i in 0..results {
+        let tag = func.dfg.tag(func.dfg.inst_results(old)[i]);
+        func.dfg.set_tag(func.dfg.inst_results(new)[i], tag);
+    }
+
+    let srcloc = func.srclocs.get(old).copied().unwrap_or_default();
+    if !srcloc.is_default() {
+        if func.srclocs.len() <= new.into() {
+            func.srclocs.resize(new.into(), SourceLoc::default());
+            func.srclocs.push(srcloc);
+        } else {
+            func.srclocs[new] = srcloc;
+        }
+    }
+}
+
+/// Determines the trip count of a loop by evaluating the exit condition of the loop for each
+/// iteration. The exit condition may only depend on constants and header phis that are
+/// initialized with a constant and updated with a value that can be calculated from constants.
+struct TripCount<'a> {
+    func: &'a Function,
+    lp: &'a Loop,
+    vals: AHashMap<Value, Option<Const>>,
+}
+
+impl TripCount<'_> {
+    fn solve(mut self, phis: &[HeaderPhi], cond: Value, continue_if: bool) -> Option<usize> {
+        let mut state: Vec<_> =
+            phis.iter().map(|phi| self.func.dfg.value_def(phi.init).as_const()).collect();
+        for trip_count in 0..=MAX_TRIP_COUNT {
+            self.vals.clear();
+            self.vals.extend(zip(phis, &state).map(|(phi, val)| (phi.val, *val)));
+            match self.eval(cond)? {
+                Const::Bool(val) if val == continue_if => (),
+                Const::Bool(_) => return Some(trip_count),
+                _ => return None,
+            }
+            state = phis.iter().map(|phi| self.eval(phi.next)).collect();
+        }
+        None
+    }
+
+    fn eval(&mut self, val: Value) -> Option<Const> {
+        if let Some(res) = self.vals.get(&val) {
+            return *res;
+        }
+        let res = match self.func.dfg.value_def(val) {
+            ValueDef::Const(val) => Some(val),
+            ValueDef::Result(inst, _) => self.eval_inst(inst),
+            ValueDef::Param(_) | ValueDef::Invalid => None,
+        };
+        self.vals.insert(val, res);
+        res
+    }
+
+    fn eval_inst(&mut self, inst: Inst) -> Option<Const> {
+        // values calculated outside of the loop are only known if they are constants
+        let bb = self.func.layout.inst_block(inst)?;
+        if !self.lp.contains(bb) {
+            return None;
+        }
+        match self.func.dfg.insts[inst] {
+            InstructionData::Unary { opcode: Opcode::OptBarrier, arg } => self.eval(arg),
+            InstructionData::Unary { opcode, arg } => match (opcode, self.eval(arg)?) {
+                (Opcode::Ineg, Const::Int(val)) => val.checked_neg().map(Const::Int),
+                (opcode, val) => fold_unary(opcode, val),
+            },
+            InstructionData::Binary { opcode, args: [lhs, rhs] } => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                // integer arithmetic that overflows (or divides by zero) is not evaluated, the
+                // trip count of such a loop is unknown
+                let res = match (opcode, lhs, rhs) {
+                    (Opcode::Iadd, Const::Int(lhs), Const::Int(rhs)) => lhs.checked_add(rhs),
+                    (Opcode::Isub, Const::Int(lhs), Const::Int(rhs)) => lhs.checked_sub(rhs),
+                    (Opcode::Imul, Const::Int(lhs), Const::Int(rhs)) => lhs.checked_mul(rhs),
+                    (Opcode::Idiv, Const::Int(lhs), Const::Int(rhs)) => lhs.checked_div(rhs),
+                    (Opcode::Irem, Const::Int(lhs), Const::Int(rhs)) => lhs.checked_rem(rhs),
+                    (Opcode::Ishl, Const::Int(lhs), Const::Int(rhs)) => {
+                        u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_shl(rhs))
+                    }
+                    (Opcode::Ishr, Const::Int(lhs), Const::Int(rhs)) => {
+                        u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_shr(rhs))
+                    }
+                    _ => return Some(fold_binary(opcode, lhs, rhs)),
+                };
+                res.map(Const::Int)
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use expect_test::{expect, Expect};
+use mir::{ControlFlowGraph, DominatorTree};
+use mir_reader::parse_function;
+
+use crate::unroll_loops;
+
+fn check(src: &str, expect: Expect) {
+    let (mut func, _) = parse_function(src).unwrap();
+    let mut cfg = ControlFlowGraph::new();
+    cfg.compute(&func);
+    let mut dom_tree = DominatorTree::default();
+    unroll_loops(&mut func, &mut cfg, &mut dom_tree, &|_| true);
+    expect.assert_eq(&func.to_debug_string());
+}
+
+#[test]
+fn constant_trip_count() {
+    let src = r#"
+        function %bar(v10) {
+            v3 = fconst 0.0
+            v4 = iconst 0
+            v5 = iconst 1
+            v20 = iconst 3
+        block0:
+            jmp block1
+        block1:
+            v11 = phi [v4, block0], [v13, block2]
+            v12 = phi [v3, block0], [v15, block2]
+            v16 = ilt v11, v20
+            br v16, block2[loop], block3
+        block2:
+            v13 = iadd v11, v5
+            v14 = exp v10
+            v15 = fadd v12, v14
+            jmp block1
+        block3:
+            v17 = optbarrier v12
+        }
+    "#;
+    check(
+        src,
+        expect![[r#"
+            function %bar(v10) {
+                v3 = fconst 0.0
+                v4 = iconst 0
+                v5 = iconst 1
+                v20 = iconst 3
+            block0:
+                jmp block4
+
+            block4:
+                v21 = ilt v4, v20
+                jmp block5
+
+            block5:
+                v22 = iadd v4, v5
+                v23 = exp v10
+                v24 = fadd v3, v23
+                jmp block6
+
+            block6:
+                v25 = ilt v22, v20
+                jmp block7
+
+            block7:
+                v26 = iadd v22, v5
+                v27 = exp v10
+                v28 = fadd v24, v27
+                jmp block8
+
+            block8:
+                v29 = ilt v26, v20
+                jmp block9
+
+            block9:
+                v30 = iadd v26, v5
+                v31 = exp v10
+                v32 = fadd v28, v31
+                jmp block1
+
+            block1:
+                v16 = ilt v30, v20
+                jmp block3
+
+            block3:
+                v17 = optbarrier v32
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn unknown_trip_count() {
+    let src = r#"
+        function %bar(v10, v11) {
+            v4 = iconst 0
+            v5 = iconst 1
+        block0:
+            jmp block1
+        block1:
+            v12 = phi [v4, block0], [v13, block2]
+            v14 = ilt v12, v11
+            br v14, block2[loop], block3
+        block2:
+            v13 = iadd v12, v5
+            jmp block1
+        block3:
+            v15 = optbarrier v12
+        }
+    "#;
+    check(
+        src,
+        expect![[r#"
+            function %bar(v10, v11) {
+                v4 = iconst 0
+                v5 = iconst 1
+            block0:
+                jmp block1
+
+            block1:
+                v12 = phi [v4, block0], [v13, block2]
+                v14 = ilt v12, v11
+                br v14, block2[loop], block3
+
+            block2:
+                v13 = iadd v12, v5
+                jmp block1
+
+            block3:
+                v15 = optbarrier v12
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn overflowing_trip_count() {
+    // the counter overflows in the third iteration, wrapping around would end the loop
+    let src = r#"
+        function %bar(v10) {
+            v4 = iconst 0
+            v5 = iconst 4
+            v6 = iconst 2147483640
+        block0:
+            jmp block1
+        block1:
+            v12 = phi [v6, block0], [v13, block2]
+            v14 = igt v12, v4
+            br v14, block2[loop], block3
+        block2:
+            v13 = iadd v12, v5
+            jmp block1
+        block3:
+            v15 = optbarrier v12
+        }
+    "#;
+    check(
+        src,
+        expect![[r#"
+            function %bar(v10) {
+                v4 = iconst 0
+                v5 = iconst 4
+                v6 = iconst 2147483640
+            block0:
+                jmp block1
+
+            block1:
+                v12 = phi [v6, block0], [v13, block2]
+                v14 = igt v12, v4
+                br v14, block2[loop], block3
+
+            block2:
+                v13 = iadd v12, v5
+                jmp block1
+
+            block3:
+                v15 = optbarrier v12
+            }
+        "#]],
+    );
+}
@@ -0,0 +1,147 @@
+use std::ops::Index;
+
+use bitset::BitSet;
+use mir::builder::InstBuilder;
+use mir::cursor::{Cursor, FuncCursor};
+use mir::{Block, ControlFlowGraph, DominatorTree, Function, InstructionData};
+
+/// A natural loop: the `header` and all blocks that can reach one of the back edges to the
+/// `header` without passing through the `header`.
+#[derive(Debug, Clone)]
+pub struct Loop {
+    pub header: Block,
+    /// Blocks inside the loop that jump back to the header.
+    pub latches: Vec<Block>,
+    /// All blocks of the loop (including the header).
+    pub blocks: BitSet<Block>,
+}
+
+impl Loop {
+    pub fn contains(&self, block: Block) -> bool {
+        // blocks created after the loop was found (preheaders) are outside the domain
+        usize::from(block) < self.blocks.domain_size() && self.blocks.contains(block)
+    }
+
+    /// Returns the single block outside the loop that jumps to the header.
+    pub fn entry(&self, cfg: &ControlFlowGraph) -> Option<Block> {
+        let mut preds = cfg.pred_iter(self.header).filter(|&pred| !self.contains(pred));
+        let pred = preds.next()?;
+        preds.next().is_none().then_some(pred)
+    }
+
+    /// Returns the blocks outside the loop that are reached from a block inside the loop.
+    pub fn exits(&self, cfg: &ControlFlowGraph) -> Vec<(Block, Block)> {
+        self.blocks
+            .iter()
+            .flat_map(|bb| cfg.succ_iter(bb).map(move |succ| (bb, succ)))
+            .filter(|&(_, succ)| !self.contains(succ))
+            .collect()
+    }
+}
+
+/// The natural loops of a function, ordered from the innermost to the outermost loop so that
+/// nested loops can be processed inside out.
+#[derive(Debug, Default)]
+pub struct LoopInfo {
+    loops: Vec<Loop>,
+}
+
+impl LoopInfo {
+    /// Finds all natural loops in `func`. The dominator tree must be up to date (including
+    /// the postorder).
+    pub fn compute(&mut self, func: &Function, cfg: &ControlFlowGraph, dom_tree: &DominatorTree) {
+        self.loops.clear();
+        let mut stack = Vec::new();
+        for &header in dom_tree.cfg_postorder().iter().rev() {
+            // an edge is a back edge if its target dominates its source
+            let latches: Vec<_> =
+                cfg.pred_iter(header).filter(|&pred| dom_tree.dominates(pred, header)).collect();
+            if latches.is_empty() {
+                continue;
+            }
+
+            let mut blocks = BitSet::new_empty(func.layout.num_blocks());
+            blocks.insert(header);
+            stack.extend_from_slice(&latches);
+            while let Some(bb) = stack.pop() {
+                if blocks.insert(bb) {
+                    // unreachable predecessors are not dominated by the header
+                    stack
+                        .extend(cfg.pred_iter(bb).filter(|&pred| dom_tree.dominates(pred, header)));
+                }
+            }
+            self.loops.push(Loop { header, latches, blocks })
+        }
+
+        // an inner loop is always strictly smaller than the loops it is nested in
+        self.loops.sort_by_key(|lp| lp.blocks.count());
+    }
+
+    pub fn len(&self) -> usize {
+        self.loops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loops.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Loop> {
+        self.loops.iter()
+    }
+
+    /// Returns the preheader of the `idx`-th loop. If the loop is entered from a single block
+    /// that has other successors, a new preheader is inserted before the header and added
+    /// to all loops that enclose the loop.
+    pub fn ensure_preheader(
+        &mut self,
+        idx: usize,
+        func: &mut Function,
+        cfg: &mut ControlFlowGraph,
+    ) -> Option<Block> {
+        let header = self.loops[idx].header;
+        let pred = self.loops[idx].entry(cfg)?;
+        if cfg.unique_succ(pred) == Some(header) {
+            return Some(pred);
+        }
+
+        let preheader = func.layout.make_block();
+        func.layout.insert_block(preheader, header);
+        FuncCursor::new(func).at_bottom(preheader).ins().jump(header);
+        let term = func.layout.last_inst(pred).unwrap();
+        if let InstructionData::Branch { then_dst, else_dst, .. } = &mut func.dfg.insts[term] {
+            for dst in [then_dst, else_dst] {
+                if *dst == header {
+                    *dst = preheader;
+                }
+            }
+        } else {
+            unreachable!("block with multiple successors must end with a branch")
+        }
+        func.update_phi_edges(header, pred, preheader);
+
+        cfg.ensure_bb(preheader);
+        cfg.recompute_block(func, pred);
+        cfg.recompute_block(func, preheader);
+
+        for lp in &mut self.loops {
+            if lp.contains(pred) {
+                lp.blocks.ensure(func.layout.num_blocks());
+                lp.blocks.insert(preheader);
+            }
+        }
+        Some(preheader)
+    }
+
+    /// Returns whether `lp` contains no other loops.
+    pub fn is_innermost(&self, lp: &Loop) -> bool {
+        self.loops.iter().all(|other| other.header == lp.header || !lp.contains(other.header))
+    }
+}
+
+impl Index<usize> for LoopInfo {
+    type Output = Loop;
+
+    fn index(&self, idx: usize) -> &Loop {
+        &self.loops[idx]
+    }
+}
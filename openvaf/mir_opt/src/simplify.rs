@@ -2,8 +2,8 @@ use std::marker::PhantomData;
 use std::mem::swap;
 
 use mir::{
-    Function, Inst, InstructionData, Opcode, PhiNode, Value, ValueDef, F_N_ONE, F_ONE, F_TEN,
-    F_TWO, F_ZERO, N_ONE, ONE, ZERO,
+    Const, Function, Inst, InstructionData, Opcode, PhiNode, Value, ValueDef, F_N_ONE, F_ONE,
+    F_TEN, F_TWO, F_ZERO, N_ONE, ONE, ZERO,
 };
 
 use crate::const_eval::{eval_binary, eval_unary};
@@ -118,7 +118,15 @@ impl<'a, FP: Arithmetic, M: Fn(Value, &Function) -> Value> SimplifyCtx<'a, FP, M
 
                 return None;
             }
-            Opcode::Exp => Opcode::Ln,
+            // exp(ln(x)) -> x is only valid for x >= 0 (ln(x) is NaN otherwise)
+            Opcode::Exp => {
+                if let Some(x) = self.as_unary(arg, Opcode::Ln) {
+                    if self.is_non_negative(x, 2) {
+                        return Some(x);
+                    }
+                }
+                return None;
+            }
             Opcode::Ln => Opcode::Exp,
             Opcode::Log => {
                 if let Some([x, y]) = self.as_binary(arg, Opcode::Pow) {
@@ -249,6 +257,36 @@ impl<'a, FP: Arithmetic, M: Fn(Value, &Function) -> Value> SimplifyCtx<'a, FP, M
         None
     }
 
+    /// Returns whether `val` is known to be positive, zero or NaN. Up to `depth` levels of
+    /// additions, multiplications and divisions are looked through.
+    fn is_non_negative(&self, val: Value, depth: u32) -> bool {
+        match self.func.dfg.value_def(val) {
+            ValueDef::Const(Const::Float(val)) => f64::from(val) >= 0.0,
+            ValueDef::Const(Const::Int(val)) => val >= 0,
+            ValueDef::Result(inst, _) => match self.func.dfg.insts[inst] {
+                InstructionData::Unary {
+                    opcode: Opcode::Exp | Opcode::Sqrt | Opcode::Cosh,
+                    ..
+                }
+                | InstructionData::Binary { opcode: Opcode::Hypot, .. } => true,
+                InstructionData::Binary { opcode: Opcode::Fmul, args: [lhs, rhs] }
+                    if self.map_val(lhs) == self.map_val(rhs) =>
+                {
+                    true
+                }
+                InstructionData::Binary {
+                    opcode: Opcode::Fadd | Opcode::Fmul | Opcode::Fdiv,
+                    args: [lhs, rhs],
+                } if depth != 0 => {
+                    self.is_non_negative(self.map_val(lhs), depth - 1)
+                        && self.is_non_negative(self.map_val(rhs), depth - 1)
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
     fn is_neg(&self, unary_op: Opcode, bin_op: Opcode, lhs: Value, rhs: Value) -> bool {
         if self.as_unary(lhs, unary_op) == Some(rhs) || self.as_unary(rhs, unary_op) == Some(lhs) {
             return true;
@@ -454,11 +492,11 @@ impl<'a, FP: Arithmetic, M: Fn(Value, &Function) -> Value> SimplifyCtx<'a, FP, M
             }
         }
 
-        // sqrt(X) * sqrt(X) -> X
+        // sqrt(X) * sqrt(X) -> X (for X >= 0, sqrt(X) is NaN otherwise)
         if A::HAS_SQRT {
             if let Some(x) = self.as_unary(lhs, Opcode::Sqrt) {
                 if let Some(y) = self.as_unary(rhs, Opcode::Sqrt) {
-                    if x == y {
+                    if x == y && self.is_non_negative(x, 2) {
                         return Some(x);
                     }
                 }
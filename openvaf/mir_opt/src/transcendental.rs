@@ -0,0 +1,170 @@
+use ahash::AHashMap;
+use mir::builder::InstBuilder;
+use mir::cursor::{Cursor, FuncCursor};
+use mir::{Const, DominatorTree, Function, Inst, InstructionData, Opcode, Value, F_ONE};
+
+#[cfg(test)]
+mod tests;
+
+/// `pow(x, n)` is only expanded into multiplications for `|n| <= MAX_POW_EXPONENT`.
+const MAX_POW_EXPONENT: u32 = 16;
+
+/// Algebraic simplifications of transcendental functions that `inst_combine` can not perform
+/// because they require dominance information or insert new instructions:
+///
+/// * Calls of the same function with the same arguments (like the `exp(x)` inserted by
+///   `mir_autodiff` for the derivative of `exp(x)`) are replaced with the call that dominates
+///   them. This allows `inst_combine` to simplify `sqrt(x) * sqrt(x)` and `exp(ln(x))`.
+/// * `pow(x, n)` with a constant integer `n` is replaced with multiplications.
+/// * `sin(x)` and `cos(x)` are moved into the same block so that the backend can calculate
+///   both with a single `sincos` call.
+///
+/// The dominator tree (including the postorder) must be up to date. Returns whether the
+/// function was changed.
+pub fn simplify_transcendental(func: &mut Function, dom_tree: &DominatorTree) -> bool {
+    let mut calls: AHashMap<(Opcode, [Value; 2]), Vec<Inst>> = AHashMap::new();
+    let mut cos_calls = Vec::new();
+    let mut changed = false;
+
+    for &bb in dom_tree.cfg_postorder().iter().rev() {
+        let mut cursor = func.layout.block_inst_cursor(bb);
+        while let Some(inst) = cursor.next(&func.layout) {
+            let (opcode, args) = match func.dfg.insts[inst] {
+                InstructionData::Unary { opcode, arg } if is_transcendental(opcode) => {
+                    (opcode, [arg, arg])
+                }
+                InstructionData::Binary { opcode: Opcode::Pow, args: [x, n] } => {
+                    if let Some(val) = expand_pow(func, inst, x, n) {
+                        replace_inst(func, inst, val);
+                        changed = true;
+                        continue;
+                    }
+                    (Opcode::Pow, [x, n])
+                }
+                InstructionData::Binary { opcode, args } if is_transcendental(opcode) => {
+                    (opcode, args)
+                }
+                _ => continue,
+            };
+
+            let candidates = calls.entry((opcode, args)).or_default();
+            // instructions are visited in reverse postorder so a dominating call was already
+            // visited, calls in the same block are always before `inst`
+            let dominating = candidates.iter().copied().find(|&candidate| {
+                let candidate_bb = func.layout.inst_block(candidate).unwrap();
+                dom_tree.dominates(bb, candidate_bb)
+            });
+            if let Some(dominating) = dominating {
+                let val = func.dfg.first_result(dominating);
+                replace_inst(func, inst, val);
+                changed = true;
+            } else {
+                candidates.push(inst);
+                if opcode == Opcode::Cos {
+                    cos_calls.push((inst, args))
+                }
+            }
+        }
+    }
+
+    for (cos, args) in cos_calls {
+        let sin_calls = if let Some(sin_calls) = calls.get(&(Opcode::Sin, args)) {
+            sin_calls
+        } else {
+            continue;
+        };
+        let cos_bb = func.layout.inst_block(cos).unwrap();
+        for &sin in sin_calls {
+            let sin_bb = func.layout.inst_block(sin).unwrap();
+            if sin_bb == cos_bb {
+                break;
+            }
+            // the call that is executed later is moved to the one that dominates it
+            if dom_tree.dominates(cos_bb, sin_bb) {
+                move_after(func, cos, sin);
+                changed = true;
+                break;
+            }
+            if dom_tree.dominates(sin_bb, cos_bb) {
+                move_after(func, sin, cos);
+                changed = true;
+                break;
+            }
+        }
+    }
+
+    changed
+}
+
+fn is_transcendental(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        Opcode::Sqrt
+            | Opcode::Exp
+            | Opcode::Ln
+            | Opcode::Log
+            | Opcode::Sin
+            | Opcode::Cos
+            | Opcode::Tan
+            | Opcode::Asin
+            | Opcode::Acos
+            | Opcode::Atan
+            | Opcode::Sinh
+            | Opcode::Cosh
+            | Opcode::Tanh
+            | Opcode::Asinh
+            | Opcode::Acosh
+            | Opcode::Atanh
+            | Opcode::Hypot
+            | Opcode::Atan2
+            | Opcode::Pow
+    )
+}
+
+/// Replaces `pow(x, n)` with repeated squaring if `n` is a (small) integer constant.
+fn expand_pow(func: &mut Function, inst: Inst, x: Value, n: Value) -> Option<Value> {
+    let n: f64 = if let Some(Const::Float(n)) = func.dfg.value_def(n).as_const() {
+        n.into()
+    } else {
+        return None;
+    };
+    if n.fract() != 0.0 || n.abs() > MAX_POW_EXPONENT as f64 {
+        return None;
+    }
+    let mut exp = n.abs() as u32;
+
+    let mut cursor = FuncCursor::new(func).at_inst(inst);
+    let mut res = None;
+    let mut base = x;
+    while exp != 0 {
+        if exp & 1 == 1 {
+            res = Some(match res {
+                Some(res) => cursor.ins().fmul(res, base),
+                None => base,
+            });
+        }
+        exp >>= 1;
+        if exp != 0 {
+            base = cursor.ins().fmul(base, base);
+        }
+    }
+
+    let res = res.unwrap_or(F_ONE);
+    if n < 0.0 {
+        Some(cursor.ins().fdiv(F_ONE, res))
+    } else {
+        Some(res)
+    }
+}
+
+fn replace_inst(func: &mut Function, inst: Inst, val: Value) {
+    let old = func.dfg.first_result(inst);
+    func.dfg.replace_uses(old, val);
+    func.dfg.zap_inst(inst);
+    func.layout.remove_inst(inst);
+}
+
+fn move_after(func: &mut Function, inst: Inst, after: Inst) {
+    func.layout.remove_inst(inst);
+    func.layout.append_inst(inst, after);
+}
@@ -0,0 +1,150 @@
+use expect_test::{expect, Expect};
+use mir::{ControlFlowGraph, DominatorTree};
+use mir_reader::parse_function;
+
+use crate::{inst_combine, simplify_transcendental};
+
+fn check(src: &str, expect: Expect) {
+    let (mut func, _) = parse_function(src).unwrap();
+    let mut cfg = ControlFlowGraph::new();
+    cfg.compute(&func);
+    let mut dom_tree = DominatorTree::default();
+    dom_tree.compute(&func, &cfg, true, false, false);
+    simplify_transcendental(&mut func, &dom_tree);
+    expect.assert_eq(&func.to_debug_string());
+}
+
+fn check_inst_combine(src: &str, expect: Expect) {
+    let (mut func, _) = parse_function(src).unwrap();
+    inst_combine(&mut func);
+    expect.assert_eq(&func.to_debug_string());
+}
+
+#[test]
+fn reuse_and_expand_pow() {
+    let src = r#"
+        function %bar(v10, v11) {
+            v20 = fconst 0x1.8000000000000p1
+        block0:
+            v12 = exp v10
+            v13 = exp v10
+            v14 = fmul v12, v13
+            v15 = pow v11, v20
+            v16 = fadd v14, v15
+            v17 = optbarrier v16
+        }
+    "#;
+    check(
+        src,
+        expect![[r#"
+            function %bar(v10, v11) {
+            block0:
+                v12 = exp v10
+                v14 = fmul v12, v12
+                v21 = fmul v11, v11
+                v22 = fmul v11, v21
+                v16 = fadd v14, v22
+                v17 = optbarrier v16
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn sin_cos_pair() {
+    let src = r#"
+        function %bar(v10, v11) {
+        block0:
+            v12 = sin v10
+            br v11, block1, block2
+        block1:
+            v13 = cos v10
+            v14 = fmul v12, v13
+            jmp block2
+        block2:
+            v15 = phi [v12, block0], [v14, block1]
+            v16 = optbarrier v15
+        }
+    "#;
+    check(
+        src,
+        expect![[r#"
+            function %bar(v10, v11) {
+            block0:
+                v12 = sin v10
+                v13 = cos v10
+                br v11, block1, block2
+
+            block1:
+                v14 = fmul v12, v13
+                jmp block2
+
+            block2:
+                v15 = phi [v12, block0], [v14, block1]
+                v16 = optbarrier v15
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn exp_ln_domain() {
+    // exp(ln(x)) -> x is only valid if x can not be negative
+    let src = r#"
+        function %bar(v10) {
+        block0:
+            v11 = ln v10
+            v12 = exp v11
+            v13 = sqrt v10
+            v14 = ln v13
+            v15 = exp v14
+            v16 = fadd v12, v15
+            v17 = optbarrier v16
+        }
+    "#;
+    check_inst_combine(
+        src,
+        expect![[r#"
+            function %bar(v10) {
+            block0:
+                v11 = ln v10
+                v12 = exp v11
+                v13 = sqrt v10
+                v14 = ln v13
+                v16 = fadd v12, v13
+                v17 = optbarrier v16
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn sqrt_square_domain() {
+    // sqrt(x) * sqrt(x) -> x is only valid if x can not be negative
+    let src = r#"
+        function %bar(v10, v11) {
+        block0:
+            v12 = sqrt v10
+            v13 = fmul v12, v12
+            v14 = exp v11
+            v15 = sqrt v14
+            v16 = fmul v15, v15
+            v17 = fadd v13, v16
+            v18 = optbarrier v17
+        }
+    "#;
+    check_inst_combine(
+        src,
+        expect![[r#"
+            function %bar(v10, v11) {
+            block0:
+                v12 = sqrt v10
+                v13 = fmul v12, v12
+                v14 = exp v11
+                v15 = sqrt v14
+                v17 = fadd v13, v14
+                v18 = optbarrier v17
+            }
+        "#]],
+    );
+}
@@ -0,0 +1,232 @@
+//! Runs pipelines of `mir_opt` passes and `mir_autodiff` on textual MIR. This crate backs the
+//! `mir-opt` developer binary which is used to reduce miscompilations to small reproducers.
+//!
+//! Some passes need information that is usually provided by `sim_back`. For textual MIR it is
+//! derived from conventions that are also used by the unit tests:
+//!
+//! * The results of `optbarrier` instructions are the outputs of the function. They are kept
+//!   alive by dead code elimination.
+//! * A function declared as `%ddx_vN` calculates the derivative of its argument by the
+//!   function parameter `vN`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use bitset::{BitSet, HybridBitSet, SparseBitMatrix};
+use mir::{
+    ControlFlowGraph, DominatorTree, FuncRef, Function, InstructionData, KnownDerivatives, Opcode,
+    Unknown, Value,
+};
+use mir_opt::{
+    aggressive_dead_code_elimination, dead_code_elimination, inst_combine,
+    loop_invariant_code_motion, simplify_cfg, simplify_cfg_no_phi_merge, simplify_transcendental,
+    sparse_conditional_constant_propagation, unroll_loops, GVN,
+};
+use typed_indexmap::TiSet;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    Dce,
+    Adce,
+    Sccp,
+    InstCombine,
+    SimplifyCfg,
+    SimplifyCfgNoPhiMerge,
+    Gvn,
+    Licm,
+    UnrollLoops,
+    Transcendental,
+    Autodiff,
+}
+
+impl Pass {
+    pub const ALL: [Pass; 11] = [
+        Pass::Dce,
+        Pass::Adce,
+        Pass::Sccp,
+        Pass::InstCombine,
+        Pass::SimplifyCfg,
+        Pass::SimplifyCfgNoPhiMerge,
+        Pass::Gvn,
+        Pass::Licm,
+        Pass::UnrollLoops,
+        Pass::Transcendental,
+        Pass::Autodiff,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Dce => "dce",
+            Pass::Adce => "adce",
+            Pass::Sccp => "sccp",
+            Pass::InstCombine => "inst-combine",
+            Pass::SimplifyCfg => "simplify-cfg",
+            Pass::SimplifyCfgNoPhiMerge => "simplify-cfg-no-phi-merge",
+            Pass::Gvn => "gvn",
+            Pass::Licm => "licm",
+            Pass::UnrollLoops => "unroll-loops",
+            Pass::Transcendental => "transcendental",
+            Pass::Autodiff => "autodiff",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Pass::Dce => "remove instructions that do not contribute to an optbarrier",
+            Pass::Adce => "aggressive dead code elimination (also removes dead branches)",
+            Pass::Sccp => "sparse conditional constant propagation",
+            Pass::InstCombine => "algebraic simplification of single instructions",
+            Pass::SimplifyCfg => "merge blocks, remove trivial branches and merge phis",
+            Pass::SimplifyCfgNoPhiMerge => "simplify-cfg without merging phis",
+            Pass::Gvn => "global value numbering",
+            Pass::Licm => "loop invariant code motion",
+            Pass::UnrollLoops => "fully unroll loops with a constant trip count",
+            Pass::Transcendental => "share transcendental functions and expand pow(x, n)",
+            Pass::Autodiff => "replace calls to %ddx_vN with derivatives",
+        }
+    }
+
+    /// Parses a comma separated list of passes.
+    pub fn parse_pipeline(src: &str) -> Result<Vec<Pass>> {
+        src.split(',').map(str::trim).filter(|pass| !pass.is_empty()).map(Pass::from_str).collect()
+    }
+}
+
+impl FromStr for Pass {
+    type Err = anyhow::Error;
+
+    fn from_str(src: &str) -> Result<Pass> {
+        if let Some(pass) = Pass::ALL.iter().find(|pass| pass.name() == src) {
+            Ok(*pass)
+        } else {
+            bail!("unknown pass '{src}' (run with --list-passes to see all passes)")
+        }
+    }
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Runs `passes` on `func` in order. The function is validated after every pass; `after_pass`
+/// is called after every pass that produced valid MIR.
+pub fn run_pipeline(
+    func: &mut Function,
+    passes: &[Pass],
+    mut after_pass: impl FnMut(Pass, &Function),
+) -> Result<()> {
+    if !func.validate() {
+        bail!("input MIR is invalid");
+    }
+    for &pass in passes {
+        run_pass(func, pass);
+        if !func.validate() {
+            bail!("MIR is invalid after {pass}");
+        }
+        after_pass(pass, func)
+    }
+    Ok(())
+}
+
+pub fn run_pass(func: &mut Function, pass: Pass) {
+    let mut cfg = ControlFlowGraph::new();
+    cfg.compute(func);
+    let mut dom_tree = DominatorTree::default();
+
+    match pass {
+        Pass::Dce => {
+            let outputs = output_values(func);
+            dead_code_elimination(func, &outputs);
+        }
+        Pass::Adce => {
+            let outputs = output_values(func);
+            dom_tree.compute(func, &cfg, true, true, false);
+            let mut control_dep = SparseBitMatrix::new_square(0);
+            dom_tree.compute_postdom_frontiers(&cfg, &mut control_dep);
+            aggressive_dead_code_elimination(
+                func,
+                &mut cfg,
+                &|val, _| outputs.contains(val),
+                &control_dep,
+            );
+        }
+        Pass::Sccp => sparse_conditional_constant_propagation(func, &cfg),
+        Pass::InstCombine => inst_combine(func),
+        Pass::SimplifyCfg => simplify_cfg(func, &mut cfg),
+        Pass::SimplifyCfgNoPhiMerge => simplify_cfg_no_phi_merge(func, &mut cfg),
+        Pass::Gvn => {
+            dom_tree.compute(func, &cfg, true, true, false);
+            let num_params = (0..func.dfg.num_values())
+                .filter(|&val| func.dfg.value_def(val.into()).as_param().is_some())
+                .count();
+            let mut gvn = GVN::default();
+            gvn.init(func, &dom_tree, num_params as u32);
+            gvn.solve(func);
+            gvn.remove_unnecessary_insts(func, &dom_tree);
+            gvn.clear(func);
+        }
+        Pass::Licm => {
+            dom_tree.compute(func, &cfg, true, false, false);
+            loop_invariant_code_motion(func, &mut cfg, &dom_tree);
+        }
+        Pass::UnrollLoops => {
+            let pure: Vec<_> =
+                func.dfg.signatures.iter().map(|signature| !signature.has_sideeffects).collect();
+            // calls with side effects (like noise sources) must not be duplicated
+            let can_duplicate = |func_ref: FuncRef| pure[usize::from(func_ref)];
+            unroll_loops(func, &mut cfg, &mut dom_tree, &can_duplicate);
+        }
+        Pass::Transcendental => {
+            dom_tree.compute(func, &cfg, true, false, false);
+            simplify_transcendental(func, &dom_tree);
+        }
+        Pass::Autodiff => {
+            dom_tree.compute(func, &cfg, true, false, true);
+            let derivatives = known_derivatives(func);
+            mir_autodiff::auto_diff(&mut *func, &dom_tree, &derivatives, &[]);
+        }
+    }
+}
+
+/// The results of all `optbarrier` instructions.
+fn output_values(func: &Function) -> BitSet<Value> {
+    let mut outputs = BitSet::new_empty(func.dfg.num_values());
+    for bb in func.layout.blocks() {
+        for inst in func.layout.block_insts(bb) {
+            if let InstructionData::Unary { opcode: Opcode::OptBarrier, .. } = func.dfg.insts[inst]
+            {
+                outputs.insert(func.dfg.first_result(inst));
+            }
+        }
+    }
+    outputs
+}
+
+/// Functions named `ddx_vN` calculate the derivative by the parameter `vN`.
+fn known_derivatives(func: &Function) -> KnownDerivatives {
+    let ddx_calls: Vec<_> = func
+        .dfg
+        .signatures
+        .iter_enumerated()
+        .filter_map(|(func_ref, signature)| {
+            let param: u32 = signature.name.strip_prefix("ddx_v")?.parse().ok()?;
+            Some((func_ref, Value::from(param)))
+        })
+        .collect();
+    let unknowns: TiSet<Unknown, Value> = ddx_calls.iter().map(|(_, val)| *val).collect();
+    let ddx_calls = ddx_calls
+        .into_iter()
+        .map(|(func_ref, val)| {
+            let mut unknown = HybridBitSet::new_empty();
+            unknown.insert(unknowns.unwrap_index(&val), unknowns.len());
+            (func_ref, (unknown, HybridBitSet::new_empty()))
+        })
+        .collect();
+    KnownDerivatives { unknowns, ddx_calls }
+}
@@ -0,0 +1,110 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::exit;
+
+use anyhow::{bail, Context, Result};
+use mir_reader::parse_functions;
+use mir_tool::{run_pipeline, Pass};
+
+const USAGE: &str = "\
+Runs a pipeline of MIR passes on textual MIR and prints the result.
+
+USAGE:
+    mir-opt [OPTIONS] <INPUT>
+
+ARGS:
+    <INPUT>    file containing one or more MIR functions, '-' reads from stdin
+
+OPTIONS:
+    -p, --passes <PASSES>    comma separated list of passes that are run in order
+    -o, --output <FILE>      write the result to FILE instead of stdout
+        --print-after-each   print the MIR after every pass
+        --list-passes        print all available passes
+    -h, --help               print this message
+
+The MIR is validated before and after every pass. An invalid result is reported
+together with the pass that produced it.
+";
+
+struct Args {
+    input: String,
+    passes: Vec<Pass>,
+    output: Option<String>,
+    print_after_each: bool,
+}
+
+fn parse_args() -> Result<Option<Args>> {
+    let mut args = std::env::args().skip(1);
+    let mut input = None;
+    let mut passes = Vec::new();
+    let mut output = None;
+    let mut print_after_each = false;
+    while let Some(arg) = args.next() {
+        match &*arg {
+            "-h" | "--help" => {
+                print!("{USAGE}");
+                return Ok(None);
+            }
+            "--list-passes" => {
+                for pass in Pass::ALL {
+                    println!("{:<28}{}", pass.name(), pass.description());
+                }
+                return Ok(None);
+            }
+            "-p" | "--passes" => {
+                let pipeline = args.next().context("missing value for --passes")?;
+                passes.extend(Pass::parse_pipeline(&pipeline)?);
+            }
+            "-o" | "--output" => output = Some(args.next().context("missing value for --output")?),
+            "--print-after-each" => print_after_each = true,
+            _ if arg.starts_with("--passes=") => {
+                passes.extend(Pass::parse_pipeline(&arg["--passes=".len()..])?)
+            }
+            _ if arg.starts_with('-') && arg != "-" => bail!("unknown option '{arg}'\n\n{USAGE}"),
+            _ if input.is_some() => bail!("more than one input file was specified"),
+            _ => input = Some(arg),
+        }
+    }
+
+    let input = if let Some(input) = input { input } else { bail!("no input file\n\n{USAGE}") };
+    Ok(Some(Args { input, passes, output, print_after_each }))
+}
+
+fn run() -> Result<()> {
+    let args = if let Some(args) = parse_args()? { args } else { return Ok(()) };
+
+    let src = if args.input == "-" {
+        let mut src = String::new();
+        io::stdin().read_to_string(&mut src).context("failed to read stdin")?;
+        src
+    } else {
+        fs::read_to_string(&args.input).with_context(|| format!("failed to read {}", args.input))?
+    };
+    let (mut funcs, literals) =
+        parse_functions(&src).map_err(|err| anyhow::anyhow!("failed to parse MIR: {err}"))?;
+
+    let mut out = String::new();
+    for func in &mut funcs {
+        run_pipeline(func, &args.passes, |pass, func| {
+            if args.print_after_each {
+                eprintln!("; after {pass}\n{}", func.print(&literals));
+            }
+        })?;
+        out.push_str(&func.print(&literals).to_string());
+        out.push('\n');
+    }
+
+    if let Some(output) = &args.output {
+        fs::write(output, out).with_context(|| format!("failed to write {output}"))?;
+    } else {
+        io::stdout().write_all(out.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {err:#}");
+        exit(1);
+    }
+}
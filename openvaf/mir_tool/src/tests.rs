@@ -0,0 +1,44 @@
+use expect_test::{expect, Expect};
+use mir_reader::parse_function;
+
+use crate::{run_pipeline, Pass};
+
+fn check(src: &str, pipeline: &str, expect: Expect) {
+    let (mut func, _) = parse_function(src).unwrap();
+    let passes = Pass::parse_pipeline(pipeline).unwrap();
+    let mut executed = Vec::new();
+    run_pipeline(&mut func, &passes, |pass, _| executed.push(pass)).unwrap();
+    assert_eq!(executed, passes);
+    expect.assert_eq(&func.to_debug_string());
+}
+
+#[test]
+fn pipeline() {
+    let src = r#"
+        function %bar(v10) {
+        block0:
+            v11 = exp v10
+            v12 = sqrt v11
+            v13 = sqrt v11
+            v14 = fmul v12, v13
+            v15 = optbarrier v14
+        }
+    "#;
+    check(
+        src,
+        "transcendental, inst-combine, dce",
+        expect![[r#"
+            function %bar(v10) {
+            block0:
+                v11 = exp v10
+                v15 = optbarrier v11
+            }
+        "#]],
+    );
+}
+
+#[test]
+fn unknown_pass() {
+    let err = Pass::parse_pipeline("sccp,foo").unwrap_err();
+    assert_eq!(err.to_string(), "unknown pass 'foo' (run with --list-passes to see all passes)");
+}
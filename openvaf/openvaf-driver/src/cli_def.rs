@@ -5,7 +5,7 @@ use anyhow::bail;
 use camino::Utf8Path;
 use clap::builder::{PossibleValue, PossibleValuesParser, ValueParser};
 use clap::{Arg, ArgAction, Command, ValueHint};
-use openvaf::{builtin_lints, get_target_names, host_triple, LintLevel};
+use openvaf::{builtin_lints, get_target_names, host_triple, LinkerFlavor, LintLevel};
 use path_absolutize::Absolutize;
 
 const ABOUT: &str = r"For further information visit https://openvaf.semimod.de.";
@@ -30,11 +30,17 @@ pub fn main_command() -> Command {
             dump_unopt_mir(),
             dump_ir(),
             dump_unopt_ir(),
+            dump_opt_stats(),
+            param_sensitivities(),
+            hessian(),
+            debug_info(),
+            modelcard(),
             cache_dir(),
             opt_lvl(),
             target(),
             supported_targets(),
             target_cpu(),
+            linker_flavor(),
             codegen_opts(),
             interface(),
             expand(),
@@ -52,11 +58,17 @@ pub const DUMPMIR: &str = "dump-mir";
 pub const DUMPUNOPTMIR: &str = "dump-unopt-mir";
 pub const DUMPIR: &str = "dump-ir";
 pub const DUMPUNOPTIR: &str = "dump-unopt-ir";
+pub const DUMPOPTSTATS: &str = "dump-opt-stats";
+pub const PARAM_SENSITIVITIES: &str = "param-sensitivities";
+pub const HESSIAN: &str = "hessian";
+pub const DEBUGINFO: &str = "debug-info";
+pub const MODELCARD: &str = "modelcard";
 pub const TARGET: &str = "target";
 pub const SUPPORTED_TARGETS: &str = "supported-targets";
 pub const LINTS: &str = "lints";
 pub const TARGET_CPU: &str = "target_cpu";
 pub const CODEGEN: &str = "codegen";
+pub const LINKER_FLAVOR: &str = "linker-flavor";
 pub const INPUT: &str = "input";
 pub const INCLUDE: &str = "include";
 pub const OUTPUT: &str = "output";
@@ -117,6 +129,38 @@ fn dump_unopt_ir() -> Arg {
         .long_help("Dump unoptimized LLVM IR during compilation.\nUsed for debugging.")
 }
 
+fn dump_opt_stats() -> Arg {
+    flag(DUMPOPTSTATS, "dump-opt-stats")
+        .help("Print the size of the MIR after each optimization pass.")
+        .long_help("Print the number of instructions (and calls of transcendental functions) in the MIR after each optimization pass.\nUsed to evaluate optimizations.")
+}
+
+fn param_sensitivities() -> Arg {
+    flag(PARAM_SENSITIVITIES, "param-sensitivities")
+        .help("Generate the derivatives of the residual by the model parameters.")
+        .long_help("Generate the derivatives of the residual by the model parameters.\nThese are returned by the load_param_sensitivity function of the OSDI descriptor.\nIncreases compile time and library size for models with many parameters.")
+}
+
+fn hessian() -> Arg {
+    flag(HESSIAN, "hessian")
+        .help("Generate the second derivatives of the residual by the unknowns.")
+        .long_help("Generate the second derivatives of the residual by the unknowns.\nThese are returned as hessian-vector products by the load_hessian_vector_product function of the OSDI descriptor.\nRequired for harmonic balance and distortion analysis.")
+}
+
+fn debug_info() -> Arg {
+    flag(DEBUGINFO, "debug-info")
+        .help("Emit debug info that maps the generated code to the Verilog-A source.")
+        .long_help("Emit DWARF debug info that maps the generated code to the lines of the Verilog-A source.\nVerilog-A variables are available as local variables in the debugger.\nValues may still be optimized out unless combined with -O0.")
+}
+
+fn modelcard() -> Arg {
+    input_file_path_arg(MODELCARD)
+        .long(MODELCARD)
+        .help("Compile the model parameters of a modelcard into the model as constants.")
+        .long_help("Compile the model parameters of a modelcard into the model as constants.\nThe SPICE modelcard (.model name type param=value ...) is read and the model parameters it sets are replaced by constants before optimization.\nThe generated model only exposes the remaining parameters.")
+        .required(false)
+}
+
 fn target() -> Arg {
     let vals = get_target_names().fold(String::new(), |mut dst, it| {
         dst.push('\n');
@@ -161,6 +205,17 @@ fn target_cpu() -> Arg {
         .value_hint(ValueHint::Other)
 }
 
+fn linker_flavor() -> Arg {
+    Arg::new(LINKER_FLAVOR)
+        .long(LINKER_FLAVOR)
+        .help("Linker used to create the shared library.")
+        .long_help("Linker used to create the shared library.\nBy default the system linker of the target is used.\n'builtin' uses the ELF linker built into OpenVAF (x86_64 linux only) which requires no C toolchain.\nIf the system linker is not installed the builtin linker is used automatically where supported.")
+        .value_name("FLAVOR")
+        .value_parser(PossibleValuesParser::new(LinkerFlavor::NAMES))
+        .required(false)
+        .value_hint(ValueHint::Other)
+}
+
 fn codegen_opts() -> Arg {
     Arg::new(CODEGEN)
         .long(CODEGEN)
@@ -5,14 +5,15 @@ use anyhow::{bail, Context, Result};
 use camino::Utf8PathBuf;
 use clap::ArgMatches;
 use openvaf::{
-    builtin_lints, get_target_names, host_triple, AbsPathBuf, LLVMCodeGenOptLevel, LintLevel,
+    builtin_lints, get_target_names, host_triple, AbsPathBuf, LLVMCodeGenOptLevel, LinkerFlavor,
+    LintLevel,
 };
 use termcolor::{Color, ColorChoice, ColorSpec, WriteColor};
 
 use crate::cli_def::{
-    ALLOW, BATCHMODE, CACHE_DIR, CODEGEN, DEFINE, DENY, DRYRUN, DUMPIR, DUMPMIR, DUMPUNOPTIR,
-    DUMPUNOPTMIR, INCLUDE, INPUT, LINTS, OPT_LVL, OUTPUT, SUPPORTED_TARGETS, TARGET, TARGET_CPU,
-    WARN,
+    ALLOW, BATCHMODE, CACHE_DIR, CODEGEN, DEBUGINFO, DEFINE, DENY, DRYRUN, DUMPIR, DUMPMIR,
+    DUMPOPTSTATS, DUMPUNOPTIR, DUMPUNOPTMIR, HESSIAN, INCLUDE, INPUT, LINKER_FLAVOR, LINTS,
+    MODELCARD, OPT_LVL, OUTPUT, PARAM_SENSITIVITIES, SUPPORTED_TARGETS, TARGET, TARGET_CPU, WARN,
 };
 use crate::{CompilationDestination, Opts};
 
@@ -97,13 +98,17 @@ pub fn matches_to_opts(matches: ArgMatches) -> Result<Opts> {
     let target = matches.get_one::<String>(TARGET).cloned().unwrap_or_else(|| host.to_owned());
     let default_cpu = if host != target { "generic" } else { "native" };
 
-    let target = if let Some(target) = openvaf::Target::search(&target) {
+    let mut target = if let Some(target) = openvaf::Target::search(&target) {
         target
     } else {
         // should never happened but helpful to provide support just in case
         bail!("The target {target} is not supported by  this binary")
     };
 
+    if let Some(flavor) = matches.get_one::<String>(LINKER_FLAVOR) {
+        target.options.linker_flavor = LinkerFlavor::from_str(flavor).unwrap();
+    }
+
     let target_cpu: String =
         matches.get_one(TARGET_CPU).cloned().unwrap_or_else(|| default_cpu.to_owned());
 
@@ -121,6 +126,11 @@ pub fn matches_to_opts(matches: ArgMatches) -> Result<Opts> {
         dump_unopt_mir: matches.get_flag(DUMPUNOPTMIR),
         dump_ir: matches.get_flag(DUMPIR),
         dump_unopt_ir: matches.get_flag(DUMPUNOPTIR),
+        dump_opt_stats: matches.get_flag(DUMPOPTSTATS),
+        param_sensitivities: matches.get_flag(PARAM_SENSITIVITIES),
+        hessian: matches.get_flag(HESSIAN),
+        debug_info: matches.get_flag(DEBUGINFO),
+        modelcard: matches.get_one::<Utf8PathBuf>(MODELCARD).cloned(),
         dry_run: matches.get_flag(DRYRUN),
     })
 }
@@ -1,4 +1,5 @@
 use core::slice;
+use std::fs::read;
 use std::mem::{size_of, size_of_val};
 
 use basedb::lints::LintLevel;
@@ -8,17 +9,26 @@ use hir::CompilationDB;
 use crate::Opts;
 
 // TODO: use high level hir API instead of low leve database API
-fn hash(db: &CompilationDB, defines: &[String]) -> md5::Digest {
+fn hash(db: &CompilationDB, opts: &Opts) -> md5::Digest {
     let mut hash_builder = md5::Context::new();
     let cu = db.compilation_unit();
 
     // hash settings
     hash_builder.consume(cu.root_file().0.to_ne_bytes());
 
-    hash_builder.consume(defines.len().to_ne_bytes());
-    for def in defines {
+    hash_builder.consume(opts.defines.len().to_ne_bytes());
+    for def in &opts.defines {
         hash_builder.consume(def)
     }
+    hash_builder.consume([
+        opts.param_sensitivities as u8,
+        opts.hessian as u8,
+        opts.debug_info as u8,
+    ]);
+    if let Some(modelcard) = &opts.modelcard {
+        // an unreadable modelcard is reported during compilation
+        hash_builder.consume(read(modelcard).unwrap_or_default());
+    }
 
     hash_builder.consume(env!("CARGO_PKG_VERSION"));
     let lints = db.global_lint_overwrites(cu.root_file());
@@ -49,7 +59,7 @@ fn hash(db: &CompilationDB, defines: &[String]) -> md5::Digest {
 }
 
 pub fn file_name(db: &CompilationDB, opts: &Opts) -> String {
-    let hash = u128::from_ne_bytes(*hash(db, &opts.defines));
+    let hash = u128::from_ne_bytes(*hash(db, opts));
     let hash = base_n::encode(hash, base_n::CASE_INSENSITIVE);
     format!("{}.osdi", hash)
 }
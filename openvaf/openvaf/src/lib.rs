@@ -1,8 +1,8 @@
-use std::fs::{create_dir_all, remove_file};
+use std::fs::{create_dir_all, read_to_string, remove_file};
 use std::io::Write;
 use std::time::Instant;
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use basedb::diagnostics::{ConsoleSink, DiagnosticSink};
 pub use basedb::lints::{builtin as builtin_lints, LintLevel};
 use basedb::BaseDB;
@@ -11,13 +11,15 @@ use hir::CompilationDB;
 use linker::link;
 pub use llvm_sys::target_machine::LLVMCodeGenOptLevel;
 use mir_llvm::LLVMBackend;
+pub use osdi::OsdiJit;
 pub use paths::AbsPathBuf;
-use sim_back::{collect_modules, print_intern, print_module};
+use sim_back::{collect_modules, print_intern, print_module, ModuleInfo};
 pub use target::host_triple;
-pub use target::spec::{get_target_names, Target};
+pub use target::spec::{get_target_names, LinkerFlavor, Target};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 mod cache;
+pub mod modelcard;
 
 #[derive(Debug, Clone)]
 pub enum CompilationDestination {
@@ -30,6 +32,11 @@ pub enum CompilationTermination {
     FatalDiagnostic,
 }
 
+pub enum JitTermination {
+    Compiled { lib: OsdiJit },
+    FatalDiagnostic,
+}
+
 #[derive(Debug, Clone)]
 pub struct Opts {
     pub dry_run: bool,
@@ -46,6 +53,16 @@ pub struct Opts {
     pub dump_unopt_mir: bool,
     pub dump_ir: bool,
     pub dump_unopt_ir: bool,
+    /// print the size of the MIR after each optimization pass
+    pub dump_opt_stats: bool,
+    /// generate the derivatives of the residual by the model parameters
+    pub param_sensitivities: bool,
+    /// generate the second derivatives of the residual by the unknowns
+    pub hessian: bool,
+    /// emit DWARF debug info that maps the generated code to the Verilog-A source
+    pub debug_info: bool,
+    /// modelcard whose model parameters are compiled into the model as constants
+    pub modelcard: Option<Utf8PathBuf>,
 }
 // pub fn dump_json(opts: &Opts) -> Result<CompilationTermination> {
 //     let input =
@@ -175,11 +192,13 @@ pub fn compile(opts: &Opts) -> Result<CompilationTermination> {
     };
 
     // Lowering of natures from AST into HIR happens here
-    let modules = if let Some(modules) = collect_modules(&db, false, &mut ConsoleSink::new(&db)) {
+    let mut modules = if let Some(modules) = collect_modules(&db, false, &mut ConsoleSink::new(&db))
+    {
         modules
     } else {
         return Ok(CompilationTermination::FatalDiagnostic);
     };
+    specialize(&db, &mut modules, opts)?;
 
     let back = LLVMBackend::new(&opts.codegen_opts, &opts.target, opts.target_cpu.clone(), &[]);
     if opts.dry_run {
@@ -198,6 +217,10 @@ pub fn compile(opts: &Opts) -> Result<CompilationTermination> {
         opts.dump_unopt_mir,
         opts.dump_ir,
         opts.dump_unopt_ir,
+        opts.dump_opt_stats,
+        opts.param_sensitivities,
+        opts.hessian,
+        opts.debug_info,
     );
 
     // Dump natures, disciplines, and their attributes
@@ -225,6 +248,13 @@ pub fn compile(opts: &Opts) -> Result<CompilationTermination> {
     }
     */
 
+    if opts.dump_opt_stats {
+        for (module, cmodule) in modules.iter().zip(compiled_modules.iter()) {
+            println!("Optimization statistics of {}", module.module.name(&db));
+            println!("{}", cmodule.opt_stats);
+        }
+    }
+
     // Dump MIR of compiled modules
     if opts.dump_mir || opts.dump_unopt_mir {
         let cu = db.compilation_unit();
@@ -275,3 +305,83 @@ pub fn compile(opts: &Opts) -> Result<CompilationTermination> {
 
     Ok(CompilationTermination::Compiled { lib_file })
 }
+
+/// Compiles a Verilog-A file just like [`compile`] but links the generated code directly into
+/// the current process with the LLVM JIT. Neither temporary files nor a system linker are
+/// required. `opts.output` and the dump options are ignored and `opts.target` must be the host.
+pub fn compile_jit(opts: &Opts) -> Result<JitTermination> {
+    let start = Instant::now();
+
+    match Target::host_target() {
+        Some(host) if host.llvm_target == opts.target.llvm_target => (),
+        _ => bail!("JIT compilation is only supported for the host target"),
+    }
+
+    let input =
+        opts.input.canonicalize().with_context(|| format!("failed to resolve {}", opts.input))?;
+    let input = AbsPathBuf::assert(input);
+    let db = CompilationDB::new_fs(input, &opts.include, &opts.defines, &opts.lints)?;
+
+    let mut modules = if let Some(modules) = collect_modules(&db, false, &mut ConsoleSink::new(&db))
+    {
+        modules
+    } else {
+        return Ok(JitTermination::FatalDiagnostic);
+    };
+    specialize(&db, &mut modules, opts)?;
+
+    let back = LLVMBackend::new(&opts.codegen_opts, &opts.target, opts.target_cpu.clone(), &[]);
+    let name = opts.input.file_stem().unwrap_or("openvaf_jit");
+    let (lib, _, _) = osdi::compile_jit(
+        &db,
+        &modules,
+        name,
+        &opts.target,
+        &back,
+        opts.opt_lvl,
+        opts.param_sensitivities,
+        opts.hessian,
+    )
+    .map_err(|err| anyhow!("JIT compilation failed: {err}"))?;
+
+    let seconds = Instant::elapsed(&start).as_secs_f64();
+    let mut stderr = StandardStream::stderr(ColorChoice::Auto);
+    stderr.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))?;
+    write!(&mut stderr, "Finished")?;
+    stderr.set_color(&ColorSpec::new())?;
+    writeln!(&mut stderr, " JIT compiling {} in {:.2}s", opts.input.file_name().unwrap(), seconds)?;
+
+    Ok(JitTermination::Compiled { lib })
+}
+
+/// Replaces the model parameters set in `opts.modelcard` (if any) by constants.
+/// The compiled models only expose the remaining parameters.
+fn specialize(db: &CompilationDB, modules: &mut [ModuleInfo], opts: &Opts) -> Result<()> {
+    let path = match &opts.modelcard {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let src = read_to_string(path).with_context(|| format!("failed to read modelcard {path}"))?;
+    let values =
+        modelcard::parse(&src).with_context(|| format!("failed to parse modelcard {path}"))?;
+
+    let mut stderr = StandardStream::stderr(ColorChoice::Auto);
+    for module in modules {
+        let unknown = module
+            .specialize(db, &values)
+            .map_err(|err| anyhow!("invalid modelcard {path}: {err}"))?;
+        if unknown.is_empty() {
+            continue;
+        }
+        stderr.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)).set_bold(true))?;
+        write!(&mut stderr, "warning")?;
+        stderr.set_color(&ColorSpec::new())?;
+        writeln!(
+            &mut stderr,
+            ": {} of {path} are not model parameters of {} and were ignored",
+            unknown.join(", "),
+            module.module.name(db)
+        )?;
+    }
+    Ok(())
+}
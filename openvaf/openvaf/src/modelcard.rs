@@ -0,0 +1,91 @@
+use anyhow::{bail, Result};
+
+#[cfg(test)]
+mod tests;
+
+/// Parses the parameter values of a SPICE modelcard:
+///
+/// ```spice
+/// * comment
+/// .model nch nmos level=54
+/// + vth0 = 0.4 tox=1.2n
+/// ```
+///
+/// Everything that is not an assignment (the `.model` keyword, the model name and type) is
+/// ignored so files that only contain `name=value` pairs are accepted as well. Values may use
+/// the usual SPICE scale factors (`1.2n`, `3meg`, ...).
+pub fn parse(src: &str) -> Result<Vec<(String, f64)>> {
+    let mut tokens = Vec::new();
+    for line in src.lines() {
+        let line = line.trim_start();
+        if line.starts_with('*') {
+            continue;
+        }
+        let line = line.split([';', '$']).next().unwrap_or_default();
+        let line = line.strip_prefix('+').unwrap_or(line);
+        tokens.extend(
+            line.split(|c: char| c.is_whitespace() || matches!(c, '(' | ')' | ','))
+                .flat_map(split_assignment)
+                .filter(|token| !token.is_empty()),
+        );
+    }
+
+    let mut res = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens.get(i + 1) != Some(&"=") {
+            if tokens[i] == "=" {
+                bail!("expected a parameter name before '='");
+            }
+            i += 1;
+            continue;
+        }
+        let name = tokens[i];
+        let Some(val) = tokens.get(i + 2) else { bail!("missing value for parameter {name}") };
+        let Some(val) = parse_value(val) else {
+            bail!("invalid value '{val}' for parameter {name}; expected a number")
+        };
+        res.push((name.to_owned(), val));
+        i += 3;
+    }
+    Ok(res)
+}
+
+/// Splits `a=b` into `a`, `=` and `b`.
+fn split_assignment(token: &str) -> impl Iterator<Item = &str> {
+    let (lhs, rhs) = token.split_once('=').map_or((token, None), |(lhs, rhs)| (lhs, Some(rhs)));
+    [Some(lhs), rhs.map(|_| "="), rhs].into_iter().flatten()
+}
+
+/// Parses a number with an optional SPICE scale factor. Any letters following the scale
+/// factor (units) are ignored.
+fn parse_value(src: &str) -> Option<f64> {
+    let (val, suffix) = (1..=src.len())
+        .rev()
+        .filter(|&i| src.is_char_boundary(i))
+        .find_map(|i| Some((src[..i].parse::<f64>().ok()?, &src[i..])))?;
+    // an exponent without digits (`1e`) is not a scale factor
+    if !suffix.chars().all(|c| c.is_ascii_alphabetic()) || suffix.starts_with(['e', 'E']) {
+        return None;
+    }
+    let suffix = suffix.to_ascii_lowercase();
+    let scale = if suffix.starts_with("meg") {
+        1e6
+    } else if suffix.starts_with("mil") {
+        25.4e-6
+    } else {
+        match suffix.chars().next() {
+            Some('t') => 1e12,
+            Some('g') => 1e9,
+            Some('k') => 1e3,
+            Some('m') => 1e-3,
+            Some('u') => 1e-6,
+            Some('n') => 1e-9,
+            Some('p') => 1e-12,
+            Some('f') => 1e-15,
+            Some('a') => 1e-18,
+            _ => 1.0,
+        }
+    };
+    Some(val * scale)
+}
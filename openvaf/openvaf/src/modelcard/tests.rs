@@ -0,0 +1,33 @@
+use super::parse;
+
+#[test]
+fn modelcard() {
+    let src = "* corner tt
+.model nch nmos (level=54 version = 4.8
++ vth0=0.4 tox = 1.2n ; gate oxide
++ rsh=3meg cj=1.5ff)
+";
+    let vals = parse(src).unwrap();
+    let expected = [
+        ("level", 54.0),
+        ("version", 4.8),
+        ("vth0", 0.4),
+        ("tox", 1.2e-9),
+        ("rsh", 3e6),
+        ("cj", 1.5e-15),
+    ];
+    assert_eq!(vals.len(), expected.len());
+    for ((name, val), (expected_name, expected_val)) in vals.iter().zip(expected) {
+        assert_eq!(name, expected_name);
+        assert!((val - expected_val).abs() <= 1e-12 * expected_val.abs());
+    }
+}
+
+#[test]
+fn invalid_value() {
+    let err = parse(".model nch nmos vth0=high").unwrap_err();
+    assert_eq!(err.to_string(), "invalid value 'high' for parameter vth0; expected a number");
+    assert!(parse(".model nch nmos vth0=").is_err());
+    assert!(parse(".model nch nmos vth0=1e").is_err());
+    assert!(parse(".model nch nmos vth0=1e-").is_err());
+}
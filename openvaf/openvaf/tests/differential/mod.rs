@@ -0,0 +1,480 @@
+//! Differential testing of the compiled OSDI models against the MIR interpreter.
+//!
+//! Each model is evaluated for random parameter sets, temperatures and bias points with
+//! the native `eval` function (loaded with the mock simulator) and by interpreting both the
+//! optimized and the unoptimized MIR. The first value where the results disagree is reported.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use camino::Utf8Path;
+use hir::diagnostics::ConsoleSink;
+use hir::{CompilationDB, Parameter, Type};
+use lasso::Rodeo;
+use mini_harness::Result;
+use openvaf::AbsPathBuf;
+use sim_back::dae::SimUnknown;
+use sim_back::interpret::{
+    EvalResult, InterpretedInstance, InterpretedModel, ParamValues, SimInfo, SimParams,
+    EVAL_RET_FLAG_FATAL,
+};
+use sim_back::{collect_modules, CompileOpts, CompiledModule, SimUnknownKind};
+use typed_index_collections::TiVec;
+
+use crate::compile_and_load;
+use crate::load::{osdi_str, EvalFlags, OsdiDescriptor, PARA_KIND_MASK, PARA_KIND_OPVAR};
+use crate::mock_sim::CALC_ALL;
+
+const NUM_PARAM_SETS: usize = 4;
+const NUM_BIAS_POINTS: usize = 8;
+/// probability that a parameter is perturbed in a random parameter set
+const PERTURB_PROBABILITY: f64 = 0.25;
+/// upper bound of the (non-negative) offset added to parameters that default to zero,
+/// scaling those would leave them unchanged
+const ZERO_PERTURBATION: f64 = 0.1;
+const NOISE_FREQ: f64 = 1e3;
+const REL_TOL: f64 = 1e-6;
+/// absolute tolerance relative to the largest magnitude of the compared quantity
+const ABS_TOL: f64 = 1e-9;
+
+pub fn differential_test(dir: &Path) -> Result {
+    let name = dir.file_name().unwrap().to_str().unwrap().to_lowercase();
+    let main_file = dir.join(format!("{name}.va"));
+    let main_file: &Utf8Path = main_file.as_path().try_into().unwrap();
+    let desc = compile_and_load(main_file);
+
+    let root_file = AbsPathBuf::assert(main_file.canonicalize()?);
+    let db = CompilationDB::new_fs(root_file, &[], &[], &[])?;
+    let modules = collect_modules(&db, false, &mut ConsoleSink::new(&db))
+        .ok_or_else(|| format!("compilation of {main_file} failed"))?;
+    let mut literals = Rodeo::new();
+    let opt = CompiledModule::new(&db, &modules[0], &mut literals, &CompileOpts::default());
+    let unopt = CompiledModule::new_unoptimized(&db, &modules[0], &mut literals);
+
+    let harness = Harness::new(&db, desc, &opt, &unopt, &literals, Rng::new(&name))?;
+    harness.run()
+}
+
+struct Harness<'a> {
+    db: &'a CompilationDB,
+    desc: &'static OsdiDescriptor,
+    opt: &'a CompiledModule<'a>,
+    unopt: &'a CompiledModule<'a>,
+    literals: &'a Rodeo,
+    rng: Rng,
+    /// OSDI parameter id of each model/instance parameter
+    osdi_params: HashMap<Parameter, u32>,
+    /// unknowns of the unoptimized MIR corresponding to the unknowns of the optimized MIR
+    unopt_unknowns: TiVec<SimUnknown, Option<SimUnknown>>,
+}
+
+/// Values computed by one of the three backends, indexed like the OSDI descriptor
+struct Results {
+    ret_flags: u32,
+    residuals: Vec<[f64; 4]>,
+    jacobian: Vec<(f64, f64)>,
+    noise: Vec<f64>,
+}
+
+impl<'a> Harness<'a> {
+    fn new(
+        db: &'a CompilationDB,
+        desc: &'static OsdiDescriptor,
+        opt: &'a CompiledModule<'a>,
+        unopt: &'a CompiledModule<'a>,
+        literals: &'a Rodeo,
+        rng: Rng,
+    ) -> Result<Harness<'a>> {
+        // the OSDI descriptor is generated from the optimized MIR
+        if desc.num_nodes as usize != opt.dae_system.unknowns.len()
+            || desc.num_jacobian_entries as usize != opt.dae_system.jacobian.len()
+            || desc.num_noise_src as usize != opt.dae_system.noise_sources.len()
+            || desc.num_collapsible != opt.node_collapse.num_pairs()
+        {
+            return Err("OSDI descriptor does not match the optimized MIR".into());
+        }
+
+        let osdi_ids: HashMap<_, _> = desc
+            .params()
+            .iter()
+            .enumerate()
+            .filter(|(_, param)| param.flags & PARA_KIND_MASK != PARA_KIND_OPVAR)
+            .map(|(id, param)| (unsafe { osdi_str(*param.name) }, id as u32))
+            .collect();
+        let osdi_params = opt
+            .info
+            .params
+            .iter()
+            .map(|(param, info)| (*param, osdi_ids[info.name.as_str()]))
+            .collect();
+
+        let unopt_unknowns = opt
+            .dae_system
+            .unknowns
+            .iter()
+            .map(|kind| unopt.dae_system.unknowns.index(kind))
+            .collect();
+
+        Ok(Harness { db, desc, opt, unopt, literals, rng, osdi_params, unopt_unknowns })
+    }
+
+    fn run(mut self) -> Result {
+        let defaults = self.default_params();
+        for param_set in 0..NUM_PARAM_SETS {
+            // the first parameter set always uses the default values
+            let mut params = Vec::new();
+            if param_set != 0 {
+                for &(param, default) in &defaults {
+                    if self.rng.next_f64() < PERTURB_PROBABILITY {
+                        let val = if default == 0.0 {
+                            self.rng.range(0.0, ZERO_PERTURBATION)
+                        } else {
+                            default * self.rng.range(0.8, 1.2)
+                        };
+                        params.push((param, val));
+                    }
+                }
+            }
+            let temperature = self.rng.range(250.0, 400.0);
+            self.run_param_set(&params, temperature)
+                .map_err(|err| format!("parameter set {param_set} (T={temperature}K): {err}"))?;
+        }
+        Ok(())
+    }
+
+    /// the default values of all real (model and instance) parameters after processing
+    fn default_params(&self) -> Vec<(Parameter, f64)> {
+        let model = InterpretedModel::setup(
+            self.opt,
+            self.literals,
+            ParamValues::default(),
+            SimParams::default(),
+        );
+        let inst = model.setup_instance(
+            ParamValues::default(),
+            300.0,
+            self.desc.num_terminals,
+            SimParams::default(),
+        );
+        self.opt
+            .info
+            .params
+            .keys()
+            .filter(|param| param.ty(self.db) == Type::Real)
+            .filter_map(|&param| {
+                let val = model.param(param).or_else(|| inst.param(param))?.f64();
+                val.is_finite().then_some((param, val))
+            })
+            .collect()
+    }
+
+    fn run_param_set(
+        &mut self,
+        params: &[(Parameter, f64)],
+        temperature: f64,
+    ) -> Result<(), String> {
+        let connected = self.desc.num_terminals;
+
+        // native
+        let model = self.desc.new_model();
+        let mut given = ParamValues::default();
+        for &(param, val) in params {
+            model.set_real_param(self.osdi_params[&param], val);
+            given.set_param(param, val);
+        }
+        let osdi_model_res = model.process_params();
+
+        // interpreted
+        let sim_params = SimParams::default();
+        let opt_model = InterpretedModel::setup(self.opt, self.literals, given.clone(), sim_params);
+        let unopt_model = InterpretedModel::setup(self.unopt, self.literals, given, sim_params);
+
+        let model_ok = |model: &InterpretedModel| {
+            model.errors.is_empty() && model.ret_flags & EVAL_RET_FLAG_FATAL == 0
+        };
+        let (opt_ok, unopt_ok) = (model_ok(&opt_model), model_ok(&unopt_model));
+        if osdi_model_res.is_ok() != opt_ok || opt_ok != unopt_ok {
+            return Err(format!(
+                "model setup diverges: osdi {osdi_model_res:?}, optimized {:?}, unoptimized {:?}",
+                opt_model.errors, unopt_model.errors
+            ));
+        }
+        if !opt_ok {
+            return Ok(());
+        }
+
+        let mut instance = model.new_instance();
+        let osdi_inst_res = instance.mock_simulation(&model, connected, temperature);
+        let opt_inst =
+            opt_model.setup_instance(ParamValues::default(), temperature, connected, sim_params);
+        let unopt_inst =
+            unopt_model.setup_instance(ParamValues::default(), temperature, connected, sim_params);
+
+        let inst_ok = |inst: &InterpretedInstance| {
+            inst.errors.is_empty() && inst.ret_flags & EVAL_RET_FLAG_FATAL == 0
+        };
+        let (opt_ok, unopt_ok) = (inst_ok(&opt_inst), inst_ok(&unopt_inst));
+        if osdi_inst_res.is_ok() != opt_ok || opt_ok != unopt_ok {
+            return Err(format!(
+                "instance setup diverges: osdi {:?}, optimized {:?}, unoptimized {:?}",
+                osdi_inst_res.as_ref().map(|_| ()),
+                opt_inst.errors,
+                unopt_inst.errors
+            ));
+        }
+        let Ok(mut sim) = osdi_inst_res else { return Ok(()) };
+
+        let opt_collapsed: Vec<_> = opt_inst.collapsed_pairs().map(usize::from).collect();
+        let osdi_collapsed: Vec<_> = instance
+            .collapsed()
+            .iter()
+            .enumerate()
+            .filter_map(|(pair, &collapsed)| collapsed.then_some(pair))
+            .collect();
+        if opt_collapsed != osdi_collapsed {
+            return Err(format!(
+                "collapsed nodes diverge: osdi {osdi_collapsed:?}, optimized {opt_collapsed:?}"
+            ));
+        }
+
+        let node_mapping: Vec<_> =
+            instance.node_mapping().iter().map(|node| node.get() as usize).collect();
+        for bias_point in 0..NUM_BIAS_POINTS {
+            for val in &mut sim.solve[1..] {
+                *val = self.rng.range(-1.0, 1.0);
+            }
+            let prev_solve: TiVec<SimUnknown, f64> =
+                node_mapping.iter().map(|&node| sim.solve[node]).collect();
+
+            let ret_flags = instance.eval(&model, &mut sim, EvalFlags::empty());
+            instance.load_noise(&model, &mut sim, NOISE_FREQ);
+            let native = Results {
+                ret_flags: ret_flags.bits(),
+                residuals: instance.read_node_residuals(),
+                jacobian: instance.read_matrix_entries(&model),
+                noise: sim.noise_dense.clone(),
+            };
+
+            let optimized = self.opt_results(eval(self.opt, &opt_inst, &prev_solve))?;
+            let unopt_solve: TiVec<SimUnknown, f64> = self
+                .unopt
+                .dae_system
+                .unknowns
+                .iter()
+                .map(|kind| {
+                    self.opt.dae_system.unknowns.index(kind).map_or(0.0, |node| prev_solve[node])
+                })
+                .collect();
+            let unoptimized = self.unopt_results(eval(self.unopt, &unopt_inst, &unopt_solve))?;
+
+            let res = compare(self, "optimized MIR", &native, &optimized)
+                .and_then(|_| compare(self, "unoptimized MIR", &optimized, &unoptimized));
+            if let Err(err) = res {
+                return Err(format!("bias point {bias_point} {:?}: {err}", &sim.solve[1..]));
+            }
+        }
+        Ok(())
+    }
+
+    fn opt_results(&self, res: EvalResult) -> Result<Results, String> {
+        let residuals = res
+            .resist_residual
+            .iter_enumerated()
+            .map(|(node, &resist)| {
+                [
+                    resist,
+                    res.react_residual[node],
+                    res.resist_lim_rhs[node],
+                    res.react_lim_rhs[node],
+                ]
+            })
+            .collect();
+        Ok(Results {
+            ret_flags: res.ret_flags,
+            residuals,
+            jacobian: res
+                .resist_jacobian
+                .iter()
+                .zip(res.react_jacobian.iter())
+                .map(|(&resist, &react)| (resist, react))
+                .collect(),
+            noise: res.noise_density(NOISE_FREQ).map_err(|err| err.to_string())?,
+        })
+    }
+
+    /// maps the results of the unoptimized MIR onto the unknowns, matrix entries and noise
+    /// sources of the optimized MIR. Anything that was removed by the optimizations is zero.
+    fn unopt_results(&self, res: EvalResult) -> Result<Results, String> {
+        let residuals = self
+            .unopt_unknowns
+            .iter()
+            .map(|node| {
+                node.map_or([0.0; 4], |node| {
+                    [
+                        res.resist_residual[node],
+                        res.react_residual[node],
+                        res.resist_lim_rhs[node],
+                        res.react_lim_rhs[node],
+                    ]
+                })
+            })
+            .collect();
+
+        let unopt_jacobian: HashMap<_, _> = self
+            .unopt
+            .dae_system
+            .jacobian
+            .iter_enumerated()
+            .map(|(id, entry)| {
+                let row = self.unopt.dae_system.unknowns[entry.row];
+                let col = self.unopt.dae_system.unknowns[entry.col];
+                ((row, col), (res.resist_jacobian[id], res.react_jacobian[id]))
+            })
+            .collect();
+        let jacobian = self
+            .opt
+            .dae_system
+            .jacobian
+            .iter()
+            .map(|entry| {
+                let row = self.opt.dae_system.unknowns[entry.row];
+                let col = self.opt.dae_system.unknowns[entry.col];
+                unopt_jacobian.get(&(row, col)).copied().unwrap_or((0.0, 0.0))
+            })
+            .collect();
+
+        // noise sources are matched by name and nodes (sources with the same key are summed)
+        let noise_key = |module: &CompiledModule, i: usize| {
+            let src = &module.dae_system.noise_sources[i];
+            let unknowns = &module.dae_system.unknowns;
+            (src.name, unknowns[src.hi], src.lo.map(|lo| unknowns[lo]))
+        };
+        let mut unopt_noise: HashMap<_, f64> = HashMap::new();
+        let densities = res.noise_density(NOISE_FREQ).map_err(|err| err.to_string())?;
+        for (i, density) in densities.into_iter().enumerate() {
+            *unopt_noise.entry(noise_key(self.unopt, i)).or_default() += density;
+        }
+        let noise = (0..self.opt.dae_system.noise_sources.len())
+            .map(|i| unopt_noise.get(&noise_key(self.opt, i)).copied().unwrap_or(0.0))
+            .collect();
+
+        Ok(Results { ret_flags: res.ret_flags, residuals, jacobian, noise })
+    }
+
+    fn node_name(&self, node: usize) -> &'static str {
+        unsafe { osdi_str(self.desc.nodes()[node].name) }
+    }
+}
+
+fn eval(
+    module: &CompiledModule,
+    inst: &InterpretedInstance,
+    prev_solve: &TiVec<SimUnknown, f64>,
+) -> EvalResult {
+    let num_states = module.intern.lim_state.len();
+    let prev_state = vec![0.0; num_states];
+    let mut next_state = vec![0.0; num_states];
+    inst.eval(&mut SimInfo {
+        flags: CALC_ALL.bits(),
+        abstime: 0.0,
+        prev_solve,
+        prev_state: &prev_state,
+        next_state: &mut next_state,
+        simparams: SimParams::default(),
+        limits: &[],
+    })
+}
+
+/// reports the first value in `res` that diverges from `reference`
+fn compare(
+    harness: &Harness,
+    backend: &str,
+    reference: &Results,
+    res: &Results,
+) -> Result<(), String> {
+    if reference.ret_flags != res.ret_flags {
+        return Err(format!(
+            "{backend}: return flags diverge (expected {}, found {})",
+            reference.ret_flags, res.ret_flags
+        ));
+    }
+
+    const RESIDUALS: [&str; 4] =
+        ["resist residual", "react residual", "resist lim_rhs", "react lim_rhs"];
+    for (kind, name) in RESIDUALS.iter().enumerate() {
+        let scale = max_abs(reference.residuals.iter().map(|residual| residual[kind]));
+        for (node, (expected, found)) in reference.residuals.iter().zip(&res.residuals).enumerate()
+        {
+            check(scale, expected[kind], found[kind]).map_err(|msg| {
+                format!("{backend}: {name} of node {} diverges: {msg}", harness.node_name(node))
+            })?;
+        }
+    }
+
+    let entries = harness.desc.matrix_entries();
+    for react in [false, true] {
+        let get = |entry: &(f64, f64)| if react { entry.1 } else { entry.0 };
+        let scale = max_abs(reference.jacobian.iter().map(get));
+        for (i, (expected, found)) in reference.jacobian.iter().zip(&res.jacobian).enumerate() {
+            check(scale, get(expected), get(found)).map_err(|msg| {
+                let nodes = entries[i].nodes;
+                format!(
+                    "{backend}: {} jacobian entry ({}, {}) diverges: {msg}",
+                    if react { "react" } else { "resist" },
+                    harness.node_name(nodes.node_1 as usize),
+                    harness.node_name(nodes.node_2 as usize),
+                )
+            })?;
+        }
+    }
+
+    let scale = max_abs(reference.noise.iter().copied());
+    for (i, (&expected, &found)) in reference.noise.iter().zip(&res.noise).enumerate() {
+        check(scale, expected, found).map_err(|msg| {
+            let name = unsafe { osdi_str(harness.desc.noise()[i].name) };
+            format!("{backend}: noise source {name:?} diverges: {msg}")
+        })?;
+    }
+
+    Ok(())
+}
+
+fn max_abs(vals: impl Iterator<Item = f64>) -> f64 {
+    vals.filter(|val| val.is_finite()).fold(0.0, |max, val| max.max(val.abs()))
+}
+
+fn check(scale: f64, expected: f64, found: f64) -> Result<(), String> {
+    if expected.is_nan() && found.is_nan() || expected == found {
+        return Ok(());
+    }
+    let tol = REL_TOL * expected.abs().max(found.abs()) + ABS_TOL * scale;
+    if (expected - found).abs() <= tol {
+        Ok(())
+    } else {
+        Err(format!("expected {expected:e}, found {found:e}"))
+    }
+}
+
+/// A small deterministic xorshift generator so that failures are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: &str) -> Rng {
+        // FNV-1a
+        let seed = seed.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        Rng(seed | 1)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn range(&mut self, lo: f64, hi: f64) -> f64 {
+        lo + (hi - lo) * self.next_f64()
+    }
+}
@@ -1,23 +1,24 @@
 use std::f64::consts;
 use std::path::Path;
 
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use expect_test::expect_file;
 use float_cmp::assert_approx_eq;
 use llvm_sys::target_machine::LLVMCodeGenOptLevel;
 use mini_harness::{harness, Result};
-use openvaf::{CompilationDestination, CompilationTermination};
+use openvaf::{CompilationDestination, CompilationTermination, JitTermination};
 use stdx::{ignore_dev_tests, openvaf_test_data, project_root};
-use target::spec::Target;
+use target::spec::{LinkerFlavor, Target};
 
-use crate::load::{load_osdi_lib, EvalFlags, OsdiDescriptor};
+use crate::load::{load_osdi_jit, load_osdi_lib, osdi_str, EvalFlags, OsdiDescriptor};
 use crate::mock_sim::{MockSimulation, ALPHA};
 
+mod differential;
 mod load;
 mod mock_sim;
 
-fn compile_and_load(root_file: &Utf8Path) -> &'static OsdiDescriptor {
-    let openvaf_opts = openvaf::Opts {
+fn opts(root_file: &Utf8Path) -> openvaf::Opts {
+    openvaf::Opts {
         defines: Vec::new(),
         codegen_opts: Vec::new(),
         lints: Vec::new(),
@@ -35,9 +36,16 @@ fn compile_and_load(root_file: &Utf8Path) -> &'static OsdiDescriptor {
         dump_unopt_mir: false,
         dump_ir: false,
         dump_unopt_ir: false,
-    };
+        dump_opt_stats: false,
+        param_sensitivities: false,
+        hessian: false,
+        debug_info: false,
+        modelcard: None,
+    }
+}
 
-    let res = openvaf::compile(&openvaf_opts).unwrap();
+fn compile_and_load(root_file: &Utf8Path) -> &'static OsdiDescriptor {
+    let res = openvaf::compile(&opts(root_file)).unwrap();
     let lib_file = match res {
         CompilationTermination::Compiled { lib_file } => lib_file,
         CompilationTermination::FatalDiagnostic => {
@@ -49,6 +57,38 @@ fn compile_and_load(root_file: &Utf8Path) -> &'static OsdiDescriptor {
     &libs[0]
 }
 
+fn jit_compile_and_load(root_file: &Utf8Path) -> &'static OsdiDescriptor {
+    let res = openvaf::compile_jit(&opts(root_file)).unwrap();
+    let lib = match res {
+        JitTermination::Compiled { lib } => lib,
+        JitTermination::FatalDiagnostic => {
+            panic!("openvaf: compilation of {root_file} failed");
+        }
+    };
+    let libs = unsafe { load_osdi_jit(lib).unwrap() };
+    assert_eq!(libs.len(), 1);
+    &libs[0]
+}
+
+/// A path for a library that is only used by a single test. The libraries are opened with
+/// dlopen so each test needs its own file, placed outside of the checked in test data.
+fn test_lib_file(name: &str) -> Utf8PathBuf {
+    Utf8PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+/// Compiles a model with custom `opts` and loads its (only) descriptor
+fn compile_opts_and_load(opts: &openvaf::Opts) -> Result<&'static OsdiDescriptor> {
+    let lib_file = match openvaf::compile(opts)? {
+        CompilationTermination::Compiled { lib_file } => lib_file,
+        CompilationTermination::FatalDiagnostic => {
+            panic!("openvaf: compilation of {} failed", opts.input);
+        }
+    };
+    let libs = unsafe { load_osdi_lib(&lib_file)? };
+    assert_eq!(libs.len(), 1);
+    Ok(&libs[0])
+}
+
 // fn integration_test(dir: &str) -> Result {
 //     let path: Utf8PathBuf = project_root().join("integration_tests").try_into().unwrap();
 //     let name = dir.to_lowercase();
@@ -183,6 +223,30 @@ macro_rules! assert_approx_eq {
     };
 }
 
+/// Checks the residual and jacobian of `diode_lim.va` (compiled to `desc`) at a fixed bias
+/// point, used to test the different ways a model can be compiled and loaded.
+fn check_diode_lim(desc: &OsdiDescriptor) -> Result<()> {
+    const IS: f64 = 1e-12;
+    const CJ0: f64 = 10e-9;
+    const KB: f64 = 1.3806488e-23;
+    const Q: f64 = 1.602176565e-19;
+    const VT: f64 = KB * 300.0 / Q;
+
+    let model = desc.new_model();
+    model.set_real_param(1, IS);
+    model.set_real_param(5, CJ0);
+    model.process_params()?;
+    let mut instance = model.new_instance();
+    let mut sim = instance.mock_simulation(&model, desc.num_terminals, 300.0)?;
+    sim.set_voltage("A", 0.3);
+    instance.eval(&model, &mut sim, EvalFlags::empty());
+    instance.load_dae(&model, &mut sim);
+
+    assert_approx_eq!(sim.read_residual("A").0, IS * (f64::exp(0.3 / VT) - 1.0));
+    assert_approx_eq!(sim.read_jacobian("A", "A").0, IS / VT * f64::exp(0.3 / VT));
+    Ok(())
+}
+
 fn test_noise() -> Result<()> {
     if stdx::IS_CI && cfg!(windows) {
         return Ok(());
@@ -222,8 +286,220 @@ fn test_noise() -> Result<()> {
     Ok(())
 }
 
+fn test_jit() -> Result<()> {
+    if stdx::IS_CI && cfg!(windows) {
+        return Ok(());
+    }
+
+    // the JIT must produce the same descriptor as the linked shared object
+    let main_file = openvaf_test_data("osdi").join("diode_lim.va");
+    let main_file: &Utf8Path = main_file.as_path().try_into().unwrap();
+    let desc = jit_compile_and_load(main_file);
+    let expect = format!("{desc:?}");
+    expect_file![openvaf_test_data("osdi").join("diode_lim.snap")].assert_eq(&expect);
+    check_diode_lim(desc)
+}
+
+fn test_builtin_linker() -> Result<()> {
+    let main_file = openvaf_test_data("osdi").join("diode_lim.va");
+    let mut opts = opts(main_file.as_path().try_into().unwrap());
+    opts.target.options.linker_flavor = LinkerFlavor::Builtin;
+    if !(opts.target.arch == "x86_64" && cfg!(target_os = "linux")) {
+        return Ok(());
+    }
+
+    let lib_file = test_lib_file("diode_lim_builtin.osdi");
+    opts.output = CompilationDestination::Path { lib_file };
+    let lib_file = match openvaf::compile(&opts)? {
+        CompilationTermination::Compiled { lib_file } => lib_file,
+        CompilationTermination::FatalDiagnostic => panic!("openvaf: compilation failed"),
+    };
+    let libs = unsafe { load_osdi_lib(&lib_file)? };
+    assert_eq!(libs.len(), 1);
+    let desc = &libs[0];
+    let expect = format!("{desc:?}");
+    expect_file![openvaf_test_data("osdi").join("diode_lim.snap")].assert_eq(&expect);
+    check_diode_lim(desc)
+}
+
+/// Evaluates `sensitivity.va` at a fixed bias point with `params` set. Returns the resistive
+/// and reactive residual of each node followed by the operating point variables or, if
+/// `param` is set, the sensitivities of these values to `param`.
+fn eval_sensitivity_model(
+    desc: &'static OsdiDescriptor,
+    params: &[(u32, f64)],
+    param: Option<u32>,
+) -> Result<Vec<f64>> {
+    let model = desc.new_model();
+    for &(id, val) in params {
+        model.set_real_param(id, val);
+    }
+    model.process_params()?;
+    let mut instance = model.new_instance();
+    let mut sim = instance.mock_simulation(&model, desc.num_terminals, 300.0)?;
+    sim.set_voltage("A", 0.65);
+    sim.set_voltage("CI", 0.05);
+    instance.eval(&model, &mut sim, EvalFlags::CALC_OP | EvalFlags::CALC_PARAM_SENSITIVITY);
+
+    let res = if let Some(param) = param {
+        let mut resist = vec![0.0; sim.solve.len()];
+        let mut react = vec![0.0; sim.solve.len()];
+        let mut opvars = vec![0.0; desc.num_opvars as usize];
+        desc.load_param_sensitivity(
+            instance.data,
+            model.data,
+            param,
+            resist.as_mut_ptr(),
+            react.as_mut_ptr(),
+        );
+        desc.load_opvar_sensitivity(instance.data, model.data, param, opvars.as_mut_ptr());
+        [resist, react, opvars].concat()
+    } else {
+        instance.load_dae(&model, &mut sim);
+        let opvars: Vec<_> = (0..desc.num_opvars).map(|i| instance.read_opvar(&model, i)).collect();
+        [sim.residual_resist.clone(), sim.residual_react.clone(), opvars].concat()
+    };
+    Ok(res)
+}
+
+fn test_param_sensitivity() -> Result<()> {
+    if stdx::IS_CI && cfg!(windows) {
+        return Ok(());
+    }
+
+    let main_file = openvaf_test_data("osdi").join("sensitivity.va");
+    let mut opts = opts(main_file.as_path().try_into().unwrap());
+    opts.param_sensitivities = true;
+    let desc = compile_opts_and_load(&opts)?;
+    assert_eq!(desc.opvars().len(), 2);
+
+    // gs is not set so its default 1/rs contributes to the sensitivity by rs
+    let params =
+        [("is", 1e-14), ("n", 1.0), ("rs", 10.0), ("gs", 0.1), ("cj0", 1e-12), ("tt", 1e-9)];
+    for (name, val) in params {
+        let id = desc.param_id(name);
+        let analytic = eval_sensitivity_model(desc, &[], Some(id))?;
+        let step = 1e-6 * val;
+        let hi = eval_sensitivity_model(desc, &[(id, val + step)], None)?;
+        let lo = eval_sensitivity_model(desc, &[(id, val - step)], None)?;
+        let fd: Vec<_> = hi.iter().zip(&lo).map(|(hi, lo)| (hi - lo) / (2.0 * step)).collect();
+        let scale = fd.iter().fold(0f64, |acc, val| acc.max(val.abs()));
+        for (i, (&analytic, &fd)) in analytic.iter().zip(&fd).enumerate() {
+            if (analytic - fd).abs() > 1e-5 * fd.abs() + 1e-9 * scale {
+                return Err(format!("sensitivity {i} by {name}: {analytic} != {fd} (FD)").into());
+            }
+        }
+    }
+
+    // integer parameters have no sensitivities
+    let analytic = eval_sensitivity_model(desc, &[], Some(desc.param_id("mode")))?;
+    assert!(analytic.iter().all(|&val| val == 0.0));
+    Ok(())
+}
+
+fn test_modelcard() -> Result<()> {
+    if stdx::IS_CI && cfg!(windows) {
+        return Ok(());
+    }
+
+    let main_file = openvaf_test_data("osdi").join("sensitivity.va");
+    let mut opts = opts(main_file.as_path().try_into().unwrap());
+    let lib_file = test_lib_file("sensitivity_reference.osdi");
+    opts.output = CompilationDestination::Path { lib_file };
+    let reference = compile_opts_and_load(&opts)?;
+    let lib_file = test_lib_file("sensitivity_specialized.osdi");
+    opts.output = CompilationDestination::Path { lib_file };
+    opts.modelcard = Some(opts.input.with_extension("mod"));
+    let desc = compile_opts_and_load(&opts)?;
+
+    let names = |desc: &OsdiDescriptor| -> Vec<&str> {
+        desc.params().iter().map(|param| unsafe { osdi_str(*param.name) }).collect()
+    };
+    let expected: Vec<_> =
+        names(reference).into_iter().filter(|name| !["is", "tt", "mode"].contains(name)).collect();
+    assert_eq!(names(desc), expected);
+
+    let params = [(reference.param_id("is"), 2e-14), (reference.param_id("tt"), 0.5e-9)];
+    let expected = eval_sensitivity_model(reference, &params, None)?;
+    let res = eval_sensitivity_model(desc, &[], None)?;
+    for (i, (&res, &expected)) in res.iter().zip(&expected).enumerate() {
+        if (res - expected).abs() > 1e-12 * expected.abs() {
+            return Err(format!("value {i}: {res} != {expected}").into());
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates `sensitivity.va` at the node voltages `solve` (indexed like the nodes of the mock
+/// simulation). Returns the resistive and reactive jacobian entries or, if `dir` is set, the
+/// product of the hessian and `dir`.
+fn eval_hessian_model(
+    desc: &'static OsdiDescriptor,
+    solve: &[f64],
+    dir: Option<&[f64]>,
+) -> Result<Vec<f64>> {
+    let model = desc.new_model();
+    model.process_params()?;
+    let mut instance = model.new_instance();
+    let mut sim = instance.mock_simulation(&model, desc.num_terminals, 300.0)?;
+    sim.solve.copy_from_slice(solve);
+    instance.eval(&model, &mut sim, EvalFlags::CALC_HESSIAN);
+
+    let (resist, react) = if let Some(dir) = dir {
+        let mut dir = dir.to_owned();
+        let mut resist = vec![0.0; desc.matrix_entries().len()];
+        let mut react = vec![0.0; desc.matrix_entries().len()];
+        desc.load_hessian_vector_product(
+            instance.data,
+            model.data,
+            dir.as_mut_ptr(),
+            resist.as_mut_ptr(),
+            react.as_mut_ptr(),
+        );
+        (resist, react)
+    } else {
+        instance.read_matrix_entries(&model).into_iter().unzip()
+    };
+    Ok([resist, react].concat())
+}
+
+fn test_hessian() -> Result<()> {
+    if stdx::IS_CI && cfg!(windows) {
+        return Ok(());
+    }
+
+    let main_file = openvaf_test_data("osdi").join("sensitivity.va");
+    let mut opts = opts(main_file.as_path().try_into().unwrap());
+    opts.hessian = true;
+    let lib_file = test_lib_file("sensitivity_hessian.osdi");
+    opts.output = CompilationDestination::Path { lib_file };
+    let desc = compile_opts_and_load(&opts)?;
+
+    // nodes of the mock simulation: gnd, A, C, CI
+    let solve = [0.0, 0.65, 0.0, 0.05];
+    let dir = [0.0, 0.3, -0.2, 0.5];
+    let analytic = eval_hessian_model(desc, &solve, Some(&dir))?;
+
+    let step = 1e-6;
+    let shift = |sign: f64| -> Vec<f64> {
+        solve.iter().zip(&dir).map(|(val, dir)| val + sign * step * dir).collect()
+    };
+    let hi = eval_hessian_model(desc, &shift(1.0), None)?;
+    let lo = eval_hessian_model(desc, &shift(-1.0), None)?;
+    let fd: Vec<_> = hi.iter().zip(&lo).map(|(hi, lo)| (hi - lo) / (2.0 * step)).collect();
+    let scale = fd.iter().fold(0f64, |acc, val| acc.max(val.abs()));
+    assert!(scale > 0.0);
+    for (i, (&analytic, &fd)) in analytic.iter().zip(&fd).enumerate() {
+        if (analytic - fd).abs() > 1e-5 * fd.abs() + 1e-9 * scale {
+            return Err(format!("hessian vector product {i}: {analytic} != {fd} (FD)").into());
+        }
+    }
+    Ok(())
+}
+
 harness! {
     // TODO: run this in CI, somehow this test is flakey tough regarding the linker invocation (and really slow)
     Test::from_dir("integration", &integration_test, &ignore_dev_tests, &project_root().join("integration_tests")),
-    [Test::new("$limit", &test_limit),Test::new("noise", &test_noise)]
+    Test::from_dir("differential", &differential::differential_test, &ignore_dev_tests, &project_root().join("integration_tests")),
+    [Test::new("$limit", &test_limit),Test::new("noise", &test_noise),Test::new("jit", &test_jit),Test::new("builtin_linker", &test_builtin_linker),Test::new("param_sensitivity", &test_param_sensitivity),Test::new("hessian", &test_hessian),Test::new("modelcard", &test_modelcard)]
 }
@@ -7,11 +7,12 @@ use std::panic::catch_unwind;
 use std::process::exit;
 use std::{ptr, slice};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use bitflags::bitflags;
 use camino::Utf8Path;
 use libc::c_void;
 use libloading::Library;
+use openvaf::OsdiJit;
 use stdx::format_to;
 use stdx::iter::zip;
 
@@ -33,6 +34,22 @@ impl OsdiDescriptor {
         unsafe { slice::from_raw_parts(self.param_opvar, self.num_params as usize) }
     }
 
+    pub fn opvars(&self) -> &[OsdiParamOpvar] {
+        // # SAFETY: OsdiDescriptor can only be constructed from FFI and is assumed to contain
+        // valid data
+        unsafe {
+            slice::from_raw_parts(self.param_opvar, (self.num_params + self.num_opvars) as usize)
+        }
+        .split_at(self.num_params as usize)
+        .1
+    }
+
+    /// The id of the parameter `name`
+    pub fn param_id(&self, name: &str) -> u32 {
+        let pos = self.params().iter().position(|param| unsafe { osdi_str(*param.name) } == name);
+        pos.unwrap_or_else(|| panic!("unknown parameter {name}")) as u32
+    }
+
     pub fn collapsible(&self) -> &[OsdiNodePair] {
         // SAFETY: self.data is a valid allocation and the descriptor is assumed valid
         unsafe { slice::from_raw_parts(self.collapsible, self.num_collapsible as usize) }
@@ -310,6 +327,82 @@ impl OsdiInstance {
         Ok(internal_nodes)
     }
 
+    /// Reads the residuals calculated by the last call to `eval` for each node (resist, react,
+    /// resist_lim_rhs, react_lim_rhs). Unlike `load_residual_*` collapsed nodes are not summed.
+    pub fn read_node_residuals(&self) -> Vec<[f64; 4]> {
+        let data = self.data as *const u8;
+        let read = |off: u32| {
+            if off == u32::MAX {
+                0.0
+            } else {
+                // SAFETY: offsets are provided by the descriptor and point into the instance data
+                unsafe { data.add(off as usize).cast::<f64>().read() }
+            }
+        };
+        self.descriptor
+            .nodes()
+            .iter()
+            .map(|node| {
+                [
+                    read(node.resist_residual_off),
+                    read(node.react_residual_off),
+                    read(node.resist_limit_rhs_off),
+                    read(node.react_limit_rhs_off),
+                ]
+            })
+            .collect()
+    }
+
+    /// Loads the (resist, react) jacobian calculated by the last call to `eval` for each matrix
+    /// entry. The matrix pointers are temporarily redirected so that collapsed entries are
+    /// not summed.
+    pub fn read_matrix_entries(&self, model: &OsdiModel) -> Vec<(f64, f64)> {
+        let entries = self.descriptor.matrix_entries();
+        let mut resist = vec![0.0; entries.len()];
+        let mut react = vec![0.0; entries.len()];
+        let data = self.data as *mut u8;
+        let ptrs_resist = self.matrix_ptrs_resist();
+        let (resist_ptr, react_ptr) = (resist.as_mut_ptr(), react.as_mut_ptr());
+        let mut old_ptrs = Vec::with_capacity(2 * entries.len());
+
+        for (i, (entry, ptr_resist)) in zip(entries, ptrs_resist).enumerate() {
+            // SAFETY: offsets are provided by the descriptor and point into the instance data
+            unsafe {
+                old_ptrs.push(ptr_resist.replace(resist_ptr.add(i)));
+                if entry.react_ptr_off != u32::MAX {
+                    let ptr: *mut *mut f64 = data.add(entry.react_ptr_off as usize).cast();
+                    old_ptrs.push(ptr.replace(react_ptr.add(i)));
+                }
+            }
+        }
+
+        self.descriptor.load_jacobian_resist(self.data, model.data);
+        self.descriptor.load_jacobian_react(self.data, model.data, 1.0);
+
+        let mut old_ptrs = old_ptrs.into_iter();
+        for (entry, ptr_resist) in zip(entries, ptrs_resist) {
+            ptr_resist.set(old_ptrs.next().unwrap());
+            if entry.react_ptr_off != u32::MAX {
+                unsafe {
+                    let ptr: *mut *mut f64 = data.add(entry.react_ptr_off as usize).cast();
+                    ptr.write(old_ptrs.next().unwrap());
+                }
+            }
+        }
+
+        zip(resist, react).collect()
+    }
+
+    /// Reads the value of the i-th (real) operating point variable calculated by the last
+    /// call to `eval` with `CALC_OP`.
+    pub fn read_opvar(&self, model: &OsdiModel, i: u32) -> f64 {
+        let id = self.descriptor.num_params + i;
+        let ptr = self.descriptor.access(self.data, model.data, id, ACCESS_FLAG_READ);
+        assert!(!ptr.is_null(), "invalid opvar access");
+        // SAFETY: the pointer was provided by the descriptor
+        unsafe { ptr.cast::<f64>().read() }
+    }
+
     // pub fn set_real_param(&mut self, param: u32, val: f64) {
     //     let ptr =
     //         unsafe { self.descriptor.access(ptr::null_mut(), self.data, param, ACCESS_FLAG_SET) };
@@ -324,29 +417,39 @@ impl OsdiInstance {
 pub unsafe fn load_osdi_lib(path: &Utf8Path) -> Result<&'static [OsdiDescriptor]> {
     let lib = Library::new(path)?;
     let lib = Box::leak(Box::new(lib));
+    load_osdi_symbols(|sym| Ok(*lib.get::<*mut c_void>(sym.as_bytes())?))
+}
+
+pub unsafe fn load_osdi_jit(lib: OsdiJit) -> Result<&'static [OsdiDescriptor]> {
+    let lib = Box::leak(Box::new(lib));
+    load_osdi_symbols(|sym| lib.symbol(sym).map_err(|err| anyhow!("{err}")))
+}
 
-    let major_version: &u32 = *lib.get(b"OSDI_VERSION_MAJOR\0")?;
-    let minor_version: &u32 = *lib.get(b"OSDI_VERSION_MINOR\0")?;
+unsafe fn load_osdi_symbols(
+    sym: impl Fn(&str) -> Result<*mut c_void>,
+) -> Result<&'static [OsdiDescriptor]> {
+    let major_version = *(sym("OSDI_VERSION_MAJOR")? as *const u32);
+    let minor_version = *(sym("OSDI_VERSION_MINOR")? as *const u32);
 
-    if *major_version != 0 || *minor_version != 4 {
+    if major_version != 0 || minor_version != 4 {
         bail!("invalid version v{major_version}.{minor_version}",);
     }
 
-    let num_descriptors: &u32 = *lib.get(b"OSDI_NUM_DESCRIPTORS\0")?;
-    let descriptors: *const OsdiDescriptor = *lib.get(b"OSDI_DESCRIPTORS\0")?;
+    let num_descriptors = *(sym("OSDI_NUM_DESCRIPTORS")? as *const u32);
+    let descriptors = sym("OSDI_DESCRIPTORS")? as *const OsdiDescriptor;
 
     let descriptors: &[OsdiDescriptor] =
-        slice::from_raw_parts(descriptors, *num_descriptors as usize);
+        slice::from_raw_parts(descriptors, num_descriptors as usize);
 
-    if let Ok(osdi_log_ptr) =
-        lib.get::<*mut unsafe extern "C" fn(*mut c_void, *const c_char, u32)>(b"osdi_log\0")
-    {
+    if let Ok(osdi_log_ptr) = sym("osdi_log") {
+        let osdi_log_ptr =
+            osdi_log_ptr as *mut unsafe extern "C" fn(*mut c_void, *const c_char, u32);
         osdi_log_ptr.write(osdi_log)
     }
-    if let Ok(osdi_lim_table) = lib.get(b"OSDI_LIM_TABLE\0") {
-        let lim_table_base: *mut OsdiLimFunction = *osdi_lim_table;
-        let lim_table_len: &u32 = *lib.get(b"OSDI_LIM_TABLE_LEN\0")?;
-        let lim_table = slice::from_raw_parts_mut(lim_table_base, *lim_table_len as usize);
+    if let Ok(osdi_lim_table) = sym("OSDI_LIM_TABLE") {
+        let lim_table_base = osdi_lim_table as *mut OsdiLimFunction;
+        let lim_table_len = *(sym("OSDI_LIM_TABLE_LEN")? as *const u32);
+        let lim_table = slice::from_raw_parts_mut(lim_table_base, lim_table_len as usize);
         for lim_func in lim_table {
             if osdi_str(lim_func.name) == "pnjlim" {
                 assert_eq!(lim_func.num_args, 2);
@@ -540,6 +643,8 @@ bitflags! {
         const ANALYSIS_IC = ANALYSIS_IC;
         const ANALYSIS_STATIC = ANALYSIS_STATIC;
         const ANALYSIS_NODESET = ANALYSIS_NODESET;
+        const CALC_PARAM_SENSITIVITY = CALC_PARAM_SENSITIVITY;
+        const CALC_HESSIAN = CALC_HESSIAN;
     }
 }
 
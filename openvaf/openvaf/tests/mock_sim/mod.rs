@@ -8,6 +8,14 @@ use libc::c_void;
 use stdx::iter::zip;
 
 pub const ALPHA: f64 = 0.172;
+/// flags that are always passed to `eval` by the mock simulator
+pub const CALC_ALL: EvalFlags = EvalFlags::CALC_RESIST_JACOBIAN
+    .union(EvalFlags::CALC_RESIST_RESIDUAL)
+    .union(EvalFlags::CALC_RESIST_LIM_RHS)
+    .union(EvalFlags::CALC_REACT_JACOBIAN)
+    .union(EvalFlags::CALC_REACT_RESIDUAL)
+    .union(EvalFlags::CALC_REACT_LIM_RHS)
+    .union(EvalFlags::CALC_NOISE);
 
 use crate::load::{
     osdi_str, EvalFlags, EvalRetFlags, OsdiInstance, OsdiModel, OsdiSimInfo, OsdiSimParas,
@@ -225,13 +233,7 @@ impl OsdiInstance {
         mut flags: EvalFlags,
     ) -> EvalRetFlags {
         // always calculate everything
-        flags |= EvalFlags::CALC_RESIST_JACOBIAN
-            | EvalFlags::CALC_RESIST_RESIDUAL
-            | EvalFlags::CALC_RESIST_LIM_RHS
-            | EvalFlags::CALC_REACT_JACOBIAN
-            | EvalFlags::CALC_REACT_RESIDUAL
-            | EvalFlags::CALC_REACT_LIM_RHS
-            | EvalFlags::CALC_NOISE;
+        flags |= CALC_ALL;
         let sim_params = OsdiSimParas {
             names: &mut ptr::null_mut(),
             vals: ptr::null_mut(),
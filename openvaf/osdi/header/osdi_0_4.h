@@ -45,6 +45,8 @@
 #define ANALYSIS_IC 16384
 #define ANALYSIS_STATIC 32768
 #define ANALYSIS_NODESET 65536
+#define CALC_PARAM_SENSITIVITY 131072
+#define CALC_HESSIAN 262144
 
 #define EVAL_RET_FLAG_LIM 1
 #define EVAL_RET_FLAG_FATAL 2
@@ -221,6 +223,9 @@ typedef struct OsdiDescriptor {
   void (*load_jacobian_with_offset_react)(void *inst, void* model, size_t offset);
   OsdiNatureRef* unknown_nature;
   OsdiNatureRef* residual_nature;
+  void (*load_param_sensitivity)(void *inst, void *model, uint32_t param_id, double *dst_resist, double *dst_react);
+  void (*load_hessian_vector_product)(void *inst, void *model, double *dir, double *dst_resist, double *dst_react);
+  void (*load_opvar_sensitivity)(void *inst, void *model, uint32_t param_id, double *dst);
 }OsdiDescriptor;
 
 typedef struct OsdiNature {
@@ -14,7 +14,9 @@ use llvm_sys::core::{
 };
 use llvm_sys::{LLVMIntPredicate, LLVMLinkage, LLVMUnnamedAddr, LLVMValue};
 use mir::{FuncRef, Function};
-use mir_llvm::{BuiltCallbackFun, CallbackFun, CodegenCx, LLVMBackend, ModuleLlvm, UNNAMED};
+use mir_llvm::{
+    BuiltCallbackFun, CallbackFun, CodegenCx, DebugInfo, LLVMBackend, ModuleLlvm, UNNAMED,
+};
 use sim_back::dae::DaeSystem;
 use sim_back::init::Initialization;
 use sim_back::node_collapse::NodeCollapse;
@@ -85,6 +87,8 @@ pub struct OsdiCompilationUnit<'a, 'b, 'll> {
     pub cx: &'a CodegenCx<'b, 'll>,
     pub module: &'a OsdiModule<'b>,
     pub lim_dispatch_table: Option<&'ll llvm_sys::LLVMValue>,
+    /// set when the module is compiled with `--debug-info`
+    pub debug_info: Option<&'a DebugInfo<'ll>>,
 }
 
 impl<'a, 'b, 'll> OsdiCompilationUnit<'a, 'b, 'll> {
@@ -121,7 +125,21 @@ impl<'a, 'b, 'll> OsdiCompilationUnit<'a, 'b, 'll> {
             } else {
                 None
             };
-        OsdiCompilationUnit { db, inst_data, model_data, tys, cx, module, lim_dispatch_table }
+        OsdiCompilationUnit {
+            db,
+            inst_data,
+            model_data,
+            tys,
+            cx,
+            module,
+            lim_dispatch_table,
+            debug_info: None,
+        }
+    }
+
+    pub fn with_debug_info(mut self, debug_info: Option<&'a DebugInfo<'ll>>) -> Self {
+        self.debug_info = debug_info;
+        self
     }
 
     pub fn lim_dispatch_table(&self) -> &'ll llvm_sys::LLVMValue {
@@ -158,6 +176,7 @@ impl<'a> OsdiModule<'a> {
             model_param_setup,
             model_param_intern,
             node_collapse,
+            opt_stats: _,
         } = module;
         OsdiModule {
             sym,
@@ -0,0 +1,62 @@
+use hir::{CompilationDB, Type};
+use hir_lower::{HirInterner, PlaceKind, SourceLocMap};
+use llvm_sys::target_machine::LLVMCodeGenOptLevel;
+use mir::{Function, SourceLoc, Tag};
+use mir_llvm::{CodegenCx, DebugInfo, DebugType, FunctionDebugInfo};
+
+use crate::compilation_unit::{OsdiCompilationUnit, OsdiModule};
+
+/// Creates the compile unit for the file that declares `module`.
+pub(crate) fn new_debug_info<'ll>(
+    db: &CompilationDB,
+    module: &OsdiModule,
+    cx: &CodegenCx<'_, 'll>,
+    opt_lvl: LLVMCodeGenOptLevel,
+) -> DebugInfo<'ll> {
+    let path = module.info.module.decl_pos(db).file_path(db);
+    DebugInfo::new(cx, &path, opt_lvl != LLVMCodeGenOptLevel::LLVMCodeGenLevelNone)
+}
+
+impl<'a, 'll> OsdiCompilationUnit<'a, '_, 'll> {
+    /// Creates the debug info for `llfunc` which was generated from `func`. The source locations
+    /// of `func` are resolved with `srclocs` and the tags of its values refer to the places in
+    /// `places`. Returns `None` if the module is compiled without debug info.
+    pub fn function_debug_info(
+        &self,
+        llfunc: &'ll llvm_sys::LLVMValue,
+        name: &str,
+        func: &Function,
+        srclocs: &SourceLocMap,
+        places: &HirInterner,
+    ) -> Option<FunctionDebugInfo<'a, 'll>> {
+        let debug_info = self.debug_info?;
+        let db = self.db;
+        let decl = self.module.info.module.decl_pos(db);
+        let mut res = debug_info.function(llfunc, name, &decl.file_path(db), decl.line);
+
+        let mut locs: Vec<_> = func.srclocs.iter().map(|loc| loc.bits().unsigned_abs()).collect();
+        locs.sort_unstable();
+        locs.dedup();
+        for loc in locs {
+            let loc = SourceLoc::new(loc as i32);
+            if let Some(pos) = srclocs.source_pos(db, loc) {
+                res.add_location(loc, &pos.file_path(db), pos.line, pos.col);
+            }
+        }
+
+        for (i, kind) in places.outputs.keys().enumerate() {
+            let var = if let PlaceKind::Var(var) = *kind { var } else { continue };
+            let ty = match var.ty(db) {
+                Type::Real => DebugType::Real,
+                Type::Integer => DebugType::Integer,
+                Type::Bool => DebugType::Bool,
+                // strings and arrays are never stored in SSA values
+                _ => continue,
+            };
+            let pos = var.decl_pos(db);
+            res.add_variable(Tag::from(i), &var.name(db), &pos.file_path(db), pos.line, ty);
+        }
+
+        Some(res)
+    }
+}
@@ -19,9 +19,9 @@ use crate::bitfield::{is_flag_set, is_flag_set_mem, is_flag_unset};
 use crate::compilation_unit::{general_callbacks, OsdiCompilationUnit};
 use crate::inst_data::OsdiInstanceParam;
 use crate::metadata::osdi_0_4::{
-    ANALYSIS_IC, CALC_NOISE, CALC_OP, CALC_REACT_JACOBIAN, CALC_REACT_LIM_RHS, CALC_REACT_RESIDUAL,
-    CALC_RESIST_JACOBIAN, CALC_RESIST_LIM_RHS, CALC_RESIST_RESIDUAL, ENABLE_LIM, EVAL_RET_FLAG_LIM,
-    INIT_LIM,
+    ANALYSIS_IC, CALC_HESSIAN, CALC_NOISE, CALC_OP, CALC_PARAM_SENSITIVITY, CALC_REACT_JACOBIAN,
+    CALC_REACT_LIM_RHS, CALC_REACT_RESIDUAL, CALC_RESIST_JACOBIAN, CALC_RESIST_LIM_RHS,
+    CALC_RESIST_RESIDUAL, ENABLE_LIM, EVAL_RET_FLAG_LIM, INIT_LIM,
 };
 use crate::metadata::OsdiLimFunction;
 use crate::OsdiLimId;
@@ -102,6 +102,12 @@ impl<'ll> OsdiCompilationUnit<'_, '_, 'll> {
         let intern = module.intern;
 
         let mut builder = Builder::new(cx, func, llfunc, Some(cx.ty_int()), false);
+        let name = format!("eval_{}", &module.sym);
+        if let Some(debug_info) =
+            self.function_debug_info(llfunc, &name, func, &intern.srclocs, intern)
+        {
+            builder.set_debug_info(debug_info);
+        }
 
         let handle = unsafe { &*llvm_sys::core::LLVMGetParam(NonNull::from(llfunc).as_ptr(), 0) };
         let instance = unsafe { &*llvm_sys::core::LLVMGetParam(NonNull::from(llfunc).as_ptr(), 1) };
@@ -428,6 +434,31 @@ impl<'ll> OsdiCompilationUnit<'_, '_, 'll> {
                 }
             };
             Self::build_store_results(&mut builder, llfunc, &flags, CALC_NOISE, &store_noise);
+            let store_sensitivities = |builder: &mut Builder<'_, '_, 'll>| {
+                for sensitivity in &inst_data.param_sensitivities {
+                    for eval_output in sensitivity.eval_outputs() {
+                        inst_data.store_eval_output(eval_output, instance, builder)
+                    }
+                }
+                for sensitivity in &inst_data.opvar_sensitivities {
+                    inst_data.store_eval_output(sensitivity.val, instance, builder)
+                }
+            };
+            Self::build_store_results(
+                &mut builder,
+                llfunc,
+                &flags,
+                CALC_PARAM_SENSITIVITY,
+                &store_sensitivities,
+            );
+            let store_hessian = |builder: &mut Builder<'_, '_, 'll>| {
+                for entry in &inst_data.hessian {
+                    for eval_output in entry.eval_outputs() {
+                        inst_data.store_eval_output(eval_output, instance, builder)
+                    }
+                }
+            };
+            Self::build_store_results(&mut builder, llfunc, &flags, CALC_HESSIAN, &store_hessian);
 
             inst_data.store_bound_step(instance, &builder);
 
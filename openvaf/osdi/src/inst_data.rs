@@ -168,6 +168,103 @@ impl MatrixEntry {
     }
 }
 
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct ParamSensitivity {
+    pub param: Parameter,
+    pub unknown: SimUnknown,
+    pub resist: Option<EvalOutput>,
+    pub react: Option<EvalOutput>,
+}
+
+impl ParamSensitivity {
+    pub fn new<'ll>(
+        sensitivity: &dae::ParamSensitivity,
+        module: &OsdiModule<'_>,
+        slots: &mut TiMap<EvalOutputSlot, mir::Value, &'ll llvm_sys::LLVMType>,
+        ty_real: &'ll llvm_sys::LLVMType,
+    ) -> ParamSensitivity {
+        let mut get_output = |mut val| {
+            val = strip_optbarrier(module.eval, val);
+            if val == F_ZERO {
+                None
+            } else {
+                Some(EvalOutput::new(module, val, slots, false, ty_real))
+            }
+        };
+        ParamSensitivity {
+            param: sensitivity.param,
+            unknown: sensitivity.unknown,
+            resist: get_output(sensitivity.resist),
+            react: get_output(sensitivity.react),
+        }
+    }
+
+    pub fn eval_outputs(&self) -> impl Iterator<Item = EvalOutput> {
+        self.resist.into_iter().chain(self.react)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct OpVarSensitivity {
+    pub param: Parameter,
+    pub var: Variable,
+    pub val: EvalOutput,
+}
+
+impl OpVarSensitivity {
+    pub fn new<'ll>(
+        sensitivity: &dae::OpVarSensitivity,
+        module: &OsdiModule<'_>,
+        slots: &mut TiMap<EvalOutputSlot, mir::Value, &'ll llvm_sys::LLVMType>,
+        ty_real: &'ll llvm_sys::LLVMType,
+    ) -> OpVarSensitivity {
+        let val = strip_optbarrier(module.eval, sensitivity.val);
+        OpVarSensitivity {
+            param: sensitivity.param,
+            var: sensitivity.var,
+            val: EvalOutput::new(module, val, slots, false, ty_real),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct HessianEntry {
+    pub row: SimUnknown,
+    pub col: SimUnknown,
+    pub dir: SimUnknown,
+    pub resist: Option<EvalOutput>,
+    pub react: Option<EvalOutput>,
+}
+
+impl HessianEntry {
+    pub fn new<'ll>(
+        entry: &dae::HessianEntry,
+        module: &OsdiModule<'_>,
+        slots: &mut TiMap<EvalOutputSlot, mir::Value, &'ll llvm_sys::LLVMType>,
+        ty_real: &'ll llvm_sys::LLVMType,
+    ) -> HessianEntry {
+        let mut get_output = |mut val| {
+            val = strip_optbarrier(module.eval, val);
+            if val == F_ZERO {
+                None
+            } else {
+                Some(EvalOutput::new(module, val, slots, false, ty_real))
+            }
+        };
+        HessianEntry {
+            row: entry.row,
+            col: entry.col,
+            dir: entry.dir,
+            resist: get_output(entry.resist),
+            react: get_output(entry.react),
+        }
+    }
+
+    pub fn eval_outputs(&self) -> impl Iterator<Item = EvalOutput> {
+        self.resist.into_iter().chain(self.react)
+    }
+}
+
 #[derive(Debug)]
 pub struct NoiseSource {
     pub factor: EvalOutput,
@@ -218,6 +315,9 @@ pub struct OsdiInstanceData<'ll> {
 
     pub residual: TiVec<SimUnknown, Residual>,
     pub noise: Vec<NoiseSource>,
+    pub param_sensitivities: Vec<ParamSensitivity>,
+    pub opvar_sensitivities: Vec<OpVarSensitivity>,
+    pub hessian: Vec<HessianEntry>,
     pub opvars: IndexMap<Variable, EvalOutput, RandomState>,
     pub jacobian: TiVec<MatrixEntryId, MatrixEntry>,
     pub bound_step: Option<EvalOutputSlot>,
@@ -278,6 +378,28 @@ impl<'ll> OsdiInstanceData<'ll> {
             .iter()
             .map(|source| NoiseSource::new(source, module, &mut eval_outputs, ty_f64))
             .collect();
+        let param_sensitivities = module
+            .dae_system
+            .param_sensitivities
+            .iter()
+            .map(|sensitivity| {
+                ParamSensitivity::new(sensitivity, module, &mut eval_outputs, ty_f64)
+            })
+            .collect();
+        let opvar_sensitivities = module
+            .dae_system
+            .opvar_sensitivities
+            .iter()
+            .map(|sensitivity| {
+                OpVarSensitivity::new(sensitivity, module, &mut eval_outputs, ty_f64)
+            })
+            .collect();
+        let hessian = module
+            .dae_system
+            .hessian
+            .iter()
+            .map(|entry| HessianEntry::new(entry, module, &mut eval_outputs, ty_f64))
+            .collect();
         let bound_step = module.intern.outputs.get(&PlaceKind::BoundStep).and_then(|val| {
             let mut val = val.expand()?;
             val = strip_optbarrier(module.eval, val);
@@ -332,6 +454,9 @@ impl<'ll> OsdiInstanceData<'ll> {
             cache_slots,
             residual,
             noise,
+            param_sensitivities,
+            opvar_sensitivities,
+            hessian,
             opvars,
             jacobian,
             bound_step,
@@ -0,0 +1,39 @@
+use mir_llvm::{LLVMString, ObjectBuffer, OrcJit};
+
+use crate::initialize_llvm;
+
+/// An OSDI library that was linked into the current process by the LLVM JIT
+/// (see [`compile_jit`](crate::compile_jit)).
+///
+/// The exported symbols (`OSDI_DESCRIPTORS`, `OSDI_VERSION_MAJOR`, `osdi_log`, ...) are the
+/// same ones a shared library produced by [`compile`](crate::compile) exports and can be
+/// looked up with [`OsdiJit::symbol`]. All pointers into the library are invalidated when
+/// the `OsdiJit` is dropped.
+pub struct OsdiJit {
+    jit: OrcJit,
+}
+
+impl OsdiJit {
+    pub(crate) fn new(objects: impl Iterator<Item = ObjectBuffer>) -> Result<OsdiJit, LLVMString> {
+        initialize_llvm();
+        let jit = unsafe { OrcJit::new()? };
+        for obj in objects {
+            jit.add_object(obj)?;
+        }
+        Ok(OsdiJit { jit })
+    }
+
+    /// Returns the address of the symbol `name` exported by the library
+    pub fn symbol<T>(&self, name: &str) -> Result<*mut T, LLVMString> {
+        self.jit.lookup(name).map(|ptr| ptr.cast())
+    }
+
+    /// Returns a pointer to the first element of `OSDI_DESCRIPTORS` together with the number
+    /// of descriptors. The pointer must be cast to the `OsdiDescriptor` struct of the OSDI header.
+    pub fn descriptors<T>(&self) -> Result<(*const T, usize), LLVMString> {
+        let num_descriptors: *mut u32 = self.symbol("OSDI_NUM_DESCRIPTORS")?;
+        let descriptors: *mut T = self.symbol("OSDI_DESCRIPTORS")?;
+        let num_descriptors = unsafe { *num_descriptors } as usize;
+        Ok((descriptors as *const T, num_descriptors))
+    }
+}
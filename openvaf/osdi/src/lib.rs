@@ -11,22 +11,26 @@ use hir_lower::{CallBackKind, HirInterner, ParamKind};
 use lasso::Rodeo;
 use llvm_sys::target::{LLVMABISizeOfType, LLVMDisposeTargetData};
 use llvm_sys::target_machine::LLVMCodeGenOptLevel;
-use mir_llvm::{CodegenCx, LLVMBackend};
+use mir_llvm::{CodegenCx, LLVMBackend, LLVMString, ModuleLlvm, ObjectBuffer};
 use ndatable::nda_arrays;
 use salsa::ParallelDatabase;
-use sim_back::{CompiledModule, ModuleInfo};
+use sim_back::{CompileOpts, CompiledModule, ModuleInfo};
 use stdx::{impl_debug_display, impl_idx_from};
 use target::spec::Target;
 use typed_indexmap::TiSet;
 
 use crate::compilation_unit::{new_codegen, OsdiCompilationUnit, OsdiModule};
+use crate::debug_info::new_debug_info;
+pub use crate::jit::OsdiJit;
 use crate::metadata::osdi_0_4::OsdiTys;
 use crate::metadata::OsdiLimFunction;
 
 mod access;
 mod bitfield;
 mod compilation_unit;
+mod debug_info;
 mod inst_data;
+mod jit;
 mod metadata;
 mod model_data;
 
@@ -55,6 +59,30 @@ fn initialize_llvm() {
     });
 }
 
+/// Where the object code generated by [`codegen`] is placed
+enum Emit<'a> {
+    None,
+    Files(&'a [Utf8PathBuf]),
+    Memory(&'a Mutex<Vec<Option<ObjectBuffer>>>),
+}
+
+impl Emit<'_> {
+    fn emit(&self, llmod: &ModuleLlvm, idx: usize) {
+        match self {
+            Emit::None => (),
+            Emit::Files(paths) => {
+                llmod.optimize();
+                assert_eq!(llmod.emit_object(paths[idx].as_ref()), Ok(()))
+            }
+            Emit::Memory(objects) => {
+                llmod.optimize();
+                let obj = llmod.emit_object_buffer().expect("object code generation failed");
+                objects.lock().unwrap()[idx] = Some(obj);
+            }
+        }
+    }
+}
+
 pub fn compile<'a>(
     db: &'a CompilationDB,
     modules: &'a [ModuleInfo],
@@ -67,7 +95,95 @@ pub fn compile<'a>(
     dump_unopt_mir: bool,
     dump_ir: bool,
     dump_unopt_ir: bool,
+    dump_opt_stats: bool,
+    param_sensitivities: bool,
+    hessian: bool,
+    debug_info: bool,
 ) -> (Vec<Utf8PathBuf>, Vec<CompiledModule<'a>>, Rodeo) {
+    let name = dst.file_stem().expect("destination is a file").to_owned();
+    let mut paths: Vec<Utf8PathBuf> = (0..modules.len() * 4)
+        .map(|i| {
+            let num = base_n::encode((i + 1) as u128, CASE_INSENSITIVE);
+            let extension = format!("o{num}");
+            dst.with_extension(extension)
+        })
+        .collect();
+    paths.push(dst.with_extension("o"));
+
+    let emit = if emit { Emit::Files(&paths) } else { Emit::None };
+    let (compiled_modules, literals) = codegen(
+        db,
+        modules,
+        &name,
+        target,
+        back,
+        emit,
+        opt_lvl,
+        dump_mir,
+        dump_unopt_mir,
+        dump_ir,
+        dump_unopt_ir,
+        dump_opt_stats,
+        param_sensitivities,
+        hessian,
+        debug_info,
+    );
+    (paths, compiled_modules, literals)
+}
+
+/// Compiles `modules` just like [`compile`] but keeps the generated object code in memory
+/// and links it into the current process with an [`OsdiJit`] instead of invoking the
+/// system linker. This only works if `target` is the host target.
+pub fn compile_jit<'a>(
+    db: &'a CompilationDB,
+    modules: &'a [ModuleInfo],
+    name: &str,
+    target: &'a Target,
+    back: &'a LLVMBackend,
+    opt_lvl: LLVMCodeGenOptLevel,
+    param_sensitivities: bool,
+    hessian: bool,
+) -> Result<(OsdiJit, Vec<CompiledModule<'a>>, Rodeo), LLVMString> {
+    let objects = Mutex::new((0..=modules.len() * 4).map(|_| None).collect());
+    let (compiled_modules, literals) = codegen(
+        db,
+        modules,
+        name,
+        target,
+        back,
+        Emit::Memory(&objects),
+        opt_lvl,
+        false,
+        false,
+        false,
+        false,
+        false,
+        param_sensitivities,
+        hessian,
+        false,
+    );
+    let objects = objects.into_inner().unwrap().into_iter().map(|obj| obj.unwrap());
+    let jit = OsdiJit::new(objects)?;
+    Ok((jit, compiled_modules, literals))
+}
+
+fn codegen<'a>(
+    db: &'a CompilationDB,
+    modules: &'a [ModuleInfo],
+    name: &str,
+    target: &'a Target,
+    back: &'a LLVMBackend,
+    emit: Emit<'_>,
+    opt_lvl: LLVMCodeGenOptLevel,
+    dump_mir: bool,
+    dump_unopt_mir: bool,
+    dump_ir: bool,
+    dump_unopt_ir: bool,
+    dump_opt_stats: bool,
+    param_sensitivities: bool,
+    hessian: bool,
+    debug_info: bool,
+) -> (Vec<CompiledModule<'a>>, Rodeo) {
     initialize_llvm();
     let mut literals = Rodeo::new();
     let mut lim_table = TiSet::default();
@@ -75,7 +191,14 @@ pub fn compile<'a>(
     let modules: Vec<_> = modules
         .iter()
         .map(|module| {
-            let mir = CompiledModule::new(db, module, &mut literals, dump_unopt_mir, dump_mir);
+            let opts = CompileOpts {
+                dump_unopt_mir,
+                dump_mir,
+                param_sensitivities,
+                hessian,
+                opt_stats: dump_opt_stats,
+            };
+            let mir = CompiledModule::new(db, module, &mut literals, &opts);
             for cb in mir.intern.callbacks.iter() {
                 if let CallBackKind::BuiltinLimit { name, num_args } = *cb {
                     lim_table.ensure(OsdiLimFunction { name, num_args: num_args - 2 });
@@ -85,16 +208,6 @@ pub fn compile<'a>(
         })
         .collect();
 
-    let name = dst.file_stem().expect("destination is a file").to_owned();
-
-    let mut paths: Vec<Utf8PathBuf> = (0..modules.len() * 4)
-        .map(|i| {
-            let num = base_n::encode((i + 1) as u128, CASE_INSENSITIVE);
-            let extension = format!("o{num}");
-            dst.with_extension(extension)
-        })
-        .collect();
-
     let target_data = unsafe {
         let src = CString::new(target.data_layout.clone()).unwrap();
         &*llvm_sys::target::LLVMCreateTargetData(src.as_ptr())
@@ -113,8 +226,6 @@ pub fn compile<'a>(
 
     let db = db.snapshot();
 
-    let main_file = dst.with_extension("o");
-
     let unoptirs = Arc::new(Mutex::new(HashMap::new()));
     let irs = Arc::new(Mutex::new(HashMap::new()));
 
@@ -125,7 +236,7 @@ pub fn compile<'a>(
         let db = db;
         let literals_ = &literals;
         let target_data_ = target_data;
-        let paths = &paths;
+        let emit = &emit;
 
         for (i, module) in osdi_modules.iter().enumerate() {
             let _db = db.snapshot();
@@ -146,11 +257,7 @@ pub fn compile<'a>(
                 }
                 debug_assert!(llmod.verify_and_print());
 
-                if emit {
-                    let path = &paths[i * 4];
-                    llmod.optimize();
-                    assert_eq!(llmod.emit_object(path.as_ref()), Ok(()))
-                }
+                emit.emit(&llmod, i * 4);
 
                 if dump_ir {
                     let mut irs = irs_clone.lock().unwrap();
@@ -167,20 +274,21 @@ pub fn compile<'a>(
                 let llmod = unsafe { back.new_module(&name, opt_lvl).unwrap() };
                 let cx = new_codegen(back, &llmod, literals_);
                 let tys = OsdiTys::new(&cx, NonNull::from(target_data_).as_ptr());
-                let cguint = OsdiCompilationUnit::new(&_db, module, &cx, &tys, false);
+                let debug_info = debug_info.then(|| new_debug_info(&_db, module, &cx, opt_lvl));
+                let cguint = OsdiCompilationUnit::new(&_db, module, &cx, &tys, false)
+                    .with_debug_info(debug_info.as_ref());
 
                 cguint.setup_model();
+                if let Some(debug_info) = &debug_info {
+                    debug_info.finalize();
+                }
                 if dump_unopt_ir {
                     let mut unoptirs = unoptirs_clone.lock().unwrap();
                     unoptirs.insert((i, name), cx.to_str().to_string());
                 }
                 debug_assert!(llmod.verify_and_print());
 
-                if emit {
-                    let path = &paths[i * 4 + 1];
-                    llmod.optimize();
-                    assert_eq!(llmod.emit_object(path.as_ref()), Ok(()))
-                }
+                emit.emit(&llmod, i * 4 + 1);
 
                 if dump_ir {
                     let mut irs = irs_clone.lock().unwrap();
@@ -197,9 +305,14 @@ pub fn compile<'a>(
                 let llmod = unsafe { back.new_module(&name, opt_lvl).unwrap() };
                 let cx = new_codegen(back, &llmod, literals_);
                 let tys = OsdiTys::new(&cx, NonNull::from(target_data_).as_ptr());
-                let mut cguint = OsdiCompilationUnit::new(&_db, module, &cx, &tys, false);
+                let debug_info = debug_info.then(|| new_debug_info(&_db, module, &cx, opt_lvl));
+                let mut cguint = OsdiCompilationUnit::new(&_db, module, &cx, &tys, false)
+                    .with_debug_info(debug_info.as_ref());
 
                 cguint.setup_instance();
+                if let Some(debug_info) = &debug_info {
+                    debug_info.finalize();
+                }
                 if dump_unopt_ir {
                     let mut unoptirs = unoptirs_clone.lock().unwrap();
                     unoptirs.insert((i, name), cx.to_str().to_string());
@@ -208,11 +321,7 @@ pub fn compile<'a>(
                 //println!("llmod: {}", _ir);
                 debug_assert!(llmod.verify_and_print());
 
-                if emit {
-                    let path = &paths[i * 4 + 2];
-                    llmod.optimize();
-                    assert_eq!(llmod.emit_object(path.as_ref()), Ok(()))
-                }
+                emit.emit(&llmod, i * 4 + 2);
 
                 if dump_ir {
                     let mut irs = irs_clone.lock().unwrap();
@@ -229,20 +338,21 @@ pub fn compile<'a>(
                 let llmod = unsafe { back.new_module(&access, opt_lvl).unwrap() };
                 let cx = new_codegen(back, &llmod, literals_);
                 let tys = OsdiTys::new(&cx, NonNull::from(target_data_).as_ptr());
-                let cguint = OsdiCompilationUnit::new(&_db, module, &cx, &tys, true);
+                let debug_info = debug_info.then(|| new_debug_info(&_db, module, &cx, opt_lvl));
+                let cguint = OsdiCompilationUnit::new(&_db, module, &cx, &tys, true)
+                    .with_debug_info(debug_info.as_ref());
 
                 cguint.eval();
+                if let Some(debug_info) = &debug_info {
+                    debug_info.finalize();
+                }
                 if dump_unopt_ir {
                     let mut unoptirs = unoptirs_clone.lock().unwrap();
                     unoptirs.insert((i, access), llmod.to_str().to_string());
                 }
                 debug_assert!(llmod.verify_and_print());
 
-                if emit {
-                    let path = &paths[i * 4 + 3];
-                    llmod.optimize();
-                    assert_eq!(llmod.emit_object(path.as_ref()), Ok(()))
-                }
+                emit.emit(&llmod, i * 4 + 3);
 
                 if dump_ir {
                     let mut irs = irs_clone.lock().unwrap();
@@ -251,7 +361,7 @@ pub fn compile<'a>(
             });
         }
 
-        let llmod = unsafe { back.new_module(&name, opt_lvl).unwrap() };
+        let llmod = unsafe { back.new_module(name, opt_lvl).unwrap() };
         let cx = new_codegen(back, &llmod, &literals);
         let tys = OsdiTys::new(&cx, NonNull::from(target_data).as_ptr());
 
@@ -370,12 +480,7 @@ pub fn compile<'a>(
 
         debug_assert!(llmod.verify_and_print());
 
-        if emit {
-            // println!("{}", llmod.to_str());
-            llmod.optimize();
-            // println!("{}", llmod.to_str());
-            assert_eq!(llmod.emit_object(main_file.as_ref()), Ok(()))
-        }
+        emit.emit(&llmod, osdi_modules.len() * 4);
     });
 
     if dump_unopt_ir {
@@ -398,9 +503,8 @@ pub fn compile<'a>(
         }
     }
 
-    paths.push(main_file);
     unsafe { LLVMDisposeTargetData(NonNull::from(target_data).as_ptr()) };
-    (paths, compiled_modules, literals)
+    (compiled_modules, literals)
 }
 
 impl OsdiModule<'_> {
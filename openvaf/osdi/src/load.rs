@@ -1,17 +1,23 @@
 use core::ffi::c_uint;
 use std::ptr::NonNull;
 
+use ahash::{AHashMap, RandomState};
+use hir::Parameter;
+use indexmap::IndexMap;
+
 use llvm_sys::core::{
-    LLVMAppendBasicBlockInContext, LLVMBuildCall2, LLVMBuildFAdd, LLVMBuildFDiv, LLVMBuildFMul,
-    LLVMBuildFSub, LLVMBuildGEP2, LLVMBuildRetVoid, LLVMBuildStore, LLVMCreateBuilderInContext,
-    LLVMDisposeBuilder, LLVMGetParam, LLVMPositionBuilderAtEnd,
+    LLVMAddCase, LLVMAppendBasicBlockInContext, LLVMBuildBr, LLVMBuildCall2, LLVMBuildFAdd,
+    LLVMBuildFDiv, LLVMBuildFMul, LLVMBuildFSub, LLVMBuildGEP2, LLVMBuildLoad2, LLVMBuildRetVoid,
+    LLVMBuildStore, LLVMBuildSwitch, LLVMCreateBuilderInContext, LLVMDisposeBuilder, LLVMGetParam,
+    LLVMPositionBuilderAtEnd,
 };
 use mir_llvm::UNNAMED;
-use sim_back::dae::NoiseSourceKind;
+use sim_back::dae::{MatrixEntryId, NoiseSourceKind};
 use stdx::iter::zip;
 use typed_index_collections::TiVec;
 
 use crate::compilation_unit::OsdiCompilationUnit;
+use crate::inst_data::OsdiInstanceParam;
 #[derive(Debug, Clone, Copy)]
 pub enum JacobianLoadType {
     Tran,
@@ -199,6 +205,266 @@ impl<'ll> OsdiCompilationUnit<'_, '_, 'll> {
         llfunc
     }
 
+    /// Adds the derivatives of the residual by the parameter `param_id` (OSDI parameter id)
+    /// to `dst_resist` and `dst_react`. Both arrays are indexed like the residual passed to
+    /// `load_residual_resist`/`load_residual_react`. Parameters without sensitivities (for
+    /// example integer or string parameters) leave the arrays untouched.
+    pub fn load_param_sensitivity(&self) -> &'ll llvm_sys::LLVMValue {
+        let OsdiCompilationUnit { inst_data, cx, module, .. } = self;
+        let ptr_ty = cx.ty_ptr();
+        let fun_ty = cx.ty_func(&[ptr_ty, ptr_ty, cx.ty_int(), ptr_ty, ptr_ty], cx.ty_void());
+        let name = &format!("load_param_sensitivity_{}", module.sym);
+        let llfunc = cx.declare_int_c_fn(name, fun_ty);
+
+        let mut params: IndexMap<_, Vec<_>, RandomState> = IndexMap::default();
+        for sensitivity in &inst_data.param_sensitivities {
+            params.entry(sensitivity.param).or_default().push(sensitivity);
+        }
+
+        unsafe {
+            let entry = LLVMAppendBasicBlockInContext(
+                NonNull::from(cx.llcx).as_ptr(),
+                NonNull::from(llfunc).as_ptr(),
+                UNNAMED,
+            );
+            let exit = LLVMAppendBasicBlockInContext(
+                NonNull::from(cx.llcx).as_ptr(),
+                NonNull::from(llfunc).as_ptr(),
+                UNNAMED,
+            );
+            let llbuilder = LLVMCreateBuilderInContext(NonNull::from(cx.llcx).as_ptr());
+
+            LLVMPositionBuilderAtEnd(llbuilder, entry);
+
+            // get params
+            let inst = &*LLVMGetParam(NonNull::from(llfunc).as_ptr(), 0);
+            let model = &*LLVMGetParam(NonNull::from(llfunc).as_ptr(), 1);
+            let param_id = LLVMGetParam(NonNull::from(llfunc).as_ptr(), 2);
+            let dst_resist = &*LLVMGetParam(NonNull::from(llfunc).as_ptr(), 3);
+            let dst_react = &*LLVMGetParam(NonNull::from(llfunc).as_ptr(), 4);
+
+            let switch = LLVMBuildSwitch(llbuilder, param_id, exit, params.len() as u32);
+            for (&param, sensitivities) in &params {
+                let bb = LLVMAppendBasicBlockInContext(
+                    NonNull::from(cx.llcx).as_ptr(),
+                    NonNull::from(llfunc).as_ptr(),
+                    UNNAMED,
+                );
+                let id = cx.const_unsigned_int(self.osdi_param_id(param));
+                LLVMAddCase(switch, NonNull::from(id).as_ptr(), bb);
+                LLVMPositionBuilderAtEnd(llbuilder, bb);
+
+                for sensitivity in sensitivities {
+                    for (val, dst) in
+                        [(sensitivity.resist, dst_resist), (sensitivity.react, dst_react)]
+                    {
+                        if let Some(val) = val {
+                            let val = self.load_eval_output(val, inst, model, &*llbuilder);
+                            inst_data.store_contrib(
+                                cx,
+                                sensitivity.unknown,
+                                inst,
+                                dst,
+                                val,
+                                &*llbuilder,
+                                false,
+                            );
+                        }
+                    }
+                }
+                LLVMBuildBr(llbuilder, exit);
+            }
+
+            LLVMPositionBuilderAtEnd(llbuilder, exit);
+            LLVMBuildRetVoid(llbuilder);
+            LLVMDisposeBuilder(llbuilder);
+        }
+
+        llfunc
+    }
+
+    /// Adds the derivatives of the operating point variables by the parameter `param_id`
+    /// (OSDI parameter id) to `dst`. `dst[i]` corresponds to the i-th operating point
+    /// variable (OSDI id `num_params + i`). Only real operating point variables have
+    /// sensitivities.
+    pub fn load_opvar_sensitivity(&self) -> &'ll llvm_sys::LLVMValue {
+        let OsdiCompilationUnit { inst_data, cx, module, .. } = self;
+        let ptr_ty = cx.ty_ptr();
+        let fun_ty = cx.ty_func(&[ptr_ty, ptr_ty, cx.ty_int(), ptr_ty], cx.ty_void());
+        let name = &format!("load_opvar_sensitivity_{}", module.sym);
+        let llfunc = cx.declare_int_c_fn(name, fun_ty);
+
+        let mut params: IndexMap<_, Vec<_>, RandomState> = IndexMap::default();
+        for sensitivity in &inst_data.opvar_sensitivities {
+            params.entry(sensitivity.param).or_default().push(sensitivity);
+        }
+
+        unsafe {
+            let entry = LLVMAppendBasicBlockInContext(
+                NonNull::from(cx.llcx).as_ptr(),
+                NonNull::from(llfunc).as_ptr(),
+                UNNAMED,
+            );
+            let exit = LLVMAppendBasicBlockInContext(
+                NonNull::from(cx.llcx).as_ptr(),
+                NonNull::from(llfunc).as_ptr(),
+                UNNAMED,
+            );
+            let llbuilder = LLVMCreateBuilderInContext(NonNull::from(cx.llcx).as_ptr());
+
+            LLVMPositionBuilderAtEnd(llbuilder, entry);
+
+            // get params
+            let inst = &*LLVMGetParam(NonNull::from(llfunc).as_ptr(), 0);
+            let model = &*LLVMGetParam(NonNull::from(llfunc).as_ptr(), 1);
+            let param_id = LLVMGetParam(NonNull::from(llfunc).as_ptr(), 2);
+            let dst = LLVMGetParam(NonNull::from(llfunc).as_ptr(), 3);
+
+            let ty_double = NonNull::from(cx.ty_double()).as_ptr();
+            let switch = LLVMBuildSwitch(llbuilder, param_id, exit, params.len() as u32);
+            for (&param, sensitivities) in &params {
+                let bb = LLVMAppendBasicBlockInContext(
+                    NonNull::from(cx.llcx).as_ptr(),
+                    NonNull::from(llfunc).as_ptr(),
+                    UNNAMED,
+                );
+                let id = cx.const_unsigned_int(self.osdi_param_id(param));
+                LLVMAddCase(switch, NonNull::from(id).as_ptr(), bb);
+                LLVMPositionBuilderAtEnd(llbuilder, bb);
+
+                for sensitivity in sensitivities {
+                    let val = self.load_eval_output(sensitivity.val, inst, model, &*llbuilder);
+                    let idx = inst_data.opvars.get_index_of(&sensitivity.var).unwrap();
+                    let idx = cx.const_unsigned_int(idx as u32);
+                    let mut gep_indices = [NonNull::from(idx).as_ptr()];
+                    let ptr = LLVMBuildGEP2(
+                        llbuilder,
+                        ty_double,
+                        dst,
+                        gep_indices.as_mut_ptr(),
+                        1,
+                        UNNAMED,
+                    );
+                    let old = LLVMBuildLoad2(llbuilder, ty_double, ptr, UNNAMED);
+                    let val = LLVMBuildFAdd(llbuilder, old, NonNull::from(val).as_ptr(), UNNAMED);
+                    LLVMBuildStore(llbuilder, val, ptr);
+                }
+                LLVMBuildBr(llbuilder, exit);
+            }
+
+            LLVMPositionBuilderAtEnd(llbuilder, exit);
+            LLVMBuildRetVoid(llbuilder);
+            LLVMDisposeBuilder(llbuilder);
+        }
+
+        llfunc
+    }
+
+    /// The OSDI id of `param`: instance parameters come first, model parameters are offset
+    /// by the number of instance parameters (same as in the descriptor).
+    fn osdi_param_id(&self, param: Parameter) -> u32 {
+        let OsdiCompilationUnit { inst_data, model_data, .. } = self;
+        let id = match inst_data.params.get_index_of(&OsdiInstanceParam::User(param)) {
+            Some(id) => id,
+            None => inst_data.params.len() + model_data.params.get_index_of(&param).unwrap(),
+        };
+        id as u32
+    }
+
+    /// Adds the product of the derivatives of the jacobian by the unknowns (the hessian) and
+    /// the vector `dir` to `dst_resist` and `dst_react`:
+    ///
+    /// dst[k] += sum_j ddx(J_k, x_j) * dir[j]
+    ///
+    /// `dir` is indexed like the residual while `dst_resist` and `dst_react` are indexed
+    /// like the `jacobian_entries` of the descriptor.
+    pub fn load_hessian_vector_product(&self) -> &'ll llvm_sys::LLVMValue {
+        let OsdiCompilationUnit { inst_data, cx, module, .. } = self;
+        let ptr_ty = cx.ty_ptr();
+        let fun_ty = cx.ty_func(&[ptr_ty; 5], cx.ty_void());
+        let name = &format!("load_hessian_vector_product_{}", module.sym);
+        let llfunc = cx.declare_int_c_fn(name, fun_ty);
+
+        let jacobian_entries: AHashMap<_, _> = module
+            .dae_system
+            .jacobian
+            .iter_enumerated()
+            .map(|(id, entry)| ((entry.row, entry.col), id))
+            .collect();
+
+        unsafe {
+            let entry = LLVMAppendBasicBlockInContext(
+                NonNull::from(cx.llcx).as_ptr(),
+                NonNull::from(llfunc).as_ptr(),
+                UNNAMED,
+            );
+            let llbuilder = LLVMCreateBuilderInContext(NonNull::from(cx.llcx).as_ptr());
+
+            LLVMPositionBuilderAtEnd(llbuilder, entry);
+
+            // get params
+            let inst = &*LLVMGetParam(NonNull::from(llfunc).as_ptr(), 0);
+            let model = &*LLVMGetParam(NonNull::from(llfunc).as_ptr(), 1);
+            let dir = LLVMGetParam(NonNull::from(llfunc).as_ptr(), 2);
+            let dst_resist = LLVMGetParam(NonNull::from(llfunc).as_ptr(), 3);
+            let dst_react = LLVMGetParam(NonNull::from(llfunc).as_ptr(), 4);
+
+            let ty_double = NonNull::from(cx.ty_double()).as_ptr();
+            let mut products: TiVec<MatrixEntryId, [Option<_>; 2]> =
+                vec![[None; 2]; module.dae_system.jacobian.len()].into();
+            for entry in &inst_data.hessian {
+                let id = if let Some(&id) = jacobian_entries.get(&(entry.row, entry.col)) {
+                    id
+                } else {
+                    continue;
+                };
+
+                let off = inst_data.read_node_off(cx, entry.dir, inst, &*llbuilder);
+                let mut gep_indices = [NonNull::from(off).as_ptr()];
+                let ptr =
+                    LLVMBuildGEP2(llbuilder, ty_double, dir, gep_indices.as_mut_ptr(), 1, UNNAMED);
+                let dir_val = LLVMBuildLoad2(llbuilder, ty_double, ptr, UNNAMED);
+
+                for (val, dst) in zip([entry.resist, entry.react], &mut products[id]) {
+                    if let Some(val) = val {
+                        let val = self.load_eval_output(val, inst, model, &*llbuilder);
+                        let product =
+                            LLVMBuildFMul(llbuilder, NonNull::from(val).as_ptr(), dir_val, UNNAMED);
+                        *dst = Some(match *dst {
+                            Some(sum) => LLVMBuildFAdd(llbuilder, sum, product, UNNAMED),
+                            None => product,
+                        });
+                    }
+                }
+            }
+
+            for (id, products) in products.iter_enumerated() {
+                for (product, dst) in zip(products, [dst_resist, dst_react]) {
+                    if let Some(product) = *product {
+                        let idx = cx.const_unsigned_int(u32::from(id));
+                        let mut gep_indices = [NonNull::from(idx).as_ptr()];
+                        let ptr = LLVMBuildGEP2(
+                            llbuilder,
+                            ty_double,
+                            dst,
+                            gep_indices.as_mut_ptr(),
+                            1,
+                            UNNAMED,
+                        );
+                        let old = LLVMBuildLoad2(llbuilder, ty_double, ptr, UNNAMED);
+                        let val = LLVMBuildFAdd(llbuilder, old, product, UNNAMED);
+                        LLVMBuildStore(llbuilder, val, ptr);
+                    }
+                }
+            }
+
+            LLVMBuildRetVoid(llbuilder);
+            LLVMDisposeBuilder(llbuilder);
+        }
+
+        llfunc
+    }
+
     pub fn load_lim_rhs(&self, reactive: bool) -> &'ll llvm_sys::LLVMValue {
         let OsdiCompilationUnit { inst_data, cx, module, .. } = self;
         let void_ptr = cx.ty_ptr();
@@ -444,6 +444,9 @@ impl<'ll> OsdiCompilationUnit<'_, '_, 'll> {
                 load_jacobian_with_offset_react: self.load_jacobian(JacobianLoadType::React, true),
                 unknown_nature: uvec,
                 residual_nature: rvec,
+                load_param_sensitivity: self.load_param_sensitivity(),
+                load_hessian_vector_product: self.load_hessian_vector_product(),
+                load_opvar_sensitivity: self.load_opvar_sensitivity(),
             }
         }
     }
@@ -65,6 +65,8 @@ pub const ANALYSIS_TRAN: u32 = 8192;
 pub const ANALYSIS_IC: u32 = 16384;
 pub const ANALYSIS_STATIC: u32 = 32768;
 pub const ANALYSIS_NODESET: u32 = 65536;
+pub const CALC_PARAM_SENSITIVITY: u32 = 131072;
+pub const CALC_HESSIAN: u32 = 262144;
 pub const EVAL_RET_FLAG_LIM: u32 = 1;
 pub const EVAL_RET_FLAG_FATAL: u32 = 2;
 pub const EVAL_RET_FLAG_FINISH: u32 = 4;
@@ -410,6 +412,9 @@ pub struct OsdiDescriptor<'ll> {
     pub load_jacobian_with_offset_react: &'ll llvm_sys::LLVMValue,
     pub unknown_nature: Vec<OsdiNatureRef>,
     pub residual_nature: Vec<OsdiNatureRef>,
+    pub load_param_sensitivity: &'ll llvm_sys::LLVMValue,
+    pub load_hessian_vector_product: &'ll llvm_sys::LLVMValue,
+    pub load_opvar_sensitivity: &'ll llvm_sys::LLVMValue,
 }
 impl<'ll> OsdiDescriptor<'ll> {
     pub fn to_ll_val(
@@ -474,6 +479,9 @@ impl<'ll> OsdiDescriptor<'ll> {
             self.load_jacobian_with_offset_react,
             ctx.const_arr_ptr(tys.osdi_nature_ref, &arr_46),
             ctx.const_arr_ptr(tys.osdi_nature_ref, &arr_47),
+            self.load_param_sensitivity,
+            self.load_hessian_vector_product,
+            self.load_opvar_sensitivity,
         ];
         let ty = tys.osdi_descriptor;
         ctx.const_struct(ty, &fields)
@@ -531,6 +539,9 @@ impl OsdiTyBuilder<'_, '_, '_> {
             ctx.ty_ptr(),
             ctx.ty_ptr(),
             ctx.ty_ptr(),
+            ctx.ty_ptr(),
+            ctx.ty_ptr(),
+            ctx.ty_ptr(),
         ];
         let ty = ctx.ty_struct("OsdiDescriptor", &fields);
         self.osdi_descriptor = Some(ty);
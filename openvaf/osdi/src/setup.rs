@@ -141,6 +141,12 @@ impl<'ll> OsdiCompilationUnit<'_, '_, 'll> {
         let mut cfg = ControlFlowGraph::new();
         cfg.compute(func);
         let mut builder = Builder::new(cx, func, llfunc, Some(cx.ty_int()), true);
+        let name = format!("setup_model_{}", &self.module.sym);
+        if let Some(debug_info) =
+            self.function_debug_info(llfunc, &name, func, &intern.srclocs, intern)
+        {
+            builder.set_debug_info(debug_info);
+        }
         let postorder: Vec<_> = cfg.postorder(func).collect();
 
         let handle = unsafe { llvm_sys::core::LLVMGetParam(NonNull::from(llfunc).as_ptr(), 0) };
@@ -300,11 +306,23 @@ impl<'ll> OsdiCompilationUnit<'_, '_, 'll> {
         // Debug: mark_collapsed output: (llfunc, fn_type)
         let llfunc = self.setup_instance_prototype();
         // Debug: setup_instance_prototype output: llfunc
+        // the values of init are tagged with the places of eval
+        let name = format!("setup_instance_{}", &self.module.sym);
+        let debug_info = self.function_debug_info(
+            llfunc,
+            &name,
+            &self.module.init.func,
+            &self.module.init.intern.srclocs,
+            self.module.intern,
+        );
         let OsdiCompilationUnit { inst_data, model_data, tys, cx, module, .. } = self;
 
         let func = &module.init.func;
         let intern = &module.init.intern;
         let mut builder = Builder::new(cx, func, llfunc, Some(cx.ty_int()), true);
+        if let Some(debug_info) = debug_info {
+            builder.set_debug_info(debug_info);
+        }
 
         let handle = unsafe { llvm_sys::core::LLVMGetParam(NonNull::from(llfunc).as_ptr(), 0) };
         let instance = unsafe { &*llvm_sys::core::LLVMGetParam(NonNull::from(llfunc).as_ptr(), 1) };
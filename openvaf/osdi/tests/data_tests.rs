@@ -33,6 +33,10 @@ fn test_compile(root_file: &Path) {
         false,
         false,
         false,
+        false,
+        false,
+        false,
+        false,
     );
 }
 
@@ -3,6 +3,7 @@ use std::io;
 use stdx::impl_display;
 use vfs::{InvalidTextFormatErr, VfsPath};
 
+use crate::processor::MACRO_EXPANSION_LIMIT;
 use crate::sourcemap::CtxSpan;
 
 #[derive(Debug, PartialEq, Clone, Eq)]
@@ -10,7 +11,8 @@ pub enum PreprocessorDiagnostic {
     MacroArgumentCountMismatch { expected: usize, found: usize, span: CtxSpan },
     MacroNotFound { name: String, span: CtxSpan },
     MacroNotDefined { name: String, span: CtxSpan },
-    MacroRecursion { name: String, span: CtxSpan },
+    MacroRecursion { name: String, span: CtxSpan, expansion: Vec<(String, CtxSpan)> },
+    MacroExpansionLimit { name: String, span: CtxSpan, expansion: Vec<(String, CtxSpan)> },
     UnsupportedCompDir { name: String, span: CtxSpan },
     FileNotFound { file: String, error: io::ErrorKind, span: Option<CtxSpan> },
     InvalidTextFormat { span: Option<CtxSpan>, file: VfsPath, err: InvalidTextFormatErr },
@@ -27,6 +29,7 @@ impl_display! {
         MacroNotFound{name,..} =>  "macro '`{}' has not been declared", name;
         MacroNotDefined{name,..} =>  "cannot undefine macro '`{}'", name;
         MacroRecursion { name,..} => "macro '`{}' was called recursively",name;
+        MacroExpansionLimit { name, ..} => "expansion of macro '`{}' exceeds the maximum macro expansion depth of {}", name, MACRO_EXPANSION_LIMIT;
         UnsupportedCompDir { name,.. } => "unsupported compiler directive {}",name;
         FileNotFound { file, error, .. } => "failed to read '{}': {}", file, std::io::Error::from(*error);
         InvalidTextFormat {  file, ..} => "failed to read {}: file contents are not valid text", file;
@@ -12,19 +12,28 @@ use typed_index_collections::{TiSlice, TiVec};
 use vfs::{FileId, VfsPath};
 
 use crate::diagnostics::PreprocessorDiagnostic::{
-    self, MacroArgumentCountMismatch, MacroNotFound, UnexpectedToken,
+    self, MacroArgumentCountMismatch, MacroExpansionLimit, MacroNotFound, MacroRecursion,
+    UnexpectedToken,
 };
 use crate::grammar::{parse_condition, parse_define, parse_include, parse_macro_call};
 use crate::parser::{CompilerDirective, Parser, PreprocessorToken};
 use crate::sourcemap::{CtxSpan, FileSpan, SourceContext, SourceMap};
 use crate::{Diagnostics, FileReadError, ScopedTextArea, SourceProvider, Token};
 
+/// Maximum number of macro expansions that may be nested inside each other.
+/// Recursive macros are detected separately, this limit only guards against
+/// excessively deep (but finite) chains of macros calling each other.
+pub(crate) const MACRO_EXPANSION_LIMIT: usize = 256;
+
 pub(crate) struct Processor<'a> {
     pub(crate) source_map: SourceMap,
     sources: &'a dyn SourceProvider,
     arena: &'a ScopedTextArea,
     macros: AHashMap<&'a str, Macro<'a>>,
     include_dirs: Arc<[VfsPath]>,
+    /// The macros that are currently being expanded (outermost first)
+    /// together with the span of the call that started the expansion.
+    expansion_stack: Vec<(&'a str, CtxSpan)>,
 }
 
 impl<'a> Processor<'a> {
@@ -51,6 +60,7 @@ impl<'a> Processor<'a> {
             arena: storage,
             sources,
             include_dirs: sources.include_dirs(root_file),
+            expansion_stack: Vec::new(),
         };
         Ok(res)
     }
@@ -149,8 +159,24 @@ impl<'a> Processor<'a> {
         dst: &mut Vec<Token>,
         errors: &mut Diagnostics,
     ) {
-        // TODO track recursion
-        //
+        if let Some(pos) = self.expansion_stack.iter().position(|(name, _)| *name == call.name) {
+            errors.push(MacroRecursion {
+                name: call.name.to_owned(),
+                span,
+                expansion: self.expansion_chain(pos),
+            });
+            return;
+        }
+
+        if self.expansion_stack.len() >= MACRO_EXPANSION_LIMIT {
+            errors.push(MacroExpansionLimit {
+                name: call.name.to_owned(),
+                span,
+                expansion: self.expansion_chain(0),
+            });
+            return;
+        }
+
         let parent_ctx_span = self.source_map.ctx_data(span.ctx).decl.range.start();
         if let Some(def) = self.macros.get(&call.name).cloned() {
             let new_args: TiVec<_, _> = call
@@ -169,10 +195,12 @@ impl<'a> Processor<'a> {
 
             if new_args.len() == def.arg_cnt || def.arg_cnt == 0 {
                 let ctx = self.source_map.add_ctx(def.span.to_file_span(&self.source_map), span);
+                self.expansion_stack.push((call.name, span));
                 for ParsedToken { kind, range } in &def.body {
                     let span = CtxSpan { range: range - def.span.range.start(), ctx };
                     self.process_macro_token(kind, span, &new_args, dst, errors)
                 }
+                self.expansion_stack.pop();
                 if new_args.len() > def.arg_cnt {
                     // macro definition has no arguments, but some were parsed as part of the call
                     // so put the arguments back
@@ -196,6 +224,11 @@ impl<'a> Processor<'a> {
         }
     }
 
+    /// Returns the active macro expansions starting at the `start`th entry of the expansion stack
+    fn expansion_chain(&self, start: usize) -> Vec<(String, CtxSpan)> {
+        self.expansion_stack[start..].iter().map(|&(name, span)| (name.to_owned(), span)).collect()
+    }
+
     pub(crate) fn process_file(&mut self, mut p: Parser<'a, '_>, err: &mut Diagnostics) {
         while !p.at(PreprocessorToken::Eof) {
             self.process_token(&mut p, err)
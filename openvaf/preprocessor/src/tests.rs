@@ -4,6 +4,9 @@ use std::{cell::RefCell, path::PathBuf};
 use expect_test::expect_file;
 use vfs::{FileId, Vfs, VfsPath};
 
+use crate::diagnostics::PreprocessorDiagnostic;
+use crate::processor::MACRO_EXPANSION_LIMIT;
+use crate::sourcemap::CtxSpan;
 use crate::{preprocess, Preprocess, SourceProvider};
 
 struct TestSourceProvider {
@@ -181,3 +184,48 @@ fn source_map_triple_replacement() {
         "source_map_triple_replacement",
     )
 }
+
+#[test]
+fn macro_recursion() {
+    let sources = TestSourceProvider::new(vec![]);
+    let src = r#"
+`define foo(x) `bar(x)
+`define bar(x) (`foo(x) + 1)
+`define baz 2
+`foo(`baz)
+"#;
+    let file = sources.vfs.borrow_mut().add_virt_file("/macro_recursion.va", src.to_owned().into());
+    let Preprocess { diagnostics, sm, .. } = preprocess(&sources, file);
+    let [PreprocessorDiagnostic::MacroRecursion { name, span, expansion }] = &diagnostics[..]
+    else {
+        panic!("expected a single macro recursion error but found {diagnostics:?}")
+    };
+    assert_eq!(name, "foo");
+    let names: Vec<_> = expansion.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["foo", "bar"]);
+
+    let vfs = sources.vfs.borrow();
+    let text = |span: CtxSpan| {
+        let span = span.to_file_span(&sm);
+        vfs.file_contents(span.file).unwrap()[span.range].to_owned()
+    };
+    assert_eq!(text(*span), "`foo(x)");
+    assert_eq!(text(expansion[0].1), "`foo(`baz)");
+    assert_eq!(text(expansion[1].1), "`bar(x)");
+}
+
+#[test]
+fn macro_expansion_limit() {
+    let sources = TestSourceProvider::new(vec![]);
+    let mut src = String::new();
+    for i in 0..=MACRO_EXPANSION_LIMIT {
+        src.push_str(&format!("`define m{i} `m{}\n", i + 1));
+    }
+    src.push_str("`m0\n");
+    let file = sources.vfs.borrow_mut().add_virt_file("/macro_limit.va", src.into());
+    let Preprocess { diagnostics, .. } = preprocess(&sources, file);
+    let [PreprocessorDiagnostic::MacroExpansionLimit { expansion, .. }] = &diagnostics[..] else {
+        panic!("expected a single expansion limit error but found {diagnostics:?}")
+    };
+    assert_eq!(expansion.len(), MACRO_EXPANSION_LIMIT);
+}
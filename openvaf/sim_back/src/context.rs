@@ -1,15 +1,17 @@
 use bitset::{BitSet, SparseBitMatrix};
-use hir::CompilationDB;
-use hir_lower::{HirInterner, MirBuilder, PlaceKind};
+use hir::{CompilationDB, Parameter, Type};
+use hir_lower::{CallBackKind, HirInterner, MirBuilder, PlaceKind};
 use lasso::Rodeo;
-use mir::{InstructionData, Block, ControlFlowGraph, DominatorTree, Function, Inst, Value};
+use mir::{Block, ControlFlowGraph, DominatorTree, Function, Inst, InstructionData, Value};
 use mir_opt::{
-    aggressive_dead_code_elimination, dead_code_elimination, inst_combine, propagate_direct_taint,
-    propagate_taint, simplify_cfg, simplify_cfg_no_phi_merge,
-    sparse_conditional_constant_propagation, GVN,
+    aggressive_dead_code_elimination, dead_code_elimination, inst_combine,
+    loop_invariant_code_motion, propagate_direct_taint, propagate_taint, simplify_cfg,
+    simplify_cfg_no_phi_merge, simplify_transcendental, sparse_conditional_constant_propagation,
+    unroll_loops, GVN,
 };
 use stdx::packed_option::PackedOption;
 
+use crate::stats::{InstCount, OptStats};
 use crate::ModuleInfo;
 
 pub(crate) struct Context<'a> {
@@ -22,9 +24,22 @@ pub(crate) struct Context<'a> {
     pub(crate) output_values: BitSet<Value>,
     pub(crate) op_dependent_insts: BitSet<Inst>,
     pub(crate) op_dependent_vals: Vec<Value>,
+    /// whether value level optimizations (constant propagation, instruction combining and
+    /// global value numbering) are performed, disabled to obtain a reference for testing
+    pub(crate) optimize_values: bool,
+    /// parameters (and their values built by [`Context::insert_param_defaults`]) whose
+    /// sensitivities are included in the DAE system
+    pub(crate) sensitivity_params: Vec<(Parameter, Value)>,
+    /// whether the second derivatives of the residual are included in the DAE system
+    pub(crate) build_hessian: bool,
+    /// whether [`Context::opt_stats`] are recorded, counting the instructions after each
+    /// pass is not free
+    pub(crate) collect_opt_stats: bool,
+    /// size of the MIR after each optimization pass
+    pub(crate) opt_stats: OptStats,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum OptimiziationStage {
     Initial,
     PostDerivative,
@@ -40,7 +55,7 @@ impl<'a> Context<'a> {
                 PlaceKind::Contribute { .. }
                 | PlaceKind::ImplicitResidual { .. }
                 | PlaceKind::CollapseImplicitEquation(_)
-                | PlaceKind::IsVoltageSrc(_) 
+                | PlaceKind::IsVoltageSrc(_)
                 | PlaceKind::BoundStep => true,
                 PlaceKind::Var(var) => module.op_vars.contains_key(&var),
                 _ => false,
@@ -52,6 +67,7 @@ impl<'a> Context<'a> {
         .build(literals);
         // TODO hidden state
         intern.insert_var_init(db, &mut func, literals);
+        intern.specialize_params(db, &mut func, |param| module.fixed_params.get(&param).copied());
 
         Context {
             output_values: BitSet::new_empty(func.dfg.num_values()),
@@ -63,26 +79,84 @@ impl<'a> Context<'a> {
             module,
             op_dependent_insts: BitSet::new_empty(0),
             op_dependent_vals: Vec::new(),
+            optimize_values: true,
+            sensitivity_params: Vec::new(),
+            build_hessian: false,
+            collect_opt_stats: false,
+            opt_stats: OptStats::default(),
+        }
+    }
+
+    /// Prepares the calculation of the sensitivities of the DAE system to all real
+    /// parameters. The processing of the parameter defaults in `model_param_setup` is
+    /// replicated so that the derivatives include the dependencies between parameters.
+    pub fn insert_param_defaults(&mut self, literals: &mut Rodeo) {
+        let params: Vec<_> = self
+            .module
+            .params
+            .keys()
+            .copied()
+            .filter(|param| param.ty(self.db) == Type::Real)
+            .collect();
+        let vals = self.intern.insert_param_defaults(self.db, &mut self.func, literals, &params);
+        let fixed_params = &self.module.fixed_params;
+        self.intern
+            .specialize_params(self.db, &mut self.func, |param| fixed_params.get(&param).copied());
+        self.sensitivity_params = params.into_iter().zip(vals).collect();
+        self.compute_cfg();
+    }
+
+    /// Records the size change caused by `pass` if [`Context::collect_opt_stats`] is set.
+    fn record_opt_stats(
+        &mut self,
+        stage: OptimiziationStage,
+        pass: &'static str,
+        size: &mut Option<InstCount>,
+    ) {
+        if let Some(size) = size {
+            *size = self.opt_stats.record(stage, pass, *size, &self.func);
         }
     }
 
     pub fn optimize(&mut self, stage: OptimiziationStage) -> GVN {
+        let mut size = self.collect_opt_stats.then(|| InstCount::new(&self.func));
         if stage == OptimiziationStage::Initial {
             dead_code_elimination(&mut self.func, &self.output_values);
+            self.record_opt_stats(stage, "dce", &mut size);
+        }
+        if self.optimize_values {
+            self.compute_domtree(true, false, false);
+            simplify_transcendental(&mut self.func, &self.dom_tree);
+            self.record_opt_stats(stage, "transcendental", &mut size);
+            sparse_conditional_constant_propagation(&mut self.func, &self.cfg);
+            self.record_opt_stats(stage, "sccp", &mut size);
+            inst_combine(&mut self.func);
+            self.record_opt_stats(stage, "inst_combine", &mut size);
         }
-        sparse_conditional_constant_propagation(&mut self.func, &self.cfg);
-        inst_combine(&mut self.func);
         if stage == OptimiziationStage::Final {
             simplify_cfg(&mut self.func, &mut self.cfg);
         } else {
             simplify_cfg_no_phi_merge(&mut self.func, &mut self.cfg);
         }
+        self.record_opt_stats(stage, "simplify_cfg", &mut size);
+        if self.optimize_values {
+            if stage == OptimiziationStage::Initial {
+                self.unroll_loops();
+                self.record_opt_stats(stage, "unroll_loops", &mut size);
+            }
+            self.compute_domtree(true, false, false);
+            loop_invariant_code_motion(&mut self.func, &mut self.cfg, &self.dom_tree);
+            self.record_opt_stats(stage, "licm", &mut size);
+        }
         self.compute_domtree(true, true, false);
 
         let mut gvn = GVN::default();
         gvn.init(&self.func, &self.dom_tree, self.intern.params.len() as u32);
-        gvn.solve(&mut self.func);
-        gvn.remove_unnecessary_insts(&mut self.func, &self.dom_tree);
+        if self.optimize_values {
+            gvn.solve(&mut self.func);
+            gvn.remove_unnecessary_insts(&mut self.func, &self.dom_tree);
+            self.record_opt_stats(stage, "gvn", &mut size);
+        }
 
         if stage == OptimiziationStage::Final {
             let mut control_dep = SparseBitMatrix::new_square(0);
@@ -94,11 +168,47 @@ impl<'a> Context<'a> {
                 &control_dep,
             );
             simplify_cfg(&mut self.func, &mut self.cfg);
+            self.record_opt_stats(stage, "adce", &mut size);
         }
 
         gvn
     }
 
+    /// Fully unrolls loops with a constant trip count so that the derivatives (which are
+    /// calculated afterwards) can be simplified for each iteration separately.
+    fn unroll_loops(&mut self) {
+        let callbacks = &self.intern.callbacks;
+        // limit state and noise sources are identified by their call so these calls must
+        // remain unique
+        let can_duplicate = |cb| {
+            !matches!(
+                callbacks[cb],
+                CallBackKind::StoreLimit(_)
+                    | CallBackKind::WhiteNoise { .. }
+                    | CallBackKind::FlickerNoise { .. }
+                    | CallBackKind::NoiseTable(_)
+            )
+        };
+        if !unroll_loops(&mut self.func, &mut self.cfg, &mut self.dom_tree, &can_duplicate) {
+            return;
+        }
+
+        for uses in self.intern.callback_uses.iter_mut() {
+            uses.clear();
+        }
+        for bb in self.func.layout.blocks() {
+            for inst in self.func.layout.block_insts(bb) {
+                if let Some(cb) = self.func.dfg.func_ref(inst) {
+                    self.intern.callback_uses[cb].push(inst);
+                }
+            }
+        }
+
+        sparse_conditional_constant_propagation(&mut self.func, &self.cfg);
+        inst_combine(&mut self.func);
+        simplify_cfg_no_phi_merge(&mut self.func, &mut self.cfg);
+    }
+
     pub fn compute_cfg(&mut self) {
         self.cfg.compute(&self.func);
     }
@@ -148,7 +258,7 @@ impl<'a> Context<'a> {
                 self.op_dependent_vals.push(val)
             }
         }
-        
+
         // Propagate taint
         propagate_direct_taint(
             &self.func,
@@ -167,9 +277,9 @@ impl<'a> Context<'a> {
         for (cb, uses) in self.intern.callback_uses.iter_mut_enumerated() {
             // Ff callback is op dependent
             if self.intern.callbacks[cb].op_dependent() {
-                // Remove uses that appear in instructions that are not inserted into the layout. 
-                // Add to op dependent instructions. 
-                // Add the results of these instructions to op dependent values. 
+                // Remove uses that appear in instructions that are not inserted into the layout.
+                // Add to op dependent instructions.
+                // Add the results of these instructions to op dependent values.
                 uses.retain(|&inst| {
                     if self.func.layout.inst_block(inst).is_none() {
                         return false;
@@ -183,13 +293,13 @@ impl<'a> Context<'a> {
             }
         }
         // Go through parameters, if the corresponding value is not dead and is op dependent
-        // (i.e. current, voltage, abstime, ...) add it to op dependent values. 
+        // (i.e. current, voltage, abstime, ...) add it to op dependent values.
         for (param, &val) in self.intern.params.iter() {
             if !dfg.value_dead(val) && param.op_dependent() {
                 self.op_dependent_vals.push(val)
             }
         }
-        
+
         // Propagate taint
         propagate_taint(
             &self.func,
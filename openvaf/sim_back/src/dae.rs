@@ -1,3 +1,4 @@
+use hir::{Parameter, Variable};
 use indexmap::IndexSet;
 use mir::{strip_optbarrier, Value, F_ZERO};
 use stdx::{impl_debug_display, impl_idx_from};
@@ -48,6 +49,15 @@ pub struct DaeSystem {
     /// Jacobian entry counts
     pub num_resistive: u32,
     pub num_reactive: u32,
+    /// The derivatives of the residual by the model parameters (dI_i/dp, dQ_i/dp).
+    /// Only calculated when parameter sensitivities were requested.
+    pub param_sensitivities: Vec<ParamSensitivity>,
+    /// The derivatives of the operating point variables by the model parameters.
+    /// Only calculated when parameter sensitivities were requested.
+    pub opvar_sensitivities: Vec<OpVarSensitivity>,
+    /// The derivatives of the jacobian entries by the unknowns (the second derivatives of
+    /// the residual). Only calculated when second derivatives were requested.
+    pub hessian: Vec<HessianEntry>,
 }
 
 impl DaeSystem {
@@ -102,6 +112,23 @@ impl DaeSystem {
             matrix_entry.resist = sparsify(matrix_entry.resist);
             matrix_entry.react = sparsify(matrix_entry.react);
             matrix_entry.resist != F_ZERO || matrix_entry.react != F_ZERO
+        });
+
+        self.param_sensitivities.retain_mut(|sensitivity| {
+            sensitivity.resist = sparsify(sensitivity.resist);
+            sensitivity.react = sparsify(sensitivity.react);
+            sensitivity.resist != F_ZERO || sensitivity.react != F_ZERO
+        });
+
+        self.opvar_sensitivities.retain_mut(|sensitivity| {
+            sensitivity.val = sparsify(sensitivity.val);
+            sensitivity.val != F_ZERO
+        });
+
+        self.hessian.retain_mut(|entry| {
+            entry.resist = sparsify(entry.resist);
+            entry.react = sparsify(entry.react);
+            entry.resist != F_ZERO || entry.react != F_ZERO
         })
     }
 }
@@ -226,6 +253,36 @@ pub struct MatrixEntry {
     pub react: Value,
 }
 
+/// The derivative of a residual by a parameter. The derivative includes the dependence of
+/// other parameters on `param` through their default values.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub struct ParamSensitivity {
+    pub param: Parameter,
+    pub unknown: SimUnknown,
+    pub resist: Value,
+    pub react: Value,
+}
+
+/// The derivative of an operating point variable by a parameter, includes the dependence
+/// of other parameters on `param` like [`ParamSensitivity`].
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub struct OpVarSensitivity {
+    pub param: Parameter,
+    pub var: Variable,
+    pub val: Value,
+}
+
+/// The derivative of the jacobian entry (`row`, `col`) by the unknown `dir`:
+/// (ddx(ddx(I_row, x_col), x_dir), ddx(ddx(Q_row, x_col), x_dir)).
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub struct HessianEntry {
+    pub row: SimUnknown,
+    pub col: SimUnknown,
+    pub dir: SimUnknown,
+    pub resist: Value,
+    pub react: Value,
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct MatrixEntryId(u32);
 impl_idx_from!(MatrixEntryId(u32));
@@ -1,10 +1,10 @@
-use std::mem::replace;
+use std::mem::{replace, take};
 use std::vec;
 
 use ahash::AHashMap;
 use bitset::BitSet;
-use hir::{BranchWrite, CompilationDB, Node, ParamSysFun};
-use hir_lower::{CurrentKind, HirInterner, ImplicitEquation, ParamKind};
+use hir::{BranchWrite, CompilationDB, Node, ParamSysFun, Parameter, Variable};
+use hir_lower::{CurrentKind, HirInterner, ImplicitEquation, ParamKind, PlaceKind};
 use indexmap::IndexSet;
 use mir::builder::InstBuilder;
 use mir::cursor::{Cursor, FuncCursor};
@@ -12,11 +12,15 @@ use mir::{
     strip_optbarrier, Block, ControlFlowGraph, DominatorTree, Inst, KnownDerivatives, Unknown,
     Value, FALSE, F_ONE, F_ZERO, TRUE,
 };
-use mir_autodiff::auto_diff;
+use mir_autodiff::{auto_diff_higher_order, derivative_of};
 use typed_index_collections::TiVec;
 
 use crate::context::Context;
-use crate::dae::{DaeSystem, MatrixEntry, Residual, ResidualNatureKind, SimUnknown};
+use crate::dae::{
+    DaeSystem, HessianEntry, MatrixEntry, OpVarSensitivity, ParamSensitivity, Residual,
+    ResidualNatureKind, SimUnknown,
+};
+use crate::module_info::OpVarDerivative;
 use crate::noise::NoiseSource;
 use crate::topology::{BranchInfo, Contribution};
 use crate::util::{add, is_op_dependent, update_optbarrier};
@@ -53,6 +57,16 @@ macro_rules! get_residual {
     }};
 }
 
+/// The unknowns used to calculate the sensitivities to a parameter
+struct ParamUnknowns {
+    param: Parameter,
+    /// the parameter value read by the model, `None` if the model does not read it directly
+    val: Option<Unknown>,
+    /// the parameter value built by [`HirInterner::insert_param_defaults`]
+    default_val: Value,
+    default: Unknown,
+}
+
 pub(super) struct Builder<'a> {
     pub(super) system: DaeSystem,
     pub(super) cursor: FuncCursor<'a>,
@@ -62,6 +76,9 @@ pub(super) struct Builder<'a> {
     pub(super) dom_tree: &'a mut DominatorTree,
     pub(super) op_dependent_insts: &'a BitSet<Inst>,
     pub(super) output_values: &'a mut BitSet<Value>,
+    pub(super) sensitivity_params: Vec<(Parameter, Value)>,
+    pub(super) build_hessian: bool,
+    pub(super) op_var_derivatives: Vec<(Variable, OpVarDerivative)>,
 }
 
 impl<'a> Builder<'a> {
@@ -76,6 +93,14 @@ impl<'a> Builder<'a> {
             dom_tree: &mut ctx.dom_tree,
             op_dependent_insts: &ctx.op_dependent_insts,
             output_values: &mut ctx.output_values,
+            sensitivity_params: take(&mut ctx.sensitivity_params),
+            build_hessian: ctx.build_hessian,
+            op_var_derivatives: ctx
+                .module
+                .op_vars
+                .iter()
+                .filter_map(|(&var, info)| Some((var, info.derivative?)))
+                .collect(),
         };
 
         // ensure ports are the first unknowns and always have an unknown
@@ -92,20 +117,38 @@ impl<'a> Builder<'a> {
 
     pub(super) fn finish(mut self) -> DaeSystem {
         let sim_unknown_reads = self.sim_unknown_reads();
-        let derivative_info = self.intern.unknowns(&self.cursor, true);
-        let extra_derivatives = self
+        let mut derivative_info = self.intern.unknowns(&self.cursor, true);
+        let param_unknowns = self.param_unknowns(&mut derivative_info);
+        let sensitivity_opvars = self.sensitivity_opvars();
+        let mut extra_derivatives = self
             .jacobian_derivatives(sim_unknown_reads.iter().map(|&(_, val)| val), &derivative_info);
+        self.param_derivatives(&param_unknowns, &sensitivity_opvars, &mut extra_derivatives);
+        let columns = self.jacobian_columns(&sim_unknown_reads, &derivative_info);
+        let hessian_derivatives =
+            if self.build_hessian { self.hessian_derivatives(&columns) } else { Vec::new() };
+        let hessian_derivatives: Vec<_> =
+            hessian_derivatives.iter().map(|(val, unknowns)| (*val, &unknowns[..])).collect();
         // TODO(pref): incrementially update dom_tree (for switch branches) instead
         self.dom_tree.compute(self.cursor.func, self.cfg, true, false, true);
-        let derivatives =
-            auto_diff(&mut *self.cursor.func, self.dom_tree, &derivative_info, &extra_derivatives);
+        let derivatives = auto_diff_higher_order(
+            &mut *self.cursor.func,
+            self.dom_tree,
+            &derivative_info,
+            &extra_derivatives,
+            &hessian_derivatives,
+        );
         drop(extra_derivatives);
         // auto_diff may in an unlikely case add extra bb at the end, ensure we are building everything at the end
         self.cursor.goto_exit();
 
-        self.build_jacobian(&sim_unknown_reads, &derivative_info, &derivatives);
+        self.build_jacobian(&columns, &derivatives);
+        if self.build_hessian {
+            self.build_hessian(&columns, &derivatives);
+        }
+        self.build_param_sensitivities(&param_unknowns, &sensitivity_opvars, &derivatives);
         self.build_lim_rhs(&derivative_info, derivatives);
         self.ensure_optbarriers();
+        self.build_op_var_derivatives();
 
         self.build_input_unknown_pairs();
 
@@ -268,10 +311,54 @@ impl<'a> Builder<'a> {
         }
     }
 
-    fn build_jacobian(
-        &mut self,
+    /// The derivatives that make up each column of the jacobian: for every simulation unknown
+    /// the unknowns the residual is derived by and whether the derivative is negated.
+    /// Voltage probes read two simulation unknowns (V(x, y) = V(x) - V(y)) and limited
+    /// probes are additionally derived by their limited values.
+    fn jacobian_columns(
+        &self,
         sim_unknown_reads: &[(ParamKind, Value)],
         derivative_info: &KnownDerivatives,
+    ) -> Vec<(SimUnknown, Unknown, bool)> {
+        let mut columns = Vec::with_capacity(sim_unknown_reads.len());
+        let mut add_column = |sim_unknown: SimUnknownKind, unknown, negate| {
+            let sim_unknown = if let Some(unknown) = self.system.unknowns.index(&sim_unknown) {
+                unknown
+            } else {
+                return;
+            };
+            if let Some(lim_vals) = self.intern.lim_state.raw.get(&unknown) {
+                for (val, negate_lim) in lim_vals {
+                    if let Some(lim_unknown) = derivative_info.unknowns.index(val) {
+                        columns.push((sim_unknown, lim_unknown, negate != *negate_lim));
+                    }
+                }
+            }
+
+            if let Some(unknown) = derivative_info.unknowns.index(&unknown) {
+                columns.push((sim_unknown, unknown, negate));
+            }
+        };
+        for &(kind, val) in sim_unknown_reads {
+            let unknown = match kind {
+                ParamKind::Voltage { hi, lo } => {
+                    if let Some(lo) = lo {
+                        add_column(SimUnknownKind::KirchoffLaw(lo), val, true);
+                    }
+                    SimUnknownKind::KirchoffLaw(hi)
+                }
+                ParamKind::ImplicitUnknown(equation) => SimUnknownKind::Implicit(equation),
+                ParamKind::Current(kind) => SimUnknownKind::Current(kind),
+                _ => continue,
+            };
+            add_column(unknown, val, false);
+        }
+        columns
+    }
+
+    fn build_jacobian(
+        &mut self,
+        columns: &[(SimUnknown, Unknown, bool)],
         derivatives: &AHashMap<(Value, Unknown), Value>,
     ) {
         self.system.jacobian =
@@ -287,52 +374,12 @@ impl<'a> Builder<'a> {
 
         for (row, residual) in self.system.residual.iter_enumerated() {
             // construct the dense row
-            let mut add_residual = |sim_unknown: SimUnknownKind, unknown, negate| {
-                let sim_unknown = if let Some(unknown) = self.system.unknowns.index(&sim_unknown) {
-                    unknown
-                } else {
-                    return;
-                };
-                let (resist, react) = &mut dense_row[sim_unknown];
-                if let Some(lim_vals) = self.intern.lim_state.raw.get(&unknown) {
-                    for (val, negate_lim) in lim_vals {
-                        let lim_unknown = if let Some(it) = derivative_info.unknowns.index(val) {
-                            it
-                        } else {
-                            continue;
-                        };
-                        add(resist, residual.resist, lim_unknown, negate != *negate_lim);
-                        add(
-                            resist,
-                            residual.resist_small_signal,
-                            lim_unknown,
-                            negate != *negate_lim,
-                        );
-                        add(react, residual.react, lim_unknown, negate != *negate_lim);
-                        add(react, residual.react_small_signal, lim_unknown, negate != *negate_lim);
-                    }
-                }
-
-                if let Some(unknown) = derivative_info.unknowns.index(&unknown) {
-                    add(resist, residual.resist, unknown, negate);
-                    add(resist, residual.resist_small_signal, unknown, negate);
-                    add(react, residual.react, unknown, negate);
-                    add(react, residual.react_small_signal, unknown, negate);
-                }
-            };
-            for &(kind, val) in sim_unknown_reads {
-                let unknown = match kind {
-                    ParamKind::Voltage { hi, lo } => {
-                        if let Some(lo) = lo {
-                            add_residual(SimUnknownKind::KirchoffLaw(lo), val, true);
-                        }
-                        SimUnknownKind::KirchoffLaw(hi)
-                    }
-                    ParamKind::ImplicitUnknown(equation) => SimUnknownKind::Implicit(equation),
-                    ParamKind::Current(kind) => SimUnknownKind::Current(kind),
-                    _ => continue,
-                };
-                add_residual(unknown, val, false);
+            for &(col, unknown, negate) in columns {
+                let (resist, react) = &mut dense_row[col];
+                add(resist, residual.resist, unknown, negate);
+                add(resist, residual.resist_small_signal, unknown, negate);
+                add(react, residual.react, unknown, negate);
+                add(react, residual.react_small_signal, unknown, negate);
             }
 
             // sparsify the row
@@ -350,6 +397,94 @@ impl<'a> Builder<'a> {
         }
     }
 
+    /// The second derivatives required to build the hessian. Each residual is derived by every
+    /// pair of unknowns. The derivatives are symmetric so each pair is only requested once.
+    fn hessian_derivatives(
+        &self,
+        columns: &[(SimUnknown, Unknown, bool)],
+    ) -> Vec<(Value, [Unknown; 2])> {
+        let mut unknowns: Vec<_> = columns.iter().map(|&(_, unknown, _)| unknown).collect();
+        unknowns.sort_unstable_by_key(|unknown| unknown.0);
+        unknowns.dedup();
+
+        let mut res = Vec::new();
+        for residual in &self.system.residual {
+            for val in [residual.resist, residual.react] {
+                if self.cursor.func.dfg.value_def(val).as_const().is_some() {
+                    continue;
+                }
+                for (i, &x) in unknowns.iter().enumerate() {
+                    res.extend(unknowns[i..].iter().map(|&y| (val, [x, y])))
+                }
+            }
+        }
+        res
+    }
+
+    /// Builds the derivatives of each jacobian entry by each simulation unknown from the second
+    /// derivatives of the residual. Small signal values are always zero during large signal
+    /// analysis and are therefore not included.
+    fn build_hessian(
+        &mut self,
+        columns: &[(SimUnknown, Unknown, bool)],
+        derivatives: &AHashMap<(Value, Unknown), Value>,
+    ) {
+        // the nonzero entries of the current row, most (col, dir) pairs are zero
+        let mut entries: AHashMap<(SimUnknown, SimUnknown), (Value, Value)> = AHashMap::new();
+        for (row, residual) in self.system.residual.iter_enumerated() {
+            for &(col, x, negate_x) in columns {
+                for &(dir, y, negate_y) in columns {
+                    let unknowns = if x.0 <= y.0 { [x, y] } else { [y, x] };
+                    let negate = negate_x != negate_y;
+                    let resist = derivative_of(derivatives, residual.resist, &unknowns);
+                    let react = derivative_of(derivatives, residual.react, &unknowns);
+                    if resist == F_ZERO && react == F_ZERO {
+                        continue;
+                    }
+                    let dst = entries.entry((col, dir)).or_insert((F_ZERO, F_ZERO));
+                    add(&mut self.cursor, &mut dst.0, resist, negate);
+                    add(&mut self.cursor, &mut dst.1, react, negate);
+                }
+            }
+
+            let mut row_entries: Vec<_> = entries.drain().collect();
+            row_entries.sort_unstable_by_key(|&(pos, _)| pos);
+            for ((col, dir), (resist, react)) in row_entries {
+                // contributions may cancel
+                if resist == F_ZERO && react == F_ZERO {
+                    continue;
+                }
+                self.system.hessian.push(HessianEntry { row, col, dir, resist, react });
+            }
+        }
+    }
+
+    /// Replaces the value of each operating point variable declared with the `derivative_of`
+    /// attribute by the corresponding (final) jacobian entry.
+    fn build_op_var_derivatives(&mut self) {
+        for (var, derivative) in take(&mut self.op_var_derivatives) {
+            let unknown = |node| self.system.unknowns.index(&SimUnknownKind::KirchoffLaw(node));
+            let entry =
+                unknown(derivative.flow).zip(unknown(derivative.wrt)).and_then(|(row, col)| {
+                    self.system.jacobian.iter().find(|entry| entry.row == row && entry.col == col)
+                });
+            let val = entry.map_or(F_ZERO, |entry| {
+                let val = if derivative.reactive { entry.react } else { entry.resist };
+                // the optbarrier of the jacobian entry may be removed by sparsify
+                strip_optbarrier(&self.cursor, val)
+            });
+            let val = self.cursor.ins().optbarrier(val);
+            if let Some(output) = self.intern.outputs.get_mut(&PlaceKind::Var(var)) {
+                if let Some(old) = output.expand() {
+                    self.output_values.remove(old);
+                }
+                *output = val.into();
+            }
+            self.output_values.ensure(self.cursor.func.dfg.num_values());
+            self.output_values.insert(val);
+        }
+    }
+
     pub fn jacobian_derivatives(
         &self,
         simulation_unknown: impl Iterator<Item = Value>,
@@ -400,6 +535,149 @@ impl<'a> Builder<'a> {
         res
     }
 
+    fn param_unknowns(&self, derivative_info: &mut KnownDerivatives) -> Vec<ParamUnknowns> {
+        let params = self.sensitivity_params.iter().map(|&(param, _)| param);
+        let vals: AHashMap<_, _> =
+            self.intern.param_unknowns(&self.cursor, derivative_info, params).into_iter().collect();
+        self.sensitivity_params
+            .iter()
+            .map(|&(param, default_val)| ParamUnknowns {
+                param,
+                val: vals.get(&param).copied(),
+                default_val,
+                default: derivative_info.unknowns.ensure(default_val).0,
+            })
+            .collect()
+    }
+
+    /// The operating point variables whose sensitivities are calculated. Variables declared
+    /// with `derivative_of` are excluded as their value is replaced by a jacobian entry.
+    fn sensitivity_opvars(&self) -> Vec<(Variable, Value)> {
+        if self.sensitivity_params.is_empty() {
+            return Vec::new();
+        }
+        self.intern
+            .outputs
+            .iter()
+            .filter_map(|(kind, val)| {
+                let var = match *kind {
+                    PlaceKind::Var(var) => var,
+                    _ => return None,
+                };
+                if self.op_var_derivatives.iter().any(|&(other, _)| other == var) {
+                    return None;
+                }
+                let val = strip_optbarrier(&self.cursor, val.expand()?);
+                self.cursor.func.dfg.value_def(val).as_const().is_none().then_some((var, val))
+            })
+            .collect()
+    }
+
+    /// The derivatives required to calculate the parameter sensitivities: the derivatives
+    /// of the residual and the operating point variables by each parameter and the
+    /// derivatives of the parameter defaults by each other.
+    fn param_derivatives(
+        &self,
+        params: &[ParamUnknowns],
+        opvars: &[(Variable, Value)],
+        dst: &mut Vec<(Value, Unknown)>,
+    ) {
+        for residual in &self.system.residual {
+            for val in [residual.resist, residual.react] {
+                if self.cursor.func.dfg.value_def(val).as_const().is_none() {
+                    dst.extend(params.iter().filter_map(|param| Some((val, param.val?))))
+                }
+            }
+        }
+        for &(_, val) in opvars {
+            dst.extend(params.iter().filter_map(|param| Some((val, param.val?))))
+        }
+
+        for param in params {
+            // only parameters read by the model contribute to the sensitivities
+            if param.val.is_none() {
+                continue;
+            }
+            dst.extend(
+                params
+                    .iter()
+                    .filter(|other| other.param != param.param)
+                    .map(|other| (param.default_val, other.default)),
+            );
+        }
+    }
+
+    /// Builds the derivative of each residual (and operating point variable) by each
+    /// parameter p:
+    ///
+    /// dR/dp = ∂R/∂p + Σ_q ∂R/∂q * dq/dp
+    ///
+    /// where dq/dp is the derivative of the default value of parameter q by p.
+    /// It is zero if q was given.
+    fn build_param_sensitivities(
+        &mut self,
+        params: &[ParamUnknowns],
+        opvars: &[(Variable, Value)],
+        derivatives: &AHashMap<(Value, Unknown), Value>,
+    ) {
+        let default_derivatives: Vec<Vec<_>> = params
+            .iter()
+            .map(|param| {
+                params
+                    .iter()
+                    .filter(|other| other.param != param.param)
+                    .filter_map(|other| {
+                        let ddx = derivatives.get(&(other.default_val, param.default))?;
+                        Some((other.val?, *ddx))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        for (unknown, residual) in self.system.residual.iter_enumerated() {
+            for (param, default_derivatives) in params.iter().zip(&default_derivatives) {
+                let mut sensitivity = |val| {
+                    param_sensitivity(
+                        &mut self.cursor,
+                        derivatives,
+                        param,
+                        default_derivatives,
+                        val,
+                    )
+                };
+                let resist = sensitivity(residual.resist);
+                let react = sensitivity(residual.react);
+                if resist != F_ZERO || react != F_ZERO {
+                    self.system.param_sensitivities.push(ParamSensitivity {
+                        param: param.param,
+                        unknown,
+                        resist,
+                        react,
+                    });
+                }
+            }
+        }
+
+        for &(var, val) in opvars {
+            for (param, default_derivatives) in params.iter().zip(&default_derivatives) {
+                let val = param_sensitivity(
+                    &mut self.cursor,
+                    derivatives,
+                    param,
+                    default_derivatives,
+                    val,
+                );
+                if val != F_ZERO {
+                    self.system.opvar_sensitivities.push(OpVarSensitivity {
+                        param: param.param,
+                        var,
+                        val,
+                    });
+                }
+            }
+        }
+    }
+
     pub(super) fn build_branch(&mut self, branch: BranchWrite, contributions: &BranchInfo) {
         let current = branch.into();
         // contributions.is_voltage_src is a Value that is used for choosing the branch type (voltage, current)
@@ -770,5 +1048,43 @@ impl<'a> Builder<'a> {
             entry.resist = ensure_optbarrier(entry.resist, is_kirchoff);
             entry.react = ensure_optbarrier(entry.react, is_kirchoff);
         }
+
+        for sensitivity in &mut self.system.param_sensitivities {
+            let is_kirchoff =
+                matches!(self.system.unknowns[sensitivity.unknown], SimUnknownKind::KirchoffLaw(_));
+            sensitivity.resist = ensure_optbarrier(sensitivity.resist, is_kirchoff);
+            sensitivity.react = ensure_optbarrier(sensitivity.react, is_kirchoff);
+        }
+
+        for entry in &mut self.system.hessian {
+            let is_kirchoff =
+                matches!(self.system.unknowns[entry.row], SimUnknownKind::KirchoffLaw(_));
+            entry.resist = ensure_optbarrier(entry.resist, is_kirchoff);
+            entry.react = ensure_optbarrier(entry.react, is_kirchoff);
+        }
+
+        for sensitivity in &mut self.system.opvar_sensitivities {
+            sensitivity.val = ensure_optbarrier(sensitivity.val, false);
+        }
+    }
+}
+
+/// Builds the derivative of `val` by `param` including the dependence of other parameter
+/// defaults on `param` (`default_derivatives`), see `build_param_sensitivities`.
+fn param_sensitivity(
+    cursor: &mut FuncCursor,
+    derivatives: &AHashMap<(Value, Unknown), Value>,
+    param: &ParamUnknowns,
+    default_derivatives: &[(Unknown, Value)],
+    val: Value,
+) -> Value {
+    let ddx = |unknown| derivatives.get(&(val, unknown)).copied();
+    let mut res = param.val.and_then(ddx).unwrap_or(F_ZERO);
+    for &(other, ddx_default) in default_derivatives {
+        if let Some(ddx_other) = ddx(other) {
+            let val = cursor.ins().fmul(ddx_other, ddx_default);
+            add(cursor, &mut res, val, false);
+        }
     }
+    res
 }
@@ -3,13 +3,15 @@ use std::fs;
 use expect_test::expect_file;
 use hir::diagnostics::ConsoleSink;
 use hir::CompilationDB;
+use hir_lower::PlaceKind;
 use indoc::indoc;
 use lasso::Rodeo;
+use mir::strip_optbarrier;
 use stdx::{integration_test_dir, openvaf_test_data};
 
 use crate::context::{Context, OptimiziationStage};
 use crate::dae::DaeSystem;
-use crate::topology;
+use crate::{topology, SimUnknownKind};
 
 fn run_test(src: &str) {
     let db = CompilationDB::new_virtual(src).unwrap();
@@ -146,3 +148,141 @@ fn dyn_switch_branch() {
     "#};
     run_test(src);
 }
+
+#[test]
+fn param_sensitivities() {
+    let src = indoc! {r#"
+        `include "disciplines.vams"
+        module param_sensitivities(inout a, inout c);
+            electrical a, c;
+            parameter real r=1.0;
+            parameter real g=1.0/r;
+            parameter integer n=1;
+            (*desc="total conductance"*) real gt;
+            analog begin
+                gt = n*g;
+                I(a, c) <+ gt*V(a, c);
+            end
+        endmodule
+    "#};
+    let db = CompilationDB::new_virtual(src).unwrap();
+    let module = crate::collect_modules(&db, false, &mut ConsoleSink::new(&db)).unwrap().remove(0);
+    let mut literals = Rodeo::new();
+    let mut context = Context::new(&db, &mut literals, &module);
+    context.compute_outputs(true);
+    context.compute_cfg();
+    context.optimize(OptimiziationStage::Initial);
+    let topology = topology::Topology::new(&mut context);
+    context.insert_param_defaults(&mut literals);
+    let mut dae_system = DaeSystem::new(&mut context, topology);
+    context.compute_cfg();
+    context.optimize(OptimiziationStage::Final);
+    dae_system.sparsify(&mut context);
+    assert!(context.func.validate());
+
+    let mut params: Vec<_> = dae_system
+        .param_sensitivities
+        .iter()
+        .map(|sensitivity| (sensitivity.param.name(&db), sensitivity.unknown))
+        .collect();
+    params.sort();
+    params.dedup();
+    // r only enters the residual through the default of g, n is not a real parameter
+    let names: Vec<_> = params.iter().map(|(name, _)| &**name).collect();
+    assert_eq!(names, ["g", "g", "r", "r"]);
+
+    let mut opvar_params: Vec<_> = dae_system
+        .opvar_sensitivities
+        .iter()
+        .map(|sensitivity| (sensitivity.var.name(&db), sensitivity.param.name(&db)))
+        .collect();
+    opvar_params.sort();
+    let opvar_params: Vec<_> = opvar_params.iter().map(|(var, param)| (&**var, &**param)).collect();
+    assert_eq!(opvar_params, [("gt", "g"), ("gt", "r")]);
+}
+
+#[test]
+fn hessian() {
+    let src = indoc! {r#"
+        `include "disciplines.vams"
+        module hessian(inout a, inout c);
+            electrical a, c;
+            parameter real is=1e-14;
+            analog begin
+                I(a, c) <+ is*exp(V(a, c));
+                I(a, c) <+ ddt(V(a, c)*V(a, c));
+                I(a, c) <+ V(a, c);
+            end
+        endmodule
+    "#};
+    let db = CompilationDB::new_virtual(src).unwrap();
+    let module = crate::collect_modules(&db, false, &mut ConsoleSink::new(&db)).unwrap().remove(0);
+    let mut literals = Rodeo::new();
+    let mut context = Context::new(&db, &mut literals, &module);
+    context.compute_outputs(true);
+    context.compute_cfg();
+    context.optimize(OptimiziationStage::Initial);
+    let topology = topology::Topology::new(&mut context);
+    context.build_hessian = true;
+    let mut dae_system = DaeSystem::new(&mut context, topology);
+    context.compute_cfg();
+    context.optimize(OptimiziationStage::Final);
+    dae_system.sparsify(&mut context);
+    assert!(context.func.validate());
+
+    // every jacobian entry depends on both unknowns, the linear contribution does not
+    // contribute to the hessian
+    assert_eq!(dae_system.hessian.len(), 8);
+    for entry in &dae_system.hessian {
+        assert!(dae_system.jacobian.iter().any(|it| it.row == entry.row && it.col == entry.col));
+        assert_ne!(entry.resist, mir::F_ZERO);
+        assert_ne!(entry.react, mir::F_ZERO);
+    }
+}
+
+#[test]
+fn op_var_derivatives() {
+    let src = indoc! {r#"
+        `include "disciplines.vams"
+        module op_var_derivatives(inout d, inout g, inout s);
+            electrical d, g, s;
+            (* desc="transconductance", units="S", derivative_of="I(d, s)", wrt="V(g, s)" *)
+            real gm;
+            (* derivative_of="I(g, s)", wrt="V(g, s)", reactive *) real cgg;
+            (* derivative_of="I(g, s)", wrt="V(d, s)" *) real zero;
+            analog begin
+                I(d, s) <+ V(g, s)*V(d, s);
+                I(g, s) <+ ddt(2*V(g, s));
+                gm = 1.0;
+            end
+        endmodule
+    "#};
+    let db = CompilationDB::new_virtual(src).unwrap();
+    let module = crate::collect_modules(&db, false, &mut ConsoleSink::new(&db)).unwrap().remove(0);
+    let mut literals = Rodeo::new();
+    let mut context = Context::new(&db, &mut literals, &module);
+    context.compute_outputs(true);
+    context.compute_cfg();
+    context.optimize(OptimiziationStage::Initial);
+    let topology = topology::Topology::new(&mut context);
+    let mut dae_system = DaeSystem::new(&mut context, topology);
+    context.compute_cfg();
+    context.optimize(OptimiziationStage::Final);
+    dae_system.sparsify(&mut context);
+    assert!(context.func.validate());
+
+    let [d, g, _] = module.module.ports(&db)[..] else { unreachable!() };
+    let unknown = |node| dae_system.unknowns.unwrap_index(&SimUnknownKind::KirchoffLaw(node));
+    let entry = |row, col| {
+        let (row, col) = (unknown(row), unknown(col));
+        *dae_system.jacobian.iter().find(|entry| entry.row == row && entry.col == col).unwrap()
+    };
+    let op_var = |name: &str| {
+        let (&var, _) = module.op_vars.iter().find(|(var, _)| var.name(&db) == name).unwrap();
+        let val = context.intern.outputs[&PlaceKind::Var(var)].unwrap_unchecked();
+        strip_optbarrier(&context.func, val)
+    };
+    assert_eq!(op_var("gm"), strip_optbarrier(&context.func, entry(d, g).resist));
+    assert_eq!(op_var("cgg"), strip_optbarrier(&context.func, entry(g, g).react));
+    assert_eq!(op_var("zero"), mir::F_ZERO);
+}
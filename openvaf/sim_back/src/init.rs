@@ -83,7 +83,9 @@ impl<'a> Builder<'a> {
                 func: Function::with_name(format!("{}_init", &ctx.func.name)),
                 cached_vals: IndexMap::with_capacity_and_hasher(128, RandomState::new()),
                 cache_slots: TiMap::default(),
-                intern: HirInterner::default(),
+                // parameter initialization is lowered into the init function later so the
+                // source locations must not overlap with the ones already used by `func`
+                intern: HirInterner { srclocs: ctx.intern.srclocs.clone(), ..Default::default() },
             },
             init_cache: IndexMap::with_capacity_and_hasher(256, RandomState::default()),
             func: &mut ctx.func,
@@ -0,0 +1,658 @@
+//! Evaluation of a [`CompiledModule`] with the MIR interpreter.
+//!
+//! This mirrors the functions that the OSDI backend generates (`setup_model`, `setup_instance`,
+//! `eval` and `load_noise`) without requiring LLVM. It is much slower than native code but
+//! serves as a reference when debugging miscompilations and allows evaluating models on
+//! platforms without an LLVM toolchain.
+
+use std::ffi::c_void;
+use std::fmt;
+
+use ahash::AHashMap;
+use hir::{ParamSysFun, Parameter, Variable};
+use hir_lower::{CallBackKind, CurrentKind, ParamInfoKind, ParamKind, PlaceKind, RetFlag};
+use indexmap::IndexMap;
+use lasso::{Rodeo, Spur};
+use mir::{FuncRef, Function, Param, Value};
+pub use mir_interpret::Data;
+use mir_interpret::{Func, Interpreter, InterpreterState};
+use typed_index_collections::{TiSlice, TiVec};
+
+use crate::dae::{MatrixEntryId, NoiseSourceKind, SimUnknown};
+use crate::init::CacheSlot;
+use crate::node_collapse::CollapsePair;
+use crate::{CompiledModule, SimUnknownKind};
+
+#[cfg(test)]
+mod tests;
+
+// flags shared with the OSDI interface (see osdi_0_4.rs)
+pub const CALC_RESIST_RESIDUAL: u32 = 1;
+pub const CALC_REACT_RESIDUAL: u32 = 2;
+pub const CALC_RESIST_JACOBIAN: u32 = 4;
+pub const CALC_REACT_JACOBIAN: u32 = 8;
+pub const CALC_NOISE: u32 = 16;
+pub const CALC_OP: u32 = 32;
+pub const CALC_RESIST_LIM_RHS: u32 = 64;
+pub const CALC_REACT_LIM_RHS: u32 = 128;
+pub const ENABLE_LIM: u32 = 256;
+pub const INIT_LIM: u32 = 512;
+pub const ANALYSIS_NOISE: u32 = 1024;
+pub const ANALYSIS_DC: u32 = 2048;
+pub const ANALYSIS_AC: u32 = 4096;
+pub const ANALYSIS_TRAN: u32 = 8192;
+pub const ANALYSIS_IC: u32 = 16384;
+pub const ANALYSIS_STATIC: u32 = 32768;
+pub const ANALYSIS_NODESET: u32 = 65536;
+
+pub const EVAL_RET_FLAG_LIM: u32 = 1;
+pub const EVAL_RET_FLAG_FATAL: u32 = 2;
+pub const EVAL_RET_FLAG_FINISH: u32 = 4;
+pub const EVAL_RET_FLAG_STOP: u32 = 8;
+
+/// A `$limit` function provided by the simulator. It receives whether limiting is being
+/// initialized, the new and old value and any extra arguments and returns the limited
+/// value and whether the value was changed.
+pub type LimitFn = fn(bool, f64, f64, &[f64]) -> (f64, bool);
+
+/// Parameter values explicitly specified by the user (for a model or an instance).
+/// Any parameter not present is treated as not given.
+#[derive(Default, Clone)]
+pub struct ParamValues {
+    pub params: AHashMap<Parameter, Data>,
+    pub sys_funs: AHashMap<ParamSysFun, f64>,
+}
+
+impl ParamValues {
+    pub fn set_param(&mut self, param: Parameter, val: impl Into<Data>) {
+        self.params.insert(param, val.into());
+    }
+
+    pub fn set_sys_fun(&mut self, param: ParamSysFun, val: f64) {
+        self.sys_funs.insert(param, val);
+    }
+}
+
+/// Values returned by `$simparam` and `$simparam$str`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SimParams<'a> {
+    pub real: &'a [(&'a str, f64)],
+    pub str: &'a [(&'a str, &'a str)],
+}
+
+/// The simulator state passed to [`InterpretedInstance::eval`]. Corresponds to `OsdiSimInfo`.
+pub struct SimInfo<'a> {
+    pub flags: u32,
+    pub abstime: f64,
+    pub prev_solve: &'a TiSlice<SimUnknown, f64>,
+    pub prev_state: &'a [f64],
+    pub next_state: &'a mut [f64],
+    pub simparams: SimParams<'a>,
+    pub limits: &'a [(&'a str, LimitFn)],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Stage {
+    ModelSetup,
+    InstanceSetup,
+    Eval,
+}
+
+/// A model whose parameters have been processed by the `model_param_setup` function.
+pub struct InterpretedModel<'a> {
+    module: &'a CompiledModule<'a>,
+    literals: &'a Rodeo,
+    given: ParamValues,
+    params: AHashMap<Parameter, Data>,
+    /// model parameters that were rejected by their `from`/`exclude` constraints
+    pub errors: Vec<Parameter>,
+    pub ret_flags: u32,
+}
+
+/// An instance whose parameters and operating point independent values
+/// have been computed by the `init` function.
+pub struct InterpretedInstance<'a> {
+    model: &'a InterpretedModel<'a>,
+    given: ParamValues,
+    params: AHashMap<Parameter, Data>,
+    sys_funs: AHashMap<ParamSysFun, f64>,
+    temperature: f64,
+    connected_ports: u32,
+    cache: TiVec<CacheSlot, Data>,
+    collapsed: TiVec<CollapsePair, bool>,
+    /// instance parameters that were rejected by their `from`/`exclude` constraints
+    pub errors: Vec<Parameter>,
+    pub ret_flags: u32,
+}
+
+/// The results of a single [`InterpretedInstance::eval`] call. All residuals and matrix
+/// entries are computed regardless of the `CALC_*` flags and are indexed the same way as
+/// the residuals and jacobian entries in the generated OSDI descriptor.
+#[derive(Clone)]
+pub struct EvalResult {
+    pub ret_flags: u32,
+    pub resist_residual: TiVec<SimUnknown, f64>,
+    pub react_residual: TiVec<SimUnknown, f64>,
+    pub resist_lim_rhs: TiVec<SimUnknown, f64>,
+    pub react_lim_rhs: TiVec<SimUnknown, f64>,
+    pub resist_jacobian: TiVec<MatrixEntryId, f64>,
+    pub react_jacobian: TiVec<MatrixEntryId, f64>,
+    pub bound_step: Option<f64>,
+    pub opvars: IndexMap<Variable, Data, ahash::RandomState>,
+    noise: Vec<NoiseEval>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NoiseEval {
+    White { pwr: f64, factor: f64 },
+    Flicker { pwr: f64, exp: f64, factor: f64 },
+    Table,
+}
+
+/// A feature of a module that can not be evaluated by the interpreter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unsupported {
+    /// `noise_table` and `noise_table_log` sources
+    NoiseTable,
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unsupported::NoiseTable => write!(f, "noise tables are not supported"),
+        }
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+impl EvalResult {
+    /// Computes the power spectral density of each noise source at `freq`,
+    /// equivalent to the OSDI `load_noise` function.
+    pub fn noise_density(&self, freq: f64) -> Result<Vec<f64>, Unsupported> {
+        self.noise
+            .iter()
+            .map(|src| match *src {
+                NoiseEval::White { pwr, factor } => Ok(pwr * factor * factor),
+                NoiseEval::Flicker { pwr, exp, factor } => {
+                    Ok(pwr / freq.powf(exp) * factor * factor)
+                }
+                NoiseEval::Table => Err(Unsupported::NoiseTable),
+            })
+            .collect()
+    }
+}
+
+impl<'a> InterpretedModel<'a> {
+    /// Runs the `model_param_setup` function, equivalent to OSDI `setup_model`.
+    pub fn setup(
+        module: &'a CompiledModule<'a>,
+        literals: &'a Rodeo,
+        given: ParamValues,
+        simparams: SimParams<'_>,
+    ) -> InterpretedModel<'a> {
+        let func = &module.model_param_setup;
+        let intern = &module.model_param_intern;
+
+        let args: TiVec<Param, Data> = intern
+            .params
+            .raw
+            .keys()
+            .map(|kind| match *kind {
+                ParamKind::Param(param) => given.params.get(&param).copied().unwrap_or(Data::UNDEF),
+                ParamKind::ParamGiven { param } => given.params.contains_key(&param).into(),
+                ParamKind::ParamSysFun(param) => {
+                    given.sys_funs.get(&param).copied().unwrap_or(param.default_value()).into()
+                }
+                _ => Data::UNDEF,
+            })
+            .collect();
+
+        let mut env = Env::new(module, literals, simparams, Stage::ModelSetup);
+        let state = run(func, &intern.callbacks, &mut env, &args);
+
+        let params = module
+            .info
+            .params
+            .iter()
+            .filter(|(_, info)| !info.is_instance)
+            .map(|(&param, _)| {
+                let val = intern.outputs[&PlaceKind::Param(param)].unwrap_unchecked();
+                (param, state.read(val))
+            })
+            .collect();
+
+        InterpretedModel {
+            module,
+            literals,
+            given,
+            params,
+            errors: env.errors,
+            ret_flags: env.ret_flags,
+        }
+    }
+
+    /// Returns the value of a model parameter after processing.
+    pub fn param(&self, param: Parameter) -> Option<Data> {
+        self.params.get(&param).copied()
+    }
+
+    /// Runs the `init` function, equivalent to OSDI `setup_instance`.
+    pub fn setup_instance<'m>(
+        &'m self,
+        given: ParamValues,
+        temperature: f64,
+        connected_ports: u32,
+        simparams: SimParams<'_>,
+    ) -> InterpretedInstance<'m> {
+        let module = self.module;
+        let func = &module.init.func;
+        let intern = &module.init.intern;
+
+        let sys_funs: AHashMap<_, _> = ParamSysFun::iter()
+            .map(|param| {
+                let val = given
+                    .sys_funs
+                    .get(&param)
+                    .or_else(|| self.given.sys_funs.get(&param))
+                    .copied()
+                    .unwrap_or(param.default_value());
+                (param, val)
+            })
+            .collect();
+
+        let args: TiVec<Param, Data> = intern
+            .params
+            .raw
+            .keys()
+            .map(|kind| match *kind {
+                ParamKind::Param(param) if self.is_instance_param(param) => given
+                    .params
+                    .get(&param)
+                    .or_else(|| self.given.params.get(&param))
+                    .copied()
+                    .unwrap_or(Data::UNDEF),
+                ParamKind::Param(param) => self.params[&param],
+                ParamKind::ParamGiven { param } => self.is_given(&given, param).into(),
+                ParamKind::ParamSysFun(param) => sys_funs[&param].into(),
+                ParamKind::Temperature => temperature.into(),
+                ParamKind::PortConnected { port } => {
+                    let id =
+                        module.dae_system.unknowns.unwrap_index(&SimUnknownKind::KirchoffLaw(port));
+                    (u32::from(id) < connected_ports).into()
+                }
+                _ => Data::UNDEF,
+            })
+            .collect();
+
+        let mut env = Env::new(module, self.literals, simparams, Stage::InstanceSetup);
+        env.collapsed = vec![false; module.node_collapse.num_pairs() as usize].into();
+        let state = run(func, &intern.callbacks, &mut env, &args);
+
+        let params = module
+            .info
+            .params
+            .iter()
+            .filter(|(_, info)| info.is_instance)
+            .map(|(&param, _)| {
+                let val = intern.outputs[&PlaceKind::Param(param)].unwrap_unchecked();
+                (param, state.read(val))
+            })
+            .collect();
+
+        for (&kind, val) in intern.outputs.iter() {
+            if let PlaceKind::CollapseImplicitEquation(eq) = kind {
+                if state.read(val.unwrap_unchecked()) {
+                    let eq = module.dae_system.unknowns.unwrap_index(&SimUnknownKind::Implicit(eq));
+                    module.node_collapse.hint(eq, None, |pair| env.collapsed[pair] = true);
+                }
+            }
+        }
+
+        let mut cache: TiVec<CacheSlot, Data> =
+            vec![Data::UNDEF; module.init.cache_slots.len()].into();
+        for (&val, &slot) in module.init.cached_vals.iter() {
+            cache[slot] = state.read(val);
+        }
+
+        InterpretedInstance {
+            model: self,
+            given,
+            params,
+            sys_funs,
+            temperature,
+            connected_ports,
+            cache,
+            collapsed: env.collapsed,
+            errors: env.errors,
+            ret_flags: env.ret_flags,
+        }
+    }
+
+    fn is_instance_param(&self, param: Parameter) -> bool {
+        self.module.info.params[&param].is_instance
+    }
+
+    fn is_given(&self, inst: &ParamValues, param: Parameter) -> bool {
+        let model_given = self.given.params.contains_key(&param);
+        if self.is_instance_param(param) {
+            inst.params.contains_key(&param) || model_given
+        } else {
+            model_given
+        }
+    }
+}
+
+impl<'a> InterpretedInstance<'a> {
+    /// Returns the value of an instance parameter after processing.
+    pub fn param(&self, param: Parameter) -> Option<Data> {
+        self.params.get(&param).copied()
+    }
+
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// Returns the node pairs (see [`NodeCollapse::pairs`](crate::node_collapse::NodeCollapse::pairs))
+    /// that were collapsed during instance setup.
+    pub fn collapsed_pairs(&self) -> impl Iterator<Item = CollapsePair> + '_ {
+        self.collapsed.iter_enumerated().filter_map(|(pair, &collapsed)| collapsed.then_some(pair))
+    }
+
+    /// Runs the `eval` function, equivalent to OSDI `eval`.
+    pub fn eval(&self, sim: &mut SimInfo<'_>) -> EvalResult {
+        let model = self.model;
+        let module = model.module;
+        let func = &module.eval;
+        let intern = &module.intern;
+        let flags = sim.flags;
+
+        let prev_solve = |kind: SimUnknownKind| {
+            module.dae_system.unknowns.index(&kind).map_or(0.0, |unknown| sim.prev_solve[unknown])
+        };
+
+        let mut args: TiVec<Param, Data> = intern
+            .params
+            .raw
+            .keys()
+            .map(|kind| match *kind {
+                ParamKind::Param(param) => {
+                    self.params.get(&param).copied().unwrap_or_else(|| model.params[&param])
+                }
+                ParamKind::Voltage { hi, lo } => {
+                    let hi = prev_solve(SimUnknownKind::KirchoffLaw(hi));
+                    let lo = lo.map_or(0.0, |lo| prev_solve(SimUnknownKind::KirchoffLaw(lo)));
+                    (hi - lo).into()
+                }
+                ParamKind::Current(CurrentKind::Port(_)) => 0f64.into(),
+                ParamKind::Current(kind) => prev_solve(SimUnknownKind::Current(kind)).into(),
+                ParamKind::ImplicitUnknown(eq) => prev_solve(SimUnknownKind::Implicit(eq)).into(),
+                ParamKind::Abstime => sim.abstime.into(),
+                ParamKind::Temperature => self.temperature.into(),
+                ParamKind::ParamGiven { param } => model.is_given(&self.given, param).into(),
+                ParamKind::PortConnected { port } => {
+                    let id =
+                        module.dae_system.unknowns.unwrap_index(&SimUnknownKind::KirchoffLaw(port));
+                    (u32::from(id) < self.connected_ports).into()
+                }
+                ParamKind::ParamSysFun(param) => self.sys_funs[&param].into(),
+                ParamKind::EnableIntegration => {
+                    (flags & CALC_REACT_JACOBIAN != 0 && flags & ANALYSIS_IC == 0).into()
+                }
+                ParamKind::PrevState(state) => sim.prev_state[usize::from(state)].into(),
+                ParamKind::NewState(state) => sim.next_state[usize::from(state)].into(),
+                ParamKind::EnableLim => (flags & ENABLE_LIM != 0).into(),
+                ParamKind::HiddenState(_) => Data::UNDEF,
+            })
+            .collect();
+        args.extend(self.cache.iter().copied());
+
+        let mut env = Env::new(module, model.literals, sim.simparams, Stage::Eval);
+        env.flags = flags;
+        env.limits = sim.limits;
+        env.next_state = &mut *sim.next_state;
+        let state = run(func, &intern.callbacks, &mut env, &args);
+
+        let read = |val: Value| -> f64 { state.read(val) };
+        let residual = &module.dae_system.residual;
+        let jacobian = &module.dae_system.jacobian;
+
+        let noise = module
+            .dae_system
+            .noise_sources
+            .iter()
+            .map(|src| {
+                let factor = read(src.factor);
+                match src.kind {
+                    NoiseSourceKind::WhiteNoise { pwr } => {
+                        NoiseEval::White { pwr: read(pwr), factor }
+                    }
+                    NoiseSourceKind::FlickerNoise { pwr, exp } => {
+                        NoiseEval::Flicker { pwr: read(pwr), exp: read(exp), factor }
+                    }
+                    NoiseSourceKind::NoiseTable { .. } => NoiseEval::Table,
+                }
+            })
+            .collect();
+
+        let bound_step =
+            intern.outputs.get(&PlaceKind::BoundStep).and_then(|&val| val.expand()).map(read);
+
+        let opvars = module
+            .info
+            .op_vars
+            .keys()
+            .map(|&var| {
+                let val = intern.outputs[&PlaceKind::Var(var)].unwrap_unchecked();
+                (var, state.read(val))
+            })
+            .collect();
+
+        EvalResult {
+            ret_flags: env.ret_flags,
+            resist_residual: residual.iter().map(|residual| read(residual.resist)).collect(),
+            react_residual: residual.iter().map(|residual| read(residual.react)).collect(),
+            resist_lim_rhs: residual.iter().map(|residual| read(residual.resist_lim_rhs)).collect(),
+            react_lim_rhs: residual.iter().map(|residual| read(residual.react_lim_rhs)).collect(),
+            resist_jacobian: jacobian.iter().map(|entry| read(entry.resist)).collect(),
+            react_jacobian: jacobian.iter().map(|entry| read(entry.react)).collect(),
+            bound_step,
+            opvars,
+            noise,
+        }
+    }
+}
+
+/// State shared by all callbacks during a single interpreter run.
+struct Env<'a> {
+    module: &'a CompiledModule<'a>,
+    literals: &'a Rodeo,
+    simparams: SimParams<'a>,
+    stage: Stage,
+    flags: u32,
+    ret_flags: u32,
+    errors: Vec<Parameter>,
+    collapsed: TiVec<CollapsePair, bool>,
+    next_state: &'a mut [f64],
+    limits: &'a [(&'a str, LimitFn)],
+}
+
+struct Callback<'a, 'e> {
+    kind: &'a CallBackKind,
+    env: *mut Env<'e>,
+}
+
+impl<'a> Env<'a> {
+    fn new(
+        module: &'a CompiledModule<'a>,
+        literals: &'a Rodeo,
+        simparams: SimParams<'a>,
+        stage: Stage,
+    ) -> Env<'a> {
+        Env {
+            module,
+            literals,
+            simparams,
+            stage,
+            flags: 0,
+            ret_flags: 0,
+            errors: Vec::new(),
+            collapsed: TiVec::new(),
+            next_state: &mut [],
+            limits: &[],
+        }
+    }
+
+    fn call(
+        &mut self,
+        kind: &CallBackKind,
+        state: &mut InterpreterState,
+        args: &[Value],
+        rets: &[Value],
+    ) {
+        let val: Data = match *kind {
+            CallBackKind::SimParam => {
+                let name = self.literals.resolve(&state.read::<Spur>(args[0]));
+                match self.simparams.real.iter().find(|(param, _)| *param == name) {
+                    Some(&(_, val)) => val.into(),
+                    None => {
+                        self.ret_flags |= EVAL_RET_FLAG_FATAL;
+                        0f64.into()
+                    }
+                }
+            }
+            CallBackKind::SimParamOpt => {
+                let name = self.literals.resolve(&state.read::<Spur>(args[0]));
+                match self.simparams.real.iter().find(|(param, _)| *param == name) {
+                    Some(&(_, val)) => val.into(),
+                    None => state.read::<f64>(args[1]).into(),
+                }
+            }
+            CallBackKind::SimParamStr => {
+                let name = self.literals.resolve(&state.read::<Spur>(args[0]));
+                // only strings that appear in the model can be represented (and compared)
+                let val = self
+                    .simparams
+                    .str
+                    .iter()
+                    .find(|(param, _)| *param == name)
+                    .and_then(|(_, val)| self.literals.get(val));
+                match val {
+                    Some(val) => val.into(),
+                    None => {
+                        self.ret_flags |= EVAL_RET_FLAG_FATAL;
+                        Data::UNDEF
+                    }
+                }
+            }
+            // If these derivative were non zero they would have been removed
+            CallBackKind::Derivative(_) | CallBackKind::NodeDerivative(_) => 0f64.into(),
+            CallBackKind::ParamInfo(ParamInfoKind::Invalid, param) => {
+                let is_instance = self.module.info.params[&param].is_instance;
+                let report = match self.stage {
+                    Stage::ModelSetup => !is_instance,
+                    Stage::InstanceSetup => is_instance,
+                    Stage::Eval => false,
+                };
+                if report {
+                    self.errors.push(param);
+                }
+                return;
+            }
+            CallBackKind::CollapseHint(hi, lo) => {
+                if self.stage == Stage::InstanceSetup {
+                    let unknowns = &self.module.dae_system.unknowns;
+                    let hi = unknowns.unwrap_index(&SimUnknownKind::KirchoffLaw(hi));
+                    let lo = lo.map(|lo| unknowns.unwrap_index(&SimUnknownKind::KirchoffLaw(lo)));
+                    let collapsed = &mut self.collapsed;
+                    self.module.node_collapse.hint(hi, lo, |pair| collapsed[pair] = true);
+                }
+                return;
+            }
+            CallBackKind::BuiltinLimit { name, .. } => {
+                let vnew = state.read(args[0]);
+                let vold = state.read(args[1]);
+                let extra: Vec<f64> = args[2..].iter().map(|&arg| state.read(arg)).collect();
+                let name = self.literals.resolve(&name);
+                match self.limits.iter().find(|(lim, _)| *lim == name) {
+                    Some((_, lim)) => {
+                        let (val, changed) = lim(self.flags & INIT_LIM != 0, vnew, vold, &extra);
+                        if changed {
+                            self.ret_flags |= EVAL_RET_FLAG_LIM;
+                        }
+                        val.into()
+                    }
+                    None => vnew.into(),
+                }
+            }
+            CallBackKind::StoreLimit(lim_state) => {
+                let val: f64 = state.read(args[0]);
+                self.next_state[usize::from(lim_state)] = val;
+                val.into()
+            }
+            CallBackKind::LimDiscontinuity => {
+                self.ret_flags |= EVAL_RET_FLAG_LIM;
+                return;
+            }
+            CallBackKind::Analysis => {
+                let name = self.literals.resolve(&state.read::<Spur>(args[0]));
+                let flag = match name {
+                    "ac" => ANALYSIS_AC,
+                    "dc" => ANALYSIS_DC,
+                    "noise" => ANALYSIS_NOISE,
+                    "tran" => ANALYSIS_TRAN,
+                    "ic" => ANALYSIS_IC,
+                    "static" => ANALYSIS_STATIC,
+                    "nodeset" => ANALYSIS_NODESET,
+                    _ => 0,
+                };
+                (self.flags & flag != 0).into()
+            }
+            CallBackKind::SetRetFlag(ref flag) => {
+                self.ret_flags |= match *flag {
+                    RetFlag::Abort => EVAL_RET_FLAG_FATAL,
+                    RetFlag::Finish => EVAL_RET_FLAG_FINISH,
+                    RetFlag::Stop => EVAL_RET_FLAG_STOP,
+                    RetFlag::Limited => EVAL_RET_FLAG_LIM,
+                };
+                return;
+            }
+            CallBackKind::Print { .. } => return,
+            CallBackKind::ParamInfo(_, _)
+            | CallBackKind::TimeDerivative
+            | CallBackKind::WhiteNoise { .. }
+            | CallBackKind::FlickerNoise { .. }
+            | CallBackKind::NoiseTable(_) => 0f64.into(),
+        };
+
+        if let Some(&dst) = rets.first() {
+            state.write(dst, val)
+        }
+    }
+}
+
+fn dispatch(state: &mut InterpreterState, args: &[Value], rets: &[Value], data: *mut c_void) {
+    // Safety: `data` always points to a `Callback` created in `run` that outlives the interpreter
+    let cb = unsafe { &*(data as *const Callback) };
+    let env = unsafe { &mut *cb.env };
+    env.call(cb.kind, state, args, rets)
+}
+
+fn run(
+    func: &Function,
+    callbacks: &typed_indexmap::TiSet<FuncRef, CallBackKind>,
+    env: &mut Env<'_>,
+    args: &TiSlice<Param, Data>,
+) -> InterpreterState {
+    let env: *mut Env<'_> = env;
+    let callbacks: TiVec<FuncRef, Callback<'_, '_>> =
+        callbacks.iter().map(|kind| Callback { kind, env }).collect();
+    let calls: TiVec<FuncRef, (Func, *mut c_void)> = callbacks
+        .iter()
+        .map(|cb| (dispatch as Func, cb as *const Callback as *mut c_void))
+        .collect();
+
+    let mut interpreter = Interpreter::new(func, &calls, args);
+    interpreter.run();
+    interpreter.state
+}
@@ -0,0 +1,249 @@
+use std::fs;
+
+use float_cmp::assert_approx_eq;
+use hir::diagnostics::ConsoleSink;
+use hir::CompilationDB;
+use indoc::indoc;
+use lasso::Rodeo;
+use stdx::integration_test_dir;
+use typed_index_collections::TiVec;
+
+use crate::interpret::{
+    InterpretedModel, ParamValues, SimInfo, SimParams, Unsupported, ANALYSIS_DC, ANALYSIS_NOISE,
+    CALC_NOISE, CALC_RESIST_JACOBIAN, CALC_RESIST_RESIDUAL,
+};
+use crate::{CompileOpts, CompiledModule, SimUnknownKind};
+
+#[test]
+fn resistor() {
+    let src = fs::read_to_string(integration_test_dir("RESISTOR").join("resistor.va")).unwrap();
+    let db = CompilationDB::new_virtual(&src).unwrap();
+    let info = crate::collect_modules(&db, false, &mut ConsoleSink::new(&db)).unwrap().remove(0);
+    let mut literals = Rodeo::new();
+    let module = CompiledModule::new(&db, &info, &mut literals, &CompileOpts::default());
+
+    let r = info.params.iter().find(|(_, info)| info.name == "R").map(|(&param, _)| param);
+    let mut model_params = ParamValues::default();
+    model_params.set_param(r.unwrap(), 100f64);
+    let model = InterpretedModel::setup(&module, &literals, model_params, SimParams::default());
+    assert!(model.errors.is_empty());
+    let inst = model.setup_instance(ParamValues::default(), 300.0, 2, SimParams::default());
+    assert!(inst.errors.is_empty());
+
+    let unknowns = &module.dae_system.unknowns;
+    let mut prev_solve: TiVec<_, _> = vec![0f64; unknowns.len()].into();
+    let (a, b) = match info.module.ports(&db)[..] {
+        [a, b] => (
+            unknowns.unwrap_index(&SimUnknownKind::KirchoffLaw(a)),
+            unknowns.unwrap_index(&SimUnknownKind::KirchoffLaw(b)),
+        ),
+        _ => unreachable!(),
+    };
+    prev_solve[a] = 2.0;
+    let res = inst.eval(&mut SimInfo {
+        flags: CALC_RESIST_RESIDUAL | CALC_RESIST_JACOBIAN | ANALYSIS_DC,
+        abstime: 0.0,
+        prev_solve: &prev_solve,
+        prev_state: &[],
+        next_state: &mut [],
+        simparams: SimParams::default(),
+        limits: &[],
+    });
+    assert_eq!(res.ret_flags, 0);
+    assert_approx_eq!(f64, res.resist_residual[a], 0.02);
+    assert_approx_eq!(f64, res.resist_residual[b], -0.02);
+    for (entry, &val) in module.dae_system.jacobian.iter().zip(res.resist_jacobian.iter()) {
+        let expected = if entry.row == entry.col { 0.01 } else { -0.01 };
+        assert_approx_eq!(f64, val, expected);
+    }
+}
+
+#[test]
+fn nonlinear_branches() {
+    // exponential diode that is continued linearly above vcrit
+    let src = indoc! {r#"
+        `include "disciplines.vams"
+        module diode_lin(inout a, inout c);
+            electrical a, c;
+            parameter real is=1e-14;
+            parameter real vcrit=0.6;
+            real vd, id;
+            analog begin
+                vd = V(a, c);
+                if (vd > vcrit)
+                    id = is * (exp(vcrit / 0.025) * (1 + (vd - vcrit) / 0.025) - 1);
+                else
+                    id = is * (exp(vd / 0.025) - 1);
+                I(a, c) <+ id;
+            end
+        endmodule
+    "#};
+    let db = CompilationDB::new_virtual(src).unwrap();
+    let info = crate::collect_modules(&db, false, &mut ConsoleSink::new(&db)).unwrap().remove(0);
+    let mut literals = Rodeo::new();
+    let module = CompiledModule::new(&db, &info, &mut literals, &CompileOpts::default());
+    let model =
+        InterpretedModel::setup(&module, &literals, ParamValues::default(), SimParams::default());
+    assert!(model.errors.is_empty());
+    let inst = model.setup_instance(ParamValues::default(), 300.0, 2, SimParams::default());
+    assert!(inst.errors.is_empty());
+
+    let unknowns = &module.dae_system.unknowns;
+    let (a, c) = match info.module.ports(&db)[..] {
+        [a, c] => (
+            unknowns.unwrap_index(&SimUnknownKind::KirchoffLaw(a)),
+            unknowns.unwrap_index(&SimUnknownKind::KirchoffLaw(c)),
+        ),
+        _ => unreachable!(),
+    };
+
+    let is = 1e-14f64;
+    let exp_crit = (0.6f64 / 0.025).exp();
+    // one voltage on each side of the branch, applied with a nonzero cathode potential
+    for (vd, id, gd) in [
+        (0.3, is * ((0.3f64 / 0.025).exp() - 1.0), is / 0.025 * (0.3f64 / 0.025).exp()),
+        (0.9, is * (exp_crit * (1.0 + 0.3 / 0.025) - 1.0), is / 0.025 * exp_crit),
+    ] {
+        let mut prev_solve: TiVec<_, _> = vec![0f64; unknowns.len()].into();
+        prev_solve[a] = vd - 0.5;
+        prev_solve[c] = -0.5;
+        let res = inst.eval(&mut SimInfo {
+            flags: CALC_RESIST_RESIDUAL | CALC_RESIST_JACOBIAN | ANALYSIS_DC,
+            abstime: 0.0,
+            prev_solve: &prev_solve,
+            prev_state: &[],
+            next_state: &mut [],
+            simparams: SimParams::default(),
+            limits: &[],
+        });
+        assert_eq!(res.ret_flags, 0);
+        assert_approx_eq!(f64, res.resist_residual[a], id, epsilon = id.abs() * 1e-12);
+        assert_approx_eq!(f64, res.resist_residual[c], -id, epsilon = id.abs() * 1e-12);
+        for (entry, &val) in module.dae_system.jacobian.iter().zip(res.resist_jacobian.iter()) {
+            let expected = if entry.row == entry.col { gd } else { -gd };
+            assert_approx_eq!(f64, val, expected, epsilon = gd * 1e-12);
+        }
+    }
+}
+
+#[test]
+fn noise_table_unsupported() {
+    let src = indoc! {r#"
+        `include "disciplines.vams"
+        module noisy(inout a, inout c);
+            electrical a, c;
+            analog begin
+                I(a, c) <+ V(a, c) / 1k;
+                I(a, c) <+ noise_table("noise.tbl", "table");
+            end
+        endmodule
+    "#};
+    let db = CompilationDB::new_virtual(src).unwrap();
+    let info = crate::collect_modules(&db, false, &mut ConsoleSink::new(&db)).unwrap().remove(0);
+    let mut literals = Rodeo::new();
+    let module = CompiledModule::new(&db, &info, &mut literals, &CompileOpts::default());
+    let model =
+        InterpretedModel::setup(&module, &literals, ParamValues::default(), SimParams::default());
+    let inst = model.setup_instance(ParamValues::default(), 300.0, 2, SimParams::default());
+
+    let prev_solve: TiVec<_, _> = vec![0f64; module.dae_system.unknowns.len()].into();
+    let res = inst.eval(&mut SimInfo {
+        flags: CALC_NOISE | ANALYSIS_NOISE,
+        abstime: 0.0,
+        prev_solve: &prev_solve,
+        prev_state: &[],
+        next_state: &mut [],
+        simparams: SimParams::default(),
+        limits: &[],
+    });
+    assert_eq!(res.noise_density(10.0), Err(Unsupported::NoiseTable));
+}
+
+#[test]
+fn unrolled_for_loop() {
+    // truncated taylor series of g * (exp(vd) - 1)
+    let src = indoc! {r#"
+        `include "disciplines.vams"
+        module series(inout a, inout c);
+            electrical a, c;
+            parameter real g=1m;
+            real vd, id, term;
+            integer i;
+            analog begin
+                vd = V(a, c);
+                id = 0;
+                term = g * vd;
+                for (i = 0; i < 6; i = i + 1) begin
+                    id = id + term;
+                    term = term * vd / (i + 2);
+                end
+                I(a, c) <+ id;
+            end
+        endmodule
+    "#};
+    let db = CompilationDB::new_virtual(src).unwrap();
+    let info = crate::collect_modules(&db, false, &mut ConsoleSink::new(&db)).unwrap().remove(0);
+    let mut literals = Rodeo::new();
+    let opt = CompiledModule::new(&db, &info, &mut literals, &CompileOpts::default());
+    let unopt = CompiledModule::new_unoptimized(&db, &info, &mut literals);
+    let (a, c) = match info.module.ports(&db)[..] {
+        [a, c] => (SimUnknownKind::KirchoffLaw(a), SimUnknownKind::KirchoffLaw(c)),
+        _ => unreachable!(),
+    };
+
+    // residual and jacobian by unknown so that the results of both modules can be compared
+    let eval = |module: &CompiledModule, vd: f64| {
+        let model = InterpretedModel::setup(
+            module,
+            &literals,
+            ParamValues::default(),
+            SimParams::default(),
+        );
+        let inst = model.setup_instance(ParamValues::default(), 300.0, 2, SimParams::default());
+        let unknowns = &module.dae_system.unknowns;
+        let mut prev_solve: TiVec<_, _> = vec![0f64; unknowns.len()].into();
+        prev_solve[unknowns.unwrap_index(&a)] = vd;
+        let res = inst.eval(&mut SimInfo {
+            flags: CALC_RESIST_RESIDUAL | CALC_RESIST_JACOBIAN | ANALYSIS_DC,
+            abstime: 0.0,
+            prev_solve: &prev_solve,
+            prev_state: &[],
+            next_state: &mut [],
+            simparams: SimParams::default(),
+            limits: &[],
+        });
+        assert_eq!(res.ret_flags, 0);
+        let residual: Vec<_> =
+            [a, c].iter().map(|kind| res.resist_residual[unknowns.unwrap_index(kind)]).collect();
+        let mut jacobian: Vec<_> = module
+            .dae_system
+            .jacobian
+            .iter()
+            .zip(res.resist_jacobian.iter())
+            .map(|(entry, &val)| (unknowns[entry.row], unknowns[entry.col], val))
+            .collect();
+        jacobian.sort_by_key(|&(row, col, _)| {
+            (
+                [a, c].iter().position(|&kind| kind == row),
+                [a, c].iter().position(|&kind| kind == col),
+            )
+        });
+        (residual, jacobian)
+    };
+
+    for vd in [-0.4, 0.1, 0.7] {
+        let (opt_residual, opt_jacobian) = eval(&opt, vd);
+        let (unopt_residual, unopt_jacobian) = eval(&unopt, vd);
+        let expected: f64 =
+            (1..=6).map(|n| 1e-3 * vd.powi(n) / (1..=n).product::<i32>() as f64).sum();
+        assert_approx_eq!(f64, opt_residual[0], expected, epsilon = 1e-15);
+        for (opt, unopt) in opt_residual.iter().zip(&unopt_residual) {
+            assert_approx_eq!(f64, *opt, *unopt, epsilon = 1e-15);
+        }
+        assert_eq!(opt_jacobian.len(), unopt_jacobian.len());
+        for (opt, unopt) in opt_jacobian.iter().zip(&unopt_jacobian) {
+            assert_eq!((opt.0, opt.1), (unopt.0, unopt.1));
+            assert_approx_eq!(f64, opt.2, unopt.2, epsilon = 1e-15);
+        }
+    }
+}
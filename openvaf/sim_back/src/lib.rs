@@ -3,10 +3,12 @@ use hir_lower::{CurrentKind, HirInterner, ImplicitEquation, ParamKind};
 use lasso::Rodeo;
 use mir::Function;
 use mir_opt::{simplify_cfg, sparse_conditional_constant_propagation};
-pub use module_info::{collect_modules, ModuleInfo};
+pub use module_info::{collect_modules, InvalidValue, ModuleInfo};
+pub use stats::{InstCount, OptStats, PassStats};
 use stdx::impl_debug_display;
 
-use crate::context::{Context, OptimiziationStage};
+use crate::context::Context;
+pub use crate::context::OptimiziationStage;
 use crate::dae::DaeSystem;
 use crate::init::Initialization;
 use crate::node_collapse::NodeCollapse;
@@ -15,9 +17,11 @@ use crate::topology::Topology;
 mod context;
 pub mod dae;
 pub mod init;
+pub mod interpret;
 mod module_info;
 pub mod node_collapse;
 mod noise;
+mod stats;
 mod topology;
 
 mod util;
@@ -49,6 +53,27 @@ pub struct CompiledModule<'a> {
     pub model_param_setup: Function,
     pub model_param_intern: HirInterner,
     pub node_collapse: NodeCollapse,
+    /// size of the evaluation MIR after each optimization pass, only recorded if
+    /// [`CompileOpts::opt_stats`] is set
+    pub opt_stats: OptStats,
+}
+
+/// Options for [`CompiledModule::new`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompileOpts {
+    /// Print the MIR before the DAE system is built.
+    pub dump_unopt_mir: bool,
+    /// Print the MIR after all optimizations.
+    pub dump_mir: bool,
+    /// Calculate the derivatives of the residual by all real parameters
+    /// (see [`DaeSystem::param_sensitivities`]).
+    pub param_sensitivities: bool,
+    /// Calculate the second derivatives of the residual by the unknowns
+    /// (see [`DaeSystem::hessian`]).
+    pub hessian: bool,
+    /// Record the size of the MIR after each optimization pass (see
+    /// [`CompiledModule::opt_stats`]).
+    pub opt_stats: bool,
 }
 
 pub fn print_module(
@@ -153,15 +178,42 @@ pub fn print_mir(literals: &Rodeo, func: &Function) {
 }
 
 impl<'a> CompiledModule<'a> {
+    /// Compiles `module` with the optimizations enabled.
     pub fn new(
         db: &CompilationDB,
         module: &'a ModuleInfo,
         literals: &mut Rodeo,
-        dump_unopt_mir: bool,
-        dump_mir: bool,
+        opts: &CompileOpts,
+    ) -> CompiledModule<'a> {
+        Self::build(db, module, literals, opts, true)
+    }
+
+    /// Builds the module without value level optimizations. Only the transformations required
+    /// to construct the DAE system and split off the instance setup are performed. The result
+    /// is much slower to evaluate but serves as a reference for the optimized MIR.
+    pub fn new_unoptimized(
+        db: &CompilationDB,
+        module: &'a ModuleInfo,
+        literals: &mut Rodeo,
+    ) -> CompiledModule<'a> {
+        Self::build(db, module, literals, &CompileOpts::default(), false)
+    }
+
+    fn build(
+        db: &CompilationDB,
+        module: &'a ModuleInfo,
+        literals: &mut Rodeo,
+        opts: &CompileOpts,
+        optimize: bool,
     ) -> CompiledModule<'a> {
+        let CompileOpts { dump_unopt_mir, dump_mir, param_sensitivities, hessian, opt_stats } =
+            *opts;
+
         // Build MIR for the module
         let mut cx = Context::new(db, literals, module);
+        cx.optimize_values = optimize;
+        cx.build_hessian = hessian;
+        cx.collect_opt_stats = opt_stats;
 
         if dump_unopt_mir {
             println!("Unoptimized MIR (no DAE) of {}", module.module.name(db));
@@ -177,6 +229,10 @@ impl<'a> CompiledModule<'a> {
         // Add extra stuff needed for evaluating the DAE system
         let topology = Topology::new(&mut cx);
         debug_assert!(cx.func.validate());
+        if param_sensitivities {
+            cx.insert_param_defaults(literals);
+            debug_assert!(cx.func.validate());
+        }
         let mut dae_system = DaeSystem::new(&mut cx, topology);
         debug_assert!(cx.func.validate());
 
@@ -211,6 +267,8 @@ impl<'a> CompiledModule<'a> {
             .collect();
         // Add initialization of instance parameters
         init.intern.insert_param_init(db, &mut init.func, literals, false, true, &inst_params);
+        let fixed_param = |param| module.fixed_params.get(&param).copied();
+        init.intern.specialize_params(db, &mut init.func, fixed_param);
 
         // Model setup MIR
         let mut model_param_setup = Function::default();
@@ -224,10 +282,13 @@ impl<'a> CompiledModule<'a> {
             true,
             &model_params,
         );
+        model_param_intern.specialize_params(db, &mut model_param_setup, fixed_param);
         cx.cfg.compute(&model_param_setup);
         simplify_cfg(&mut model_param_setup, &mut cx.cfg);
-        sparse_conditional_constant_propagation(&mut model_param_setup, &cx.cfg);
-        simplify_cfg(&mut model_param_setup, &mut cx.cfg);
+        if optimize {
+            sparse_conditional_constant_propagation(&mut model_param_setup, &cx.cfg);
+            simplify_cfg(&mut model_param_setup, &mut cx.cfg);
+        }
 
         if dump_mir {
             println!("Optimized model setup MIR of {}", module.module.name(db));
@@ -252,6 +313,7 @@ impl<'a> CompiledModule<'a> {
             model_param_intern,
             model_param_setup,
             node_collapse,
+            opt_stats: cx.opt_stats,
         }
     }
 }
@@ -1,12 +1,15 @@
+use std::fmt;
+
 use ahash::AHashSet;
 use hir::diagnostics::{BaseDB, ConsoleSink, Diagnostic, FileId, Label, LabelStyle, Report};
 use hir::{
-    CompilationDB, CompilationUnit, DiagnosticSink, Module, ParamSysFun, Parameter,
-    ResolvedAliasParameter, ScopeDef, Variable,
+    AstCache, BodyRef, CompilationDB, CompilationUnit, ConstraintValue, DiagnosticSink, ExprId,
+    Literal, Module, Node, ParamConstraint, ParamSysFun, Parameter, Ref, ResolvedAliasParameter,
+    ScopeDef, Type, Variable,
 };
 use indexmap::IndexMap;
 use smol_str::SmolStr;
-use syntax::ast::{self, Expr};
+use syntax::ast::{self, ConstraintKind, Expr, UnaryOp};
 use syntax::sourcemap::FileSpan;
 use syntax::AstNode;
 
@@ -45,9 +48,87 @@ pub struct ModuleInfo {
     pub params: IndexMap<Parameter, ParamInfo, ahash::RandomState>,
     pub sys_fun_alias: IndexMap<ParamSysFun, Vec<SmolStr>, ahash::RandomState>,
     pub op_vars: IndexMap<Variable, OpVar, ahash::RandomState>,
+    /// model parameters replaced by constants (see [`ModuleInfo::specialize`])
+    pub fixed_params: IndexMap<Parameter, f64, ahash::RandomState>,
+}
+
+/// A modelcard value that is not valid for the parameter it sets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InvalidValue<'a> {
+    /// the value of an integer parameter is not an integer
+    NonInteger { name: &'a str, val: f64 },
+    /// the value violates the `from`/`exclude` constraints of the parameter
+    OutOfBounds { name: &'a str, val: f64 },
 }
 
+impl fmt::Display for InvalidValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            InvalidValue::NonInteger { name, val } => {
+                write!(f, "value {val} of integer parameter {name} is not an integer")
+            }
+            InvalidValue::OutOfBounds { name, val } => {
+                write!(f, "value {val} of parameter {name} is out of bounds")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvalidValue<'_> {}
+
 impl ModuleInfo {
+    /// Specialises the module for a modelcard. The model parameters in `values` are removed
+    /// from `params` and are replaced by constants during compilation. Parameters are matched
+    /// case insensitively by their name or alias. Returns the names that do not refer to a
+    /// real or integer model parameter. Values of integer parameters must be integers and all
+    /// values must satisfy the bounds (`from`/`exclude`) of their parameter. The module is
+    /// left unchanged if any value is invalid.
+    pub fn specialize<'a>(
+        &mut self,
+        db: &CompilationDB,
+        values: &'a [(String, f64)],
+    ) -> Result<Vec<&'a str>, InvalidValue<'a>> {
+        let mut unknown = Vec::new();
+        let mut fixed: IndexMap<Parameter, (&str, f64), ahash::RandomState> = IndexMap::default();
+        for (name, val) in values {
+            let param = self.params.iter().find_map(|(&param, info)| {
+                let matches = info.name.eq_ignore_ascii_case(name)
+                    || info.alias.iter().any(|alias| alias.eq_ignore_ascii_case(name));
+                let supported =
+                    !info.is_instance && matches!(param.ty(db), Type::Real | Type::Integer);
+                (matches && supported).then_some(param)
+            });
+            match param {
+                Some(param) => {
+                    let is_int = val.fract() == 0.0 && i32::try_from(*val as i64).is_ok();
+                    if param.ty(db) == Type::Integer && !is_int {
+                        return Err(InvalidValue::NonInteger { name, val: *val });
+                    }
+                    fixed.insert(param, (&**name, *val));
+                }
+                None => unknown.push(&**name),
+            }
+        }
+
+        let fixed_val = |param: Parameter| {
+            fixed
+                .get(&param)
+                .map(|&(_, val)| val)
+                .or_else(|| self.fixed_params.get(&param).copied())
+        };
+        for (&param, &(name, val)) in &fixed {
+            if !in_bounds(db, param, val, &fixed_val) {
+                return Err(InvalidValue::OutOfBounds { name, val });
+            }
+        }
+
+        for (param, (_, val)) in fixed {
+            self.params.shift_remove(&param);
+            self.fixed_params.insert(param, val);
+        }
+        Ok(unknown)
+    }
+
     fn collect(
         db: &CompilationDB,
         cu: CompilationUnit,
@@ -62,6 +143,7 @@ impl ModuleInfo {
 
         let ast = cu.ast(db);
 
+        let assigned_vars: AHashSet<_> = module.analog_block(db).borrow().assigned_vars().collect();
         let mut resolved_attrs = AHashSet::new();
         let mut declarations = module.rec_declarations(db);
         let mut add_diagnostic = |attr: ast::Attr, diag: &dyn Diagnostic| {
@@ -81,7 +163,12 @@ impl ModuleInfo {
                     // check for units or description
                     let units = var.get_attr(db, &ast, "units");
                     let desc = var.get_attr(db, &ast, "desc");
-                    if units.is_none() && desc.is_none() && !all_vars_opvars {
+                    let derivative_of = var.get_attr(db, &ast, "derivative_of");
+                    if units.is_none()
+                        && desc.is_none()
+                        && derivative_of.is_none()
+                        && !all_vars_opvars
+                    {
                         continue;
                     }
 
@@ -109,7 +196,16 @@ impl ModuleInfo {
                             lit
                         })
                         .unwrap_or_default();
-                    op_vars.insert(var, OpVar { unit: units, description: desc });
+                    let derivative = derivative_of.and_then(|attr| {
+                        let res = OpVarDerivative::collect(db, &ast, module, var, attr.clone());
+                        if let Err(message) = &res {
+                            add_diagnostic(attr.clone(), &InvalidDerivative { attr, message });
+                        } else if assigned_vars.contains(&var) {
+                            add_diagnostic(attr.clone(), &AssignedDerivative { attr, var: &path });
+                        }
+                        res.ok()
+                    });
+                    op_vars.insert(var, OpVar { unit: units, description: desc, derivative });
                 }
 
                 ScopeDef::Parameter(param) => {
@@ -193,7 +289,7 @@ impl ModuleInfo {
             }
         }
 
-        ModuleInfo { module, params, op_vars, sys_fun_alias }
+        ModuleInfo { module, params, op_vars, sys_fun_alias, fixed_params: IndexMap::default() }
     }
 }
 
@@ -220,6 +316,51 @@ impl Diagnostic for IllegalAttr {
     }
 }
 
+struct InvalidDerivative<'a> {
+    attr: ast::Attr,
+    message: &'a str,
+}
+
+impl Diagnostic for InvalidDerivative<'_> {
+    fn build_report(&self, root_file: FileId, db: &dyn BaseDB) -> Report {
+        let FileSpan { range, file } = db
+            .parse(root_file)
+            .to_file_span(self.attr.syntax().text_range(), &db.sourcemap(root_file));
+        Report::error()
+            .with_message(format!("invalid derivative operating point variable: {}", self.message))
+            .with_labels(vec![Label {
+                style: LabelStyle::Primary,
+                file_id: file,
+                range: range.into(),
+                message: "invalid derivative".to_owned(),
+            }])
+    }
+}
+
+struct AssignedDerivative<'a> {
+    attr: ast::Attr,
+    var: &'a str,
+}
+
+impl Diagnostic for AssignedDerivative<'_> {
+    fn build_report(&self, root_file: FileId, db: &dyn BaseDB) -> Report {
+        let FileSpan { range, file } = db
+            .parse(root_file)
+            .to_file_span(self.attr.syntax().text_range(), &db.sourcemap(root_file));
+        Report::warning()
+            .with_message(format!(
+                "the value assigned to operating point variable '{}' is ignored",
+                self.var
+            ))
+            .with_labels(vec![Label {
+                style: LabelStyle::Primary,
+                file_id: file,
+                range: range.into(),
+                message: "calculated from the jacobian instead".to_owned(),
+            }])
+    }
+}
+
 struct UnknownType<'a> {
     expr: Expr,
     found: &'a str,
@@ -259,4 +400,135 @@ pub struct ParamInfo {
 pub struct OpVar {
     pub unit: String,
     pub description: String,
+    /// The jacobian entry this operating point variable is calculated from
+    /// (`derivative_of` attribute).
+    pub derivative: Option<OpVarDerivative>,
+}
+
+/// An operating point variable declared as
+/// `(* derivative_of="I(a, b)", wrt="V(c, d)" *)` is the derivative of the current flowing
+/// into terminal `a` by the potential of `c` while all other potentials (relative to `d`)
+/// are kept constant. That is the entry of the jacobian in the row of `a` and the column of
+/// `c`. The reactive part of the entry is used instead if the `reactive` attribute is present.
+/// The value assigned to the variable by the model (if any) is ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpVarDerivative {
+    pub flow: Node,
+    pub wrt: Node,
+    pub reactive: bool,
+}
+
+impl OpVarDerivative {
+    fn collect(
+        db: &CompilationDB,
+        ast: &AstCache,
+        module: Module,
+        var: Variable,
+        derivative_of: ast::Attr,
+    ) -> Result<OpVarDerivative, String> {
+        if var.ty(db) != Type::Real {
+            return Err("only real variables can be derivatives".to_owned());
+        }
+
+        let str_attr = |attr: ast::Attr, name| {
+            attr.val()
+                .and_then(|val| val.as_str_literal())
+                .ok_or_else(|| format!("expected a string literal for the '{name}' attribute"))
+        };
+        let flow = str_attr(derivative_of, "derivative_of")?;
+        let wrt =
+            var.get_attr(db, ast, "wrt").ok_or_else(|| "missing 'wrt' attribute".to_owned())?;
+        let wrt = str_attr(wrt, "wrt")?;
+        let flow = resolve_access(db, module, &flow, "I")?;
+        let wrt = resolve_access(db, module, &wrt, "V")?;
+        let reactive = var.get_attr(db, ast, "reactive").is_some();
+        Ok(OpVarDerivative { flow, wrt, reactive })
+    }
+}
+
+/// Checks `val` against the `from`/`exclude` constraints of `param`. Only bounds that are
+/// literals or parameters with a known value (`fixed`) can be evaluated, constraints with
+/// other bounds are assumed to be satisfied.
+fn in_bounds(
+    db: &CompilationDB,
+    param: Parameter,
+    val: f64,
+    fixed: &dyn Fn(Parameter) -> Option<f64>,
+) -> bool {
+    let body = param.init(db);
+    let body = body.borrow();
+    let eval = |expr| eval_bound(body, expr, fixed);
+    // None if the bounds of the constraint can not be evaluated
+    let contains = |constraint: &ParamConstraint| match constraint.val {
+        ConstraintValue::Value(expr) => Some(eval(expr)? == val),
+        ConstraintValue::Range(range) => {
+            let start = eval(range.start)?;
+            let end = eval(range.end)?;
+            let above = if range.start_inclusive { start <= val } else { start < val };
+            let below = if range.end_inclusive { val <= end } else { val < end };
+            Some(above && below)
+        }
+    };
+
+    let bounds = param.bounds(db);
+    let mut from =
+        bounds.iter().filter(|constraint| constraint.kind == ConstraintKind::From).peekable();
+    let from_ok =
+        from.peek().is_none() || from.any(|constraint| contains(constraint) != Some(false));
+    let excluded = bounds.iter().any(|constraint| {
+        constraint.kind == ConstraintKind::Exclude && contains(constraint) == Some(true)
+    });
+    from_ok && !excluded
+}
+
+fn eval_bound(
+    body: BodyRef,
+    expr: ExprId,
+    fixed: &dyn Fn(Parameter) -> Option<f64>,
+) -> Option<f64> {
+    match body.get_expr(expr) {
+        hir::Expr::Literal(Literal::Int(val)) => Some(f64::from(*val)),
+        hir::Expr::Literal(Literal::Float(val)) => Some((*val).into()),
+        hir::Expr::Literal(Literal::Inf) => Some(f64::INFINITY),
+        hir::Expr::UnaryOp { expr, op: UnaryOp::Neg } => {
+            eval_bound(body, expr, fixed).map(|val| -val)
+        }
+        hir::Expr::UnaryOp { expr, op: UnaryOp::Identity } => eval_bound(body, expr, fixed),
+        hir::Expr::Read(Ref::Parameter(param)) => fixed(param),
+        _ => None,
+    }
+}
+
+/// Resolves the first node of a nature access such as `I(a, b)` or `V(a)`.
+fn resolve_access(
+    db: &CompilationDB,
+    module: Module,
+    src: &str,
+    access: &str,
+) -> Result<Node, String> {
+    let args = src
+        .trim()
+        .strip_prefix(access)
+        .and_then(|args| args.trim_start().strip_prefix('('))
+        .and_then(|args| args.trim_end().strip_suffix(')'));
+    let nodes: Vec<_> = args.map_or_else(Vec::new, |args| args.split(',').map(str::trim).collect());
+    if !(1..=2).contains(&nodes.len()) || nodes.iter().any(|node| node.is_empty()) {
+        return Err(format!(
+            "expected {access}(<node>) or {access}(<node>, <node>) found \"{src}\""
+        ));
+    }
+
+    let resolve = |name: &str| {
+        module
+            .ports(db)
+            .into_iter()
+            .chain(module.internal_nodes(db))
+            .find(|node| node.name(db) == name)
+            .ok_or_else(|| format!("unknown node \"{name}\""))
+    };
+    // the reference node is only validated
+    if let Some(reference) = nodes.get(1) {
+        resolve(reference)?;
+    }
+    resolve(nodes[0])
 }
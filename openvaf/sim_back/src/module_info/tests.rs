@@ -1,7 +1,11 @@
 use hir::diagnostics::sink::Buffer;
 use hir::diagnostics::ConsoleSink;
 use hir::CompilationDB;
+use hir_lower::ParamKind;
 use indoc::indoc;
+use lasso::Rodeo;
+
+use crate::{CompileOpts, CompiledModule};
 
 #[test]
 fn invalid_attr() {
@@ -144,6 +148,7 @@ fn opvars() {
                 OpVar {
                     unit: "m",
                     description: "hmm",
+                    derivative: None,
                 },
             ),
             (
@@ -151,6 +156,7 @@ fn opvars() {
                 OpVar {
                     unit: "m",
                     description: "hmm",
+                    derivative: None,
                 },
             ),
             (
@@ -158,6 +164,7 @@ fn opvars() {
                 OpVar {
                     unit: "m",
                     description: "",
+                    derivative: None,
                 },
             ),
             (
@@ -165,9 +172,147 @@ fn opvars() {
                 OpVar {
                     unit: "",
                     description: "hmm",
+                    derivative: None,
                 },
             ),
         ]
     "#]]
     .assert_debug_eq(&params);
 }
+
+#[test]
+fn invalid_derivative_attr() {
+    let src = indoc! {r#"
+        `include "disciplines.vams"
+        module test(inout d, inout g, inout s);
+            electrical d, g, s;
+            (* derivative_of="I(d, s)", wrt="V(g, s)" *) real gm;
+            (* derivative_of="V(d, s)", wrt="V(g, s)" *) real wrong_access;
+            (* derivative_of="I(d, s)", wrt="V(x, s)" *) real unknown_node;
+            (* derivative_of="I(d, s)" *) real missing_wrt;
+            (* derivative_of="I(d, s)", wrt="V(g, s)" *) integer count;
+        endmodule
+    "#};
+    let db = CompilationDB::new_virtual(src).unwrap();
+    let mut buf = Buffer::no_color();
+    {
+        let mut sink = ConsoleSink::buffer(&db, &mut buf);
+        sink.annonymize_paths();
+        super::collect_modules(&db, false, &mut sink);
+    }
+    expect_test::expect![[r#"
+        error: invalid derivative operating point variable: expected I(<node>) or I(<node>, <node>) found "V(d, s)"
+          --> /root.va:5:8
+          |
+        5 |     (* derivative_of="V(d, s)", wrt="V(g, s)" *) real wrong_access;
+          |        ^^^^^^^^^^^^^^^^^^^^^^^ invalid derivative
+
+        error: invalid derivative operating point variable: unknown node "x"
+          --> /root.va:6:8
+          |
+        6 |     (* derivative_of="I(d, s)", wrt="V(x, s)" *) real unknown_node;
+          |        ^^^^^^^^^^^^^^^^^^^^^^^ invalid derivative
+
+        error: invalid derivative operating point variable: missing 'wrt' attribute
+          --> /root.va:7:8
+          |
+        7 |     (* derivative_of="I(d, s)" *) real missing_wrt;
+          |        ^^^^^^^^^^^^^^^^^^^^^^^ invalid derivative
+
+        error: invalid derivative operating point variable: only real variables can be derivatives
+          --> /root.va:8:8
+          |
+        8 |     (* derivative_of="I(d, s)", wrt="V(g, s)" *) integer count;
+          |        ^^^^^^^^^^^^^^^^^^^^^^^ invalid derivative
+
+        error: could not compile `root.va` due to 4 previous errors
+
+    "#]]
+    .assert_eq(&String::from_utf8(buf.into_inner()).unwrap());
+}
+
+#[test]
+fn assigned_derivative() {
+    let src = indoc! {r#"
+        `include "disciplines.vams"
+        module test(inout d, inout g, inout s);
+            electrical d, g, s;
+            (* derivative_of="I(d, s)", wrt="V(g, s)" *) real gm;
+            (* derivative_of="I(d, s)", wrt="V(d, s)" *) real gds;
+            analog begin
+                I(d, s) <+ V(g, s)*V(d, s);
+                gm = V(d, s);
+            end
+        endmodule
+    "#};
+    let db = CompilationDB::new_virtual(src).unwrap();
+    let mut buf = Buffer::no_color();
+    let modules = {
+        let mut sink = ConsoleSink::buffer(&db, &mut buf);
+        sink.annonymize_paths();
+        super::collect_modules(&db, false, &mut sink).unwrap()
+    };
+    assert!(modules[0].op_vars.values().all(|op_var| op_var.derivative.is_some()));
+    expect_test::expect![[r#"
+        warning: the value assigned to operating point variable 'gm' is ignored
+          --> /root.va:4:8
+          |
+        4 |     (* derivative_of="I(d, s)", wrt="V(g, s)" *) real gm;
+          |        ^^^^^^^^^^^^^^^^^^^^^^^ calculated from the jacobian instead
+
+        warning: `root.va` generated 1 warning
+
+    "#]]
+    .assert_eq(&String::from_utf8(buf.into_inner()).unwrap());
+}
+
+#[test]
+fn specialize() {
+    let src = indoc! {r#"
+        `include "disciplines.vams"
+        module test(inout a, inout c);
+            electrical a, c;
+            parameter real r=1.0 exclude 0;
+            parameter integer level=1 from [1:3];
+            parameter real r2=2*r from [r:inf);
+            (* type="instance" *) parameter real w=1.0;
+            parameter real is=1e-14 from [0:inf);
+            analog I(a, c) <+ V(a, c)/(r*r2*w*level) + is;
+        endmodule
+    "#};
+    let db = CompilationDB::new_virtual(src).unwrap();
+    let mut module =
+        super::collect_modules(&db, false, &mut ConsoleSink::new(&db)).unwrap().remove(0);
+    let values = |values: &[(&str, f64)]| -> Vec<_> {
+        values.iter().map(|&(name, val)| (name.to_owned(), val)).collect()
+    };
+    let invalid = [
+        (values(&[("level", 2.5)]), "value 2.5 of integer parameter level is not an integer"),
+        (values(&[("is", -1.0)]), "value -1 of parameter is is out of bounds"),
+        (values(&[("r", 0.0)]), "value 0 of parameter r is out of bounds"),
+        (values(&[("level", 4.0)]), "value 4 of parameter level is out of bounds"),
+        (values(&[("r2", 1.0), ("r", 2.0)]), "value 1 of parameter r2 is out of bounds"),
+    ];
+    for (invalid, msg) in &invalid {
+        let err = module.specialize(&db, invalid).unwrap_err();
+        assert_eq!(err.to_string(), *msg);
+        assert!(module.fixed_params.is_empty());
+    }
+    let values = values(&[("R", 2.0), ("level", 3.0), ("w", 1.0), ("foo", 1.0), ("is", 1e-15)]);
+    assert_eq!(module.specialize(&db, &values).unwrap(), ["w", "foo"]);
+    let params: Vec<_> = module.params.values().map(|info| &*info.name).collect();
+    assert_eq!(params, ["r2", "w"]);
+    let fixed: Vec<_> =
+        module.fixed_params.iter().map(|(param, &val)| (param.name(&db), val)).collect();
+    assert_eq!(fixed, [("r".to_owned(), 2.0), ("level".to_owned(), 3.0), ("is".to_owned(), 1e-15)]);
+
+    let mut literals = Rodeo::new();
+    let compiled = CompiledModule::new(&db, &module, &mut literals, &CompileOpts::default());
+    for intern in [&compiled.intern, &compiled.init.intern, &compiled.model_param_intern] {
+        for kind in intern.params.raw.keys() {
+            if let ParamKind::Param(param) | ParamKind::ParamGiven { param } = *kind {
+                assert!(!module.fixed_params.contains_key(&param));
+            }
+        }
+    }
+}
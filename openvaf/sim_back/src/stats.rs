@@ -0,0 +1,108 @@
+use std::fmt;
+
+use mir::{Function, InstructionData, Opcode};
+
+use crate::context::OptimiziationStage;
+
+/// Size of a function, counted by [`InstCount::new`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InstCount {
+    pub insts: usize,
+    /// calls of transcendental functions (`exp`, `ln`, `pow`, `sqrt`, trigonometric functions)
+    pub transcendental: usize,
+}
+
+impl InstCount {
+    pub fn new(func: &Function) -> InstCount {
+        let mut res = InstCount::default();
+        for bb in func.layout.blocks() {
+            for inst in func.layout.block_insts(bb) {
+                res.insts += 1;
+                let opcode = match func.dfg.insts[inst] {
+                    InstructionData::Unary { opcode, .. } => opcode,
+                    InstructionData::Binary { opcode, .. } => opcode,
+                    _ => continue,
+                };
+                if matches!(
+                    opcode,
+                    Opcode::Sqrt
+                        | Opcode::Exp
+                        | Opcode::Ln
+                        | Opcode::Log
+                        | Opcode::Pow
+                        | Opcode::Hypot
+                        | Opcode::Sin
+                        | Opcode::Cos
+                        | Opcode::Tan
+                        | Opcode::Asin
+                        | Opcode::Acos
+                        | Opcode::Atan
+                        | Opcode::Atan2
+                        | Opcode::Sinh
+                        | Opcode::Cosh
+                        | Opcode::Tanh
+                        | Opcode::Asinh
+                        | Opcode::Acosh
+                        | Opcode::Atanh
+                ) {
+                    res.transcendental += 1;
+                }
+            }
+        }
+        res
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PassStats {
+    pub stage: OptimiziationStage,
+    pub pass: &'static str,
+    pub before: InstCount,
+    pub after: InstCount,
+}
+
+/// Size of the evaluation MIR before and after each optimization pass.
+#[derive(Debug, Clone, Default)]
+pub struct OptStats {
+    pub passes: Vec<PassStats>,
+}
+
+impl OptStats {
+    /// Records that `pass` changed the size of `func` from `before`. Returns the current size
+    /// so that calls can be chained.
+    pub(crate) fn record(
+        &mut self,
+        stage: OptimiziationStage,
+        pass: &'static str,
+        before: InstCount,
+        func: &Function,
+    ) -> InstCount {
+        let after = InstCount::new(func);
+        self.passes.push(PassStats { stage, pass, before, after });
+        after
+    }
+}
+
+impl fmt::Display for OptStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<16} {:<24} {:>10} {:>10} {:>8} {:>10} {:>10}",
+            "stage", "pass", "insts", "after", "removed", "transc.", "after"
+        )?;
+        for pass in &self.passes {
+            writeln!(
+                f,
+                "{:<16} {:<24} {:>10} {:>10} {:>8} {:>10} {:>10}",
+                format!("{:?}", pass.stage),
+                pass.pass,
+                pass.before.insts,
+                pass.after.insts,
+                pass.before.insts as isize - pass.after.insts as isize,
+                pass.before.transcendental,
+                pass.after.transcendental,
+            )?;
+        }
+        Ok(())
+    }
+}
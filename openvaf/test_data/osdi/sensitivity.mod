@@ -0,0 +1,3 @@
+* junction parameters of sensitivity.va
+.model d sensitivity_va (is=2e-14 tt=0.5n
++ mode=1)
@@ -0,0 +1,38 @@
+# Compares the analytic derivatives calculated by VerilogAE with central finite differences
+import numpy as np
+import verilogae
+
+REL_STEP = 1e-6
+
+model = verilogae.load("diode_derivatives.va", derivatives=["br_AC", "is", "n"])
+id_fun = model.functions["id"]
+assert id_fun.derivatives == ["br_AC", "is", "n"]
+
+vd = np.linspace(0.3, 0.9, 61)
+args = {"temperature": 320.0, "voltages": {"br_AC": vd}, "is": 2e-14, "n": 1.1}
+
+
+def eval_id(args):
+    val, _ = id_fun.eval(**args)
+    return val
+
+
+val, derivatives = id_fun.eval(**args)
+for name, analytic in derivatives.items():
+    hi = dict(args, voltages=dict(args["voltages"]))
+    lo = dict(args, voltages=dict(args["voltages"]))
+    if name == "br_AC":
+        step = REL_STEP * np.abs(vd)
+        hi["voltages"]["br_AC"] = vd + step
+        lo["voltages"]["br_AC"] = vd - step
+    else:
+        step = REL_STEP * abs(args[name])
+        hi[name] = args[name] + step
+        lo[name] = args[name] - step
+    fd = (eval_id(hi) - eval_id(lo)) / (2 * step)
+    assert np.allclose(analytic, fd, rtol=1e-5, atol=0.0), f"derivative by {name} differs"
+
+# a single bias point returns floats instead of arrays
+args["voltages"] = {"br_AC": 0.5}
+val, derivatives = id_fun.eval(**args)
+assert isinstance(val, float) and all(isinstance(d, float) for d in derivatives.values())
@@ -30,3 +30,10 @@ for fun in hl2.functions.values():
     if not np.allclose(res,data[fun.name], atol=1e-16):
         print(f"assert failed for {fun.name}")
 
+
+# the JIT must produce exactly the same code as the cached shared library
+hl2_jit = verilogae.load_jit("hicumL2V2p4p0_vae.va")
+for fun in hl2.functions.values():
+    res = hl2_jit.functions[fun.name].eval(**args)
+    if not np.array_equal(res, fun.eval(**args), equal_nan=True):
+        print(f"JIT result differs for {fun.name}")
@@ -1,14 +1,14 @@
 use std::ffi::{c_void, CStr};
 use std::os::raw::c_char;
 use std::panic::catch_unwind;
-use std::{ptr, slice};
+use std::{mem, ptr, slice};
 
 #[cfg(not(windows))]
 use libloading::os::unix::Library;
 #[cfg(windows)]
 use libloading::os::windows::Library;
 
-use crate::{export_vfs, load};
+use crate::{export_vfs, load, load_jit, VaeJit};
 
 #[repr(C)]
 #[derive(Default)]
@@ -25,6 +25,7 @@ pub struct Opts {
     pub target: Slice<u8>,
     pub cg_flags: Slice<Slice<u8>>,
     pub vfs: Vfs,
+    pub derivatives: Slice<Slice<u8>>,
 }
 
 #[repr(C)]
@@ -49,11 +50,7 @@ macro_rules! expose_ptrs {
         #[no_mangle]
         pub unsafe extern "C" fn $name(lib: *const c_void) -> *$mut $ty {
             catch_unwind(||{
-                let lib = Library::from_raw(lib as _);
-                let res = access_ptr::<$ty>(&lib, $sym.as_bytes()) as _;
-                // forget library so it doesn't get closed
-                std::mem::forget(lib);
-                res
+                access_ptr::<$ty>(lib, $sym.as_bytes()) as _
             }).unwrap_or_else(|_|ptr::null::<$ty>() as _)
         }
     )*
@@ -91,11 +88,7 @@ macro_rules! expose_consts{
         #[no_mangle]
         pub unsafe extern "C" fn $name(lib: *const c_void) -> $ty {
             catch_unwind(||{
-                let lib = Library::from_raw(lib as _);
-                let res = access_val(&lib, $sym.as_bytes());
-                // forget library so it doesn't get closed
-                std::mem::forget(lib);
-                res
+                access_val(lib, $sym.as_bytes())
             }).ok().unwrap_or_else(<$ty>::default)
         }
     )*
@@ -143,11 +136,7 @@ macro_rules! expose_named_ptrs {
                 sym_name.push(b'.');
                 sym_name.extend_from_slice($sym.as_bytes());
                 sym_name.push(b'\0');
-                let lib = Library::from_raw(lib as _);
-                let res = access_ptr::<$ty>(&lib, &sym_name) as _;
-                // forget library so it doesn't get closed
-                std::mem::forget(lib);
-                res
+                access_ptr::<$ty>(lib, &sym_name) as _
                 }
             )
             .unwrap_or_else(|_| ptr::null::<$ty>() as _)
@@ -168,6 +157,7 @@ expose_named_ptrs! {
     const verilogae_fun_currents: *const c_char = "currents";
     const verilogae_fun_voltage_defaults: f64 = "voltages.default";
     const verilogae_fun_current_defaults: f64 = "currents.default";
+    const verilogae_fun_derivatives: *const c_char = "derivatives";
 }
 
 macro_rules! expose_named_consts {
@@ -187,11 +177,7 @@ macro_rules! expose_named_consts {
                 sym_name.push(b'.');
                 sym_name.extend_from_slice($sym.as_bytes());
                 sym_name.push(b'\0');
-                let lib = Library::from_raw(lib as _);
-                let res = access_val(&lib, &sym_name);
-                // forget library so it doesn't get closed
-                std::mem::forget(lib);
-                res
+                access_val(lib, &sym_name)
                 }
             )
             .ok().unwrap_or_else(<$ty>::default)
@@ -211,6 +197,7 @@ expose_named_consts! {
     verilogae_fun_current_cnt: usize = "currents.cnt";
     verilogae_fun_voltage_default_cnt: usize = "voltages.default.cnt";
     verilogae_fun_current_default_cnt: usize = "currents.default.cnt";
+    verilogae_fun_derivative_cnt: usize = "derivatives.cnt";
 }
 
 #[derive(Clone, Copy)]
@@ -253,6 +240,7 @@ pub type VaeFun = Option<
         *mut FatPtr<i32>,
         *mut FatPtr<f64>,
         *mut c_void,
+        *mut *mut f64,
     ),
 >;
 
@@ -263,18 +251,12 @@ pub type VaeFun = Option<
 /// `lib` must be a valid pointer returned by the `load` functions or `dlopen`
 #[no_mangle]
 pub unsafe extern "C" fn verilogae_init_modelcard(lib: *const c_void) -> ModelcardInit {
-    catch_unwind(|| {
-        let lib = Library::from_raw(lib as _);
-        let res = match lib.get(b"init_modelcard\0") {
-            Ok(val) => Some(*val),
-            Err(err) => {
-                eprintln!("error: failed to access init_modelcard\n\n{}", err);
-                None
-            }
-        };
-        // forget library so it doesn't get closed
-        std::mem::forget(lib);
-        res
+    catch_unwind(|| match lookup_symbol(lib, b"init_modelcard\0") {
+        Ok(val) => mem::transmute::<*mut c_void, ModelcardInit>(val),
+        Err(err) => {
+            eprintln!("error: failed to access init_modelcard\n\n{}", err);
+            None
+        }
     })
     .ok()
     .flatten()
@@ -289,17 +271,13 @@ pub unsafe extern "C" fn verilogae_init_modelcard(lib: *const c_void) -> Modelca
 pub unsafe extern "C" fn verilogae_fun_ptr(lib: *const c_void, fun: *const c_char) -> VaeFun {
     catch_unwind(|| {
         let fun = CStr::from_ptr(fun);
-        let lib = Library::from_raw(lib as _);
-        let res = match lib.get(fun.to_bytes()) {
-            Ok(val) => Some(*val),
+        match lookup_symbol(lib, fun.to_bytes_with_nul()) {
+            Ok(val) => mem::transmute::<*mut c_void, VaeFun>(val),
             Err(err) => {
                 eprintln!("error: failed to access {}\n\n{}", fun.to_string_lossy(), err);
                 None
             }
-        };
-        // forget library so it doesn't get closed
-        std::mem::forget(lib);
-        res
+        }
     })
     .ok()
     .flatten()
@@ -309,18 +287,12 @@ pub unsafe extern "C" fn verilogae_fun_ptr(lib: *const c_void, fun: *const c_cha
 /// handle must be a valid model compiled with VerilogAE
 #[no_mangle]
 pub unsafe extern "C" fn verilogae_module_name(lib: *const c_void) -> *const c_char {
-    catch_unwind(|| {
-        let lib = Library::from_raw(lib as _);
-        let res = match lib.get::<*const *const c_char>("module_name\0".as_bytes()) {
-            Ok(val) => **val,
-            Err(err) => {
-                eprintln!("error: failed to access module_name\n\n{}", err);
-                std::ptr::null()
-            }
-        };
-        // forget library so it doesn't get closed
-        std::mem::forget(lib);
-        res
+    catch_unwind(|| match access_global::<*const c_char>(lib, b"module_name\0") {
+        Ok(val) => *val,
+        Err(err) => {
+            eprintln!("error: failed to access module_name\n\n{}", err);
+            std::ptr::null()
+        }
     })
     .unwrap_or(ptr::null())
 }
@@ -328,6 +300,8 @@ pub unsafe extern "C" fn verilogae_module_name(lib: *const c_void) -> *const c_c
 /// # Safety
 ///
 /// All required parameters must be initialized appropriately
+/// `derivatives` must point to one output array for each entry of `verilogae_fun_derivatives`
+/// (or may be null if the function has no derivatives)
 #[no_mangle]
 pub unsafe extern "C" fn verilogae_call_fun_parallel(
     fun: VaeFun,
@@ -341,6 +315,7 @@ pub unsafe extern "C" fn verilogae_call_fun_parallel(
     int_dep_break: *mut FatPtr<i32>,
     temp: *mut FatPtr<f64>,
     out: *mut c_void,
+    derivatives: *mut *mut f64,
 ) -> i32 {
     let fun = match fun {
         Some(fun) => fun,
@@ -362,6 +337,7 @@ pub unsafe extern "C" fn verilogae_call_fun_parallel(
         int_dep_break: *mut FatPtr<i32>,
         temp: *mut FatPtr<f64>,
         out: *mut c_void,
+        derivatives: *mut *mut f64,
     }
 
     unsafe impl Sync for PayLoad {}
@@ -377,6 +353,7 @@ pub unsafe extern "C" fn verilogae_call_fun_parallel(
         str_params,
         temp,
         out,
+        derivatives,
     };
 
     rayon_core::scope(|s| {
@@ -394,6 +371,7 @@ pub unsafe extern "C" fn verilogae_call_fun_parallel(
                     payload.int_dep_break,
                     payload.temp,
                     payload.out,
+                    payload.derivatives,
                 )
             })
         }
@@ -402,7 +380,7 @@ pub unsafe extern "C" fn verilogae_call_fun_parallel(
     0
 }
 
-unsafe fn access_ptr<T>(lib: &Library, sym_name: &[u8]) -> *const T {
+unsafe fn access_ptr<T>(lib: *const c_void, sym_name: &[u8]) -> *const T {
     match access_global(lib, sym_name) {
         Ok(val) => val,
         Err(err) => {
@@ -412,7 +390,7 @@ unsafe fn access_ptr<T>(lib: &Library, sym_name: &[u8]) -> *const T {
     }
 }
 
-unsafe fn access_val<T: Copy + Default>(lib: &Library, sym_name: &[u8]) -> T {
+unsafe fn access_val<T: Copy + Default>(lib: *const c_void, sym_name: &[u8]) -> T {
     match access_global(lib, sym_name) {
         Ok(val) => *val,
         Err(err) => {
@@ -422,16 +400,24 @@ unsafe fn access_val<T: Copy + Default>(lib: &Library, sym_name: &[u8]) -> T {
     }
 }
 
-// false positive
-#[allow(clippy::explicit_auto_deref)]
-unsafe fn access_global<'a, T>(
-    lib: &'a Library,
-    sym_name: &[u8],
-) -> Result<&'a T, libloading::Error> {
-    lib.get(sym_name).map(|val| {
-        let val: &'a T = *val;
-        val
-    })
+unsafe fn access_global<'a, T>(lib: *const c_void, sym_name: &[u8]) -> Result<&'a T, String> {
+    lookup_symbol(lib, sym_name).map(|val| &*(val as *const T))
+}
+
+/// Returns the address of the (null terminated) symbol `sym_name` of the model `lib`.
+/// `lib` is either a library handle obtained with `dlopen` or a JIT handle.
+unsafe fn lookup_symbol(lib: *const c_void, sym_name: &[u8]) -> Result<*mut c_void, String> {
+    if let Some(jit) = VaeJit::from_handle(lib) {
+        let name = sym_name.strip_suffix(b"\0").unwrap_or(sym_name);
+        let name = std::str::from_utf8(name).map_err(|err| err.to_string())?;
+        return jit.symbol(name).map_err(|err| err.to_string());
+    }
+
+    let lib = Library::from_raw(lib as _);
+    let res = lib.get::<*mut c_void>(sym_name).map(|val| *val).map_err(|err| err.to_string());
+    // forget library so it doesn't get closed
+    std::mem::forget(lib);
+    res
 }
 
 #[no_mangle]
@@ -594,3 +580,38 @@ pub unsafe extern "C" fn verilogae_load(
     }
     ptr::null()
 }
+
+/// Compiles a model like `verilogae_load` but links it into the current process with the LLVM
+/// JIT instead of the system linker. The object cache is not used. The returned handle can be
+/// passed to all other functions in place of a library handle, but not to `dlsym`.
+///
+/// # Safety
+/// * path must be valid for reads
+/// * opts must be valid for reads or null
+/// * opts must only contain valid data
+#[no_mangle]
+pub unsafe extern "C" fn verilogae_load_jit(
+    path: Slice<u8>,
+    full_compile: bool,
+    opts: *const Opts,
+) -> *const c_void {
+    let path = path.to_path();
+    let opts_;
+
+    let opts = if opts.is_null() {
+        opts_ = Opts::default();
+        &opts_
+    } else {
+        &*opts
+    };
+
+    let res = std::panic::catch_unwind(|| load_jit(&path, full_compile, opts));
+
+    if let Ok(res) = res {
+        match res {
+            Ok(jit) => return jit.handle(),
+            Err(err) => eprintln!("{:?}", err),
+        }
+    }
+    ptr::null()
+}
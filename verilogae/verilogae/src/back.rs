@@ -1,14 +1,16 @@
 use core::ptr::NonNull;
 use std::borrow::Borrow;
+use std::sync::Mutex;
 
-use camino::Utf8Path;
+use camino::Utf8PathBuf;
 use hir::Type;
 use hir_lower::{CallBackKind, CurrentKind, HirInterner, ParamInfoKind, ParamKind, PlaceKind};
 use lasso::Rodeo;
 use llvm_sys::target_machine::LLVMCodeGenOptLevel;
-use mir::{ControlFlowGraph, FuncRef, Function};
+use mir::{ControlFlowGraph, FuncRef, Function, Value};
 use mir_llvm::{
-    Builder, BuilderVal, BuiltCallbackFun, CallbackFun, CodegenCx, LLVMBackend, UNNAMED,
+    Builder, BuilderVal, BuiltCallbackFun, CallbackFun, CodegenCx, LLVMBackend, ModuleLlvm,
+    ObjectBuffer, UNNAMED,
 };
 use stdx::iter::multiunzip;
 use typed_index_collections::TiVec;
@@ -17,6 +19,27 @@ use typed_indexmap::TiSet;
 use crate::compiler_db::{
     current_name, voltage_name, CompilationDB, FuncSpec, InternedModel, ModelInfo,
 };
+use crate::middle::Derivatives;
+
+/// Where the object code generated by [`CodegenCtx`] is placed
+pub(crate) enum Emit<'a> {
+    Files(&'a [Utf8PathBuf]),
+    Memory(&'a Mutex<Vec<Option<ObjectBuffer>>>),
+}
+
+impl Emit<'_> {
+    fn emit(&self, module: &ModuleLlvm, idx: usize) {
+        match self {
+            Emit::Files(paths) => {
+                module.emit_object(paths[idx].as_ref()).expect("code generation failed!")
+            }
+            Emit::Memory(objects) => {
+                let obj = module.emit_object_buffer().expect("code generation failed!");
+                objects.lock().unwrap()[idx] = Some(obj);
+            }
+        }
+    }
+}
 
 pub fn sim_param_stub<'ll>(cx: &CodegenCx<'_, 'll>) -> CallbackFun<'ll> {
     CallbackFun::Prebuilt(cx.const_callback(&[cx.ty_ptr()], cx.const_real(0.0)))
@@ -309,6 +332,7 @@ impl<'ll> Codegen<'_, '_, 'll> {
 }
 
 impl CodegenCtx<'_, '_> {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn gen_func_obj(
         &self,
         db: &CompilationDB,
@@ -316,7 +340,9 @@ impl CodegenCtx<'_, '_> {
         func: &Function,
         cfg: &ControlFlowGraph,
         intern: &HirInterner,
-        dst: &Utf8Path,
+        derivatives: &Derivatives,
+        emit: &Emit<'_>,
+        idx: usize,
     ) {
         let module =
             unsafe { self.llbackend.new_module(&spec.var.name(db), self.opt_lvl).unwrap() };
@@ -336,6 +362,7 @@ impl CodegenCtx<'_, '_> {
                 cx.ty_ptr(),  // int dependency_breaking
                 cx.ty_ptr(),  // temperature
                 cx.ty_ptr(),  // ret
+                cx.ty_ptr(),  // derivatives
             ],
             cx.ty_void(),
         );
@@ -411,6 +438,11 @@ impl CodegenCtx<'_, '_> {
             unsafe { llvm_sys::core::LLVMGetParam(NonNull::from(llfun).as_ptr(), 7) };
         unsafe { codegen.read_depbreak(&*offset, &*int_dep_break, Type::Integer) };
 
+        let derivative_vals: &[Value] = derivatives.values.get(&spec.var).map_or(&[], |vals| vals);
+        let derivative_names = derivatives.unknowns[..derivative_vals.len()].iter();
+        let global_name = format!("{}.derivatives", spec.prefix);
+        codegen.export_names(derivative_names.map(String::as_str), &global_name);
+
         // setup callbacks
 
         codegen.builder.callbacks = stub_callbacks(&intern.callbacks, codegen.builder.cx);
@@ -441,6 +473,16 @@ impl CodegenCtx<'_, '_> {
 
             builder.store(out, ret_val);
 
+            // write the derivatives, each one has its own output array
+            let derivative_ptrs = llvm_sys::core::LLVMGetParam(NonNull::from(llfun).as_ptr(), 10);
+            for (i, &val) in derivative_vals.iter().enumerate() {
+                let ptr = builder.gep(cx.ty_ptr(), &*derivative_ptrs, &[cx.const_usize(i)]);
+                let out = builder.load(cx.ty_ptr(), ptr);
+                let out = builder.gep(cx.ty_double(), out, &[&*offset]);
+                let val = builder.values[val].get(&builder);
+                builder.store(out, val);
+            }
+
             builder.ret_void();
         }
 
@@ -449,10 +491,15 @@ impl CodegenCtx<'_, '_> {
         debug_assert!(module.verify_and_print(), "Invalid code generated");
         module.optimize();
 
-        module.emit_object(dst.as_ref()).expect("code generation failed!")
+        emit.emit(&module, idx)
     }
 
-    pub(crate) fn ensure_names(&mut self, db: &CompilationDB, intern: &HirInterner) {
+    pub(crate) fn ensure_names(
+        &mut self,
+        db: &CompilationDB,
+        intern: &HirInterner,
+        derivatives: &Derivatives,
+    ) {
         for param in &intern.params.raw {
             match *param.0 {
                 ParamKind::Voltage { hi, lo } => {
@@ -470,6 +517,10 @@ impl CodegenCtx<'_, '_> {
                 self.literals.get_or_intern(&*self.model_info.var_names[dep]);
             }
         }
+
+        for unknown in &derivatives.unknowns {
+            self.literals.get_or_intern(unknown);
+        }
     }
 
     fn read_params<'ll>(
@@ -654,7 +705,7 @@ impl CodegenCtx<'_, '_> {
 
     pub(crate) fn compile_model_info(
         &self,
-        dst: &Utf8Path,
+        emit: &Emit<'_>,
         interned_model: InternedModel,
         param_init_func: Function,
         param_init_intern: HirInterner,
@@ -824,7 +875,7 @@ impl CodegenCtx<'_, '_> {
         module.optimize();
         // println!("{}", module.to_str());
 
-        module.emit_object(dst.as_ref()).expect("code generation failed!");
+        emit.emit(&module, 0);
     }
 }
 
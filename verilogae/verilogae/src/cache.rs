@@ -9,7 +9,11 @@ use camino::Utf8PathBuf;
 use crate::compiler_db::CompilationDB;
 use crate::Opts;
 
-fn hash(db: &CompilationDB, module: Option<&str>) -> md5::Digest {
+fn hash<'a>(
+    db: &CompilationDB,
+    module: Option<&str>,
+    derivatives: impl Iterator<Item = &'a str>,
+) -> md5::Digest {
     let mut hash_builder = md5::Context::new();
     let cu = db.compilation_unit();
 
@@ -18,6 +22,10 @@ fn hash(db: &CompilationDB, module: Option<&str>) -> md5::Digest {
     if let Some(module) = module {
         hash_builder.consume(module);
     }
+    for unknown in derivatives {
+        hash_builder.consume(unknown);
+        hash_builder.consume(" ");
+    }
 
     hash_builder.consume(env!("CARGO_PKG_VERSION"));
     let lints = db.global_lint_overwrites(cu.root_file());
@@ -52,7 +60,7 @@ pub(crate) fn lookup(
     full_compile: bool,
     opts: &Opts,
 ) -> Result<(Utf8PathBuf, bool)> {
-    let hash = u128::from_ne_bytes(*hash(db, opts.module_name()?));
+    let hash = u128::from_ne_bytes(*hash(db, opts.module_name()?, opts.derivatives()));
     let hash = base_n::encode(hash, base_n::CASE_INSENSITIVE);
     let extension = if full_compile { "mod" } else { "modinfo" };
     let path = opts.cache_dir()?.join(format!("{}.{}", hash, extension));
@@ -0,0 +1,65 @@
+use std::ffi::c_void;
+use std::sync::{Mutex, Once};
+
+use llvm_sys::target::{LLVM_InitializeNativeAsmPrinter, LLVM_InitializeNativeTarget};
+use mir_llvm::{LLVMString, ObjectBuffer, OrcJit};
+
+static LLVM_INIT: Once = Once::new();
+
+/// The addresses of all models created by [`load_jit`](crate::load_jit). These are used to
+/// distinguish JIT handles from `dlopen` handles in the C API.
+static JIT_HANDLES: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// A VerilogAE model that was linked into the current process by the LLVM JIT.
+///
+/// The model exports the same symbols as a shared library produced by [`load`](crate::load).
+/// A pointer to a `VaeJit` can be passed to all functions of the C API in place of a
+/// library handle.
+pub struct VaeJit {
+    jit: OrcJit,
+}
+
+impl VaeJit {
+    pub(crate) fn new(
+        objects: impl Iterator<Item = ObjectBuffer>,
+    ) -> Result<&'static VaeJit, LLVMString> {
+        LLVM_INIT.call_once(|| unsafe {
+            if LLVM_InitializeNativeTarget() != 0 {
+                panic!("Failed to initialize native target");
+            }
+            if LLVM_InitializeNativeAsmPrinter() != 0 {
+                panic!("Failed to initialize native ASM printer");
+            }
+        });
+
+        let jit = unsafe { OrcJit::new()? };
+        for obj in objects {
+            jit.add_object(obj)?;
+        }
+        // the functions of a model may be called as long as the process lives
+        let res: &'static VaeJit = Box::leak(Box::new(VaeJit { jit }));
+        JIT_HANDLES.lock().unwrap().push(res.handle() as usize);
+        Ok(res)
+    }
+
+    /// Returns the address of the symbol `name` exported by the model
+    pub fn symbol(&self, name: &str) -> Result<*mut c_void, LLVMString> {
+        self.jit.lookup(name).map(|ptr| ptr.cast())
+    }
+
+    /// The handle used to refer to this model in the C API
+    pub fn handle(&'static self) -> *const c_void {
+        self as *const VaeJit as *const c_void
+    }
+
+    /// Returns the model `handle` refers to if it was created by [`load_jit`](crate::load_jit).
+    pub(crate) fn from_handle(handle: *const c_void) -> Option<&'static VaeJit> {
+        let handles = JIT_HANDLES.lock().unwrap();
+        if handles.contains(&(handle as usize)) {
+            // models are never freed so the pointer is still valid
+            Some(unsafe { &*(handle as *const VaeJit) })
+        } else {
+            None
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use std::fs;
 use std::io::Write;
+use std::sync::Mutex;
 use std::time::Instant;
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use basedb::VfsStorage;
 use camino::{Utf8Path, Utf8PathBuf};
 use lasso::Rodeo;
@@ -14,13 +15,15 @@ use linker::link;
 pub use llvm_sys::target_machine::LLVMCodeGenOptLevel;
 use mir_llvm::LLVMBackend;
 use salsa::ParallelDatabase;
-use stdx::iter::zip;
 use stdx::pretty;
+use target::spec::Target;
 use termcolor::ColorChoice::Auto;
 use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
 
 use crate::api::{Opts, VfsEntry};
+use crate::back::Emit;
 use crate::compiler_db::{CompilationDB, ModelInfo};
+pub use crate::jit::VaeJit;
 use crate::middle::{build_module_mir, build_param_init_mir};
 use crate::opts::abs_path;
 
@@ -28,6 +31,7 @@ pub mod api;
 mod back;
 mod cache;
 mod compiler_db;
+mod jit;
 mod middle;
 mod opts;
 
@@ -79,12 +83,85 @@ fn build_model(
     dst: &Utf8Path,
 ) -> Result<()> {
     let start = Instant::now();
-    let db = db;
 
     let file = path.file_name().to_owned().unwrap();
+    let info = ModelInfo::collect(&db, file, opts.module_name()?)?;
+    let target = opts.target()?;
+    let cache_dir = opts.cache_dir()?;
+
+    std::fs::create_dir_all(&cache_dir).unwrap();
+    if let Some(parent) = dst.parent() {
+        std::fs::create_dir_all(parent).unwrap();
+    }
+
+    let mut object_files = vec![cache_dir.join(format!("{}_modelinfo.o", file))];
+    if full_compile {
+        let dst_name = dst.file_name().to_owned().unwrap();
+        object_files.extend(
+            info.functions
+                .iter()
+                .map(|fun| cache_dir.join(format!("{}{}.o", dst_name, fun.prefix))),
+        );
+    }
+
+    codegen(db, &info, full_compile, local, opts, &Emit::Files(&object_files))?;
+
+    // TODO configure linker
+    link(None, &target, dst, |linker| {
+        for obj in &object_files {
+            linker.add_object(obj)
+        }
+    })
+    .context("linking failed!")?;
+
+    #[allow(unused_must_use)]
+    for file in object_files {
+        fs::remove_file(file);
+    }
+
+    print_finished(&format!("building {}", file), start)
+}
+
+/// Compiles the model at `path` just like [`load`] but links the generated code directly into
+/// the current process with the LLVM JIT. Neither temporary files nor the system linker are
+/// required. The object cache is not used, so the model is always recompiled.
+///
+/// The returned model is never unloaded (just like libraries opened by [`load`]).
+pub fn load_jit(path: &Utf8Path, full_compile: bool, opts: &Opts) -> Result<&'static VaeJit> {
+    let start = Instant::now();
 
+    let target = opts.target()?;
+    match Target::host_target() {
+        Some(host) if host.llvm_target == target.llvm_target => (),
+        _ => bail!("JIT compilation is only supported for the host target"),
+    }
+
+    let db = compiler_db::new(path, opts)?;
+    let file = path.file_name().to_owned().unwrap();
     let info = ModelInfo::collect(&db, file, opts.module_name()?)?;
 
+    let num_objects = if full_compile { info.functions.len() + 1 } else { 1 };
+    let objects = Mutex::new((0..num_objects).map(|_| None).collect());
+    codegen(db, &info, full_compile, true, opts, &Emit::Memory(&objects))?;
+
+    let objects = objects.into_inner().unwrap().into_iter().map(|obj| obj.unwrap());
+    let jit = VaeJit::new(objects).map_err(|err| anyhow!("JIT linking failed: {err}"))?;
+
+    print_finished(&format!("JIT compiling {}", file), start)?;
+    Ok(jit)
+}
+
+/// Generates the object code for the model described by `info`. The model info is emitted as
+/// the first object, followed by one object for each of `info.functions` if `full_compile`
+/// is set.
+fn codegen(
+    db: CompilationDB,
+    info: &ModelInfo,
+    full_compile: bool,
+    local: bool,
+    opts: &Opts,
+    emit: &Emit<'_>,
+) -> Result<()> {
     let target_cpu = match opts.target_cpu()? {
         Some(cpu) => cpu,
         None if local => "native",
@@ -93,48 +170,39 @@ fn build_model(
     let cg_opts: Vec<_> = opts.cg_flags().map(str::to_owned).collect();
     let target = opts.target()?;
     let backend = LLVMBackend::new(&cg_opts, &target, target_cpu.to_owned(), &[]);
-    let cache_dir = opts.cache_dir()?;
-
-    std::fs::create_dir_all(&cache_dir).unwrap();
-    if let Some(parent) = dst.parent() {
-        std::fs::create_dir_all(parent).unwrap();
-    }
-
-    let mut object_files = vec![cache_dir.join(format!("{}_modelinfo.o", file))];
 
     if full_compile {
-        let (func, intern, mut literals, cfg) = build_module_mir(&db, &info);
+        let derivatives: Vec<_> = opts.derivatives().collect();
+        let (func, intern, mut literals, cfg, derivatives) =
+            build_module_mir(&db, info, &derivatives)?;
         let interned_model = info.intern_model(&db, &mut literals);
-        let param_init = build_param_init_mir(&db, &info, &mut literals);
+        let param_init = build_param_init_mir(&db, info, &mut literals);
 
         let mut cx = back::CodegenCtx {
-            model_info: &info,
+            model_info: info,
             llbackend: &backend,
             literals: &mut literals,
             opt_lvl: opts.opt_lvl.into(),
         };
 
-        cx.compile_model_info(&object_files[0], interned_model, param_init.0, param_init.1);
-
-        let dst_name = dst.file_name().to_owned().unwrap();
-        object_files.extend(
-            info.functions
-                .iter()
-                .map(|fun| cache_dir.join(format!("{}{}.o", dst_name, fun.prefix))),
-        );
+        cx.compile_model_info(emit, interned_model, param_init.0, param_init.1);
 
         // ensure all voltage/current names are in the interner so that the interner can be
         // shared (readonly) betwenn threads
-        cx.ensure_names(&db, &intern);
+        cx.ensure_names(&db, &intern, &derivatives);
 
+        let (cx, func, cfg, intern, derivatives) = (&cx, &func, &cfg, &intern, &derivatives);
         rayon_core::scope(|s| {
             let db = db;
-            for (spec, file) in zip(&info.functions, &object_files[1..]) {
+            for (i, spec) in info.functions.iter().enumerate() {
                 let db_snap = db.snapshot();
-                s.spawn(|_| {
-                    let db_snap = db_snap;
-                    let (func, cfg) = spec.slice_mir(&func, &cfg, &intern);
-                    cx.gen_func_obj(&db_snap, spec, &func, &cfg, &intern, file)
+                s.spawn(move |_| {
+                    let spec_derivatives =
+                        derivatives.values.get(&spec.var).map_or(&[][..], |vals| &**vals);
+                    let (func, cfg) = spec.slice_mir(func, cfg, intern, spec_derivatives);
+                    // the model info is the first object
+                    let idx = i + 1;
+                    cx.gen_func_obj(&db_snap, spec, &func, &cfg, intern, derivatives, emit, idx)
                 })
             }
         })
@@ -142,37 +210,28 @@ fn build_model(
         let mut literals = Rodeo::default();
 
         let interned_model = info.intern_model(&db, &mut literals);
-        let param_init = build_param_init_mir(&db, &info, &mut literals);
+        let param_init = build_param_init_mir(&db, info, &mut literals);
 
         let cx = back::CodegenCtx {
-            model_info: &info,
+            model_info: info,
             llbackend: &backend,
             literals: &mut literals,
             opt_lvl: opts.opt_lvl.into(),
         };
 
-        cx.compile_model_info(&object_files[0], interned_model, param_init.0, param_init.1);
+        cx.compile_model_info(emit, interned_model, param_init.0, param_init.1);
     }
 
-    // TODO configure linker
-    link(None, &target, dst, |linker| {
-        for obj in &object_files {
-            linker.add_object(obj)
-        }
-    })
-    .context("linking failed!")?;
-
-    #[allow(unused_must_use)]
-    for file in object_files {
-        fs::remove_file(file);
-    }
+    Ok(())
+}
 
+fn print_finished(action: &str, start: Instant) -> Result<()> {
     let seconds = Instant::elapsed(&start).as_secs_f64();
     let mut stderr = StandardStream::stderr(Auto);
     stderr.set_color(ColorSpec::new().set_fg(Some(Color::Green)).set_bold(true))?;
     write!(&mut stderr, "Finished")?;
     stderr.set_color(&ColorSpec::new())?;
-    writeln!(&mut stderr, " building {} in {:.2}s", file, seconds)?;
+    writeln!(&mut stderr, " {} in {:.2}s", action, seconds)?;
 
     Ok(())
 }
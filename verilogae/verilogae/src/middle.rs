@@ -1,15 +1,28 @@
-use ahash::AHashSet;
+use ahash::{AHashMap, AHashSet};
+use anyhow::{bail, Result};
 use bitset::{BitSet, SparseBitMatrix};
+use hir::{Type, Variable};
 use hir_lower::{CallBackKind, HirInterner, MirBuilder, ParamKind, PlaceKind};
 use lasso::Rodeo;
-use mir::{ControlFlowGraph, DominatorTree, Function, ValueDef};
+use mir::builder::InstBuilder;
+use mir::cursor::{Cursor, FuncCursor};
+use mir::{ControlFlowGraph, DominatorTree, Function, Value, ValueDef, F_ZERO};
 use mir_autodiff::auto_diff;
 use mir_opt::{
     aggressive_dead_code_elimination, dead_code_elimination, inst_combine, simplify_cfg,
     sparse_conditional_constant_propagation,
 };
 
-use crate::compiler_db::{CompilationDB, FuncSpec, ModelInfo};
+use crate::compiler_db::{current_name, voltage_name, CompilationDB, FuncSpec, ModelInfo};
+
+/// Derivatives of the retrieved variables requested with the `derivatives` option.
+#[derive(Default)]
+pub struct Derivatives {
+    /// names of the voltages, currents and real parameters the derivatives are calculated by
+    pub unknowns: Vec<String>,
+    /// derivatives of each (real) retrieved variable in the same order as `unknowns`
+    pub values: AHashMap<Variable, Box<[Value]>>,
+}
 
 impl FuncSpec {
     pub fn slice_mir(
@@ -17,6 +30,7 @@ impl FuncSpec {
         func: &Function,
         cfg: &ControlFlowGraph,
         intern: &HirInterner,
+        derivatives: &[Value],
     ) -> (Function, ControlFlowGraph) {
         let ret_val = intern.outputs[&PlaceKind::Var(self.var)].unwrap();
         let mut func = func.clone();
@@ -51,7 +65,7 @@ impl FuncSpec {
         aggressive_dead_code_elimination(
             &mut func,
             &mut cfg,
-            &|val, _| val == ret_val,
+            &|val, _| val == ret_val || derivatives.contains(&val),
             &control_dep,
         );
         simplify_cfg(&mut func, &mut cfg);
@@ -63,7 +77,8 @@ impl FuncSpec {
 pub fn build_module_mir(
     db: &CompilationDB,
     info: &ModelInfo,
-) -> (Function, HirInterner, Rodeo, ControlFlowGraph) {
+    derivatives: &[&str],
+) -> Result<(Function, HirInterner, Rodeo, ControlFlowGraph, Derivatives)> {
     let dep_break: AHashSet<_> =
         info.functions.iter().flat_map(|func| func.dependency_breaking.iter().copied()).collect();
 
@@ -113,16 +128,67 @@ pub fn build_module_mir(
 
     let mut dom_tree = DominatorTree::default();
     dom_tree.compute(&func, &cfg, true, false, true);
-    let unknowns = intern.unknowns(&mut func, false);
-    auto_diff(&mut func, &dom_tree, &unknowns, &[]);
+    let mut unknowns = intern.unknowns(&mut func, false);
+
+    let mut derivative_unknowns = Vec::with_capacity(derivatives.len());
+    for &name in derivatives {
+        let unknown = intern.params.raw.iter().find_map(|(kind, &val)| {
+            let found = match *kind {
+                ParamKind::Voltage { hi, lo } => voltage_name(db, hi, lo) == name,
+                ParamKind::Current(kind) => current_name(db, kind) == name,
+                ParamKind::Param(param) => {
+                    param.ty(db) == Type::Real && info.params[&param].name == name
+                }
+                _ => false,
+            };
+            found.then_some(val)
+        });
+        let Some(unknown) = unknown else {
+            bail!(
+                "can not calculate derivatives by '{name}': \
+                 the model does not access a voltage, current or real parameter with that name"
+            )
+        };
+        derivative_unknowns.push(unknowns.unknowns.ensure(unknown).0);
+    }
+
+    let outputs: Vec<_> = info
+        .functions
+        .iter()
+        .filter(|spec| !derivative_unknowns.is_empty() && spec.var.ty(db) == Type::Real)
+        .map(|spec| (spec.var, intern.outputs[&PlaceKind::Var(spec.var)].unwrap()))
+        .collect();
+    let extra_derivatives: Vec<_> = outputs
+        .iter()
+        .flat_map(|&(_, val)| derivative_unknowns.iter().map(move |&unknown| (val, unknown)))
+        .collect();
+    let derivative_vals = auto_diff(&mut func, &dom_tree, &unknowns, &extra_derivatives);
+
+    // optbarriers ensure that the derivatives are not replaced by the optimizations below
+    let mut cursor = FuncCursor::new(&mut func).at_exit();
+    let values = outputs
+        .into_iter()
+        .map(|(var, val)| {
+            let derivatives = derivative_unknowns
+                .iter()
+                .map(|&unknown| {
+                    let ddx = derivative_vals.get(&(val, unknown)).copied().unwrap_or(F_ZERO);
+                    cursor.ins().optbarrier(ddx)
+                })
+                .collect();
+            (var, derivatives)
+        })
+        .collect();
+    let derivatives =
+        Derivatives { unknowns: derivatives.iter().map(|&name| name.to_owned()).collect(), values };
+
     cfg.clear();
     cfg.compute(&func);
     sparse_conditional_constant_propagation(&mut func, &cfg);
     inst_combine(&mut func);
     simplify_cfg(&mut func, &mut cfg);
 
-    (func, intern, literals, cfg)
-    // (func, intern, literals, Vec::new(), cfg)
+    Ok((func, intern, literals, cfg, derivatives))
 }
 
 pub fn build_param_init_mir(
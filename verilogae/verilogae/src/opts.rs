@@ -102,6 +102,10 @@ impl Opts {
         Self::str_list_iter(&self.macro_flags)
     }
 
+    pub(crate) fn derivatives(&self) -> impl Iterator<Item = &str> {
+        Self::str_list_iter(&self.derivatives)
+    }
+
     #[allow(clippy::type_complexity)]
     pub(crate) fn vfs(&self) -> Result<Option<Vec<(&str, &[u8])>>> {
         if self.vfs.ptr.is_null() {
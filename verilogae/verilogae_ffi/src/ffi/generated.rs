@@ -34,6 +34,7 @@ pub type VaeFun = ::std::option::Option<
         arg8: *mut FatPtr<i32>,
         arg9: *mut FatPtr<f64>,
         arg10: *mut ::std::os::raw::c_void,
+        arg11: *mut *mut f64,
     ),
 >;
 #[repr(C)]
@@ -64,6 +65,7 @@ pub struct Opts {
     pub target: Slice<u8>,
     pub cg_flags: Slice<Slice<u8>>,
     pub vfs: Vfs,
+    pub derivatives: Slice<Slice<u8>>,
 }
 extern "C" {
     #[doc = "This function returns a pointer to the `functions` global"]
@@ -412,6 +414,19 @@ extern "C" {
         fun: *const ::std::os::raw::c_char,
     ) -> *const f64;
 }
+extern "C" {
+    #[doc = "This function returns a pointer to the `derivatives` global"]
+    #[doc = " of a VerilogAE model loaded with `load`."]
+    #[doc = ""]
+    #[doc = " # Safety"]
+    #[doc = ""]
+    #[doc = " `lib` must be a valid pointer returned by the `load` functions or `dlopen`"]
+    #[doc = "`sym_name` must batch the schema fun.{NUM}derivatives"]
+    pub fn verilogae_fun_derivatives(
+        lib: *const ::std::os::raw::c_void,
+        fun: *const ::std::os::raw::c_char,
+    ) -> *const *const ::std::os::raw::c_char;
+}
 extern "C" {
     #[doc = "This function returns a pointer to the `params.real.cnt` global"]
     #[doc = " of a VerilogAE model loaded with `load`."]
@@ -520,6 +535,18 @@ extern "C" {
         fun: *const ::std::os::raw::c_char,
     ) -> usize;
 }
+extern "C" {
+    #[doc = "This function returns a pointer to the `derivatives.cnt` global"]
+    #[doc = " of a VerilogAE model loaded with `load`."]
+    #[doc = ""]
+    #[doc = " # Safety"]
+    #[doc = ""]
+    #[doc = " `lib` must be a valid pointer returned by the `load` functions or `dlopen`"]
+    pub fn verilogae_fun_derivative_cnt(
+        lib: *const ::std::os::raw::c_void,
+        fun: *const ::std::os::raw::c_char,
+    ) -> usize;
+}
 extern "C" {
     #[doc = " Obtains a pointer to the modelcard initialization function of a VerilogAE model loaded with `load`."]
     #[doc = ""]
@@ -550,6 +577,8 @@ extern "C" {
     #[doc = " # Safety"]
     #[doc = ""]
     #[doc = " All required parameters must be initialized appropriately"]
+    #[doc = " `derivatives` must point to one output array for each entry of `verilogae_fun_derivatives`"]
+    #[doc = " (or may be null if the function has no derivatives)"]
     pub fn verilogae_call_fun_parallel(
         fun: VaeFun,
         cnt: usize,
@@ -562,6 +591,7 @@ extern "C" {
         int_dep_break: *mut FatPtr<i32>,
         temp: *mut FatPtr<f64>,
         out: *mut ::std::os::raw::c_void,
+        derivatives: *mut *mut f64,
     ) -> i32;
 }
 extern "C" {
@@ -597,6 +627,21 @@ extern "C" {
         opts: *const Opts,
     ) -> *const ::std::os::raw::c_void;
 }
+extern "C" {
+    #[doc = " Compiles a model like `verilogae_load` but links it into the current process with the LLVM"]
+    #[doc = " JIT instead of the system linker. The object cache is not used. The returned handle can be"]
+    #[doc = " passed to all other functions in place of a library handle, but not to `dlsym`."]
+    #[doc = ""]
+    #[doc = " # Safety"]
+    #[doc = " * path must be valid for reads"]
+    #[doc = " * opts must be valid for reads or null"]
+    #[doc = " * opts must only contain valid data"]
+    pub fn verilogae_load_jit(
+        path: NativePath,
+        full_compile: bool,
+        opts: *const Opts,
+    ) -> *const ::std::os::raw::c_void;
+}
 pub const PARAM_FLAGS_MIN_INCLUSIVE: ParamFlags = 1;
 pub const PARAM_FLAGS_MAX_INCLUSIVE: ParamFlags = 2;
 pub const PARAM_FLAGS_INVALID: ParamFlags = 4;
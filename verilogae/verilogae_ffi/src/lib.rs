@@ -90,6 +90,7 @@ impl Drop for Opts {
                 opts.deny_lints.into_box_opt();
                 opts.cg_flags.into_box_opt();
                 opts.vfs.into_box_opt();
+                opts.derivatives.into_box_opt();
             }
             unsafe { ffi::verilogae_free_opts(opts as *mut ffi::Opts) }
         }
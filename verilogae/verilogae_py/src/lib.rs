@@ -14,7 +14,7 @@ use std::ptr;
 
 use pyo3_ffi::*;
 
-use crate::load::{load_info_py, load_py, load_vfs};
+use crate::load::{load_info_py, load_jit_py, load_py, load_vfs};
 use crate::model::{VAE_FUNCTION_TY, VAE_MODEL_TY, VAE_PARAM_TY};
 use crate::typeref::init_typerefs;
 
@@ -23,7 +23,7 @@ const FUN_FLAG: c_int = METH_FASTCALL;
 #[cfg(not(Py_3_8))]
 const FUN_FLAG: c_int = METH_VARARGS;
 
-static mut FUNCTIONS: [PyMethodDef; 4] = unsafe {
+static mut FUNCTIONS: [PyMethodDef; 5] = unsafe {
     [
     PyMethodDef {
             ml_name: "load\0".as_ptr() as *const c_char,
@@ -34,6 +34,15 @@ static mut FUNCTIONS: [PyMethodDef; 4] = unsafe {
             ml_flags: FUN_FLAG | METH_KEYWORDS,
             ml_doc: "loads a Verilog-A model by either loading it from the object cache or compiling it\0".as_ptr() as *const c_char,
     },
+    PyMethodDef {
+            ml_name: "load_jit\0".as_ptr() as *const c_char,
+            #[cfg(Py_3_8)]
+            ml_meth: PyMethodDefPointer{_PyCFunctionFastWithKeywords: load_jit_py},
+            #[cfg(not(Py_3_8))]
+            ml_meth: PyMethodDefPointer{PyCFunctionWithKeywords: load_jit_py},
+            ml_flags: FUN_FLAG | METH_KEYWORDS,
+            ml_doc: "compiles a Verilog-A model and links it into the current process with a JIT\nThis does not require a system linker or temporary files but bypasses the object cache.\0".as_ptr() as *const c_char,
+    },
     PyMethodDef {
             ml_name: "load_info\0".as_ptr() as *const c_char,
             #[cfg(Py_3_8)]
@@ -94,7 +103,7 @@ pub unsafe extern "C" fn PyInit_verilogae() -> *mut PyObject {
         PyUnicode_FromStringAndSize(version.as_ptr() as *const c_char, version.len() as isize),
     );
 
-    let all = ["__all__\0", "__version__\0", "load\0", "load_info\0", "export_vfs\0"];
+    let all = ["__all__\0", "__version__\0", "load\0", "load_jit\0", "load_info\0", "export_vfs\0"];
 
     let pyall = PyTuple_New(all.len() as isize);
     for (i, obj) in all.iter().enumerate() {
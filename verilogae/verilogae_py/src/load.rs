@@ -2,7 +2,7 @@ use std::ptr;
 
 use libc::c_char;
 use pyo3_ffi::*;
-use verilogae_ffi::{verilogae_load, Opts, Slice, Vfs, VfsEntry, VfsExport};
+use verilogae_ffi::{verilogae_load, verilogae_load_jit, Opts, Slice, Vfs, VfsEntry, VfsExport};
 
 use crate::ffi::PyDict_GET_SIZE;
 use crate::model::VaeModel;
@@ -26,6 +26,12 @@ macro_rules! handle_opt {
                 None => return ptr::null_mut(),
             }
             true
+        } else if $arg == typeref::DERIVATIVES_STR {
+            match py_to_str_list($fun, "derivatives", $val) {
+                Some(derivatives) => $dst.write().derivatives = derivatives,
+                None => return ptr::null_mut(),
+            }
+            true
         } else {
             false
         }
@@ -193,6 +199,41 @@ pub unsafe extern "C" fn load_py(
     VaeModel::new(model, true)
 }
 
+#[cfg(not(Py_3_8))]
+#[no_mangle]
+pub unsafe extern "C" fn load_jit_py(
+    _self: *mut PyObject,
+    args: *mut PyObject,
+    kwds: *mut PyObject,
+) -> *mut PyObject {
+    parse_args!("load_jit", args, kwds, path, opts);
+    let model = verilogae_load_jit(path.data, true, opts.to_ffi());
+
+    if model.is_null() {
+        return raise_runtime_runtime_exception("load_jit() compilation failed");
+    }
+
+    VaeModel::new(model, true)
+}
+
+#[cfg(Py_3_8)]
+#[no_mangle]
+pub unsafe extern "C" fn load_jit_py(
+    _self: *mut PyObject,
+    args: *const *mut PyObject,
+    nargs: Py_ssize_t,
+    kwnames: *mut PyObject,
+) -> *mut PyObject {
+    parse_args!("load_jit", args, nargs, kwnames, path, opts);
+    let model = verilogae_load_jit(path.data, true, opts.to_ffi());
+
+    if model.is_null() {
+        return raise_runtime_runtime_exception("load_jit() compilation failed");
+    }
+
+    VaeModel::new(model, true)
+}
+
 #[cfg(not(Py_3_8))]
 #[no_mangle]
 pub unsafe extern "C" fn load_info_py(
@@ -315,3 +356,23 @@ unsafe fn py_to_vfs(fun: &str, obj: *mut PyObject) -> Option<Vfs> {
 
     Some(vfs.into_boxed_slice().into())
 }
+
+unsafe fn py_to_str_list(fun: &str, arg: &str, obj: *mut PyObject) -> Option<Slice<Slice<u8>>> {
+    if PyList_Check(obj) == 0 {
+        raise_type_exception(&format!("{}() argument '{}' must have type list(str)", fun, arg));
+        return None;
+    }
+    let len = PyList_GET_SIZE(obj);
+    let mut res = Vec::with_capacity(len as usize);
+    for i in 0..len {
+        let mut size = 0;
+        let data = PyUnicode_AsUTF8AndSize(PyList_GET_ITEM(obj, i), &mut size);
+        if unlikely(data.is_null()) {
+            raise_type_exception(&format!("{}() argument '{}' must have type list(str)", fun, arg));
+            return None;
+        }
+        res.push(Slice::from_raw_parts(data as *const u8, size as usize));
+    }
+
+    Some(res.into_boxed_slice().into())
+}
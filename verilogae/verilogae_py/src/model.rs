@@ -8,14 +8,15 @@ use pyo3_ffi::structmember::{PyMemberDef, READONLY, T_OBJECT, T_OBJECT_EX};
 use pyo3_ffi::*;
 use verilogae_ffi::{
     verilogae_call_fun_parallel, verilogae_fun_current_cnt, verilogae_fun_current_default_cnt,
-    verilogae_fun_current_defaults, verilogae_fun_currents, verilogae_fun_ptr,
-    verilogae_fun_voltage_cnt, verilogae_fun_voltage_default_cnt, verilogae_fun_voltage_defaults,
-    verilogae_fun_voltages, verilogae_function_cnt, verilogae_function_symbols,
-    verilogae_functions, verilogae_init_modelcard, verilogae_int_fun_depbreak,
-    verilogae_int_fun_depbreak_cnt, verilogae_int_fun_param_cnt, verilogae_int_fun_params,
-    verilogae_int_param_cnt, verilogae_int_param_descriptions, verilogae_int_param_groups,
-    verilogae_int_param_units, verilogae_int_params, verilogae_module_name, verilogae_node_cnt,
-    verilogae_nodes, verilogae_opvars, verilogae_opvars_cnt, verilogae_real_fun_depbreak,
+    verilogae_fun_current_defaults, verilogae_fun_currents, verilogae_fun_derivative_cnt,
+    verilogae_fun_derivatives, verilogae_fun_ptr, verilogae_fun_voltage_cnt,
+    verilogae_fun_voltage_default_cnt, verilogae_fun_voltage_defaults, verilogae_fun_voltages,
+    verilogae_function_cnt, verilogae_function_symbols, verilogae_functions,
+    verilogae_init_modelcard, verilogae_int_fun_depbreak, verilogae_int_fun_depbreak_cnt,
+    verilogae_int_fun_param_cnt, verilogae_int_fun_params, verilogae_int_param_cnt,
+    verilogae_int_param_descriptions, verilogae_int_param_groups, verilogae_int_param_units,
+    verilogae_int_params, verilogae_module_name, verilogae_node_cnt, verilogae_nodes,
+    verilogae_opvars, verilogae_opvars_cnt, verilogae_real_fun_depbreak,
     verilogae_real_fun_depbreak_cnt, verilogae_real_fun_param_cnt, verilogae_real_fun_params,
     verilogae_real_param_cnt, verilogae_real_param_descriptions, verilogae_real_param_groups,
     verilogae_real_param_units, verilogae_real_params, verilogae_str_fun_param_cnt,
@@ -477,7 +478,7 @@ pub static mut VAE_FUNCTION_TY: PyTypeObject = {
     res
 };
 
-static mut VAE_FUNCTION_MEMBERS: [PyMemberDef; 6] = [
+static mut VAE_FUNCTION_MEMBERS: [PyMemberDef; 7] = [
     PyMemberDef {
         name: "name\0".as_ptr() as *mut c_char,
         type_code: T_OBJECT,
@@ -514,6 +515,14 @@ static mut VAE_FUNCTION_MEMBERS: [PyMemberDef; 6] = [
         doc: "The names of all variables the function requires for dependency breaking".as_ptr()
             as *mut c_char,
     },
+    PyMemberDef {
+        name: "derivatives\0".as_ptr() as *mut c_char,
+        type_code: T_OBJECT,
+        offset: VaeFun::offset_to.derivatives as isize,
+        flags: READONLY,
+        doc: "The names of the voltages, currents and parameters `eval` calculates derivatives by"
+            .as_ptr() as *mut c_char,
+    },
     unsafe { zero!(PyMemberDef) },
 ];
 
@@ -558,6 +567,7 @@ with_offsets! {
         currents: *mut PyObject,
         parameters: *mut PyObject,
         depbreak: *mut PyObject,
+        derivatives: *mut PyObject,
 
         int_depbreak_offset: usize,
         real_depbreak_offset: usize,
@@ -567,6 +577,7 @@ with_offsets! {
         str_params:  Box<[(*mut PyObject, &'static str)]>,
         voltages_:   Box<[(*mut PyObject, &'static str, f64)]>,
         currents_:   Box<[(*mut PyObject, &'static str, f64)]>,
+        derivatives_: Box<[*mut PyObject]>,
 
         required_kwargs: usize,
 
@@ -805,6 +816,20 @@ impl VaeFun {
             (name_py, name)
         });
 
+        let derivative_names = verilogae_fun_derivatives(handle, sym);
+        let derivative_cnt = verilogae_fun_derivative_cnt(handle, sym);
+
+        let derivatives = PyList_New(derivative_cnt as isize);
+
+        let derivatives_ = (0..derivative_cnt)
+            .map(|i| {
+                let name_py = PyUnicode_InternFromString(*derivative_names.add(i));
+                Py_INCREF(name_py);
+                PyList_SetItem(derivatives, i as isize, name_py);
+                name_py
+            })
+            .collect();
+
         let ffi = verilogae_fun_ptr(handle, sym);
         assert!(ffi.is_some(), "failed to read verilogae function");
 
@@ -815,6 +840,7 @@ impl VaeFun {
             currents,
             parameters,
             depbreak,
+            derivatives,
             real_depbreak_offset: real_param_cnt,
             int_depbreak_offset: int_param_cnt,
             real_params: real_params.chain(real_depbreak).collect(),
@@ -822,6 +848,7 @@ impl VaeFun {
             str_params: str_params.collect(),
             voltages_,
             currents_,
+            derivatives_,
 
             required_kwargs: 1
                 + real_depbreak_cnt
@@ -860,6 +887,10 @@ impl VaeFun {
         Py_XDECREF(self_.currents);
         Py_XDECREF(self_.parameters);
         Py_XDECREF(self_.depbreak);
+        Py_XDECREF(self_.derivatives);
+        for name in take(&mut self_.derivatives_).iter() {
+            Py_DECREF(*name);
+        }
 
         // make drop a noop
         take(&mut self_.real_params);
@@ -1015,56 +1046,74 @@ impl VaeFun {
         }
 
         let ptr = self_.ffi_data.as_mut_ptr();
-        if likely(len != 1) {
+        let num_derivatives = self_.derivatives_.len();
+
+        // the value of the function is written to the first output, followed by the derivatives
+        let mut scalars = vec![0f64; num_derivatives + 1];
+        let mut arrays = Vec::new();
+        let mut out: Vec<*mut f64> = if likely(len != 1) {
             let new_arr = NUMPY_API.unwrap();
-            Py_INCREF(NUMPY_CDOUBLE_DESCR);
-            let dst = new_arr(
-                NUMPY_ARR_TYPE.unwrap(), // base_type (normal numpy array)
-                NUMPY_CDOUBLE_DESCR,     // type descriptor
-                1,                       //nd
-                &mut len,                //dims
-                &mut 8,                  // strides
-                ptr::null_mut(),         //data (to be allocated)
-                0,                       // flags
-                ptr::null_mut(),         // obj (to be created)
-            );
-            let arr = NumpyArray::new(dst).unwrap();
-            verilogae_call_fun_parallel(
-                self_.ffi,
-                len as usize,
-                &mut (*ptr.add(self_.int_params.len() + self_.real_params.len())).float,
-                &mut (*ptr
-                    .add(self_.int_params.len() + self_.real_params.len() + self_.voltages_.len()))
-                .float,
-                &mut (*ptr).float,
-                &mut (*ptr.add(self_.real_params.len())).int,
-                self_.ffi_str_data.as_mut_ptr(),
-                &mut (*ptr.add(self_.real_depbreak_offset)).float,
-                &mut (*ptr.add(self_.int_depbreak_offset + self_.real_params.len())).int,
-                &mut temp,
-                arr.data(),
-            );
-            dst
+            (0..=num_derivatives)
+                .map(|_| {
+                    Py_INCREF(NUMPY_CDOUBLE_DESCR);
+                    let dst = new_arr(
+                        NUMPY_ARR_TYPE.unwrap(), // base_type (normal numpy array)
+                        NUMPY_CDOUBLE_DESCR,     // type descriptor
+                        1,                       //nd
+                        &mut len,                //dims
+                        &mut 8,                  // strides
+                        ptr::null_mut(),         //data (to be allocated)
+                        0,                       // flags
+                        ptr::null_mut(),         // obj (to be created)
+                    );
+                    arrays.push(dst);
+                    NumpyArray::new(dst).unwrap().data() as *mut f64
+                })
+                .collect()
+        } else {
+            scalars.iter_mut().map(|val| val as *mut f64).collect()
+        };
+
+        let derivatives =
+            if num_derivatives == 0 { ptr::null_mut() } else { out.as_mut_ptr().add(1) };
+
+        verilogae_call_fun_parallel(
+            self_.ffi,
+            len as usize,
+            &mut (*ptr.add(self_.int_params.len() + self_.real_params.len())).float,
+            &mut (*ptr
+                .add(self_.int_params.len() + self_.real_params.len() + self_.voltages_.len()))
+            .float,
+            &mut (*ptr).float,
+            &mut (*ptr.add(self_.real_params.len())).int,
+            self_.ffi_str_data.as_mut_ptr(),
+            &mut (*ptr.add(self_.real_depbreak_offset)).float,
+            &mut (*ptr.add(self_.int_depbreak_offset + self_.real_params.len())).int,
+            &mut temp,
+            out[0] as *mut c_void,
+            derivatives,
+        );
+
+        let results = if likely(len != 1) {
+            arrays
         } else {
-            let mut val = 0f64;
-            let val_ptr: *mut f64 = &mut val;
-            verilogae_call_fun_parallel(
-                self_.ffi,
-                len as usize,
-                &mut (*ptr.add(self_.int_params.len() + self_.real_params.len())).float,
-                &mut (*ptr
-                    .add(self_.int_params.len() + self_.real_params.len() + self_.voltages_.len()))
-                .float,
-                &mut (*ptr).float,
-                &mut (*ptr.add(self_.real_params.len())).int,
-                self_.ffi_str_data.as_mut_ptr(),
-                &mut (*ptr.add(self_.real_depbreak_offset)).float,
-                &mut (*ptr.add(self_.int_depbreak_offset + self_.real_params.len())).int,
-                &mut temp,
-                &mut val as *mut f64 as *mut _,
-            );
-            PyFloat_FromDouble(*val_ptr)
+            scalars.iter().map(|val| PyFloat_FromDouble(*val)).collect()
+        };
+
+        if num_derivatives == 0 {
+            return results[0];
         }
+
+        // functions with derivatives return a tuple (value, {unknown: derivative})
+        let derivatives = PyDict_New();
+        for (name, val) in self_.derivatives_.iter().zip(&results[1..]) {
+            PyDict_SetItem(derivatives, *name, *val);
+            Py_DECREF(*val);
+        }
+        let res = PyTuple_New(2);
+        PyTuple_SetItem(res, 0, results[0]);
+        PyTuple_SetItem(res, 1, derivatives);
+        res
     }
 }
 
@@ -40,6 +40,7 @@ pub static mut EMPTY_UNICODE: *mut PyObject = 0 as *mut PyObject;
 // Internted arguments so that kwargs check are simple pointer comparisons
 pub static mut MODULE_STR: *mut PyObject = 0 as *mut PyObject;
 pub static mut VFS_STR: *mut PyObject = 0 as *mut PyObject;
+pub static mut DERIVATIVES_STR: *mut PyObject = 0 as *mut PyObject;
 pub static mut VOLTAGES_STR: *mut PyObject = 0 as *mut PyObject;
 pub static mut CURRENTS_STR: *mut PyObject = 0 as *mut PyObject;
 pub static mut TEMPERATURE_STR: *mut PyObject = 0 as *mut PyObject;
@@ -57,6 +58,7 @@ pub fn init_typerefs() {
 
         MODULE_STR = PyUnicode_InternFromString("module\0".as_ptr() as *const c_char);
         VFS_STR = PyUnicode_InternFromString("vfs\0".as_ptr() as *const c_char);
+        DERIVATIVES_STR = PyUnicode_InternFromString("derivatives\0".as_ptr() as *const c_char);
         VOLTAGES_STR = PyUnicode_InternFromString("voltages\0".as_ptr() as *const c_char);
         CURRENTS_STR = PyUnicode_InternFromString("currents\0".as_ptr() as *const c_char);
         TEMPERATURE_STR = PyUnicode_InternFromString("temperature\0".as_ptr() as *const c_char);